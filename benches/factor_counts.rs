@@ -0,0 +1,33 @@
+//! Factors every number below a few million both ways and prints the timings.
+//!
+//! Run with `cargo bench --bench factor_counts`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use prime_tools::{get_prime_factors_with_counts, get_prime_factors_with_counts_sorted, get_primes_less_than_x};
+
+const MAX: u32 = 2_000_000;
+const ROUNDS: u32 = 5;
+
+fn best_of<F: FnMut() -> usize>(mut f: F) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let primes = get_primes_less_than_x(1_415);
+
+    let map = best_of(|| (0..MAX).map(|x| get_prime_factors_with_counts(x, &primes).len()).sum());
+    let sorted = best_of(|| (0..MAX).map(|x| get_prime_factors_with_counts_sorted(x, &primes).len()).sum());
+
+    println!("factoring 0..{} against the primes below 1415, best of {}", MAX, ROUNDS);
+    println!("  HashMap    {:>10.1?}", map);
+    println!("  sorted Vec {:>10.1?}  ({:.2}x)", sorted, map.as_secs_f64() / sorted.as_secs_f64());
+}
@@ -0,0 +1,54 @@
+//! Times `mod_pow_big` against num-bigint's own `BigUint::modpow` for odd
+//! moduli of a few sizes, full-size exponents, and prints the timings.
+//!
+//! Run with `cargo bench --features bignum --bench mod_pow_big`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use num_bigint::BigUint;
+use prime_tools::mod_pow_big;
+
+const ROUNDS: u32 = 5;
+
+fn best_of<F: FnMut() -> usize>(mut f: F) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+/// A number of the given bits from xorshift64, top bit set.
+fn random_big(state: &mut u64, bits: usize) -> BigUint {
+    let limbs: Vec<u32> = (0..bits / 32)
+        .map(|_| {
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            *state as u32
+        })
+        .collect();
+    BigUint::new(limbs) | (BigUint::from(1u32) << (bits - 1))
+}
+
+fn main() {
+    let mut state = 661;
+    println!("base^exponent mod an odd modulus, exponent as wide as the modulus, best of {}", ROUNDS);
+    for &(bits, count) in &[(128, 2_000), (256, 1_000), (512, 200), (1_024, 40), (2_048, 8)] {
+        let modulus = random_big(&mut state, bits) | BigUint::from(1u32);
+        let inputs: Vec<(BigUint, BigUint)> = (0..count).map(|_| (random_big(&mut state, bits), random_big(&mut state, bits))).collect();
+        for (base, exponent) in &inputs {
+            assert_eq!(mod_pow_big(base, exponent, &modulus), base.modpow(exponent, &modulus));
+        }
+
+        let ours = best_of(|| inputs.iter().map(|(base, exponent)| mod_pow_big(base, exponent, &modulus).bits() as usize).sum());
+        let theirs = best_of(|| inputs.iter().map(|(base, exponent)| base.modpow(exponent, &modulus).bits() as usize).sum());
+        println!("  {:>5} bits x {:>5}", bits, count);
+        println!("    mod_pow_big     {:>10.1?}  ({:.2}x)", ours, theirs.as_secs_f64() / ours.as_secs_f64());
+        println!("    BigUint::modpow {:>10.1?}", theirs);
+    }
+}
@@ -0,0 +1,116 @@
+//! Division by a fixed modulus without the hardware divider.
+
+/// A modulus with its reciprocal worked out ahead of time, for loops that
+/// reduce many values by the same m.
+///
+/// Barrett reduction turns x mod m into two or three multiplications and a
+/// subtraction or two, where `%` on a u128 is a call into a slow division
+/// routine and even a u64 `%` takes dozens of cycles. It pays off after a
+/// handful of reductions, e.g. the squarings of a Miller–Rabin test.
+///
+/// ```
+/// use prime_tools::Barrett;
+///
+/// let m = Barrett::new(1_000_000_007);
+/// assert_eq!(m.reduce(u64::MAX), u64::MAX % 1_000_000_007);
+/// assert_eq!(m.mul_mod(999_999_999, 999_999_999), 64);
+/// assert_eq!(m.pow_mod(2, 1_000_000_006), 1); // Fermat
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Barrett {
+    modulus: u64,
+    /// ⌊(2^128 - 1) / modulus⌋, which is within one of 2^128 / modulus.
+    reciprocal: u128,
+}
+
+impl Barrett {
+    /// Precomputes the reciprocal of m. Panics if m is 0.
+    pub fn new(m: u64) -> Barrett {
+        assert!(m > 0, "modulus must be positive");
+        Barrett { modulus: m, reciprocal: u128::MAX / m as u128 }
+    }
+
+    /// The modulus m.
+    pub fn modulus(&self) -> u64 {
+        self.modulus
+    }
+
+    /// x mod m.
+    pub fn reduce(&self, x: u64) -> u64 {
+        self.reduce_u128(x as u128)
+    }
+
+    /// x mod m, for any u128 x.
+    pub fn reduce_u128(&self, x: u128) -> u64 {
+        // The estimate falls short of ⌊x / m⌋ by at most 2.
+        let quotient = mul_high(x, self.reciprocal);
+        let m = self.modulus as u128;
+        let mut r = x - quotient * m;
+        while r >= m {
+            r -= m;
+        }
+        r as u64
+    }
+
+    /// (a * b) mod m, without overflow.
+    pub fn mul_mod(&self, a: u64, b: u64) -> u64 {
+        self.reduce_u128(a as u128 * b as u128)
+    }
+
+    /// base^exponent mod m, by squaring and multiplying.
+    pub fn pow_mod(&self, base: u64, mut exponent: u64) -> u64 {
+        let mut base = self.reduce(base);
+        let mut result = self.reduce(1);
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = self.mul_mod(result, base);
+            }
+            base = self.mul_mod(base, base);
+            exponent >>= 1;
+        }
+        result
+    }
+}
+
+/// The top 128 bits of the 256-bit product a * b.
+fn mul_high(a: u128, b: u128) -> u128 {
+    let (a_high, a_low) = (a >> 64, a as u64 as u128);
+    let (b_high, b_low) = (b >> 64, b as u64 as u128);
+    let low = a_low * b_low;
+    let (cross1, cross2) = (a_high * b_low, a_low * b_high);
+    let carry = ((low >> 64) + (cross1 as u64 as u128) + (cross2 as u64 as u128)) >> 64;
+    a_high * b_high + (cross1 >> 64) + (cross2 >> 64) + carry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_hardware_division() {
+        let mut x = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = || {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x
+        };
+        let mut moduli = vec![1, 2, 3, 7, 1 << 32, (1 << 32) + 1, 1 << 63, u64::MAX - 1, u64::MAX];
+        moduli.extend((0..200).map(|_| next() >> (next() % 64)).filter(|&m| m > 0));
+        for &m in &moduli {
+            let barrett = Barrett::new(m);
+            let mut values = vec![0, 1, m - 1, m, m.wrapping_add(1), u64::MAX];
+            values.extend((0..200).map(|_| next()));
+            for &a in &values {
+                assert_eq!(barrett.reduce(a), a % m, "{} mod {}", a, m);
+                let b = next();
+                assert_eq!(barrett.mul_mod(a, b), (a as u128 * b as u128 % m as u128) as u64, "{} * {} mod {}", a, b, m);
+                let wide = (a as u128) << 64 | b as u128;
+                assert_eq!(barrett.reduce_u128(wide), (wide % m as u128) as u64, "{} mod {}", wide, m);
+            }
+            assert_eq!(barrett.reduce_u128(u128::MAX), (u128::MAX % m as u128) as u64, "{}", m);
+        }
+        assert_eq!(Barrett::new(1).pow_mod(5, 0), 0);
+        assert_eq!(Barrett::new(u64::MAX - 58).pow_mod(3, u64::MAX - 59), 1);
+    }
+}
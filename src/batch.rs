@@ -0,0 +1,386 @@
+//! Batch number theory over many numbers at once.
+
+#[cfg(feature = "bignum")]
+use std::convert::TryFrom;
+
+#[cfg(feature = "bignum")]
+use num_bigint::BigUint;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::factor_report::factor_chain;
+use crate::modular::gcd_u128;
+use crate::Factorization;
+
+/// Every pair of entries in nums with a common factor, as (i, j, gcd) with
+/// i < j, in order.
+///
+/// This is the shared-prime scan for RSA moduli: two keys generated with a
+/// weak RNG that share a prime are both broken by one gcd. Checking every
+/// pair costs n^2 / 2 gcds; with the `bignum` feature, batches of 64 or more
+/// go through Bernstein's batch gcd instead, a product tree of all the
+/// numbers and a remainder tree back down that finds gcd(n_i, product of the
+/// others) for every i in quasi-linear time, and only the few numbers that
+/// turn up a factor get compared pairwise. Entries 0 and 1 are skipped.
+///
+/// ```
+/// use prime_tools::pairwise_gcd_scan;
+///
+/// let (p, q, r, s) = (1_000_003u128, 1_000_033, 1_000_037, 1_000_039);
+/// let moduli = [p * q, r * s, 999_983 * 999_979, q * r];
+/// assert_eq!(pairwise_gcd_scan(&moduli), vec![(0, 3, q), (1, 3, r)]);
+/// ```
+pub fn pairwise_gcd_scan(nums: &[u128]) -> Vec<(usize, usize, u128)> {
+    let _span = debug_span!("pairwise_gcd_scan", count = nums.len());
+    let candidates = suspects(nums);
+    let mut pairs = Vec::new();
+    for (k, &i) in candidates.iter().enumerate() {
+        for &j in &candidates[k + 1..] {
+            let g = gcd_u128(nums[i], nums[j]);
+            if g > 1 {
+                pairs.push((i, j, g));
+            }
+        }
+    }
+    pairs
+}
+
+/// Indices of the entries that might share a factor with another one.
+#[cfg(feature = "bignum")]
+fn suspects(nums: &[u128]) -> Vec<usize> {
+    const TREE_FROM: usize = 64;
+    let indices: Vec<usize> = (0..nums.len()).filter(|&i| nums[i] > 1).collect();
+    if indices.len() < TREE_FROM {
+        return indices;
+    }
+    let values: Vec<u128> = indices.iter().map(|&i| nums[i]).collect();
+    let shared = batch_gcd(&values);
+    indices.into_iter().zip(shared).filter(|&(_, g)| g > 1).map(|(i, _)| i).collect()
+}
+
+#[cfg(not(feature = "bignum"))]
+fn suspects(nums: &[u128]) -> Vec<usize> {
+    (0..nums.len()).filter(|&i| nums[i] > 1).collect()
+}
+
+/// Levels of a product tree over values, leaves first: each node is the
+/// product of the two below it (or the one, at the end of an odd level),
+/// and the last level is the product of everything. A node is None once
+/// its product no longer fits in a u128.
+///
+/// The u64 leaves keep two levels exact; for more, see `product_tree_big`.
+///
+/// ```
+/// let tree = prime_tools::product_tree(&[2, 3, 5, 7, 11]);
+/// assert_eq!(tree[1], vec![Some(6), Some(35), Some(11)]);
+/// assert_eq!(tree[3], vec![Some(2_310)]);
+///
+/// let big = prime_tools::product_tree(&[u64::MAX; 4]);
+/// assert_eq!(big[1], vec![Some(u64::MAX as u128 * u64::MAX as u128); 2]);
+/// assert_eq!(big[2], vec![None]);
+/// ```
+pub fn product_tree(values: &[u64]) -> Vec<Vec<Option<u128>>> {
+    let mut levels = vec![values.iter().map(|&v| Some(v as u128)).collect::<Vec<_>>()];
+    while levels.last().unwrap().len() > 1 {
+        let next = levels
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| pair.iter().try_fold(1u128, |product, &node| node.and_then(|v| product.checked_mul(v))))
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+/// n mod each of the moduli, reduced down a product tree: n mod the product
+/// of everything, then mod each half's product, and so on to the leaves.
+/// Nodes too big for a u128 leave n as it is.
+///
+/// The same answers as reducing n by each modulus directly. The tree saves
+/// work when n is many times the size of the moduli, which a u128 n
+/// rarely is; `remainder_tree_big` is the one for big n. Panics if a
+/// modulus is 0.
+///
+/// ```
+/// let moduli = [7, 11, 13, 1_000_003];
+/// let n = 123_456_789_012_345_678_901u128;
+/// let expected: Vec<u64> = moduli.iter().map(|&m| (n % m as u128) as u64).collect();
+/// assert_eq!(prime_tools::remainder_tree(n, &moduli), expected);
+/// ```
+pub fn remainder_tree(n: u128, moduli: &[u64]) -> Vec<u64> {
+    if moduli.is_empty() {
+        return Vec::new();
+    }
+    let levels = product_tree(moduli);
+    let mut remainders = vec![n];
+    for level in levels.iter().rev() {
+        remainders = level.iter().enumerate().map(|(i, node)| node.map_or(remainders[i / 2], |m| remainders[i / 2] % m)).collect();
+    }
+    remainders.into_iter().map(|r| r as u64).collect()
+}
+
+/// `product_tree` on BigUints, where nothing overflows. Only with the `bignum` feature.
+///
+/// ```
+/// use num_bigint::BigUint;
+///
+/// let values: Vec<BigUint> = (1u32..=20).map(BigUint::from).collect();
+/// let tree = prime_tools::product_tree_big(&values);
+/// assert_eq!(tree.len(), 6);
+/// assert_eq!(tree[5][0], BigUint::from(2_432_902_008_176_640_000u64)); // 20!
+/// ```
+#[cfg(feature = "bignum")]
+pub fn product_tree_big(values: &[BigUint]) -> Vec<Vec<BigUint>> {
+    let mut levels = vec![values.to_vec()];
+    while levels.last().unwrap().len() > 1 {
+        let next = levels.last().unwrap().chunks(2).map(|pair| pair.iter().product()).collect();
+        levels.push(next);
+    }
+    levels
+}
+
+/// `remainder_tree` on BigUints: n mod each modulus, with n as big as you
+/// like. Reducing a number of k times the moduli's size this way costs
+/// about what multiplying it out did, rather than k divisions of it per
+/// modulus. Only with the `bignum` feature. Panics if a modulus is 0.
+///
+/// ```
+/// use num_bigint::BigUint;
+///
+/// // 100! mod a few primes above 100.
+/// let factorial: BigUint = (1u32..=100).map(BigUint::from).product();
+/// let moduli: Vec<BigUint> = [101u32, 103, 107].iter().map(|&p| BigUint::from(p)).collect();
+/// let remainders = prime_tools::remainder_tree_big(&factorial, &moduli);
+/// // Wilson: (p - 1)! ≡ -1 (mod p).
+/// assert_eq!(remainders[0], BigUint::from(100u32));
+/// ```
+#[cfg(feature = "bignum")]
+pub fn remainder_tree_big(n: &BigUint, moduli: &[BigUint]) -> Vec<BigUint> {
+    if moduli.is_empty() {
+        return Vec::new();
+    }
+    let levels = product_tree_big(moduli);
+    let mut remainders = vec![n.clone()];
+    for level in levels.iter().rev() {
+        remainders = level.iter().enumerate().map(|(i, node)| &remainders[i / 2] % node).collect();
+    }
+    remainders
+}
+
+/// Which values have every prime factor in primes (are "B-smooth" for
+/// the primes up to B), by Bernstein's batch method.
+///
+/// Rather than trial dividing each value, it finds P mod v for the product
+/// P of all the primes: down a remainder tree with the `bignum` feature,
+/// and without it a u128 chunk of P at a time. v is smooth exactly when
+/// v divides (P mod v)^(2^6), since no prime appears in a u64 more than 63
+/// times. 1 counts as smooth and 0 doesn't.
+///
+/// ```
+/// let primes = [2, 3, 5, 7];
+/// let values = [360, 11, 1, 0, 2 * 3 * 5 * 7 * 7 * 7, 1_000_003, 1 << 63];
+/// assert_eq!(
+///     prime_tools::batch_smooth_filter(&values, &primes),
+///     vec![true, false, true, false, true, false, true]
+/// );
+/// ```
+pub fn batch_smooth_filter(values: &[u64], primes: &[u64]) -> Vec<bool> {
+    let _span = debug_span!("batch_smooth_filter", values = values.len(), primes = primes.len());
+    let remainders = product_remainders(values, primes);
+    values
+        .iter()
+        .zip(remainders)
+        .map(|(&v, r)| match v {
+            0 => false,
+            1 => true,
+            _ => (0..6).fold(r, |r, _| crate::mul_mod_u64(r, r, v)) == 0,
+        })
+        .collect()
+}
+
+/// The product of factors mod each value (0 for a value of 0 or 1).
+#[cfg(feature = "bignum")]
+fn product_remainders(values: &[u64], factors: &[u64]) -> Vec<u64> {
+    let moduli: Vec<BigUint> = values.iter().map(|&v| BigUint::from(v.max(1))).collect();
+    let leaves: Vec<BigUint> = factors.iter().map(|&p| BigUint::from(p)).collect();
+    let product = product_tree_big(&leaves).pop().unwrap().pop().unwrap_or_else(|| BigUint::from(1u32));
+    remainder_tree_big(&product, &moduli).iter().map(|r| u64::try_from(r).unwrap()).collect()
+}
+
+#[cfg(not(feature = "bignum"))]
+fn product_remainders(values: &[u64], factors: &[u64]) -> Vec<u64> {
+    let moduli: Vec<u64> = values.iter().map(|&v| v.max(1)).collect();
+    let mut remainders: Vec<u64> = moduli.iter().map(|&m| 1 % m).collect();
+    let mut chunk = 1u128;
+    let mut flush = |chunk: u128| {
+        for (r, &m) in remainders.iter_mut().zip(&moduli) {
+            *r = crate::mul_mod_u64(*r, (chunk % m as u128) as u64, m);
+        }
+    };
+    for &p in factors {
+        match chunk.checked_mul(p as u128) {
+            Some(product) => chunk = product,
+            None => {
+                flush(chunk);
+                chunk = p as u128;
+            }
+        }
+    }
+    flush(chunk);
+    remainders
+}
+
+/// The factorization of every x, in order, split across threads.
+///
+/// Each number gets trial division by the primes below 2^16, from the one
+/// table every thread shares, then deterministic Miller–Rabin and Brent's
+/// rho on whatever is left, as in `factorize_with_report`: quick for any
+/// u64, where `factorize` can take seconds on a semiprime. The numbers are
+/// handed out a chunk at a time from a shared counter, so a thread stuck on
+/// a hard chunk doesn't hold the others up. Small batches stay on the
+/// calling thread. The workers are std scoped threads, like the sieve's,
+/// rather than a rayon pool: one counter is all the scheduling this needs,
+/// and the crate stays free of a thread-pool dependency.
+///
+/// Panics if any x is 0, which has no factorization.
+///
+/// ```
+/// use prime_tools::{factorize_many, Factorization};
+///
+/// let xs = [360, 1, 4_294_967_291 * 4_294_967_279, u64::MAX];
+/// let factorizations = factorize_many(&xs);
+/// assert_eq!(factorizations[0], Factorization::new(360));
+/// assert_eq!(factorizations[1], Factorization::one());
+/// assert_eq!(factorizations[2].factors(), &[(4_294_967_279, 1), (4_294_967_291, 1)]);
+/// assert_eq!(factorizations[3].to_u64(), Some(u64::MAX));
+/// ```
+pub fn factorize_many(xs: &[u64]) -> Vec<Factorization> {
+    // Numbers handed to a thread at a time.
+    const CHUNK: usize = 256;
+    assert!(xs.iter().all(|&x| x > 0), "0 has no factorization");
+    let _span = debug_span!("factorize_many", count = xs.len());
+    let chunks = xs.len().div_ceil(CHUNK);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(chunks);
+    if threads <= 1 {
+        return xs.iter().map(|&x| factorize_quickly(x)).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut done: Vec<(usize, Vec<Factorization>)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let chunk = next.fetch_add(1, Ordering::Relaxed);
+                        if chunk >= chunks {
+                            return done;
+                        }
+                        let start = chunk * CHUNK;
+                        let end = (start + CHUNK).min(xs.len());
+                        done.push((chunk, xs[start..end].iter().map(|&x| factorize_quickly(x)).collect()));
+                    }
+                })
+            })
+            .collect();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    });
+    done.sort_unstable_by_key(|&(chunk, _)| chunk);
+    done.into_iter().flat_map(|(_, factorizations)| factorizations).collect()
+}
+
+/// The factorization of x > 0 by `factor_chain`, without the report.
+pub(crate) fn factorize_quickly(x: u64) -> Factorization {
+    Factorization::from_factors(&factor_chain(x, |_, _, _| {}).factors)
+}
+
+/// gcd(v_i, the product of all the other values) for every i: the product
+/// of everything goes down the tree mod each node squared.
+#[cfg(feature = "bignum")]
+fn batch_gcd(values: &[u128]) -> Vec<u128> {
+    let leaves: Vec<BigUint> = values.iter().map(|&v| BigUint::from(v)).collect();
+    let levels = product_tree_big(&leaves);
+    let mut remainders = levels.last().unwrap().clone();
+    for level in levels.iter().rev().skip(1) {
+        remainders = level.iter().enumerate().map(|(i, node)| &remainders[i / 2] % (node * node)).collect();
+    }
+    values
+        .iter()
+        .zip(remainders)
+        .map(|(&v, r)| {
+            let quotient = u128::try_from(&(r / v)).expect("P mod v^2, over v, is below v");
+            gcd_u128(quotient, v)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::miller_rabin_with_bases;
+    use crate::primality::U64_BASES;
+
+    #[test]
+    fn finds_shared_primes_in_a_large_batch() {
+        // 200 products of distinct primes, with three sharing one prime and another pair sharing a second.
+        let primes: Vec<u128> = crate::get_primes_between(1_000_000, 1_020_000).into_iter().map(u128::from).collect();
+        let mut nums: Vec<u128> = (0..200).map(|i| primes[2 * i] * primes[2 * i + 1]).collect();
+        nums[10] = primes[20] * primes[500];
+        nums[20] = primes[500] * primes[501];
+        nums[150] = primes[500] * primes[502];
+        nums[30] = primes[83] * primes[700];
+        nums.push(0);
+        nums.push(1);
+
+        let mut expected = Vec::new();
+        for i in 0..nums.len() {
+            for j in i + 1..nums.len() {
+                let g = gcd_u128(nums[i], nums[j]);
+                if g > 1 && nums[i] > 1 && nums[j] > 1 {
+                    expected.push((i, j, g));
+                }
+            }
+        }
+        assert_eq!(pairwise_gcd_scan(&nums), expected);
+        assert!(expected.len() >= 4);
+    }
+
+    #[test]
+    fn smooth_filter_matches_factorization() {
+        let primes = crate::get_primes_between(2, 300);
+        let values: Vec<u64> = (0..3_000).chain(1_000_000_000_000..1_000_000_000_200).collect();
+        let smooth = batch_smooth_filter(&values, &primes);
+        for (&v, &is_smooth) in values.iter().zip(&smooth) {
+            let expected = v > 0 && crate::factorize(v).iter().all(|&(p, _)| p < 300);
+            assert_eq!(is_smooth, expected, "{}", v);
+        }
+        assert_eq!(batch_smooth_filter(&[1, 2, 6], &[]), vec![true, false, false]);
+    }
+
+    #[test]
+    fn remainder_tree_matches_direct_reduction() {
+        let moduli: Vec<u64> = (0..37).map(|i| u64::MAX - 2 * i).chain(1..20).collect();
+        for &n in &[0, 1, u128::MAX, 0x1234_5678_9abc_def0_1122_3344_5566_7788] {
+            let expected: Vec<u64> = moduli.iter().map(|&m| (n % m as u128) as u64).collect();
+            assert_eq!(remainder_tree(n, &moduli), expected);
+        }
+        assert_eq!(remainder_tree(5, &[]), Vec::<u64>::new());
+        assert_eq!(product_tree(&[]), vec![Vec::new()]);
+    }
+
+    #[test]
+    fn factorize_many_agrees_with_factorize() {
+        let xs: Vec<u64> = (1..3_000).chain(1_000_000_000_000..1_000_000_000_300).chain(u64::MAX - 300..=u64::MAX).collect();
+        let factorizations = factorize_many(&xs);
+        assert_eq!(factorizations.len(), xs.len());
+        for (&x, factorization) in xs.iter().zip(&factorizations) {
+            assert_eq!(factorization.to_u64(), Some(x));
+            assert!(factorization.factors().iter().all(|&(p, _)| miller_rabin_with_bases(p as u128, &U64_BASES)), "{}", x);
+            if x < 3_000 {
+                assert_eq!(factorization.factors(), &crate::factorize(x)[..]);
+            }
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Arbitrary-precision arithmetic on `num_bigint::BigUint`.
+//!
+//! Only built with the `bignum` feature.
+
+use num_bigint::BigUint;
+
+/// base^exponent mod modulus.
+///
+/// Odd moduli, the ones primality tests use, go through Montgomery
+/// multiplication on 64-bit limbs with fixed-window exponentiation: about one
+/// multiplication per bit of the exponent, no division until the result is
+/// converted back, and no allocation inside the loop. Even moduli fall back
+/// to `BigUint::modpow`. Panics if modulus is 0.
+///
+/// ```
+/// use num_bigint::BigUint;
+///
+/// let p = (BigUint::from(1u32) << 127u32) - 1u32; // a Mersenne prime
+/// let base = BigUint::from(3u32);
+/// let exponent = &p - 1u32;
+/// assert_eq!(prime_tools::mod_pow_big(&base, &exponent, &p), BigUint::from(1u32));
+/// ```
+pub fn mod_pow_big(base: &BigUint, exponent: &BigUint, modulus: &BigUint) -> BigUint {
+    assert!(modulus.bits() > 0, "mod_pow_big needs a nonzero modulus");
+    if !modulus.bit(0) {
+        return base.modpow(exponent, modulus);
+    }
+    Montgomery::new(modulus).pow(base, exponent)
+}
+
+/// True if x is a strong probable prime to each of the first twelve prime
+/// bases, which no composite below 3.18 * 10^23 passes.
+pub(crate) fn is_probable_prime_big(x: &BigUint) -> bool {
+    const BASES: [u32; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if x.bits() <= 64 {
+        let low = x.to_u64_digits().first().copied().unwrap_or(0);
+        return crate::miller_rabin_with_bases(low as u128, &crate::primality::U64_BASES);
+    }
+    if BASES.iter().any(|&p| (x % p).bits() == 0) {
+        return false;
+    }
+    let minus_one = x - 1u32;
+    let s = minus_one.trailing_zeros().unwrap_or(0);
+    let d = &minus_one >> s;
+    // The squarings stay in Montgomery form; 1 and -1 are compared there too.
+    let montgomery = Montgomery::new(x);
+    let limbs = montgomery.n.len();
+    let minus_one = montgomery.encode(&minus_one);
+    let mut scratch = montgomery.scratch();
+    BASES.iter().all(|&a| {
+        let mut y = montgomery.power(&montgomery.encode(&BigUint::from(a)), &d);
+        if y[..limbs] == montgomery.one[..limbs] || y[..limbs] == minus_one[..limbs] {
+            return true;
+        }
+        for _ in 1..s {
+            montgomery.mul(&y, &y, &mut scratch);
+            std::mem::swap(&mut y, &mut scratch);
+            if y[..limbs] == minus_one[..limbs] {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// Montgomery arithmetic mod an odd n of s 64-bit limbs, with R = 2^(64s).
+///
+/// Values in Montgomery form are kept in buffers of s + 1 limbs, the length
+/// `mul` needs to work in, so a product can be swapped in as the next operand.
+/// Only the low s limbs mean anything.
+pub(crate) struct Montgomery {
+    /// n's limbs, least significant first.
+    n: Vec<u64>,
+    /// -n⁻¹ mod 2^64.
+    n_prime: u64,
+    /// R² mod n, for converting into Montgomery form.
+    r_squared: Vec<u64>,
+    /// R mod n, which is 1 in Montgomery form.
+    one: Vec<u64>,
+    modulus: BigUint,
+}
+
+impl Montgomery {
+    pub(crate) fn new(modulus: &BigUint) -> Montgomery {
+        let n = modulus.to_u64_digits();
+        let s = n.len();
+        // Newton's iteration for n⁻¹ mod 2^64: each step doubles the correct bits.
+        let mut inverse = 1u64;
+        for _ in 0..6 {
+            inverse = inverse.wrapping_mul(2u64.wrapping_sub(n[0].wrapping_mul(inverse)));
+        }
+        let r_squared = (BigUint::from(1u32) << (128 * s)) % modulus;
+        let one = (BigUint::from(1u32) << (64 * s)) % modulus;
+        Montgomery {
+            r_squared: limbs(&r_squared, s + 1),
+            one: limbs(&one, s + 1),
+            n_prime: inverse.wrapping_neg(),
+            n,
+            modulus: modulus.clone(),
+        }
+    }
+
+    /// base^exponent mod n.
+    pub(crate) fn pow(&self, base: &BigUint, exponent: &BigUint) -> BigUint {
+        self.decode(&self.power(&self.encode(base), exponent))
+    }
+
+    /// x^exponent, both in Montgomery form, a window of bits at a time: square
+    /// once per bit, then multiply by the window's power of x from a table.
+    fn power(&self, x: &[u64], exponent: &BigUint) -> Vec<u64> {
+        let bits = exponent.bits();
+        // Wider windows mean fewer multiplications but a bigger table to fill first.
+        let width = match bits {
+            0..=32 => 1,
+            33..=128 => 3,
+            129..=512 => 4,
+            _ => 5,
+        };
+        let mut table = vec![self.one.clone(), x.to_vec()];
+        for i in 2..1 << width {
+            let mut next = self.scratch();
+            self.mul(&table[i - 1], x, &mut next);
+            table.push(next);
+        }
+
+        let windows = bits.div_ceil(width);
+        let mut result = self.one.clone();
+        let mut scratch = self.scratch();
+        for window in (0..windows).rev() {
+            let mut digit = 0;
+            for bit in (window * width..(window + 1) * width).rev() {
+                // The top window has nothing to square yet.
+                if window + 1 < windows {
+                    self.mul(&result, &result, &mut scratch);
+                    std::mem::swap(&mut result, &mut scratch);
+                }
+                digit = digit << 1 | exponent.bit(bit) as usize;
+            }
+            if digit != 0 {
+                self.mul(&result, &table[digit], &mut scratch);
+                std::mem::swap(&mut result, &mut scratch);
+            }
+        }
+        result
+    }
+
+    /// x * R mod n.
+    fn encode(&self, x: &BigUint) -> Vec<u64> {
+        let mut out = self.scratch();
+        self.mul(&limbs(&(x % &self.modulus), self.n.len()), &self.r_squared, &mut out);
+        out
+    }
+
+    /// x / R mod n, back out of Montgomery form.
+    fn decode(&self, x: &[u64]) -> BigUint {
+        let mut unit = vec![0u64; self.n.len()];
+        unit[0] = 1;
+        let mut out = self.scratch();
+        self.mul(x, &unit, &mut out);
+        from_limbs(&out[..self.n.len()])
+    }
+
+    /// A buffer for `mul` to write into.
+    fn scratch(&self) -> Vec<u64> {
+        vec![0u64; self.n.len() + 1]
+    }
+
+    /// a * b / R mod n into t[..s], for a, b < n, a limb of b at a time
+    /// (FIOS). t has s + 1 limbs and mustn't be a or b.
+    fn mul(&self, a: &[u64], b: &[u64], t: &mut [u64]) {
+        let s = self.n.len();
+        // Slicing everything to s up front lets the compiler drop the bounds checks.
+        let (a, b, n, t) = (&a[..s], &b[..s], &self.n[..s], &mut t[..s + 1]);
+        t.iter_mut().for_each(|limb| *limb = 0);
+        for &b_i in b {
+            // t + a * b_i + m * n in one pass, with m chosen to make the lowest
+            // limb 0 so the sum shifts down a limb as it goes. t stays below 2n.
+            let sum = t[0] as u128 + a[0] as u128 * b_i as u128;
+            let m = (sum as u64).wrapping_mul(self.n_prime);
+            let mut product_carry = sum >> 64;
+            let mut reduce_carry = ((sum as u64) as u128 + m as u128 * n[0] as u128) >> 64;
+            for j in 1..s {
+                let sum = t[j] as u128 + a[j] as u128 * b_i as u128 + product_carry;
+                product_carry = sum >> 64;
+                let sum = (sum as u64) as u128 + m as u128 * n[j] as u128 + reduce_carry;
+                reduce_carry = sum >> 64;
+                t[j - 1] = sum as u64;
+            }
+            let sum = t[s] as u128 + product_carry + reduce_carry;
+            t[s - 1] = sum as u64;
+            t[s] = (sum >> 64) as u64;
+        }
+
+        // t < 2n; one subtraction brings it below n.
+        if t[s] != 0 || !less_than(&t[..s], n) {
+            let mut borrow = 0u64;
+            for (t_j, &n_j) in t.iter_mut().zip(n) {
+                let (difference, b1) = t_j.overflowing_sub(n_j);
+                let (difference, b2) = difference.overflowing_sub(borrow);
+                *t_j = difference;
+                borrow = (b1 || b2) as u64;
+            }
+        }
+    }
+}
+
+/// x's limbs, least significant first, padded to len.
+fn limbs(x: &BigUint, len: usize) -> Vec<u64> {
+    let mut digits = x.to_u64_digits();
+    digits.resize(len, 0);
+    digits
+}
+
+fn from_limbs(limbs: &[u64]) -> BigUint {
+    let digits: Vec<u32> = limbs.iter().flat_map(|&limb| vec![limb as u32, (limb >> 32) as u32]).collect();
+    BigUint::new(digits)
+}
+
+/// a < b for little-endian limbs of the same length.
+fn less_than(a: &[u64], b: &[u64]) -> bool {
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return x < y;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::{RngCore, SeedableRng};
+
+    fn random_big(rng: &mut impl RngCore, limbs: usize) -> BigUint {
+        let digits: Vec<u32> = (0..2 * limbs).map(|_| rng.next_u32()).collect();
+        BigUint::new(digits)
+    }
+
+    #[test]
+    fn matches_modpow() {
+        let mut rng = rand_pcg::Pcg64::seed_from_u64(661);
+        for limbs in 1..=9 {
+            for _ in 0..20 {
+                let modulus = random_big(&mut rng, limbs) | BigUint::from(1u32);
+                let base = random_big(&mut rng, limbs + 1);
+                let exponent = random_big(&mut rng, 2);
+                assert_eq!(mod_pow_big(&base, &exponent, &modulus), base.modpow(&exponent, &modulus));
+            }
+        }
+        // Exponents long enough for every window width.
+        let modulus = random_big(&mut rng, 4) | BigUint::from(1u32);
+        for limbs in 0..=12 {
+            let exponent = random_big(&mut rng, limbs) >> (rng.next_u32() % 64);
+            let base = random_big(&mut rng, 4);
+            assert_eq!(mod_pow_big(&base, &exponent, &modulus), base.modpow(&exponent, &modulus), "{}", exponent);
+        }
+    }
+
+    #[test]
+    fn probable_primes_past_u64() {
+        let big = |x: &str| x.parse::<BigUint>().unwrap();
+        let mersenne = |p: u32| (BigUint::from(1u32) << p) - 1u32;
+        assert!(is_probable_prime_big(&mersenne(89)) && is_probable_prime_big(&mersenne(127)));
+        assert!(!is_probable_prime_big(&(mersenne(89) * mersenne(61))));
+        // The smallest composite that is a strong probable prime to all twelve bases.
+        assert!(is_probable_prime_big(&big("318665857834031151167461")));
+        assert!(!is_probable_prime_big(&big("318665857834031151167463")));
+    }
+
+    #[test]
+    fn edges() {
+        let big = |x: u64| BigUint::from(x);
+        assert_eq!(mod_pow_big(&big(5), &big(0), &big(1)), big(0));
+        assert_eq!(mod_pow_big(&big(5), &big(0), &big(7)), big(1));
+        assert_eq!(mod_pow_big(&big(0), &big(0), &big(7)), big(1));
+        assert_eq!(mod_pow_big(&big(7), &big(3), &big(7)), big(0));
+        assert_eq!(mod_pow_big(&big(3), &big(5), &big(100)), big(43));
+        // All-ones limbs push every carry as far as it goes.
+        let modulus = (BigUint::from(1u32) << 256u32) - 1u32;
+        let base = &modulus - 1u32;
+        assert_eq!(mod_pow_big(&base, &big(2), &modulus), big(1));
+        assert_eq!(mod_pow_big(&base, &big(u64::MAX), &modulus), base);
+    }
+}
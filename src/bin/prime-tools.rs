@@ -0,0 +1,636 @@
+//! Command-line front end to prime_tools.
+//!
+//! `prime-tools <command> <expression>...`, where every expression is
+//! evaluated with `evaluate_expression` first, so `prime-tools isprime 2^61-1`
+//! works as written. `prime-tools repl` reads the same commands line by line
+//! and keeps one sieve for the whole session, and `prime-tools generate`
+//! writes the primes in a range to a directory of shard files. The sieving
+//! commands take `--threads` and `--memory` caps, or read them from the
+//! environment.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+
+use prime_tools::{
+    evaluate_expression, factorize_with_report, is_strong_lucas_probable_prime, miller_rabin_with_bases, try_primes_into_with_options,
+    ExpressionError, Sieve, SieveError, SieveOptions,
+};
+
+const USAGE: &str = "usage: prime-tools <command> <expression>...
+       prime-tools repl
+       prime-tools generate [--min A] --max B [--shards N] --out DIR [LIMITS]
+       prime-tools verify [--min A] --max B [LIMITS]
+       prime-tools gaps [--min A] --max B [--min-gap G] [LIMITS]
+
+commands:
+  isprime   whether each number is prime (Baillie-PSW, exact below 2^64)
+  factor    the prime factorization of each number, up to 2^64 - 1
+  next      the first prime after each number
+  pi        how many primes there are up to each number, up to 2^28
+  gap       the primes on either side of each number and the gap between them
+  eval      just the value of each expression
+  repl      read commands from stdin, one per line, sharing one sieve
+  generate  write the primes in [A, B) to N shard files in DIR, in parallel,
+            one prime per line, plus a manifest.txt listing the shards;
+            A defaults to 0 and N to 1
+  verify    check the sieve against Baillie-PSW for every number in [A, B)
+            and report disagreements; exits with 1 if there are any
+  gaps      print `gap lower_prime` for each gap of at least G (default 1)
+            between consecutive primes in [A, B), as they're found
+
+LIMITS are --threads T, the most sieving threads to run at once (default:
+one per core), and --memory M, a cap in bytes on what the sieves allocate
+(default: none). They can also come from PRIME_TOOLS_THREADS and
+PRIME_TOOLS_MEMORY; flags win. A range that can't be sieved within the cap
+fails instead of exhausting the machine.
+
+Expressions are u128 integers with + - * / % ^, parentheses and fact(n),
+e.g. 2^127-1, 10^18+9 or fact(20)+1. Quote them if your shell minds.
+
+Errors go to stderr as one JSON object per line, like
+  {\"error\":\"out_of_range\",\"message\":\"...\",\"input\":\"2^64\"}
+and set the exit code:
+  1  verify found mismatches
+  2  usage: no command, unknown command or option, missing value
+     (error \"usage\", with this text as the message when there's no command)
+  3  unreadable expression (\"empty\", \"syntax\")
+  4  arithmetic (\"overflow\", \"negative\", \"division_by_zero\")
+  5  a value the command can't take (\"out_of_range\")
+  6  reading or writing files (\"io\")
+  7  a sieve that doesn't fit in --memory (\"memory_limit\")";
+
+const COMMANDS: [&str; 6] = ["isprime", "factor", "next", "pi", "gap", "eval"];
+
+/// The session sieve stops growing here; numbers past it are tested one at a
+/// time. 2^28 keeps the sieve's prime list around 120 MB.
+const SIEVE_CAP: u64 = 1 << 28;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut session = Session { sieve: Sieve::new(1 << 16) };
+    if args.len() == 1 && args[0] == "repl" {
+        return session.repl();
+    }
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) if !rest.is_empty() => (command.as_str(), rest),
+        _ => usage(),
+    };
+    let result = match command {
+        "generate" => generate(rest),
+        "gaps" => gaps(rest),
+        "verify" => match verify(rest) {
+            Ok(true) => Ok(()),
+            Ok(false) => process::exit(1),
+            Err(failure) => Err(failure),
+        },
+        _ if COMMANDS.contains(&command) => {
+            // Carry on past a bad expression, but exit with the first failure's code.
+            let mut exit_code = 0;
+            for expression in rest {
+                match session.run(command, expression) {
+                    Ok(line) => println!("{}", line),
+                    Err(failure) => {
+                        let failure = failure.with_input(expression);
+                        failure.report();
+                        if exit_code == 0 {
+                            exit_code = failure.exit_code();
+                        }
+                    }
+                }
+            }
+            process::exit(exit_code);
+        }
+        _ => Err(Failure::new("usage", format!("unknown command {}", command)).with_input(command)),
+    };
+    if let Err(failure) = result {
+        failure.report();
+        process::exit(failure.exit_code());
+    }
+}
+
+/// A failed command, reported on stderr as a line of JSON.
+#[derive(Debug)]
+struct Failure {
+    /// A stable identifier for scripts to branch on; see USAGE.
+    code: &'static str,
+    message: String,
+    /// The expression or option value it's about, if any.
+    input: Option<String>,
+}
+
+impl Failure {
+    fn new(code: &'static str, message: impl Into<String>) -> Failure {
+        Failure { code, message: message.into(), input: None }
+    }
+
+    fn with_input(mut self, input: &str) -> Failure {
+        self.input.get_or_insert_with(|| input.to_string());
+        self
+    }
+
+    fn exit_code(&self) -> i32 {
+        match self.code {
+            "usage" => 2,
+            "empty" | "syntax" => 3,
+            "overflow" | "negative" | "division_by_zero" => 4,
+            "out_of_range" => 5,
+            "io" => 6,
+            "memory_limit" => 7,
+            _ => 1,
+        }
+    }
+
+    /// No command, or nothing for it to work on: the usage text as the message.
+    fn usage() -> Failure {
+        Failure::new("usage", USAGE)
+    }
+
+    fn to_json(&self) -> String {
+        let mut json = format!("{{\"error\":{},\"message\":{}", json_string(self.code), json_string(&self.message));
+        if let Some(input) = &self.input {
+            json += &format!(",\"input\":{}", json_string(input));
+        }
+        json + "}"
+    }
+
+    fn report(&self) {
+        eprintln!("{}", self.to_json());
+    }
+}
+
+impl From<ExpressionError> for Failure {
+    fn from(error: ExpressionError) -> Failure {
+        let code = match error {
+            ExpressionError::Empty => "empty",
+            ExpressionError::Syntax(_) => "syntax",
+            ExpressionError::Overflow => "overflow",
+            ExpressionError::Negative => "negative",
+            ExpressionError::DivisionByZero => "division_by_zero",
+        };
+        Failure::new(code, error.to_string())
+    }
+}
+
+impl From<SieveError> for Failure {
+    fn from(error: SieveError) -> Failure {
+        let code = match error {
+            SieveError::MemoryLimit { .. } => "memory_limit",
+            SieveError::AlgorithmLimit { .. } => "out_of_range",
+        };
+        Failure::new(code, error.to_string())
+    }
+}
+
+/// s as a quoted JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => quoted += &format!("\\u{:04x}", c as u32),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// State that lives as long as the process: in the REPL, that's every command.
+struct Session {
+    /// Grown on demand, doubling each time, up to SIEVE_CAP.
+    sieve: Sieve,
+}
+
+impl Session {
+    fn repl(&mut self) {
+        println!("prime-tools repl: {}, help or quit", COMMANDS.join(", "));
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            print!("> ");
+            io::stdout().flush().expect("couldn't write to stdout");
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                _ => break,
+            };
+            let line = line.trim();
+            let (command, expression) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match command {
+                "" => {}
+                "quit" | "exit" => break,
+                "help" => println!("{}", USAGE),
+                _ if COMMANDS.contains(&command) => match self.run(command, expression) {
+                    Ok(output) => println!("{}", output),
+                    Err(failure) => println!("error: {}", failure.message),
+                },
+                _ => println!("error: unknown command {}; try help", command),
+            }
+        }
+    }
+
+    /// The output line for one expression.
+    fn run(&mut self, command: &str, expression: &str) -> Result<String, Failure> {
+        let x = evaluate_expression(expression)?;
+        let out_of_range = |message: String| Failure::new("out_of_range", message);
+        match command {
+            "isprime" => Ok(format!("{} is {}", x, if self.is_prime(x) { "prime" } else { "not prime" })),
+            "factor" => {
+                if x == 0 || x > u64::MAX as u128 {
+                    return Err(out_of_range(format!("{} is out of range; factor takes 1 to 2^64 - 1", x)));
+                }
+                // Trial division, Miller–Rabin and rho, so any u64 comes back quickly.
+                let (factorization, _) = factorize_with_report(x as u64);
+                Ok(format!("{} = {}", x, factorization))
+            }
+            "next" => {
+                let p = self.next_prime(x).ok_or_else(|| out_of_range("no prime after it fits in a u128".to_string()))?;
+                Ok(format!("{} is the first prime after {}", p, x))
+            }
+            "pi" => {
+                if x >= SIEVE_CAP as u128 {
+                    return Err(out_of_range(format!("{} is out of range; pi takes up to 2^28 - 1", x)));
+                }
+                let x = x as u64;
+                self.grow(x + 1);
+                Ok(format!("pi({}) = {}", x, self.sieve.primes().partition_point(|&p| p <= x)))
+            }
+            "gap" => {
+                let below = self.previous_prime(x).ok_or_else(|| out_of_range("no prime at or below it".to_string()))?;
+                let above = self.next_prime(x).ok_or_else(|| out_of_range("no prime after it fits in a u128".to_string()))?;
+                Ok(format!("{} and {}, a gap of {}", below, above, above - below))
+            }
+            _ => Ok(x.to_string()),
+        }
+    }
+
+    fn is_prime(&mut self, x: u128) -> bool {
+        if x < SIEVE_CAP as u128 {
+            self.grow(x as u64 + 1);
+            return self.sieve.is_prime(x as u64) == Some(true);
+        }
+        is_bpsw_prime(x)
+    }
+
+    fn next_prime(&mut self, x: u128) -> Option<u128> {
+        (x.checked_add(1)?..=u128::MAX).find(|&y| self.is_prime(y))
+    }
+
+    fn previous_prime(&mut self, x: u128) -> Option<u128> {
+        (2..=x).rev().find(|&y| self.is_prime(y))
+    }
+
+    /// Makes the sieve cover every x < limit, for limit <= SIEVE_CAP.
+    fn grow(&mut self, limit: u64) {
+        if limit > self.sieve.limit() {
+            self.sieve.extend_to(limit.max(2 * self.sieve.limit()).min(SIEVE_CAP));
+        }
+    }
+}
+
+/// First line of a shard manifest.
+const MANIFEST_HEADER: &str = "prime_tools prime shards v1";
+
+/// Numbers sieved per step within a shard, which bounds each shard's memory.
+const SHARD_STEP: u64 = 1 << 24;
+
+/// `prime-tools generate`, given the arguments after the command.
+///
+/// `[min, max)` is cut into `shards` nearly equal subranges, each sieved by a
+/// thread of its own into `primes-<i>.txt`. The manifest is written last, so
+/// a directory with a manifest is complete.
+fn generate(args: &[String]) -> Result<(), Failure> {
+    let (mut min, mut max, mut shards, mut out) = (0, None, 1, None);
+    let mut limits = Limits::from_env()?;
+    for (flag, value) in flags(args)? {
+        match flag {
+            "--min" => min = number_flag(value)?,
+            "--max" => max = Some(number_flag(value)?),
+            "--shards" => shards = number_flag(value)?,
+            "--out" => out = Some(PathBuf::from(value)),
+            _ if limits.set(flag, value)? => {}
+            _ => return Err(unknown_option(flag)),
+        }
+    }
+    let max = max.ok_or_else(|| Failure::new("usage", "--max is required"))?;
+    let out = out.ok_or_else(|| Failure::new("usage", "--out is required"))?;
+    check_range(min, max)?;
+    if shards == 0 || shards > 10_000 {
+        return Err(Failure::new("out_of_range", "--shards takes 1 to 10000").with_input(&shards.to_string()));
+    }
+
+    fs::create_dir_all(&out).map_err(|error| io_failure(&out, error))?;
+    // At most `threads` shards at a time, splitting the threads and memory
+    // between them rather than letting every shard's sieve take them all.
+    let batch = limits.threads.min(shards as usize);
+    let options = limits.options(batch);
+    let ranges = split_range(min, max, shards);
+
+    let mut results: Vec<Result<u64, Failure>> = Vec::new();
+    for (b, batch_ranges) in ranges.chunks(batch).enumerate() {
+        thread::scope(|scope| {
+            let handles: Vec<_> = batch_ranges
+                .iter()
+                .enumerate()
+                .map(|(i, &(start, end))| {
+                    let path = out.join(shard_name(b * batch + i, shards));
+                    let options = &options;
+                    scope.spawn(move || write_shard(&path, start, end, options))
+                })
+                .collect();
+            results.extend(handles.into_iter().map(|handle| handle.join().expect("shard thread panicked")));
+        });
+    }
+
+    let mut manifest = format!("{}\nmin {}\nmax {}\nshards {}\n", MANIFEST_HEADER, min, max, shards);
+    for (i, (&(start, end), result)) in ranges.iter().zip(results).enumerate() {
+        let name = shard_name(i, shards);
+        let count = result?;
+        manifest += &format!("shard {} {} {} {}\n", name, start, end, count);
+    }
+    let path = out.join("manifest.txt");
+    fs::write(&path, manifest).map_err(|error| io_failure(&path, error))
+}
+
+/// `primes-<i>.txt`, with i zero-padded so the files sort in order.
+fn shard_name(i: usize, shards: u64) -> String {
+    let digits = (shards - 1).to_string().len();
+    format!("primes-{:0digits$}.txt", i, digits = digits)
+}
+
+/// Writes the primes in `[start, end)` to `path`, one per line, and returns how many there were.
+fn write_shard(path: &Path, start: u64, end: u64, options: &SieveOptions) -> Result<u64, Failure> {
+    let io_failure = |error| io_failure(path, error);
+    let mut file = BufWriter::new(File::create(path).map_err(io_failure)?);
+    let (mut primes, mut count) = (Vec::new(), 0);
+    let mut low = start;
+    while low < end {
+        let high = low.saturating_add(SHARD_STEP).min(end);
+        try_primes_into_with_options(low, high, options, &mut primes)?;
+        for p in &primes {
+            writeln!(file, "{}", p).map_err(io_failure)?;
+        }
+        count += primes.len() as u64;
+        low = high;
+    }
+    file.flush().map_err(io_failure)?;
+    Ok(count)
+}
+
+/// Baillie–PSW: no composite is known to pass, and none below 2^64 does.
+fn is_bpsw_prime(x: u128) -> bool {
+    miller_rabin_with_bases(x, &[2]) && is_strong_lucas_probable_prime(x)
+}
+
+/// A mismatch between the sieve and the one-at-a-time test.
+struct Mismatch {
+    x: u64,
+    /// What the sieve said.
+    sieve_says_prime: bool,
+    /// The sieve's primes on either side of x, if any.
+    neighbours: (Option<u64>, Option<u64>),
+}
+
+/// Most mismatches `prime-tools verify` prints one by one.
+const SHOWN_MISMATCHES: usize = 20;
+
+/// `prime-tools verify`, given the arguments after the command.
+///
+/// Every number in `[min, max)` gets both the segmented sieve's answer and a
+/// Baillie–PSW test of its own, split across the threads. Disagreements are
+/// printed with the sieve's primes around them; the exit code says whether
+/// there were any.
+fn verify(args: &[String]) -> Result<bool, Failure> {
+    let (mut min, mut max) = (0, None);
+    let mut limits = Limits::from_env()?;
+    for (flag, value) in flags(args)? {
+        match flag {
+            "--min" => min = number_flag(value)?,
+            "--max" => max = Some(number_flag(value)?),
+            _ if limits.set(flag, value)? => {}
+            _ => return Err(unknown_option(flag)),
+        }
+    }
+    let max = max.ok_or_else(|| Failure::new("usage", "--max is required"))?;
+    check_range(min, max)?;
+
+    // One single-threaded sieve per worker.
+    let options = limits.options(limits.threads);
+    let ranges = split_range(min, max, limits.threads as u64);
+    let results: Vec<Result<(u64, Vec<Mismatch>), Failure>> = thread::scope(|scope| {
+        let options = &options;
+        let handles: Vec<_> = ranges.iter().map(|&(start, end)| scope.spawn(move || verify_range(start, end, options))).collect();
+        handles.into_iter().map(|handle| handle.join().expect("verify thread panicked")).collect()
+    });
+    let results = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let primes: u64 = results.iter().map(|(count, _)| count).sum();
+    let mismatches: Vec<&Mismatch> = results.iter().flat_map(|(_, mismatches)| mismatches).collect();
+    for mismatch in mismatches.iter().take(SHOWN_MISMATCHES) {
+        let (said, other) = if mismatch.sieve_says_prime { ("prime", "composite") } else { ("composite", "prime") };
+        let show = |p: Option<u64>| p.map_or("none".to_string(), |p| p.to_string());
+        println!(
+            "mismatch at {}: the sieve says {}, Baillie-PSW says {} (sieve primes around it: {} and {})",
+            mismatch.x,
+            said,
+            other,
+            show(mismatch.neighbours.0),
+            show(mismatch.neighbours.1)
+        );
+    }
+    if mismatches.len() > SHOWN_MISMATCHES {
+        println!("... and {} more", mismatches.len() - SHOWN_MISMATCHES);
+    }
+    println!("checked [{}, {}): {} primes, {} mismatches", min, max, primes, mismatches.len());
+    Ok(mismatches.is_empty())
+}
+
+/// The sieve's prime count for `[start, end)` and where it disagrees with Baillie–PSW.
+fn verify_range(start: u64, end: u64, options: &SieveOptions) -> Result<(u64, Vec<Mismatch>), Failure> {
+    let (mut primes, mut count, mut mismatches) = (Vec::new(), 0, Vec::new());
+    let mut low = start;
+    while low < end {
+        let high = low.saturating_add(SHARD_STEP).min(end);
+        try_primes_into_with_options(low, high, options, &mut primes)?;
+        count += primes.len() as u64;
+        let mut next = 0;
+        for x in low..high {
+            let sieve_says_prime = primes.get(next) == Some(&x);
+            if sieve_says_prime {
+                next += 1;
+            }
+            if sieve_says_prime != is_bpsw_prime(x as u128) {
+                let before = if sieve_says_prime { next - 1 } else { next };
+                let neighbours = (before.checked_sub(1).map(|i| primes[i]), primes.get(next).copied());
+                mismatches.push(Mismatch { x, sieve_says_prime, neighbours });
+            }
+        }
+        low = high;
+    }
+    Ok((count, mismatches))
+}
+
+/// `prime-tools gaps`, given the arguments after the command.
+///
+/// Sieves `[min, max)` a step at a time, carrying the last prime over, and
+/// writes each gap as soon as it's found, so memory stays at one step's worth
+/// of primes however long the range.
+fn gaps(args: &[String]) -> Result<(), Failure> {
+    let (mut min, mut max, mut min_gap) = (0, None, 1);
+    let mut limits = Limits::from_env()?;
+    for (flag, value) in flags(args)? {
+        match flag {
+            "--min" => min = number_flag(value)?,
+            "--max" => max = Some(number_flag(value)?),
+            "--min-gap" => min_gap = number_flag(value)?,
+            _ if limits.set(flag, value)? => {}
+            _ => return Err(unknown_option(flag)),
+        }
+    }
+    let max = max.ok_or_else(|| Failure::new("usage", "--max is required"))?;
+    check_range(min, max)?;
+
+    let options = limits.options(1);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let (mut primes, mut previous) = (Vec::new(), None);
+    let mut low = min;
+    while low < max {
+        let high = low.saturating_add(SHARD_STEP).min(max);
+        try_primes_into_with_options(low, high, &options, &mut primes)?;
+        for &p in &primes {
+            if let Some(q) = previous.filter(|&q| p - q >= min_gap) {
+                // A closed pipe (e.g. `| head`) just means nobody wants more.
+                if writeln!(out, "{} {}", p - q, q).is_err() {
+                    return Ok(());
+                }
+            }
+            previous = Some(p);
+        }
+        low = high;
+    }
+    Ok(())
+}
+
+/// How many sieving threads may run at once and how much they may allocate between them.
+struct Limits {
+    threads: usize,
+    memory: Option<usize>,
+}
+
+impl Limits {
+    /// PRIME_TOOLS_THREADS and PRIME_TOOLS_MEMORY where they're set; one
+    /// thread per core and no memory cap where they aren't.
+    fn from_env() -> Result<Limits, Failure> {
+        let mut limits = Limits { threads: thread::available_parallelism().map_or(1, |n| n.get()), memory: None };
+        for &(variable, flag) in &[("PRIME_TOOLS_THREADS", "--threads"), ("PRIME_TOOLS_MEMORY", "--memory")] {
+            if let Ok(value) = env::var(variable) {
+                limits.set(flag, &value).map_err(|failure| Failure { message: format!("{}: {}", variable, failure.message), ..failure })?;
+            }
+        }
+        Ok(limits)
+    }
+
+    /// Takes `--threads` or `--memory`; false if flag is neither.
+    fn set(&mut self, flag: &str, value: &str) -> Result<bool, Failure> {
+        match flag {
+            "--threads" => match number_flag(value)? {
+                threads @ 1..=1024 => self.threads = threads as usize,
+                _ => return Err(Failure::new("out_of_range", "--threads takes 1 to 1024").with_input(value)),
+            },
+            "--memory" => self.memory = Some(number_flag(value)?.min(usize::MAX as u64) as usize),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Options for one of `sieves` sieves running side by side, which share
+    /// the threads and the memory evenly.
+    fn options(&self, sieves: usize) -> SieveOptions {
+        SieveOptions {
+            threads: (self.threads / sieves).max(1),
+            memory_limit: self.memory.map(|memory| memory / sieves),
+            ..SieveOptions::default()
+        }
+    }
+}
+
+/// `--flag value` pairs.
+fn flags(args: &[String]) -> Result<Vec<(&str, &str)>, Failure> {
+    args.chunks(2)
+        .map(|pair| match pair {
+            [flag, value] => Ok((flag.as_str(), value.as_str())),
+            _ => Err(Failure::new("usage", format!("{} needs a value", pair[0])).with_input(&pair[0])),
+        })
+        .collect()
+}
+
+fn unknown_option(flag: &str) -> Failure {
+    Failure::new("usage", format!("unknown option {}", flag)).with_input(flag)
+}
+
+/// A flag's value as an expression that has to fit in a u64.
+fn number_flag(value: &str) -> Result<u64, Failure> {
+    let x = evaluate_expression(value).map_err(|error| Failure::from(error).with_input(value))?;
+    if x > u64::MAX as u128 {
+        return Err(Failure::new("out_of_range", format!("{} is past 2^64 - 1", x)).with_input(value));
+    }
+    Ok(x as u64)
+}
+
+fn check_range(min: u64, max: u64) -> Result<(), Failure> {
+    if min > max {
+        return Err(Failure::new("out_of_range", format!("--min {} is past --max {}", min, max)));
+    }
+    Ok(())
+}
+
+fn io_failure(path: &Path, error: io::Error) -> Failure {
+    Failure::new("io", error.to_string()).with_input(&path.display().to_string())
+}
+
+/// `[min, max)` cut into `parts` nearly equal ranges, the last ones possibly empty.
+fn split_range(min: u64, max: u64, parts: u64) -> Vec<(u64, u64)> {
+    let width = (max - min).div_ceil(parts);
+    (0..parts).map(|i| (min.saturating_add(i.saturating_mul(width)).min(max), min.saturating_add((i + 1).saturating_mul(width)).min(max))).collect()
+}
+
+fn usage() -> ! {
+    let failure = Failure::usage();
+    failure.report();
+    process::exit(failure.exit_code());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_is_one_line_of_json() {
+        let failure = Failure::usage();
+        let json = failure.to_json();
+        assert!(json.starts_with("{\"error\":\"usage\",\"message\":\"usage: prime-tools <command>"), "{}", json);
+        assert!(json.ends_with("\"}") && !json.contains('\n'), "{}", json);
+        assert_eq!(failure.exit_code(), 2);
+        let out_of_range = Failure::new("out_of_range", "too big").with_input("2^64");
+        assert_eq!(out_of_range.to_json(), r#"{"error":"out_of_range","message":"too big","input":"2^64"}"#);
+    }
+
+    #[test]
+    fn split_range_covers_the_window() {
+        assert_eq!(split_range(10, 20, 3), vec![(10, 14), (14, 18), (18, 20)]);
+        assert_eq!(split_range(0, 2, 4), vec![(0, 1), (1, 2), (2, 2), (2, 2)]);
+        // Widths near u64::MAX saturate at max instead of overflowing.
+        let ranges = split_range(0, u64::MAX, 3);
+        assert_eq!(ranges[0], (0, u64::MAX / 3));
+        assert_eq!(ranges[2], (2 * (u64::MAX / 3), u64::MAX));
+        let ranges = split_range(1, u64::MAX, 2);
+        assert_eq!(ranges, vec![(1, 1 << 63), (1 << 63, u64::MAX)]);
+    }
+}
@@ -0,0 +1,235 @@
+//! A compact, versioned binary format for the primes in a range.
+
+use std::convert::TryInto;
+use std::fmt;
+
+use crate::get_primes_between;
+
+/// The first bytes of every block.
+const MAGIC: [u8; 4] = *b"PTPR";
+/// The format version written, and the only one read.
+const VERSION: u8 = 1;
+/// Body encoding 0: each prime as an unsigned LEB128 varint of its gap from
+/// the one before, the first measured from min.
+const GAP_VARINTS: u8 = 0;
+/// Magic, version, encoding, then min, max and count.
+const HEADER_BYTES: usize = 4 + 1 + 1 + 3 * 8;
+
+/// The primes in `[min, max)`, with a binary encoding meant to be read back
+/// by other versions of this crate and by tools in other languages.
+///
+/// A block is a 30-byte header followed by the body:
+///
+/// | bytes  | field                                          |
+/// |--------|------------------------------------------------|
+/// | 0..4   | magic, `PTPR`                                  |
+/// | 4      | format version, 1                              |
+/// | 5      | body encoding, 0 for varint gaps               |
+/// | 6..14  | min, a little-endian u64                       |
+/// | 14..22 | max, the same                                  |
+/// | 22..30 | count of primes, the same                      |
+/// | 30..   | body                                           |
+///
+/// With encoding 0 the body is `count` unsigned LEB128 varints: the first
+/// prime minus min, then each prime minus the one before. Gaps below 2^64
+/// stay under 1,600, so a prime takes one or two bytes; the primes below
+/// 10^9 come to about 51 MB.
+///
+/// ```
+/// use prime_tools::PrimeRangeBlock;
+///
+/// let block = PrimeRangeBlock::new(100, 130);
+/// assert_eq!(block.primes(), &[101, 103, 107, 109, 113, 127]);
+/// let bytes = block.encode();
+/// assert_eq!(&bytes[..6], b"PTPR\x01\x00");
+/// assert_eq!(&bytes[30..], &[1, 2, 4, 2, 4, 14]);
+/// assert_eq!(PrimeRangeBlock::decode(&bytes), Ok(block));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrimeRangeBlock {
+    min: u64,
+    max: u64,
+    primes: Vec<u64>,
+}
+
+/// Why `PrimeRangeBlock::decode` rejected its input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrimeRangeBlockError {
+    /// The input doesn't start with the `PTPR` magic.
+    NotABlock,
+    /// The block is in a format version this crate doesn't read.
+    UnsupportedVersion(u8),
+    /// The body uses an encoding this crate doesn't know.
+    UnsupportedEncoding(u8),
+    /// The input ends partway through the header or the body.
+    Truncated,
+    /// The header and body disagree: a prime outside `[min, max)`, primes
+    /// out of order, or bytes left over after `count` primes.
+    Inconsistent,
+}
+
+impl fmt::Display for PrimeRangeBlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PrimeRangeBlockError::NotABlock => write!(f, "not a prime range block"),
+            PrimeRangeBlockError::UnsupportedVersion(version) => write!(f, "unsupported prime range block version {}", version),
+            PrimeRangeBlockError::UnsupportedEncoding(encoding) => write!(f, "unsupported prime range block encoding {}", encoding),
+            PrimeRangeBlockError::Truncated => write!(f, "prime range block is truncated"),
+            PrimeRangeBlockError::Inconsistent => write!(f, "prime range block body doesn't match its header"),
+        }
+    }
+}
+
+impl std::error::Error for PrimeRangeBlockError {}
+
+impl PrimeRangeBlock {
+    /// The primes in `[min, max)`, sieved.
+    pub fn new(min: u64, max: u64) -> PrimeRangeBlock {
+        PrimeRangeBlock { min, max, primes: get_primes_between(min, max) }
+    }
+
+    /// A block of primes found some other way, or None unless they are
+    /// increasing and inside `[min, max)`. They aren't checked for primality.
+    pub fn from_primes(min: u64, max: u64, primes: Vec<u64>) -> Option<PrimeRangeBlock> {
+        let in_range = primes.first().is_none_or(|&p| p >= min) && primes.last().is_none_or(|&p| p < max);
+        if in_range && primes.windows(2).all(|pair| pair[0] < pair[1]) {
+            Some(PrimeRangeBlock { min, max, primes })
+        } else {
+            None
+        }
+    }
+
+    /// The start of the range.
+    pub fn min(&self) -> u64 {
+        self.min
+    }
+
+    /// The end of the range, which is excluded.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// The primes, in increasing order.
+    pub fn primes(&self) -> &[u64] {
+        &self.primes
+    }
+
+    /// The block in the format described above.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_BYTES + self.primes.len() * 2);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&[VERSION, GAP_VARINTS]);
+        for field in &[self.min, self.max, self.primes.len() as u64] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        let mut previous = self.min;
+        for &p in &self.primes {
+            let mut gap = p - previous;
+            while gap >= 0x80 {
+                bytes.push(gap as u8 | 0x80);
+                gap >>= 7;
+            }
+            bytes.push(gap as u8);
+            previous = p;
+        }
+        bytes
+    }
+
+    /// Reads a block written by `encode`, here or by any other version that
+    /// writes format version 1.
+    ///
+    /// ```
+    /// use prime_tools::{PrimeRangeBlock, PrimeRangeBlockError};
+    ///
+    /// let mut bytes = PrimeRangeBlock::new(0, 1_000).encode();
+    /// assert_eq!(PrimeRangeBlock::decode(&bytes).unwrap().primes().len(), 168);
+    /// bytes.pop();
+    /// assert_eq!(PrimeRangeBlock::decode(&bytes), Err(PrimeRangeBlockError::Truncated));
+    /// bytes[4] = 2;
+    /// assert_eq!(PrimeRangeBlock::decode(&bytes), Err(PrimeRangeBlockError::UnsupportedVersion(2)));
+    /// ```
+    pub fn decode(bytes: &[u8]) -> Result<PrimeRangeBlock, PrimeRangeBlockError> {
+        if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+            return Err(PrimeRangeBlockError::NotABlock);
+        }
+        if bytes.len() < HEADER_BYTES {
+            return Err(PrimeRangeBlockError::Truncated);
+        }
+        if bytes[4] != VERSION {
+            return Err(PrimeRangeBlockError::UnsupportedVersion(bytes[4]));
+        }
+        if bytes[5] != GAP_VARINTS {
+            return Err(PrimeRangeBlockError::UnsupportedEncoding(bytes[5]));
+        }
+        let field = |i: usize| u64::from_le_bytes(bytes[6 + 8 * i..14 + 8 * i].try_into().unwrap());
+        let (min, max, count) = (field(0), field(1), field(2));
+
+        let mut body = bytes[HEADER_BYTES..].iter();
+        // Every prime takes at least a byte, which bounds what a bad count can allocate.
+        let mut primes = Vec::with_capacity((count as usize).min(body.len()));
+        let mut previous = min;
+        for _ in 0..count {
+            let mut gap = 0u64;
+            let mut shift = 0;
+            loop {
+                let &byte = body.next().ok_or(PrimeRangeBlockError::Truncated)?;
+                if shift > 63 || (shift == 63 && byte & 0x7e != 0) {
+                    // More than 64 bits.
+                    return Err(PrimeRangeBlockError::Inconsistent);
+                }
+                gap |= ((byte & 0x7f) as u64) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            let p = previous.checked_add(gap).ok_or(PrimeRangeBlockError::Inconsistent)?;
+            if !primes.is_empty() && gap == 0 {
+                return Err(PrimeRangeBlockError::Inconsistent);
+            }
+            primes.push(p);
+            previous = p;
+        }
+        if body.next().is_some() || primes.last().is_some_and(|&p| p >= max) {
+            return Err(PrimeRangeBlockError::Inconsistent);
+        }
+        Ok(PrimeRangeBlock { min, max, primes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_rejects_bad_blocks() {
+        let top = PrimeRangeBlock::from_primes(1, u64::MAX, vec![2, 18_446_744_073_709_551_557]).unwrap();
+        let blocks = [(0, 0), (0, 2), (0, 100_000), (1 << 40, (1 << 40) + 10_000)].iter().map(|&(min, max)| PrimeRangeBlock::new(min, max));
+        for block in blocks.chain(std::iter::once(top)) {
+            let bytes = block.encode();
+            assert_eq!(u64::from_le_bytes(bytes[22..30].try_into().unwrap()), block.primes().len() as u64);
+            assert_eq!(PrimeRangeBlock::decode(&bytes), Ok(block));
+        }
+
+        let bytes = PrimeRangeBlock::new(0, 20).encode();
+        assert_eq!(PrimeRangeBlock::decode(b"PTP"), Err(PrimeRangeBlockError::NotABlock));
+        assert_eq!(PrimeRangeBlock::decode(&bytes[..29]), Err(PrimeRangeBlockError::Truncated));
+        let mut extra = bytes.clone();
+        extra.push(2);
+        assert_eq!(PrimeRangeBlock::decode(&extra), Err(PrimeRangeBlockError::Inconsistent));
+        let mut encoding = bytes.clone();
+        encoding[5] = 1;
+        assert_eq!(PrimeRangeBlock::decode(&encoding), Err(PrimeRangeBlockError::UnsupportedEncoding(1)));
+        // max lowered to 19 leaves the last prime, 19, outside the range.
+        let mut range = bytes.clone();
+        range[14] = 19;
+        assert_eq!(PrimeRangeBlock::decode(&range), Err(PrimeRangeBlockError::Inconsistent));
+        let mut repeat = bytes;
+        repeat[31] = 0;
+        assert_eq!(PrimeRangeBlock::decode(&repeat), Err(PrimeRangeBlockError::Inconsistent));
+
+        assert!(PrimeRangeBlock::from_primes(10, 20, vec![11, 13]).is_some());
+        assert!(PrimeRangeBlock::from_primes(10, 20, vec![13, 11]).is_none());
+        assert!(PrimeRangeBlock::from_primes(10, 20, vec![7]).is_none());
+    }
+}
@@ -0,0 +1,108 @@
+//! A process-wide sieve that every caller shares.
+//!
+//! The first question about a number below the cache's limit is answered
+//! from primes already found, so a program can pay for sieving at startup,
+//! or in the background while it does other things, instead of on its first
+//! request. The cache only ever grows; while it does, readers wait.
+
+use std::sync::{RwLock, RwLockReadGuard};
+use std::thread::{self, JoinHandle};
+
+use crate::primality::U64_BASES;
+use crate::{miller_rabin_with_bases, Sieve};
+
+static CACHE: RwLock<Sieve> = RwLock::new(Sieve::empty());
+
+/// The cache holds exactly the primes below this.
+pub fn limit() -> u64 {
+    read().limit()
+}
+
+/// Grows the cache to cover every number below limit. Does nothing if it
+/// already does.
+///
+/// Only the numbers past the current limit get sieved, so warming in steps
+/// costs the same as warming all at once.
+///
+/// ```
+/// use prime_tools::cache;
+///
+/// cache::warm_to(1_000_000);
+/// assert!(cache::limit() >= 1_000_000);
+/// assert!(cache::is_prime(999_983));
+/// ```
+pub fn warm_to(limit: u64) {
+    if read().limit() >= limit {
+        return;
+    }
+    CACHE.write().expect("a thread panicked while growing the prime cache").extend_to(limit);
+}
+
+/// `warm_to(limit)` on a thread of its own, so startup can carry on.
+///
+/// Join the handle to wait for it; dropping it lets the warming finish
+/// unobserved. Lookups made meanwhile wait for it or fall back, as their
+/// own docs say.
+///
+/// ```
+/// use prime_tools::cache;
+///
+/// let warming = cache::warm_in_background(100_000);
+/// // ... other startup work ...
+/// warming.join().unwrap();
+/// assert!(cache::limit() >= 100_000);
+/// ```
+pub fn warm_in_background(limit: u64) -> JoinHandle<()> {
+    thread::spawn(move || warm_to(limit))
+}
+
+/// Whether x is prime: a binary search when x is below the cache's limit,
+/// deterministic Miller–Rabin otherwise. The cache isn't grown for it.
+pub fn is_prime(x: u64) -> bool {
+    read().is_prime(x).unwrap_or_else(|| miller_rabin_with_bases(x as u128, &U64_BASES))
+}
+
+/// Calls f with every prime below limit, warming the cache to limit first.
+///
+/// f borrows the cache's own list, so nothing is copied; the cache can't grow
+/// until f returns.
+///
+/// ```
+/// use prime_tools::cache;
+///
+/// let sum: u64 = cache::with_primes_below(100, |primes| primes.iter().sum());
+/// assert_eq!(sum, 1_060);
+/// ```
+pub fn with_primes_below<R, F: FnOnce(&[u64]) -> R>(limit: u64, f: F) -> R {
+    warm_to(limit);
+    let sieve = read();
+    let primes = sieve.primes();
+    f(&primes[..primes.partition_point(|&p| p < limit)])
+}
+
+/// A read lock on the cache.
+fn read() -> RwLockReadGuard<'static, Sieve> {
+    CACHE.read().expect("a thread panicked while growing the prime cache")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warming_from_several_threads() {
+        let handles: Vec<_> = (1..=4).map(|i| warm_in_background(i * 50_000)).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(limit() >= 200_000);
+
+        let expected: Vec<u64> = crate::get_primes_less_than_x(200_000).iter().map(|&p| p as u64).collect();
+        with_primes_below(200_000, |primes| assert_eq!(primes, &expected[..]));
+        for x in 199_000..201_000 {
+            assert_eq!(is_prime(x), crate::is_u64_prime(x), "{}", x);
+        }
+        assert!(is_prime(18_446_744_073_709_551_557));
+        assert!(!is_prime(4_294_967_291 * 4_294_967_279));
+    }
+}
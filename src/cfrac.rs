@@ -0,0 +1,130 @@
+//! The continued fraction method (CFRAC) of Morrison and Brillhart.
+
+use crate::modular::{isqrt_u128, mul_mod};
+use crate::qs::{factor_base, large_prime_bound, multiplier, split_directly, FactorPrime, Relation, Relations, EXTRA_RELATIONS, MULTIPLIERS};
+
+/// A nontrivial factor of n from the continued fraction method, or None if
+/// n is prime or below 4.
+///
+/// The convergents A/B of √(kn) have A^2 - kn B^2 = ±Q with Q below
+/// 2√(kn), so A^2 ≡ ±Q (mod n) with Q about the size of √n, smaller than any
+/// quadratic sieve value. There is no sieve to find the smooth ones, though:
+/// every Q is trial divided by the whole factor base, which makes this the
+/// slower method from about 70 bits on. It shares the factor base, large
+/// prime pairing, GF(2) elimination and square root step with
+/// `quadratic_sieve`, and is mostly there for comparison and for its
+/// history (it factored F7 in 1970).
+///
+/// If the expansion's period runs out before there are enough relations,
+/// it starts over with another multiplier k. Small factors, perfect powers
+/// and n below 2^40 are handled as in `quadratic_sieve`.
+///
+/// Panics if n is 2^124 or more.
+///
+/// ```
+/// use prime_tools::continued_fraction_factor;
+///
+/// let (p, q) = (1_000_000_007u128, 998_244_353u128);
+/// let d = continued_fraction_factor(p * q).unwrap();
+/// assert!(d == p || d == q);
+/// assert_eq!(continued_fraction_factor(998_244_353), None);
+/// ```
+pub fn continued_fraction_factor(n: u128) -> Option<u128> {
+    assert!(n < 1 << 124, "continued_fraction_factor takes n below 2^124");
+    if let Some(answer) = split_directly(n) {
+        return answer;
+    }
+
+    let _span = debug_span!("continued_fraction_factor", bits = 128 - n.leading_zeros());
+    let best = multiplier(n);
+    let others = MULTIPLIERS.iter().copied().filter(|&k| k != best && n.checked_mul(k as u128).is_some_and(|kn| kn < 1 << 125));
+    std::iter::once(best).chain(others).find_map(|k| expand(n, k))
+}
+
+/// Walks the continued fraction of √(kn) collecting relations, or gives
+/// up with None if its period ends first.
+fn expand(n: u128, k: u64) -> Option<u128> {
+    let kn = n * k as u128;
+    let base = factor_base(n, kn, base_size(128 - kn.leading_zeros()))?;
+    let large_bound = large_prime_bound(&base);
+    let wanted = base.len() + 1 + EXTRA_RELATIONS;
+    let mut relations = Relations::new(n);
+
+    // P_i, Q_(i-1), Q_i and A_(i-2), A_(i-1) (mod n), starting from i = 1.
+    let root = isqrt_u128(kn);
+    let (mut p, mut q_previous, mut q) = (root, 1u128, kn - root * root);
+    let (mut a_previous, mut a) = (1u128, root % n);
+    let mut odd = true;
+    while relations.len() < wanted {
+        if q == 1 && !odd {
+            debug!(k, "continued fraction period ended");
+            return None;
+        }
+        // A_(i-1)^2 ≡ (-1)^i Q_i (mod n).
+        if let Some((mut columns, large)) = smooth_part(q as u64, &base, large_bound) {
+            if odd {
+                columns.push(0);
+            }
+            relations.add(Relation { y: a, columns, large });
+        }
+
+        let term = (root + p) / q;
+        let p_next = term * q - p;
+        let q_next = if p >= p_next { q_previous + term * (p - p_next) } else { q_previous - term * (p_next - p) };
+        (p, q_previous, q) = (p_next, q, q_next);
+        (a_previous, a) = (a, (mul_mod(term % n, a, n) + a_previous) % n);
+        odd = !odd;
+    }
+    relations.factor(&base)
+}
+
+/// The factor base columns of value, with repeats, and the cofactor left
+/// over if it's 1 or a large prime.
+fn smooth_part(mut value: u64, base: &[FactorPrime], large_bound: u128) -> Option<(Vec<usize>, u128)> {
+    let mut columns = Vec::new();
+    for (i, prime) in base.iter().enumerate() {
+        let p = prime.p as u64;
+        while value % p == 0 {
+            value /= p;
+            columns.push(i + 1);
+        }
+        if value == 1 {
+            break;
+        }
+    }
+    if (value as u128) < large_bound {
+        Some((columns, value as u128))
+    } else {
+        None
+    }
+}
+
+/// Factor base size for kn of the given bit length. Values are near √(kn)
+/// rather than M √(kn / 2), so a smaller base than the sieve's does.
+fn base_size(bits: u32) -> usize {
+    (60 + (bits as i64 - 64) * 200 / 64).max(40) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_semiprimes_and_short_periods() {
+        let cases = [
+            (1_000_003u128, 1_000_033u128),
+            (4_294_967_291, 4_294_967_279),
+            (1_099_511_627_689, 1_099_511_627_791),
+        ];
+        for &(p, q) in &cases {
+            let d = continued_fraction_factor(p * q).unwrap();
+            assert!(d == p || d == q, "{} * {} gave {}", p, q, d);
+        }
+        // m^2 + 1 has a period of length one for k = 1.
+        let d = continued_fraction_factor(1_208_925_819_874_113_918_875_237).unwrap();
+        assert!(d == 12_330_061 || d == 98_047_026_683_332_217);
+        assert_eq!(continued_fraction_factor(1), None);
+        assert_eq!(continued_fraction_factor(4_294_967_291u128.pow(3)), Some(4_294_967_291));
+        assert_eq!(continued_fraction_factor(618_970_019_642_690_137_449_562_111), None);
+    }
+}
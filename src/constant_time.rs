@@ -0,0 +1,154 @@
+//! Primality testing whose running time doesn't depend on the input.
+//!
+//! Every branch below is on public loop counters only; decisions about the
+//! value are made with all-ones/all-zeros masks, and multiplication mod x is
+//! Montgomery's, so no hardware division sees the secret either.
+
+/// Miller–Rabin bases that settle primality for every x below 2^64 (Jim Sinclair's set).
+const BASES: [u64; 7] = crate::primality::U64_BASES;
+
+/// True if x is prime, taking the same steps whatever x is.
+///
+/// `is_u64_prime` stops at the first divisor it finds, so how long it takes
+/// says something about the factors of x. This runs the full
+/// deterministic Miller–Rabin test, all seven bases and every squaring, on
+/// every input, for candidates that have to stay secret (e.g. RSA factors).
+/// It's several times slower than `is_u64_prime`.
+///
+/// ```
+/// use prime_tools::is_prime_ct;
+///
+/// assert!(is_prime_ct(18_446_744_073_709_551_557)); // the largest prime below 2^64
+/// assert!(!is_prime_ct(3_825_123_056_546_413_051)); // a strong pseudoprime to the first nine prime bases
+/// assert!(is_prime_ct(2));
+/// assert!(!is_prime_ct(1));
+/// ```
+pub fn is_prime_ct(x: u64) -> bool {
+    // Even x get an odd stand-in; the masks at the end throw its answer away.
+    let n = x | 1;
+    let field = Montgomery::new(n);
+    let minus_one = n.wrapping_sub(field.one);
+
+    // n - 1 = d * 2^s with d odd; the top bit keeps the shift in range for n = 1.
+    let s = ((n - 1) | 1 << 63).trailing_zeros() as u64;
+    let d = (n - 1) >> s;
+
+    let mut all_pass = !0;
+    for &base in &BASES {
+        let a = field.to_montgomery(base);
+        let mut y = field.pow(a, d);
+        // A base that's a multiple of x says nothing, so it passes.
+        let mut pass = eq_mask(a, 0) | eq_mask(y, field.one) | eq_mask(y, minus_one);
+        for i in 1..64 {
+            y = field.mul(y, y);
+            pass |= eq_mask(y, minus_one) & lt_mask(i, s);
+        }
+        all_pass &= pass;
+    }
+
+    let odd = (x & 1).wrapping_neg();
+    let above_one = !eq_mask(x >> 1, 0);
+    (odd & above_one & all_pass | eq_mask(x, 2)) & 1 == 1
+}
+
+/// Montgomery multiplication mod an odd n, with R = 2^64.
+struct Montgomery {
+    n: u64,
+    /// -n⁻¹ mod 2^64.
+    n_prime: u64,
+    /// R mod n, which is 1 in Montgomery form.
+    one: u64,
+    /// R² mod n.
+    r_squared: u64,
+}
+
+impl Montgomery {
+    fn new(n: u64) -> Montgomery {
+        // n * n ≡ 1 (mod 8), so n is its own inverse to 3 bits; Newton doubles that.
+        let mut inverse = n;
+        for _ in 0..5 {
+            inverse = inverse.wrapping_mul(2u64.wrapping_sub(n.wrapping_mul(inverse)));
+        }
+        // 2^i mod n by doubling, starting from 1 mod n.
+        let mut power = !eq_mask(n, 1) & 1;
+        let mut one = 0;
+        for i in 0..128 {
+            let (doubled, carry) = power.overflowing_add(power);
+            let (reduced, borrow) = doubled.overflowing_sub(n);
+            power = select(((carry | !borrow) as u64).wrapping_neg(), reduced, doubled);
+            if i == 63 {
+                one = power;
+            }
+        }
+        Montgomery { n, n_prime: inverse.wrapping_neg(), one, r_squared: power }
+    }
+
+    /// a * b / R mod n, for a * b < n * R.
+    fn mul(&self, a: u64, b: u64) -> u64 {
+        let t = a as u128 * b as u128;
+        let m = (t as u64).wrapping_mul(self.n_prime);
+        let (sum, carry) = t.overflowing_add(m as u128 * self.n as u128);
+        let r = (sum >> 64) as u64;
+        // The true value is carry * 2^64 + r < 2n.
+        let (reduced, borrow) = r.overflowing_sub(self.n);
+        select(((carry | !borrow) as u64).wrapping_neg(), reduced, r)
+    }
+
+    fn to_montgomery(&self, a: u64) -> u64 {
+        self.mul(a, self.r_squared)
+    }
+
+    /// a^e with a in Montgomery form, by a Montgomery ladder over all 64 bits of e.
+    fn pow(&self, a: u64, e: u64) -> u64 {
+        let (mut r0, mut r1) = (self.one, a);
+        for i in (0..64).rev() {
+            let swap = (e >> i & 1).wrapping_neg();
+            let (s0, s1) = (select(swap, r1, r0), select(swap, r0, r1));
+            let (t0, t1) = (self.mul(s0, s0), self.mul(s0, s1));
+            r0 = select(swap, t1, t0);
+            r1 = select(swap, t0, t1);
+        }
+        r0
+    }
+}
+
+/// All ones if a == b, else 0.
+fn eq_mask(a: u64, b: u64) -> u64 {
+    let x = a ^ b;
+    ((x | x.wrapping_neg()) >> 63).wrapping_sub(1)
+}
+
+/// All ones if a < b, else 0, for a, b < 2^63.
+fn lt_mask(a: u64, b: u64) -> u64 {
+    (a.wrapping_sub(b) >> 63).wrapping_neg()
+}
+
+/// a where mask is all ones, b where it's 0.
+fn select(mask: u64, a: u64, b: u64) -> u64 {
+    a & mask | b & !mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::miller_rabin_with_bases;
+
+    #[test]
+    fn agrees_with_is_u64_prime() {
+        for x in 0..100_000 {
+            assert_eq!(is_prime_ct(x), crate::is_u64_prime(x), "{}", x);
+        }
+        let mut x = 0x9e37_79b9_7f4a_7c15u64;
+        for _ in 0..10_000 {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            for &y in &[x, x | 1] {
+                assert_eq!(is_prime_ct(y), miller_rabin_with_bases(y as u128, &BASES), "{}", y);
+            }
+        }
+        for &x in &[u64::MAX, u64::MAX - 58, 1 << 63, (1 << 61) - 1, 4_294_967_291 * 4_294_967_279] {
+            assert_eq!(is_prime_ct(x), miller_rabin_with_bases(x as u128, &BASES), "{}", x);
+        }
+    }
+}
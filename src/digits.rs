@@ -0,0 +1,405 @@
+//! Properties of the digits of numbers (decimal unless a base is given), and
+//! of gluing and shuffling them.
+
+use crate::primality::U64_BASES;
+use crate::sieve::for_each_prime_between;
+use crate::{is_strong_lucas_probable_prime, miller_rabin_with_bases, SieveOptions};
+
+/// The sum of the decimal digits of x.
+///
+/// ```
+/// assert_eq!(prime_tools::digit_sum(1_729), 19);
+/// assert_eq!(prime_tools::digit_sum(0), 0);
+/// ```
+pub fn digit_sum(mut x: u64) -> u64 {
+    let mut sum = 0;
+    while x > 0 {
+        sum += x % 10;
+        x /= 10;
+    }
+    sum
+}
+
+/// The product of the decimal digits of x; 0 if any digit is 0, including x = 0 itself.
+///
+/// Always fits: a u64 has at most 20 digits and one that long starts with 1,
+/// so the product is at most 9^19.
+///
+/// ```
+/// assert_eq!(prime_tools::digit_product(1_729), 126);
+/// assert_eq!(prime_tools::digit_product(105), 0);
+/// ```
+pub fn digit_product(mut x: u64) -> u64 {
+    if x == 0 {
+        return 0;
+    }
+    let mut product = 1;
+    while x > 0 {
+        product *= x % 10;
+        x /= 10;
+    }
+    product
+}
+
+/// True if x is a Harshad (Niven) number: divisible by its digit sum. 0 isn't.
+///
+/// ```
+/// assert!(prime_tools::is_harshad(1_729)); // 19 * 91
+/// assert!(!prime_tools::is_harshad(13));
+/// ```
+pub fn is_harshad(x: u64) -> bool {
+    x > 0 && x % digit_sum(x) == 0
+}
+
+/// True if x is a Zuckerman number: divisible by its digit product, which
+/// rules out any x with a 0 digit.
+///
+/// ```
+/// assert!(prime_tools::is_zuckerman(175)); // 1 * 7 * 5 = 35, and 175 = 5 * 35
+/// assert!(!prime_tools::is_zuckerman(120));
+/// ```
+pub fn is_zuckerman(x: u64) -> bool {
+    match digit_product(x) {
+        0 => false,
+        product => x % product == 0,
+    }
+}
+
+/// The primes in `[min, max)` that are Harshad numbers.
+///
+/// A prime p past 9 has a digit sum strictly between 1 and p, so it can't be
+/// divisible by it; only the one-digit primes qualify, and the sieve never
+/// has to look past 10.
+///
+/// ```
+/// assert_eq!(prime_tools::prime_harshad_between(0, 1_000_000), vec![2, 3, 5, 7]);
+/// assert_eq!(prime_tools::prime_harshad_between(4, 6), vec![5]);
+/// ```
+pub fn prime_harshad_between(min: u64, max: u64) -> Vec<u64> {
+    let mut primes = Vec::new();
+    for_each_prime_between(min, max.min(10), &SieveOptions::default(), |p| {
+        if is_harshad(p) {
+            primes.push(p);
+        }
+    });
+    primes
+}
+
+/// True if the decimal digits of a followed by those of b spell a prime,
+/// e.g. 7 and 109 give 7109.
+///
+/// The concatenation is built in a u128, where it's tested exactly up to
+/// 2^64 and with Baillie–PSW past that. Only a and b of 19 or 20 digits each
+/// can overflow a u128; those need the `bignum` feature and get a
+/// twelve-base Miller–Rabin test, and panic without it.
+///
+/// ```
+/// assert!(prime_tools::concat_is_prime(7, 109));
+/// assert!(prime_tools::concat_is_prime(109, 7));
+/// assert!(!prime_tools::concat_is_prime(3, 5)); // 35
+/// ```
+pub fn concat_is_prime(a: u64, b: u64) -> bool {
+    let shift = 10u128.pow(digit_count(b));
+    match (a as u128).checked_mul(shift).and_then(|high| high.checked_add(b as u128)) {
+        Some(x) => is_probable_prime(x),
+        None => concat_is_prime_big(a, shift, b),
+    }
+}
+
+/// Exact up to 2^64, Baillie–PSW past that.
+fn is_probable_prime(x: u128) -> bool {
+    if x <= u64::MAX as u128 {
+        miller_rabin_with_bases(x, &U64_BASES)
+    } else {
+        miller_rabin_with_bases(x, &[2]) && is_strong_lucas_probable_prime(x)
+    }
+}
+
+#[cfg(feature = "bignum")]
+fn concat_is_prime_big(a: u64, shift: u128, b: u64) -> bool {
+    use num_bigint::BigUint;
+    crate::bignum::is_probable_prime_big(&(BigUint::from(a) * shift + b))
+}
+
+#[cfg(not(feature = "bignum"))]
+fn concat_is_prime_big(a: u64, _shift: u128, b: u64) -> bool {
+    panic!("{} followed by {} doesn't fit in a u128; enable the bignum feature", a, b)
+}
+
+/// How many decimal digits x has; 0 has one.
+fn digit_count(mut x: u64) -> u32 {
+    let mut count = 1;
+    while x >= 10 {
+        x /= 10;
+        count += 1;
+    }
+    count
+}
+
+/// Every k-element subset of `primes` in which each pair concatenates to a
+/// prime both ways round, as in `concat_is_prime`.
+///
+/// The inputs are sorted and deduplicated first, and each set comes out in
+/// increasing order, the sets in lexicographic order. Every pair is tested
+/// once up front; the sets are then the k-cliques of that graph, grown one
+/// prime at a time from the neighbours all the chosen ones share.
+///
+/// ```
+/// let primes = prime_tools::get_primes_between(0, 1_000);
+/// let sets = prime_tools::prime_pair_sets(&primes, 4);
+/// assert_eq!(sets[0], vec![3, 7, 109, 673]);
+/// ```
+pub fn prime_pair_sets(primes: &[u64], k: usize) -> Vec<Vec<u64>> {
+    let mut primes = primes.to_vec();
+    primes.sort_unstable();
+    primes.dedup();
+    let _span = debug_span!("prime_pair_sets", count = primes.len(), k);
+    // later[i]: the j > i whose primes pair up with primes[i].
+    let later: Vec<Vec<usize>> = (0..primes.len())
+        .map(|i| {
+            (i + 1..primes.len())
+                .filter(|&j| concat_is_prime(primes[i], primes[j]) && concat_is_prime(primes[j], primes[i]))
+                .collect()
+        })
+        .collect();
+    let mut sets = Vec::new();
+    let candidates: Vec<usize> = (0..primes.len()).collect();
+    extend_pair_sets(&primes, &later, &candidates, k, &mut Vec::with_capacity(k), &mut sets);
+    sets
+}
+
+/// Adds every way of finishing `chosen` to k primes from `candidates`, which
+/// all pair with everything chosen so far, to `sets`.
+fn extend_pair_sets(primes: &[u64], later: &[Vec<usize>], candidates: &[usize], k: usize, chosen: &mut Vec<u64>, sets: &mut Vec<Vec<u64>>) {
+    if chosen.len() == k {
+        sets.push(chosen.clone());
+        return;
+    }
+    for (position, &i) in candidates.iter().enumerate() {
+        let remaining: Vec<usize> = candidates[position + 1..]
+            .iter()
+            .copied()
+            .filter(|j| later[i].binary_search(j).is_ok())
+            .collect();
+        if chosen.len() + 1 + remaining.len() < k {
+            continue;
+        }
+        chosen.push(primes[i]);
+        extend_pair_sets(primes, later, &remaining, k, chosen, sets);
+        chosen.pop();
+    }
+}
+
+/// True if every rearrangement of x's digits in the given base is prime
+/// (an absolute or permutable prime), like 337, 373 and 733 in decimal.
+///
+/// A number of two or more digits can only qualify if every digit is
+/// coprime to the base, since some rearrangement ends in each digit. The
+/// distinct rearrangements are then tested in turn, each built in a u128 and
+/// tested exactly up to 2^64 and with Baillie–PSW past that, stopping at
+/// the first composite. Panics if base < 2.
+///
+/// ```
+/// use prime_tools::is_permutable_prime;
+///
+/// assert!(is_permutable_prime(337, 10));
+/// assert!(!is_permutable_prime(347, 10)); // 437 = 19 * 23
+/// assert!(is_permutable_prime(1_111_111_111_111_111_111, 10)); // R19
+/// assert!(is_permutable_prime(31, 2)); // 11111
+/// assert!(!is_permutable_prime(13, 2)); // 1101 has a 0 digit
+/// ```
+pub fn is_permutable_prime(x: u64, base: u64) -> bool {
+    assert!(base >= 2, "is_permutable_prime needs a base of at least 2");
+    let mut digits = digits_in_base(x, base);
+    if digits.len() > 1 && digits.iter().any(|&d| crate::gcd(d, base) != 1) {
+        return false;
+    }
+    digits.sort_unstable();
+    all_arrangements_prime(&mut digits, base, 0).is_some()
+}
+
+/// Every permutable prime up to n in the given base, in increasing order:
+/// the primes all of whose digit rearrangements are prime too.
+///
+/// Rather than sieving to n, it runs over the multisets of digits coprime to
+/// the base, so even n = u64::MAX is quick in small bases; each multiset's rearrangements
+/// are tested as in `is_permutable_prime`, and those up to n kept. In
+/// base 2 the only digit is 1, so these are the Mersenne primes. Panics if
+/// base < 2.
+///
+/// ```
+/// use prime_tools::permutable_primes_up_to;
+///
+/// assert_eq!(
+///     permutable_primes_up_to(1_000, 10),
+///     vec![2, 3, 5, 7, 11, 13, 17, 31, 37, 71, 73, 79, 97, 113, 131, 199, 311, 337, 373, 733, 919, 991]
+/// );
+/// assert_eq!(permutable_primes_up_to(10_000, 2), vec![3, 7, 31, 127, 8_191]);
+/// ```
+pub fn permutable_primes_up_to(n: u64, base: u64) -> Vec<u64> {
+    assert!(base >= 2, "permutable_primes_up_to needs a base of at least 2");
+    let _span = debug_span!("permutable_primes_up_to", n, base);
+    let mut found: Vec<u64> = (2..base.min(n.saturating_add(1))).filter(|&d| is_probable_prime(d as u128)).collect();
+    // Nothing up to n has two digits, so skip listing the digits of a huge base.
+    if base > n {
+        return found;
+    }
+
+    let allowed: Vec<u64> = (1..base).filter(|&d| crate::gcd(d, base) == 1).collect();
+    let mut length = 2;
+    // Lengths whose smallest number, base^(length - 1), is still at most n.
+    while (base as u128).pow(length - 1) <= n as u128 {
+        let mut digits = vec![0usize; length as usize];
+        loop {
+            let mut arrangement: Vec<u64> = digits.iter().map(|&i| allowed[i]).collect();
+            if let Some(values) = all_arrangements_prime(&mut arrangement, base, n as u128) {
+                found.extend(values);
+            }
+            // The next non-decreasing sequence of indices into allowed.
+            match digits.iter().rposition(|&i| i + 1 < allowed.len()) {
+                Some(at) => {
+                    let next = digits[at] + 1;
+                    digits[at..].iter_mut().for_each(|i| *i = next);
+                }
+                None => break,
+            }
+        }
+        length += 1;
+    }
+    found.sort_unstable();
+    found
+}
+
+/// The digits of x in the given base, most significant first; 0 has one.
+fn digits_in_base(mut x: u64, base: u64) -> Vec<u64> {
+    let mut digits = vec![x % base];
+    x /= base;
+    while x > 0 {
+        digits.push(x % base);
+        x /= base;
+    }
+    digits.reverse();
+    digits
+}
+
+/// If every distinct arrangement of the sorted digits is prime, the ones
+/// at most limit (pass 0 to keep none); None at the first composite.
+fn all_arrangements_prime(digits: &mut [u64], base: u64, limit: u128) -> Option<Vec<u64>> {
+    let mut kept = Vec::new();
+    loop {
+        // Below base^len <= base * x, so within a u128.
+        let value = digits.iter().fold(0u128, |value, &d| value * base as u128 + d as u128);
+        if !is_probable_prime(value) {
+            return None;
+        }
+        if value <= limit {
+            kept.push(value as u64);
+        }
+        if !next_permutation(digits) {
+            return Some(kept);
+        }
+    }
+}
+
+/// Rearranges digits into the next permutation in lexicographic order,
+/// skipping repeats; false (and the digits sorted again) after the last one.
+fn next_permutation(digits: &mut [u64]) -> bool {
+    let pivot = match (1..digits.len()).rev().find(|&i| digits[i - 1] < digits[i]) {
+        Some(i) => i - 1,
+        None => {
+            digits.reverse();
+            return false;
+        }
+    };
+    let swap = (pivot + 1..digits.len()).rev().find(|&i| digits[i] > digits[pivot]).unwrap();
+    digits.swap(pivot, swap);
+    digits[pivot + 1..].reverse();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digit_predicates_match_the_decimal_string() {
+        let digits = |x: u64| x.to_string().bytes().map(|b| (b - b'0') as u64).collect::<Vec<_>>();
+        for x in (0..20_000).chain(vec![u64::MAX, 17_999_999_999_999_999_999, 99_999_999_999_999_999]) {
+            let sum: u64 = digits(x).iter().sum();
+            let product: u64 = if x == 0 { 0 } else { digits(x).iter().product() };
+            assert_eq!(digit_sum(x), sum, "{}", x);
+            assert_eq!(digit_product(x), product, "{}", x);
+            assert_eq!(is_harshad(x), x > 0 && x % sum == 0, "{}", x);
+            assert_eq!(is_zuckerman(x), product > 0 && x % product == 0, "{}", x);
+        }
+        let harshad_primes: Vec<u64> = crate::get_primes_between(0, 20_000).into_iter().filter(|&p| is_harshad(p)).collect();
+        assert_eq!(harshad_primes, prime_harshad_between(0, 20_000));
+    }
+
+    #[test]
+    fn permutable_primes_agree_with_the_sieve() {
+        let primes = crate::get_primes_between(0, 5_000);
+        for &base in &[2, 3, 7, 10, 12, 30] {
+            let expected: Vec<u64> = primes.iter().copied().filter(|&p| is_permutable_prime(p, base)).collect();
+            assert_eq!(permutable_primes_up_to(4_999, base), expected, "base {}", base);
+        }
+        let all = permutable_primes_up_to(u64::MAX, 10);
+        assert_eq!(all.len(), 23);
+        assert_eq!(all.last(), Some(&1_111_111_111_111_111_111));
+        // A base past n leaves only single digits, without walking the whole base.
+        assert_eq!(permutable_primes_up_to(20, u64::MAX), vec![2, 3, 5, 7, 11, 13, 17, 19]);
+        assert!(!is_permutable_prime(0, 10) && !is_permutable_prime(1, 10));
+    }
+
+    #[test]
+    fn concatenations_match_the_decimal_string() {
+        let bpsw = |x: u128| miller_rabin_with_bases(x, &[2]) && is_strong_lucas_probable_prime(x);
+        for a in (0..200).chain(vec![1_000_003, 99_999_999_999, u64::MAX / 100]) {
+            for b in (0..200).chain(vec![10, 100, 1_000_033, u64::MAX / 10]) {
+                let x: u128 = format!("{}{}", a, b).parse().unwrap();
+                assert_eq!(concat_is_prime(a, b), bpsw(x), "{} {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "bignum")]
+    fn concatenations_past_u128() {
+        let a = 18_446_744_073_709_551_557; // the largest prime below 2^64
+        let primes: Vec<u64> = (10_000_000_000_000_000_001..10_000_000_000_000_000_400)
+            .step_by(2)
+            .filter(|&b| concat_is_prime(a, b))
+            .collect();
+        assert_eq!(
+            primes,
+            [
+                10_000_000_000_000_000_001,
+                10_000_000_000_000_000_103,
+                10_000_000_000_000_000_157,
+                10_000_000_000_000_000_289,
+                10_000_000_000_000_000_353
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "bignum"))]
+    #[should_panic]
+    fn concatenations_past_u128_need_bignum() {
+        concat_is_prime(u64::MAX, u64::MAX);
+    }
+
+    #[test]
+    fn pair_sets_below_1000() {
+        let primes: Vec<u64> = crate::get_primes_between(0, 1_000);
+        assert_eq!(prime_pair_sets(&primes, 4), vec![vec![3, 7, 109, 673], vec![23, 311, 677, 827]]);
+        for set in prime_pair_sets(&primes, 3) {
+            for &a in &set {
+                assert!(set.iter().all(|&b| a == b || concat_is_prime(a, b)), "{:?}", set);
+            }
+        }
+        assert_eq!(prime_pair_sets(&primes, 5), Vec::<Vec<u64>>::new());
+        assert_eq!(prime_pair_sets(&[7, 3, 7], 2), vec![vec![3, 7]]);
+        assert_eq!(prime_pair_sets(&[3, 7], 0), vec![vec![]]);
+    }
+}
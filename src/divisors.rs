@@ -0,0 +1,533 @@
+//! Divisor functions: the divisors themselves, how many, their sum, their product,
+//! and the same for unitary divisors.
+//!
+//! These come in three flavours. The plain ones panic when the answer doesn't
+//! fit in a u64, the `checked_` ones return None instead, and the
+//! `saturating_` ones stop at u64::MAX.
+//!
+//! 0 has no divisors here: its divisor count and sums are 0 and its divisor
+//! product is 1 (the empty product).
+
+use crate::factorize;
+
+/// Every divisor of x in increasing order, built from its factorization.
+///
+/// 0 gets an empty Vec.
+///
+/// ```
+/// assert_eq!(prime_tools::divisors(12), vec![1, 2, 3, 4, 6, 12]);
+/// ```
+pub fn divisors(x: u64) -> Vec<u64> {
+    if x == 0 {
+        return Vec::new();
+    }
+    let mut divisors = vec![1];
+    for (p, count) in factorize(x) {
+        let previous = divisors.len();
+        let mut power = 1;
+        for _ in 0..count {
+            power *= p;
+            for i in 0..previous {
+                divisors.push(divisors[i] * power);
+            }
+        }
+    }
+    divisors.sort_unstable();
+    divisors
+}
+
+/// The divisors of x paired up as (d, x / d) with d <= √x, smallest d first.
+///
+/// A square's root pairs with itself. 0 gets an empty Vec.
+///
+/// ```
+/// assert_eq!(prime_tools::divisor_pairs(36), vec![(1, 36), (2, 18), (3, 12), (4, 9), (6, 6)]);
+/// ```
+pub fn divisor_pairs(x: u64) -> Vec<(u64, u64)> {
+    divisors(x).into_iter().take_while(|&d| d <= x / d).map(|d| (d, x / d)).collect()
+}
+
+/// The unitary divisors of x in increasing order: the d with d | x and gcd(d, x / d) = 1.
+///
+/// Each one takes every factor of some primes of x and none of the others,
+/// so there are 2^ω(x) of them. 0 gets an empty Vec.
+///
+/// ```
+/// // 72 = 2^3 * 3^2
+/// assert_eq!(prime_tools::unitary_divisors(72), vec![1, 8, 9, 72]);
+/// ```
+pub fn unitary_divisors(x: u64) -> Vec<u64> {
+    if x == 0 {
+        return Vec::new();
+    }
+    let mut divisors = vec![1];
+    for (p, count) in factorize(x) {
+        let power = p.pow(count);
+        for i in 0..divisors.len() {
+            divisors.push(divisors[i] * power);
+        }
+    }
+    divisors.sort_unstable();
+    divisors
+}
+
+/// σ*(x), the sum of the unitary divisors of x.
+///
+/// Panics if the sum doesn't fit in a u64; see `checked_unitary_divisor_sum`.
+///
+/// ```
+/// assert_eq!(prime_tools::unitary_divisor_sum(72), 1 + 8 + 9 + 72);
+/// ```
+pub fn unitary_divisor_sum(x: u64) -> u64 {
+    checked_unitary_divisor_sum(x).expect("unitary_divisor_sum overflowed u64")
+}
+
+/// σ*(x), or None if it doesn't fit in a u64.
+///
+/// ```
+/// assert_eq!(prime_tools::checked_unitary_divisor_sum(u64::MAX), None);
+/// ```
+pub fn checked_unitary_divisor_sum(x: u64) -> Option<u64> {
+    if x == 0 {
+        return Some(0);
+    }
+    // The product of 1 + p^count over the prime powers of x.
+    factorize(x)
+        .into_iter()
+        .try_fold(1u64, |sum, (p, count)| sum.checked_mul(p.pow(count).checked_add(1)?))
+}
+
+/// σ*(x), or u64::MAX if it doesn't fit in a u64.
+///
+/// ```
+/// assert_eq!(prime_tools::saturating_unitary_divisor_sum(u64::MAX), u64::MAX);
+/// ```
+pub fn saturating_unitary_divisor_sum(x: u64) -> u64 {
+    checked_unitary_divisor_sum(x).unwrap_or(u64::MAX)
+}
+
+/// The number of divisors of x, d(x).
+///
+/// ```
+/// assert_eq!(prime_tools::divisor_count(12), 6);
+/// ```
+pub fn divisor_count(x: u64) -> u64 {
+    if x == 0 {
+        return 0;
+    }
+    factorize(x).iter().map(|&(_, count)| count as u64 + 1).product()
+}
+
+/// The sum of the k-th powers of the divisors of x, σ_k(x).
+///
+/// σ_0 is the divisor count and σ_1 the plain divisor sum.
+///
+/// Panics if the sum doesn't fit in a u64; see `checked_sigma_k`.
+///
+/// ```
+/// assert_eq!(prime_tools::sigma_k(12, 1), 1 + 2 + 3 + 4 + 6 + 12);
+/// assert_eq!(prime_tools::sigma_k(12, 2), 1 + 4 + 9 + 16 + 36 + 144);
+/// ```
+pub fn sigma_k(x: u64, k: u32) -> u64 {
+    checked_sigma_k(x, k).expect("sigma_k overflowed u64")
+}
+
+/// σ_k(x), or None if it doesn't fit in a u64.
+///
+/// ```
+/// assert_eq!(prime_tools::checked_sigma_k(12, 1), Some(28));
+/// assert_eq!(prime_tools::checked_sigma_k(1 << 40, 2), None);
+/// ```
+pub fn checked_sigma_k(x: u64, k: u32) -> Option<u64> {
+    if x == 0 {
+        return Some(0);
+    }
+    let mut sigma = 1u64;
+    for (p, count) in factorize(x) {
+        // 1 + p^k + p^2k + ... + p^(count * k)
+        let step = p.checked_pow(k)?;
+        let mut power = 1u64;
+        let mut sum = 1u64;
+        for _ in 0..count {
+            power = power.checked_mul(step)?;
+            sum = sum.checked_add(power)?;
+        }
+        sigma = sigma.checked_mul(sum)?;
+    }
+    Some(sigma)
+}
+
+/// σ_k(x), or u64::MAX if it doesn't fit in a u64.
+///
+/// ```
+/// assert_eq!(prime_tools::saturating_sigma_k(1 << 40, 2), u64::MAX);
+/// ```
+pub fn saturating_sigma_k(x: u64, k: u32) -> u64 {
+    checked_sigma_k(x, k).unwrap_or(u64::MAX)
+}
+
+/// The product of all the divisors of x.
+///
+/// Panics if the product doesn't fit in a u64; see `checked_divisor_product`.
+///
+/// ```
+/// assert_eq!(prime_tools::divisor_product(12), 1 * 2 * 3 * 4 * 6 * 12);
+/// ```
+pub fn divisor_product(x: u64) -> u64 {
+    checked_divisor_product(x).expect("divisor_product overflowed u64")
+}
+
+/// The product of all the divisors of x, or None if it doesn't fit in a u64.
+///
+/// ```
+/// assert_eq!(prime_tools::checked_divisor_product(16), Some(1 * 2 * 4 * 8 * 16));
+/// assert_eq!(prime_tools::checked_divisor_product(720), None);
+/// ```
+pub fn checked_divisor_product(x: u64) -> Option<u64> {
+    if x == 0 {
+        return Some(1);
+    }
+    // The divisors pair up as d * (x / d), so the product is x^(d(x) / 2).
+    // A square has an odd number of divisors and √x pairs with itself.
+    let count = divisor_count(x);
+    if count % 2 == 0 {
+        checked_pow(x, count / 2)
+    } else {
+        checked_pow(crate::isqrt(x), count)
+    }
+}
+
+/// The product of all the divisors of x, or u64::MAX if it doesn't fit in a u64.
+///
+/// ```
+/// assert_eq!(prime_tools::saturating_divisor_product(720), u64::MAX);
+/// ```
+pub fn saturating_divisor_product(x: u64) -> u64 {
+    checked_divisor_product(x).unwrap_or(u64::MAX)
+}
+
+/// The highly composite numbers up to and including n: every x with more
+/// divisors than any smaller positive number.
+///
+/// Such an x is always 2^a * 3^b * 5^c * ... with a >= b >= c >= ..., so only
+/// those products need their divisors counted.
+///
+/// ```
+/// assert_eq!(
+///     prime_tools::highly_composite_numbers_up_to(100),
+///     vec![1, 2, 4, 6, 12, 24, 36, 48, 60]
+/// );
+/// ```
+pub fn highly_composite_numbers_up_to(n: u64) -> Vec<u64> {
+    if n == 0 {
+        return Vec::new();
+    }
+
+    // (x, d(x)) for every product with non-increasing exponents.
+    let mut candidates = vec![(1, 1)];
+    let primes = crate::get_primes_less_than_x(64);
+    extend_descending_exponents(n, &primes, 0, 1, 1, u32::MAX, &mut candidates);
+    candidates.sort_unstable();
+
+    let mut record = 0;
+    let mut numbers = Vec::new();
+    for (x, divisors) in candidates {
+        if divisors > record {
+            record = divisors;
+            numbers.push(x);
+        }
+    }
+    numbers
+}
+
+fn extend_descending_exponents(n: u64, primes: &[u32], index: usize, x: u64, divisors: u64, max_exponent: u32, out: &mut Vec<(u64, u64)>) {
+    let p = match primes.get(index) {
+        Some(&p) => p as u64,
+        None => return,
+    };
+    let mut power = x;
+    for exponent in 1..=max_exponent {
+        power = match power.checked_mul(p) {
+            Some(power) if power <= n => power,
+            _ => return,
+        };
+        let d = divisors * (exponent as u64 + 1);
+        out.push((power, d));
+        extend_descending_exponents(n, primes, index + 1, power, d, exponent, out);
+    }
+}
+
+/// The superior highly composite numbers up to and including n.
+///
+/// x is superior highly composite if for some ε > 0, d(x) / x^ε beats
+/// d(y) / y^ε for every other y. Walking ε down, each prime power p^k joins
+/// at ε = ln(1 + 1/k) / ln(p), so the sequence is built by multiplying in
+/// those prime powers in order of decreasing ε.
+///
+/// ```
+/// assert_eq!(
+///     prime_tools::superior_highly_composite_numbers_up_to(10_000),
+///     vec![2, 6, 12, 60, 120, 360, 2520, 5040]
+/// );
+/// ```
+pub fn superior_highly_composite_numbers_up_to(n: u64) -> Vec<u64> {
+    // A u64 never gets a prime past 53 or a power of 2 past 2^63.
+    let mut steps: Vec<(f64, u64)> = Vec::new();
+    for p in crate::get_primes_less_than_x(60) {
+        for k in 1..64 {
+            steps.push(((1.0 + 1.0 / k as f64).ln() / (p as f64).ln(), p as u64));
+        }
+    }
+    steps.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+    let mut numbers = Vec::new();
+    let mut x = 1u64;
+    for (_, p) in steps {
+        x = match x.checked_mul(p) {
+            Some(x) if x <= n => x,
+            _ => break,
+        };
+        numbers.push(x);
+    }
+    numbers
+}
+
+/// True if x is practical: every number from 1 to x is a sum of distinct
+/// divisors of x.
+///
+/// Uses Stewart and Sierpiński's criterion on the factorization in
+/// increasing order of primes: x > 1 is practical iff its smallest prime is
+/// 2 and each later prime p is at most 1 + σ of the part of x made of the
+/// primes before p. 1 is practical and 0 isn't.
+///
+/// ```
+/// assert!(prime_tools::is_practical(12)); // 1, 2, 3, 4, 6 reach everything up to 12
+/// assert!(!prime_tools::is_practical(10)); // 4 isn't a sum of 1, 2 and 5
+/// assert!(prime_tools::is_practical(1));
+/// ```
+pub fn is_practical(x: u64) -> bool {
+    if x < 2 {
+        return x == 1;
+    }
+    // σ of the primes seen so far; p must not leave a gap past it.
+    let mut sigma: u128 = 1;
+    for (p, count) in factorize(x) {
+        let p = p as u128;
+        if p > sigma + 1 {
+            return false;
+        }
+        sigma *= (0..=count).map(|k| p.pow(k)).sum::<u128>();
+    }
+    true
+}
+
+/// The amicable pairs (a, b) with a < b <= n, ordered by a: each is the sum
+/// of the other's proper divisors.
+///
+/// Sieves the proper divisor sum of every number up to n, adding each d to
+/// all its multiples past d, so it takes about n ln n steps and eight bytes
+/// per number.
+///
+/// ```
+/// assert_eq!(prime_tools::amicable_pairs_up_to(3_000), vec![(220, 284), (1_184, 1_210), (2_620, 2_924)]);
+/// ```
+pub fn amicable_pairs_up_to(n: u64) -> Vec<(u64, u64)> {
+    let _span = debug_span!("amicable_pairs_up_to", n);
+    let n = n as usize;
+    let mut proper = vec![0u64; n + 1];
+    for d in 1..=n / 2 {
+        for multiple in (2 * d..=n).step_by(d) {
+            proper[multiple] += d as u64;
+        }
+    }
+    (2..=n)
+        .filter_map(|a| {
+            let b = proper[a];
+            if b > a as u64 && b <= n as u64 && proper[b as usize] == a as u64 {
+                Some((a as u64, b))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// True if a and b are an amicable pair: distinct, and each the sum of the
+/// other's proper divisors, i.e. σ(a) = σ(b) = a + b.
+///
+/// ```
+/// assert!(prime_tools::is_amicable_pair(284, 220));
+/// assert!(!prime_tools::is_amicable_pair(6, 6)); // perfect, not amicable
+/// ```
+pub fn is_amicable_pair(a: u64, b: u64) -> bool {
+    let sum = a as u128 + b as u128;
+    a != b && a > 0 && b > 0 && divisor_sum_u128(a) == sum && divisor_sum_u128(b) == sum
+}
+
+/// σ(x) for x > 0, which always fits in a u128.
+fn divisor_sum_u128(x: u64) -> u128 {
+    factorize(x)
+        .into_iter()
+        .map(|(p, count)| (0..=count).map(|k| (p as u128).pow(k)).sum::<u128>())
+        .product()
+}
+
+fn checked_pow(base: u64, exponent: u64) -> Option<u64> {
+    if base < 2 {
+        return Some(base);
+    }
+    // Anything past 2^64 overflows long before the exponent gets near u32::MAX.
+    if exponent >= 64 {
+        return None;
+    }
+    base.checked_pow(exponent as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_force_divisors(x: u64) -> Vec<u64> {
+        (1..=x).filter(|d| x % d == 0).collect()
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        for x in 1..2_000u64 {
+            let divisors = brute_force_divisors(x);
+            assert_eq!(super::divisors(x), divisors, "{}", x);
+            assert_eq!(divisor_count(x), divisors.len() as u64, "{}", x);
+            for k in 0..3 {
+                let expected: u64 = divisors.iter().map(|d| d.pow(k)).sum();
+                assert_eq!(sigma_k(x, k), expected, "{} {}", x, k);
+            }
+            let expected = divisors.iter().try_fold(1u64, |product, &d| product.checked_mul(d));
+            assert_eq!(checked_divisor_product(x), expected, "{}", x);
+        }
+    }
+
+    #[test]
+    fn highly_composite_matches_brute_force() {
+        let mut record = 0;
+        let mut expected = Vec::new();
+        for x in 1..=10_000 {
+            let d = brute_force_divisors(x).len();
+            if d > record {
+                record = d;
+                expected.push(x);
+            }
+        }
+        assert_eq!(highly_composite_numbers_up_to(10_000), expected);
+        assert_eq!(highly_composite_numbers_up_to(0), vec![]);
+        assert_eq!(highly_composite_numbers_up_to(1), vec![1]);
+    }
+
+    #[test]
+    fn highly_composite_near_u64_max() {
+        // A002182: the last highly composite number below 2^64.
+        let numbers = highly_composite_numbers_up_to(u64::MAX);
+        assert_eq!(numbers.last(), Some(&18_401_055_938_125_660_800));
+        assert_eq!(divisor_count(18_401_055_938_125_660_800), 184_320);
+    }
+
+    #[test]
+    fn superior_highly_composite_sequence() {
+        let numbers = superior_highly_composite_numbers_up_to(u64::MAX);
+        assert_eq!(
+            numbers[..15],
+            [2, 6, 12, 60, 120, 360, 2520, 5040, 55440, 720720, 1441440, 4324320, 21621600, 367567200, 6983776800]
+        );
+        // Each term is the previous one times a single prime.
+        for pair in numbers.windows(2) {
+            assert_eq!(pair[1] % pair[0], 0);
+            assert!(crate::is_u64_prime(pair[1] / pair[0]));
+        }
+        let highly_composite = highly_composite_numbers_up_to(u64::MAX);
+        for x in superior_highly_composite_numbers_up_to(u64::MAX) {
+            assert!(highly_composite.contains(&x), "{}", x);
+        }
+    }
+
+    #[test]
+    fn practical_numbers_match_subset_sums() {
+        for x in 0..1_500u64 {
+            // reachable[m]: some set of distinct divisors adds up to m.
+            let mut reachable = vec![false; x as usize + 1];
+            reachable[0] = true;
+            for d in brute_force_divisors(x) {
+                for m in (d as usize..=x as usize).rev() {
+                    reachable[m] |= reachable[m - d as usize];
+                }
+            }
+            assert_eq!(is_practical(x), x > 0 && reachable.iter().all(|&r| r), "{}", x);
+        }
+        // Powers of 2 and primorials always are.
+        assert!(is_practical(1 << 63));
+        assert!(is_practical(2 * 3 * 5 * 7 * 11 * 13 * 17 * 19 * 23 * 29 * 31 * 37 * 41 * 43 * 47));
+        assert!(!is_practical(2 * 5));
+    }
+
+    #[test]
+    fn amicable_pairs_below_100_000() {
+        let pairs = amicable_pairs_up_to(100_000);
+        assert_eq!(
+            pairs,
+            [
+                (220, 284), (1_184, 1_210), (2_620, 2_924), (5_020, 5_564), (6_232, 6_368), (10_744, 10_856), (12_285, 14_595),
+                (17_296, 18_416), (63_020, 76_084), (66_928, 66_992), (67_095, 71_145), (69_615, 87_633), (79_750, 88_730)
+            ]
+        );
+        for &(a, b) in &pairs {
+            assert!(is_amicable_pair(a, b) && is_amicable_pair(b, a));
+        }
+        // Only pairs that fit entirely count.
+        assert_eq!(amicable_pairs_up_to(284).len(), 1);
+        assert_eq!(amicable_pairs_up_to(283), vec![]);
+        assert_eq!(amicable_pairs_up_to(0), vec![]);
+        assert!(!is_amicable_pair(0, 0) && !is_amicable_pair(220, 285) && !is_amicable_pair(u64::MAX, u64::MAX - 1));
+    }
+
+    #[test]
+    fn pairs_and_unitary_divisors_match_brute_force() {
+        for x in 1..2_000u64 {
+            let divisors = brute_force_divisors(x);
+            let pairs: Vec<(u64, u64)> = divisors.iter().filter(|&&d| d * d <= x).map(|&d| (d, x / d)).collect();
+            assert_eq!(divisor_pairs(x), pairs, "{}", x);
+
+            let unitary: Vec<u64> = divisors.iter().cloned().filter(|&d| crate::gcd(d, x / d) == 1).collect();
+            assert_eq!(unitary_divisor_sum(x), unitary.iter().sum::<u64>(), "{}", x);
+            assert_eq!(unitary_divisors(x), unitary, "{}", x);
+        }
+        assert_eq!(divisor_pairs(u64::MAX).len(), 64);
+        assert_eq!(divisor_pairs(u64::MAX).last(), Some(&(4_294_967_295, 4_294_967_297)));
+        assert_eq!(unitary_divisors(1 << 63), vec![1, 1 << 63]);
+    }
+
+    #[test]
+    fn zero_has_no_divisors() {
+        assert_eq!(divisor_count(0), 0);
+        assert_eq!(divisors(0), vec![]);
+        assert_eq!(divisor_pairs(0), vec![]);
+        assert_eq!(unitary_divisors(0), vec![]);
+        assert_eq!(unitary_divisor_sum(0), 0);
+        assert_eq!(sigma_k(0, 1), 0);
+        assert_eq!(divisor_product(0), 1);
+    }
+
+    #[test]
+    fn overflow_at_the_edges() {
+        assert_eq!(checked_sigma_k(u64::MAX, 0), Some(128));
+        assert_eq!(checked_sigma_k(u64::MAX, 1), None);
+        assert_eq!(checked_sigma_k(2, 64), None);
+        assert_eq!(checked_sigma_k(2, 63), Some((1 << 63) + 1));
+        assert_eq!(checked_divisor_product(1_000_000_007), Some(1_000_000_007));
+        assert_eq!(checked_divisor_product(1 << 10), Some(1 << 55));
+        assert_eq!(checked_divisor_product(1 << 11), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "sigma_k overflowed u64")]
+    fn sigma_k_panics_on_overflow() {
+        sigma_k(u64::MAX, 1);
+    }
+}
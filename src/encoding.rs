@@ -0,0 +1,100 @@
+//! Exponent vectors packed into one integer as 2^e0 * 3^e1 * 5^e2 * ...
+//!
+//! By unique factorization every positive integer is exactly one such product,
+//! so this is a lossless way to turn a multiset (exponent i = how many copies
+//! of item i) into a number and back, as in Gödel numbering.
+
+use crate::Sieve;
+
+/// 2^exps[0] * 3^exps[1] * 5^exps[2] * ..., the i-th prime raised to exps[i].
+///
+/// Panics if the product doesn't fit in a u128; see `checked_encode_exponents`.
+///
+/// ```
+/// assert_eq!(prime_tools::encode_exponents(&[2, 0, 1]), 4 * 5);
+/// assert_eq!(prime_tools::encode_exponents(&[]), 1);
+/// ```
+pub fn encode_exponents(exps: &[u32]) -> u128 {
+    checked_encode_exponents(exps).expect("encode_exponents overflowed u128")
+}
+
+/// The same product, or None if it doesn't fit in a u128.
+///
+/// Trailing zeros don't change the result.
+///
+/// ```
+/// assert_eq!(prime_tools::checked_encode_exponents(&[127]), Some(1 << 127));
+/// assert_eq!(prime_tools::checked_encode_exponents(&[128]), None);
+/// ```
+pub fn checked_encode_exponents(exps: &[u32]) -> Option<u128> {
+    let len = exps.iter().rposition(|&e| e > 0).map_or(0, |last| last + 1);
+    let exps = &exps[..len];
+
+    let mut sieve = Sieve::new(64);
+    while sieve.primes().len() < len {
+        sieve.extend_to(sieve.limit() * 2);
+    }
+    exps.iter()
+        .zip(sieve.primes())
+        .try_fold(1u128, |product, (&e, &p)| product.checked_mul((p as u128).checked_pow(e)?))
+}
+
+/// The exponent vector of x: exps[i] is the power of the i-th prime in x.
+///
+/// The inverse of `encode_exponents`, without trailing zeros (1 gives an
+/// empty Vec). Primes are tried in order until x is used up, so this takes
+/// time roughly proportional to x's largest prime factor. Panics for x = 0.
+///
+/// ```
+/// assert_eq!(prime_tools::decode_exponents(20), vec![2, 0, 1]);
+/// assert_eq!(prime_tools::decode_exponents(1), vec![]);
+/// ```
+pub fn decode_exponents(x: u128) -> Vec<u32> {
+    assert!(x > 0, "0 isn't a product of prime powers");
+    let mut exps = Vec::new();
+    let mut rest = x;
+    let mut sieve = Sieve::new(64);
+    let mut i = 0;
+    while rest > 1 {
+        if i == sieve.primes().len() {
+            sieve.extend_to(sieve.limit() * 2);
+        }
+        let p = sieve.primes()[i] as u128;
+        let mut e = 0;
+        while rest % p == 0 {
+            rest /= p;
+            e += 1;
+        }
+        exps.push(e);
+        i += 1;
+    }
+    exps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        for x in 1..5_000u128 {
+            let exps = decode_exponents(x);
+            assert_ne!(exps.last(), Some(&0));
+            assert_eq!(encode_exponents(&exps), x, "{}", x);
+        }
+        let exps = vec![3, 0, 0, 7, 1, 0, 0, 0, 0, 0, 2];
+        assert_eq!(decode_exponents(encode_exponents(&exps)), exps);
+    }
+
+    #[test]
+    fn edges() {
+        assert_eq!(encode_exponents(&[0, 0, 0]), 1);
+        assert_eq!(checked_encode_exponents(&[1; 27]), None);
+        assert_eq!(encode_exponents(&[1; 26]), 232_862_364_358_497_360_900_063_316_880_507_363_070);
+        // 7919 is the 1000th prime.
+        let mut exps = vec![0; 999];
+        exps.push(1);
+        assert_eq!(encode_exponents(&exps), 7_919);
+        assert_eq!(decode_exponents(7_919), exps);
+    }
+}
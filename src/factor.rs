@@ -0,0 +1,801 @@
+//! Prime factorization of u64s.
+
+/// Prime factors of x with their counts, smallest prime first.
+///
+/// Uses trial division by 2, 3 and then 6k ± 1 up to the square root of
+/// whatever is left, so it's quick for numbers with small factors and takes a
+/// few seconds for a product of two ~32 bit primes.
+///
+/// 0 and 1 have no prime factors, so they get an empty Vec.
+///
+/// ```
+/// assert_eq!(prime_tools::factorize(360), vec![(2, 3), (3, 2), (5, 1)]);
+/// ```
+///
+/// ```
+/// assert_eq!(prime_tools::factorize(1_000_000_007), vec![(1_000_000_007, 1)]);
+/// ```
+pub fn factorize(x: u64) -> Vec<(u64, u32)> {
+    let mut factors = Vec::new();
+    factorize_into(x, &mut factors);
+    factors
+}
+
+/// Like `factorize`, but fills `out` (clearing it first) so a loop can reuse one buffer.
+///
+/// ```
+/// let mut factors = Vec::new();
+/// for x in 2..1_000 {
+///     prime_tools::factorize_into(x, &mut factors);
+///     assert!(!factors.is_empty());
+/// }
+/// assert_eq!(factors, vec![(3, 3), (37, 1)]);
+/// ```
+pub fn factorize_into(x: u64, out: &mut Vec<(u64, u32)>) {
+    out.clear();
+    if x < 2 {
+        return;
+    }
+
+    let mut rest = x;
+    for &p in &[2, 3] {
+        divide_out(&mut rest, p, out);
+    }
+
+    let mut p = 5;
+    let mut w = 2;
+    while p <= rest / p {
+        divide_out(&mut rest, p, out);
+        p += w;
+        w = 6 - w;
+    }
+
+    if rest > 1 {
+        out.push((rest, 1));
+    }
+}
+
+/// The factorization of every number in `[min, max)`, in order, each one as
+/// `factorize` would give it.
+///
+/// Rather than factoring the numbers one at a time, every prime up to √max is
+/// walked across the window hitting only its own multiples (a sieve of
+/// divisors), and whatever is left of each number afterwards is one big prime.
+/// That makes a window of a million numbers near 10^12 a fraction of a second.
+///
+/// ```
+/// assert_eq!(
+///     prime_tools::factorize_range(10, 13),
+///     vec![vec![(2, 1), (5, 1)], vec![(11, 1)], vec![(2, 2), (3, 1)]]
+/// );
+/// ```
+pub fn factorize_range(min: u64, max: u64) -> Vec<Vec<(u64, u32)>> {
+    if min >= max {
+        return Vec::new();
+    }
+    let _span = debug_span!("factorize_range", min, max);
+    let len = (max - min) as usize;
+    let mut rest: Vec<u64> = (min..max).collect();
+    if min == 0 {
+        // 0 has no factorization; as 1 it just never divides.
+        rest[0] = 1;
+    }
+    let mut factors = vec![Vec::new(); len];
+
+    let highest_factor = crate::isqrt(max - 1) as u32;
+    crate::sieve::for_each_prime_up_to(highest_factor, &crate::SieveOptions::default(), |p| {
+        let first = match min % p {
+            0 => min,
+            r => match min.checked_add(p - r) {
+                Some(first) => first,
+                None => return,
+            },
+        };
+        let mut offset = (first - min) as usize;
+        while offset < len {
+            divide_out(&mut rest[offset], p, &mut factors[offset]);
+            offset += p as usize;
+        }
+    });
+
+    for (rest, factors) in rest.into_iter().zip(&mut factors) {
+        if rest > 1 {
+            factors.push((rest, 1));
+        }
+    }
+    factors
+}
+
+/// The numbers in `[min, max)` with exactly k distinct prime factors, ω(x) = k.
+///
+/// Sieves ω a segment of the window at a time: each prime up to √max adds
+/// one to its multiples and multiplies itself into them once per power
+/// dividing them, and a number whose product then falls short of it has one
+/// more prime factor past √max. 0 is never included; 1 is the one number with
+/// ω = 0. See `sphenic_numbers_between` for the squarefree k = 3 case.
+///
+/// ```
+/// assert_eq!(
+///     prime_tools::numbers_with_omega_between(2, 40, 2),
+///     vec![6, 10, 12, 14, 15, 18, 20, 21, 22, 24, 26, 28, 33, 34, 35, 36, 38, 39]
+/// );
+/// assert_eq!(prime_tools::numbers_with_omega_between(0, 500, 4), vec![210, 330, 390, 420, 462]);
+/// ```
+pub fn numbers_with_omega_between(min: u64, max: u64, k: u32) -> Vec<u64> {
+    const SEGMENT: u64 = 1 << 16;
+    let _span = debug_span!("numbers_with_omega_between", min, max, k);
+    let mut found = Vec::new();
+    let min = min.max(1);
+    if min >= max {
+        return found;
+    }
+    let primes = crate::sieve::primes_up_to(crate::isqrt(max - 1) as u32, &crate::SieveOptions::default());
+    let mut omega = vec![0u32; SEGMENT as usize];
+    let mut product = vec![0u64; SEGMENT as usize];
+    let mut low = min;
+    while low < max {
+        let high = low.saturating_add(SEGMENT).min(max);
+        let len = (high - low) as usize;
+        omega[..len].fill(0);
+        product[..len].fill(1);
+        for &p in &primes {
+            let p = p as u64;
+            let mut power = p;
+            loop {
+                let mut multiple = low.div_ceil(power).saturating_mul(power);
+                while multiple < high {
+                    let i = (multiple - low) as usize;
+                    if power == p {
+                        omega[i] += 1;
+                    }
+                    product[i] *= p;
+                    multiple = multiple.saturating_add(power);
+                }
+                match power.checked_mul(p) {
+                    Some(next) if next < high => power = next,
+                    _ => break,
+                }
+            }
+        }
+        for (i, (&omega, &product)) in omega.iter().zip(&product).take(len).enumerate() {
+            let x = low + i as u64;
+            if omega + (product != x) as u32 == k {
+                found.push(x);
+            }
+        }
+        low = high;
+    }
+    found
+}
+
+/// The sphenic numbers in `[min, max)`: products of three distinct primes.
+///
+/// `numbers_with_omega_between(min, max, 3)` with the numbers that aren't
+/// squarefree taken out.
+///
+/// ```
+/// assert_eq!(prime_tools::sphenic_numbers_between(0, 120), vec![30, 42, 66, 70, 78, 102, 105, 110, 114]);
+/// ```
+pub fn sphenic_numbers_between(min: u64, max: u64) -> Vec<u64> {
+    let squarefree = crate::squarefree_between(min, max);
+    let mut numbers = numbers_with_omega_between(min, max, 3);
+    numbers.retain(|&x| squarefree[(x - min) as usize]);
+    numbers
+}
+
+/// The smallest prime factor of x, or None for 0 and 1.
+///
+/// Stops at the first factor it finds, so even numbers cost one division.
+/// If nothing below 2^16 divides x, a Miller–Rabin test spots a prime x,
+/// and Pollard's rho splits a composite one.
+///
+/// ```
+/// assert_eq!(prime_tools::least_prime_factor(91), Some(7));
+/// assert_eq!(prime_tools::least_prime_factor(1_000_000_007), Some(1_000_000_007));
+/// assert_eq!(prime_tools::least_prime_factor(1), None);
+/// ```
+pub fn least_prime_factor(x: u64) -> Option<u64> {
+    if x < 2 {
+        return None;
+    }
+    for p in small_primes() {
+        if p > x / p {
+            return Some(x);
+        }
+        if x % p == 0 {
+            return Some(p);
+        }
+    }
+    chain_primes(x).min()
+}
+
+/// The largest prime factor of x, or None for 0 and 1.
+///
+/// Divides out the primes below 2^16 in order, but as soon as what's left is
+/// prime (by Miller–Rabin) that's the answer, so a big prime cofactor never
+/// gets trial divided. A composite cofactor left after that is split with
+/// Pollard's rho.
+///
+/// ```
+/// assert_eq!(prime_tools::greatest_prime_factor(360), Some(5));
+/// assert_eq!(prime_tools::greatest_prime_factor(2 * 1_000_000_007), Some(1_000_000_007));
+/// assert_eq!(prime_tools::greatest_prime_factor(0), None);
+/// ```
+pub fn greatest_prime_factor(x: u64) -> Option<u64> {
+    if x < 2 {
+        return None;
+    }
+    let mut rest = x;
+    if is_prime(rest) {
+        return Some(rest);
+    }
+    for p in small_primes() {
+        if rest % p == 0 {
+            while rest % p == 0 {
+                rest /= p;
+            }
+            if rest == 1 {
+                return Some(p);
+            }
+            if is_prime(rest) {
+                return Some(rest);
+            }
+        }
+    }
+    chain_primes(rest).max()
+}
+
+/// The primes below 2^16, from the table.
+fn small_primes() -> impl Iterator<Item = u64> {
+    crate::small_primes::SMALL_PRIMES.iter().map(|&p| p as u64)
+}
+
+/// The prime factors of n > 0 that the chain finds, in no particular order.
+fn chain_primes(n: u64) -> impl Iterator<Item = u64> {
+    crate::factor_report::factor_chain(n, |_, _, _| {}).factors.into_iter().map(|(p, _)| p)
+}
+
+fn is_prime(x: u64) -> bool {
+    crate::miller_rabin_with_bases(x as u128, &crate::primality::U64_BASES)
+}
+
+/// One walk of Brent's variant of Pollard's rho on an odd composite n,
+/// iterating y -> y^2 + c from y = start: a nontrivial factor, or None if
+/// the walk closed up on n itself and needs another c.
+pub(crate) fn brent_rho(n: u64, c: u64, start: u64) -> Option<u64> {
+    // gcds are taken over products of this many differences at once.
+    const BATCH: u64 = 128;
+    let mul = |a: u64, b: u64| (a as u128 * b as u128 % n as u128) as u64;
+    let f = |y: u64| ((mul(y, y) as u128 + c as u128) % n as u128) as u64;
+    let mut y = start;
+    let (mut x, mut saved) = (y, y);
+    let (mut g, mut r, mut product) = (1, 1, 1);
+    while g == 1 {
+        x = y;
+        for _ in 0..r {
+            y = f(y);
+        }
+        let mut k = 0;
+        while k < r && g == 1 {
+            saved = y;
+            for _ in 0..BATCH.min(r - k) {
+                y = f(y);
+                product = mul(product, x.abs_diff(y));
+            }
+            g = crate::gcd(product, n);
+            k += BATCH;
+        }
+        r *= 2;
+    }
+    if g == n {
+        // The batch went past the factor (or the walk closed up); retrace it one step at a time.
+        loop {
+            saved = f(saved);
+            g = crate::gcd(x.abs_diff(saved), n);
+            if g > 1 {
+                break;
+            }
+        }
+    }
+    if g != n {
+        Some(g)
+    } else {
+        None
+    }
+}
+
+/// The largest k with p^k dividing x.
+///
+/// Every power of p divides 0, so that gets u32::MAX. Panics if p < 2.
+///
+/// ```
+/// assert_eq!(prime_tools::p_adic_valuation(48, 2), 4);
+/// assert_eq!(prime_tools::p_adic_valuation(48, 5), 0);
+/// assert_eq!(prime_tools::p_adic_valuation(0, 3), u32::MAX);
+/// ```
+pub fn p_adic_valuation(x: u64, p: u64) -> u32 {
+    assert!(p >= 2, "p_adic_valuation needs p >= 2, got {}", p);
+    if x == 0 {
+        return u32::MAX;
+    }
+    let mut rest = x;
+    let mut k = 0;
+    while rest % p == 0 {
+        rest /= p;
+        k += 1;
+    }
+    k
+}
+
+/// The largest k with p^k dividing n!, for a prime p.
+///
+/// Uses Legendre's formula, k = ⌊n/p⌋ + ⌊n/p²⌋ + ..., so n! itself is never
+/// formed. The answer can pass u32::MAX for big n, hence the u64. Panics if
+/// p < 2; for p that isn't prime the result is meaningless.
+///
+/// ```
+/// // 10! = 3628800 = 2^8 * 3^4 * 5^2 * 7
+/// assert_eq!(prime_tools::p_adic_valuation_of_factorial(10, 2), 8);
+/// assert_eq!(prime_tools::p_adic_valuation_of_factorial(10, 5), 2);
+/// ```
+pub fn p_adic_valuation_of_factorial(n: u64, p: u64) -> u64 {
+    assert!(p >= 2, "p_adic_valuation_of_factorial needs p >= 2, got {}", p);
+    let mut k = 0;
+    let mut rest = n;
+    while rest > 0 {
+        rest /= p;
+        k += rest;
+    }
+    k
+}
+
+/// Some((p, k)) if x = p^k for a prime p and k >= 1, otherwise None.
+///
+/// Tries every exponent from 63 down, taking the integer k-th root and
+/// checking whether it's a prime whose k-th power gives x back. Primality
+/// is deterministic Miller–Rabin, so this is quick for any u64.
+///
+/// ```
+/// assert_eq!(prime_tools::as_prime_power(243), Some((3, 5)));
+/// assert_eq!(prime_tools::as_prime_power(13), Some((13, 1)));
+/// assert_eq!(prime_tools::as_prime_power(36), None);
+/// assert_eq!(prime_tools::as_prime_power(1), None);
+/// ```
+pub fn as_prime_power(x: u64) -> Option<(u64, u32)> {
+    if x < 2 {
+        return None;
+    }
+    for k in (2..64).rev() {
+        let root = integer_root(x, k);
+        if root >= 2 && root.pow(k) == x && is_prime(root) {
+            return Some((root, k));
+        }
+    }
+    if is_prime(x) {
+        Some((x, 1))
+    } else {
+        None
+    }
+}
+
+/// The largest r with r^k <= x, for k >= 1.
+pub(crate) fn integer_root(x: u64, k: u32) -> u64 {
+    let mut r = (x as f64).powf(1.0 / k as f64) as u64;
+    // The float estimate can be off by one either way.
+    while r > 0 && r.checked_pow(k).is_none_or(|power| power > x) {
+        r -= 1;
+    }
+    while (r + 1).checked_pow(k).is_some_and(|power| power <= x) {
+        r += 1;
+    }
+    r
+}
+
+/// The prime signature of x: the exponents of its prime factorization, largest first.
+///
+/// Two numbers have the same signature when they factor the same way, e.g.
+/// 12 = 2^2 * 3 and 50 = 2 * 5^2 are both "a square times a prime".
+///
+/// 0 and 1 get an empty signature.
+///
+/// ```
+/// assert_eq!(prime_tools::prime_signature(12), vec![2, 1]);
+/// assert_eq!(prime_tools::prime_signature(50), vec![2, 1]);
+/// assert_eq!(prime_tools::prime_signature(7), vec![1]);
+/// ```
+pub fn prime_signature(x: u64) -> Vec<u32> {
+    let mut signature: Vec<u32> = factorize(x).into_iter().map(|(_, count)| count).collect();
+    signature.sort_unstable_by(|a, b| b.cmp(a));
+    signature
+}
+
+/// Splits x into (s, f) with x = s² · f and f squarefree, so √x = s√f.
+///
+/// s² is the largest square dividing x and f is its squarefree part, the
+/// product of the primes that appear in x an odd number of times. 0 gets
+/// (0, 1).
+///
+/// ```
+/// assert_eq!(prime_tools::square_decomposition(72), (6, 2));
+/// assert_eq!(prime_tools::square_decomposition(49), (7, 1));
+/// assert_eq!(prime_tools::square_decomposition(30), (1, 30));
+/// ```
+pub fn square_decomposition(x: u64) -> (u64, u64) {
+    if x == 0 {
+        return (0, 1);
+    }
+    factorize(x).into_iter().fold((1, 1), |(root, squarefree), (p, count)| {
+        (root * p.pow(count / 2), if count % 2 == 1 { squarefree * p } else { squarefree })
+    })
+}
+
+/// True if n = pq for distinct primes p, q ≡ 3 (mod 4), a Blum integer.
+///
+/// Squaring mod a Blum integer permutes the quadratic residues, which is
+/// what the Blum Blum Shub generator and the Rabin cryptosystem rely on.
+/// Anything ≡ 1 (mod 4) is factored with Miller–Rabin and Pollard's rho, so
+/// a 64-bit modulus made of two 32-bit primes is answered quickly.
+///
+/// ```
+/// assert!(prime_tools::is_blum_integer(21)); // 3 * 7
+/// assert!(!prime_tools::is_blum_integer(9)); // 3 * 3
+/// assert!(!prime_tools::is_blum_integer(33 * 5));
+/// ```
+pub fn is_blum_integer(n: u64) -> bool {
+    // p ≡ q ≡ 3 (mod 4) makes n ≡ 1 (mod 4), which also rules out 0.
+    if n % 4 != 1 {
+        return false;
+    }
+    match crate::batch::factorize_quickly(n).factors() {
+        &[(p, 1), (q, 1)] => p % 4 == 3 && q % 4 == 3,
+        _ => false,
+    }
+}
+
+/// The powerful numbers up to n in increasing order: those where every
+/// prime factor appears at least squared. 1 counts.
+///
+/// Each one is a²b³ for exactly one squarefree b, so they're generated that
+/// way rather than by factoring, with the squarefree b up to n^(1/3) taken
+/// from `squarefree_between`. There are about 2.17·√n of them.
+///
+/// ```
+/// assert_eq!(prime_tools::powerful_numbers_up_to(50), vec![1, 4, 8, 9, 16, 25, 27, 32, 36, 49]);
+/// ```
+pub fn powerful_numbers_up_to(n: u64) -> Vec<u64> {
+    let _span = debug_span!("powerful_numbers_up_to", n);
+    let cube_root = integer_root(n, 3);
+    let squarefree = crate::squarefree_between(0, cube_root + 1);
+    let mut powerful = Vec::new();
+    for b in (1..=cube_root).filter(|&b| squarefree[b as usize]) {
+        let cube = b * b * b;
+        powerful.extend((1..=crate::isqrt(n / cube)).map(|a| a * a * cube));
+    }
+    powerful.sort_unstable();
+    powerful
+}
+
+/// True if x is an Achilles number: powerful, but not a perfect power.
+///
+/// So every exponent in x's factorization is at least 2 and they have no
+/// common factor, like 72 = 2^3 * 3^2.
+///
+/// ```
+/// assert!(prime_tools::is_achilles_number(72));
+/// assert!(!prime_tools::is_achilles_number(36)); // 6^2
+/// assert!(!prime_tools::is_achilles_number(24)); // 3 appears once
+/// ```
+pub fn is_achilles_number(x: u64) -> bool {
+    let exponents = prime_signature(x);
+    exponents.len() >= 2
+        && exponents.iter().all(|&count| count >= 2)
+        && exponents.iter().fold(0, |g, &count| crate::gcd(g, count as u64)) == 1
+}
+
+/// The smallest number with the given prime signature, or None if it doesn't fit in a u64.
+///
+/// The exponents can come in any order; the biggest goes on 2, the next on 3
+/// and so on. Zero exponents are ignored.
+///
+/// ```
+/// assert_eq!(prime_tools::least_number_with_signature(&[1, 2]), Some(12));
+/// assert_eq!(prime_tools::least_number_with_signature(&[]), Some(1));
+/// assert_eq!(prime_tools::least_number_with_signature(&[64]), None);
+/// ```
+pub fn least_number_with_signature(signature: &[u32]) -> Option<u64> {
+    let mut exponents: Vec<u32> = signature.iter().cloned().filter(|&e| e > 0).collect();
+    exponents.sort_unstable_by(|a, b| b.cmp(a));
+
+    // Even a single factor of every one of the first 16 primes overflows a u64.
+    if exponents.len() > 15 {
+        return None;
+    }
+    let primes = crate::get_primes_less_than_x(50);
+    exponents
+        .iter()
+        .zip(primes)
+        .try_fold(1u64, |product, (&e, p)| product.checked_mul((p as u64).checked_pow(e)?))
+}
+
+/// The gcd of two numbers given as factorizations like the ones `factorize` returns.
+///
+/// Both inputs must be sorted by prime; so is the result. Primes only one side
+/// has drop out, and the rest keep the smaller count.
+///
+/// ```
+/// use prime_tools::{factorize, gcd_of_factorizations};
+///
+/// assert_eq!(gcd_of_factorizations(&factorize(360), &factorize(84)), factorize(12));
+/// ```
+pub fn gcd_of_factorizations(a: &[(u64, u32)], b: &[(u64, u32)]) -> Vec<(u64, u32)> {
+    merge_factorizations(a, b, |x, y| x.min(y))
+}
+
+/// The lcm of two numbers given as factorizations like the ones `factorize` returns.
+///
+/// Both inputs must be sorted by prime; so is the result. Every prime from
+/// either side is kept with the larger count.
+///
+/// ```
+/// use prime_tools::{factorize, lcm_of_factorizations};
+///
+/// assert_eq!(lcm_of_factorizations(&factorize(360), &factorize(84)), factorize(2520));
+/// ```
+pub fn lcm_of_factorizations(a: &[(u64, u32)], b: &[(u64, u32)]) -> Vec<(u64, u32)> {
+    merge_factorizations(a, b, |x, y| x.max(y))
+}
+
+/// Walks both factorizations in prime order, a missing prime counting as 0,
+/// and keeps every prime whose combined count is nonzero.
+pub(crate) fn merge_factorizations(a: &[(u64, u32)], b: &[(u64, u32)], combine: impl Fn(u32, u32) -> u32) -> Vec<(u64, u32)> {
+    let mut merged = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let (p, x, y) = match (a.get(i), b.get(j)) {
+            (Some(&(p, x)), Some(&(q, y))) if p == q => {
+                i += 1;
+                j += 1;
+                (p, x, y)
+            }
+            (Some(&(p, x)), Some(&(q, _))) if p < q => {
+                i += 1;
+                (p, x, 0)
+            }
+            (Some(&(p, x)), None) => {
+                i += 1;
+                (p, x, 0)
+            }
+            (_, Some(&(q, y))) => {
+                j += 1;
+                (q, 0, y)
+            }
+            (None, None) => unreachable!(),
+        };
+        let count = combine(x, y);
+        if count > 0 {
+            merged.push((p, count));
+        }
+    }
+    merged
+}
+
+/// Divides every factor p out of rest, recording (p, count) if there were any.
+fn divide_out(rest: &mut u64, p: u64, factors: &mut Vec<(u64, u32)>) {
+    let mut count = 0;
+    while *rest % p == 0 {
+        *rest /= p;
+        count += 1;
+    }
+    if count > 0 {
+        factors.push((p, count));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn powerful_and_achilles_numbers_match_their_factorizations() {
+        let n = 200_000;
+        let powerful = powerful_numbers_up_to(n);
+        let expected: Vec<u64> = (1..=n).filter(|&x| factorize(x).iter().all(|&(_, count)| count >= 2)).collect();
+        assert_eq!(powerful, expected);
+        let achilles: Vec<u64> = powerful.iter().copied().filter(|&x| is_achilles_number(x)).take(10).collect();
+        assert_eq!(achilles, vec![72, 108, 200, 288, 392, 432, 500, 648, 675, 800]);
+        assert!((1..=n).filter(|&x| is_achilles_number(x)).all(|x| powerful.binary_search(&x).is_ok()));
+        assert!(!is_achilles_number(0) && !is_achilles_number(1));
+        assert_eq!(powerful_numbers_up_to(0), vec![]);
+        assert_eq!(powerful_numbers_up_to(1), vec![1]);
+        assert_eq!(powerful_numbers_up_to(u64::MAX >> 24).len(), 2_263_456);
+    }
+
+    #[test]
+    fn square_decompositions_multiply_back() {
+        for x in (1..10_000).chain([u64::MAX, 1 << 63, (1 << 32) - 1, 1_000_003 * 1_000_003 * 7]) {
+            let (root, squarefree) = square_decomposition(x);
+            assert_eq!(root * root * squarefree, x, "{}", x);
+            assert!(factorize(squarefree).iter().all(|&(_, count)| count == 1), "{}", x);
+        }
+        assert_eq!(square_decomposition(0), (0, 1));
+        assert_eq!(square_decomposition(1 << 63), (1 << 31, 2));
+    }
+
+    #[test]
+    fn blum_integers() {
+        let expected = [21, 33, 57, 69, 77, 93, 129, 133, 141, 161, 177, 201, 209, 213, 217, 237, 249, 253];
+        let found: Vec<u64> = (0..256).filter(|&n| is_blum_integer(n)).collect();
+        assert_eq!(found, expected);
+        assert!(is_blum_integer(4_294_967_291 * 3));
+        assert!(is_blum_integer(4_294_967_291 * 4_294_967_279));
+        assert!(!is_blum_integer(4_294_967_291 * 4_294_967_291));
+    }
+
+    #[test]
+    fn least_and_greatest_prime_factors() {
+        let mut factors = Vec::new();
+        for x in 0..20_000 {
+            factorize_into(x, &mut factors);
+            assert_eq!(least_prime_factor(x), factors.first().map(|&(p, _)| p), "{}", x);
+            assert_eq!(greatest_prime_factor(x), factors.last().map(|&(p, _)| p), "{}", x);
+        }
+        for &(x, least, greatest) in &[
+            (u64::MAX, 3, 6_700_417),
+            (1_000_003 * 1_000_033, 1_000_003, 1_000_033),
+            (65_521 * 65_537, 65_521, 65_537),
+            (65_537 * 65_537, 65_537, 65_537),
+            ((1 << 33) * 1_000_000_007, 2, 1_000_000_007),
+            (18_446_744_073_709_551_557, 18_446_744_073_709_551_557, 18_446_744_073_709_551_557),
+            (4_294_967_279 * 4_294_967_291, 4_294_967_279, 4_294_967_291),
+            (65_537 * 65_539 * 65_543, 65_537, 65_543),
+            (4_294_967_291 * 4_294_967_291, 4_294_967_291, 4_294_967_291),
+        ] {
+            assert_eq!(least_prime_factor(x), Some(least), "{}", x);
+            assert_eq!(greatest_prime_factor(x), Some(greatest), "{}", x);
+        }
+    }
+
+    #[test]
+    fn factorize_into_clears_first() {
+        let mut out = vec![(7, 7)];
+        factorize_into(1, &mut out);
+        assert!(out.is_empty());
+        factorize_into(360, &mut out);
+        factorize_into(98, &mut out);
+        assert_eq!(out, vec![(2, 1), (7, 2)]);
+    }
+
+    #[test]
+    fn tiny_inputs() {
+        assert_eq!(factorize(0), vec![]);
+        assert_eq!(factorize(1), vec![]);
+        assert_eq!(factorize(2), vec![(2, 1)]);
+        assert_eq!(factorize(3), vec![(3, 1)]);
+        assert_eq!(factorize(4), vec![(2, 2)]);
+    }
+
+    #[test]
+    fn matches_the_u32_factor_map() {
+        let primes = crate::get_primes_less_than_x(1_000);
+        for x in 0..20_000u32 {
+            let mut expected: Vec<(u64, u32)> = crate::get_prime_factors_with_counts(x, &primes)
+                .into_iter()
+                .map(|(p, count)| (p as u64, count))
+                .collect();
+            expected.sort_unstable();
+            assert_eq!(factorize(x as u64), expected, "{}", x);
+        }
+    }
+
+    #[test]
+    fn omega_filters_match_factorize() {
+        for &(min, max) in &[(0, 5_000), (1, 2), (4, 4), (1_000_000_000_000 - 300, 1_000_000_000_000 + 300)] {
+            for k in 0..=5 {
+                let expected: Vec<u64> = (min.max(1)..max).filter(|&x| factorize(x).len() == k as usize).collect();
+                assert_eq!(numbers_with_omega_between(min, max, k), expected, "[{}, {}) ω = {}", min, max, k);
+            }
+            let sphenic: Vec<u64> = (min..max).filter(|&x| prime_signature(x) == [1, 1, 1]).collect();
+            assert_eq!(sphenic_numbers_between(min, max), sphenic, "[{}, {})", min, max);
+        }
+    }
+
+    #[test]
+    fn factorize_range_matches_factorize() {
+        for &(min, max) in &[(0, 3_000), (1, 2), (97, 98), (1_000_000_000_000 - 500, 1_000_000_000_000 + 500)] {
+            let expected: Vec<_> = (min..max).map(factorize).collect();
+            assert_eq!(factorize_range(min, max), expected, "{}..{}", min, max);
+        }
+        assert_eq!(factorize_range(5, 5), Vec::<Vec<(u64, u32)>>::new());
+        assert_eq!(factorize_range((1 << 40) - 3, 1 << 40), vec![factorize((1 << 40) - 3), factorize((1 << 40) - 2), factorize((1 << 40) - 1)]);
+    }
+
+    #[test]
+    fn valuations() {
+        for x in 1..3_000u64 {
+            for (p, count) in factorize(x) {
+                assert_eq!(p_adic_valuation(x, p), count, "{} {}", x, p);
+            }
+            assert_eq!(p_adic_valuation(x, 4), p_adic_valuation(x, 2) / 2, "{}", x);
+        }
+        assert_eq!(p_adic_valuation(1 << 63, 2), 63);
+        assert_eq!(p_adic_valuation(u64::MAX, u64::MAX), 1);
+
+        let mut counts = [0u64; 3];
+        for n in 1..2_000u64 {
+            for (i, &p) in [2, 3, 7].iter().enumerate() {
+                counts[i] += p_adic_valuation(n, p) as u64;
+                assert_eq!(p_adic_valuation_of_factorial(n, p), counts[i], "{} {}", n, p);
+            }
+        }
+        assert_eq!(p_adic_valuation_of_factorial(0, 2), 0);
+        assert_eq!(p_adic_valuation_of_factorial(u64::MAX, 2), u64::MAX - 64);
+    }
+
+    #[test]
+    #[should_panic]
+    fn valuation_base_one_panics() {
+        p_adic_valuation(8, 1);
+    }
+
+    #[test]
+    fn prime_powers() {
+        for x in 0..20_000u64 {
+            let factors = factorize(x);
+            let expected = if factors.len() == 1 { Some(factors[0]) } else { None };
+            assert_eq!(as_prime_power(x), expected, "{}", x);
+        }
+        assert_eq!(as_prime_power(1 << 63), Some((2, 63)));
+        assert_eq!(as_prime_power(4_294_967_291 * 4_294_967_291), Some((4_294_967_291, 2)));
+        assert_eq!(as_prime_power(12_157_665_459_056_928_801), Some((3, 40)));
+        assert_eq!(as_prime_power(u64::MAX), None);
+        assert_eq!(as_prime_power(18_446_744_073_709_551_557), Some((18_446_744_073_709_551_557, 1)));
+        assert_eq!(integer_root(u64::MAX, 2), u32::MAX as u64);
+        assert_eq!(integer_root(u64::MAX, 63), 2);
+    }
+
+    #[test]
+    fn signature_round_trip() {
+        for x in 1..5_000u64 {
+            let least = least_number_with_signature(&prime_signature(x)).unwrap();
+            assert!(least <= x, "{}", x);
+            assert_eq!(prime_signature(least), prime_signature(x), "{}", x);
+        }
+    }
+
+    #[test]
+    fn signature_edges() {
+        assert_eq!(prime_signature(0), vec![]);
+        assert_eq!(prime_signature(1), vec![]);
+        assert_eq!(prime_signature(u64::MAX), vec![1; 7]);
+        assert_eq!(least_number_with_signature(&[0, 0, 3]), Some(8));
+        assert_eq!(least_number_with_signature(&[63]), Some(1 << 63));
+        assert_eq!(least_number_with_signature(&[1; 15]), Some(614_889_782_588_491_410));
+        assert_eq!(least_number_with_signature(&[1; 16]), None);
+    }
+
+    #[test]
+    fn gcd_and_lcm_match_euclid() {
+        for a in 1..200u64 {
+            for b in 1..200u64 {
+                let (mut x, mut y) = (a, b);
+                while y != 0 {
+                    let t = x % y;
+                    x = y;
+                    y = t;
+                }
+                let (fa, fb) = (factorize(a), factorize(b));
+                assert_eq!(gcd_of_factorizations(&fa, &fb), factorize(x), "{} {}", a, b);
+                assert_eq!(lcm_of_factorizations(&fa, &fb), factorize(a / x * b), "{} {}", a, b);
+            }
+        }
+        assert_eq!(gcd_of_factorizations(&[], &[(2, 3)]), vec![]);
+        assert_eq!(lcm_of_factorizations(&[], &[(2, 3)]), vec![(2, 3)]);
+    }
+
+    #[test]
+    fn big_inputs() {
+        assert_eq!(factorize(u64::MAX), vec![(3, 1), (5, 1), (17, 1), (257, 1), (641, 1), (65_537, 1), (6_700_417, 1)]);
+        assert_eq!(factorize(1 << 63), vec![(2, 63)]);
+        assert_eq!(factorize(600_851_475_143), vec![(71, 1), (839, 1), (1_471, 1), (6_857, 1)]);
+    }
+}
@@ -0,0 +1,161 @@
+//! A bounded, least-recently-used memo of factorizations.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use crate::batch::factorize_quickly;
+use crate::Factorization;
+
+/// Recently asked-for factorizations and totients, up to a fixed number of
+/// entries, dropping the least recently used first.
+///
+/// Meant for services that keep seeing the same inputs, such as group
+/// orders sent by clients: a repeat costs a hash lookup instead of a
+/// factorization. Misses are factored as in `factorize_many`, which is
+/// quick for any u64. The cache locks internally, so one can be shared
+/// between threads by reference; the factoring on a miss happens outside
+/// the lock.
+///
+/// ```
+/// use prime_tools::{FactorCache, Factorization};
+///
+/// let cache = FactorCache::with_capacity(2);
+/// assert_eq!(cache.factorize(360), Factorization::new(360));
+/// assert_eq!(cache.totient(1_000_003 * 1_000_033), 1_000_002 * 1_000_032);
+/// cache.factorize(360);
+/// // 1_000_003 * 1_000_033 is now the least recently used, and goes.
+/// cache.factorize(97);
+/// assert!(cache.contains(360) && cache.contains(97));
+/// assert!(!cache.contains(1_000_003 * 1_000_033));
+/// assert_eq!(cache.len(), 2);
+/// ```
+#[derive(Debug)]
+pub struct FactorCache {
+    capacity: usize,
+    inner: Mutex<Lru>,
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    /// x -> (its factorization, φ(x), when it was last used).
+    entries: HashMap<u64, (Factorization, u64, u64)>,
+    /// When each entry was last used -> x, oldest first.
+    order: BTreeMap<u64, u64>,
+    clock: u64,
+}
+
+impl FactorCache {
+    /// An empty cache that holds at most `capacity` numbers. A capacity of
+    /// 0 caches nothing.
+    pub fn with_capacity(capacity: usize) -> FactorCache {
+        FactorCache { capacity, inner: Mutex::new(Lru::default()) }
+    }
+
+    /// The most numbers the cache holds.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// How many numbers the cache holds now.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// True if the cache holds nothing.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether x is cached. Doesn't count as a use of it.
+    pub fn contains(&self, x: u64) -> bool {
+        self.lock().entries.contains_key(&x)
+    }
+
+    /// Empties the cache.
+    pub fn clear(&self) {
+        *self.lock() = Lru::default();
+    }
+
+    /// The factorization of x, from the cache if it's there. Panics for
+    /// x = 0, which has no factorization.
+    pub fn factorize(&self, x: u64) -> Factorization {
+        self.entry(x).0
+    }
+
+    /// Euler's φ(x), from the cache if x is there. φ(0) is 0, and isn't
+    /// cached.
+    pub fn totient(&self, x: u64) -> u64 {
+        if x == 0 {
+            return 0;
+        }
+        self.entry(x).1
+    }
+
+    fn entry(&self, x: u64) -> (Factorization, u64) {
+        assert!(x > 0, "0 has no factorization");
+        if let Some(found) = self.lock().get(x) {
+            return found;
+        }
+        let factorization = factorize_quickly(x);
+        let totient = factorization.factors().iter().fold(x, |phi, &(p, _)| phi / p * (p - 1));
+        if self.capacity > 0 {
+            self.lock().insert(x, factorization.clone(), totient, self.capacity);
+        }
+        (factorization, totient)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Lru> {
+        self.inner.lock().expect("a thread panicked while using the factor cache")
+    }
+}
+
+impl Lru {
+    /// The entry for x, marked as just used.
+    fn get(&mut self, x: u64) -> Option<(Factorization, u64)> {
+        let clock = self.clock + 1;
+        let (factorization, totient, used) = self.entries.get_mut(&x)?;
+        self.order.remove(used);
+        *used = clock;
+        self.order.insert(clock, x);
+        self.clock = clock;
+        Some((factorization.clone(), *totient))
+    }
+
+    /// Adds x, dropping the least recently used entry if that makes more
+    /// than capacity. Another thread may have added x meanwhile.
+    fn insert(&mut self, x: u64, factorization: Factorization, totient: u64, capacity: usize) {
+        self.clock += 1;
+        if let Some((_, _, used)) = self.entries.insert(x, (factorization, totient, self.clock)) {
+            self.order.remove(&used);
+        }
+        self.order.insert(self.clock, x);
+        if self.entries.len() > capacity {
+            let (_, oldest) = self.order.pop_first().unwrap();
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = FactorCache::with_capacity(100);
+        for x in 1..=300u64 {
+            assert_eq!(cache.factorize(x).factors(), &crate::factorize(x)[..]);
+            assert_eq!(cache.totient(x), crate::totient(x));
+            // Keep touching 1..=10 so they're never the oldest.
+            cache.factorize(x % 10 + 1);
+        }
+        assert_eq!(cache.len(), 100);
+        assert!((1..=10).all(|x| cache.contains(x)));
+        assert!((211..=300).all(|x| cache.contains(x)));
+        assert!(!cache.contains(210));
+
+        let nothing = FactorCache::with_capacity(0);
+        assert_eq!(nothing.totient(36), 12);
+        assert!(nothing.is_empty());
+    }
+}
@@ -0,0 +1,245 @@
+//! Factorization with a record of which algorithm did what, and how long it took.
+
+use std::time::{Duration, Instant};
+
+use crate::factor::brent_rho;
+use crate::miller_rabin_with_bases;
+use crate::primality::U64_BASES;
+use crate::small_primes::SMALL_PRIMES;
+use crate::Factorization;
+
+/// An algorithm in `factorize_with_report`'s chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FactorAlgorithm {
+    /// Trial division by the primes below 2^16. What it leaves is 1, a
+    /// prime, or a product of primes above 2^16.
+    TrialDivision,
+    /// Deterministic Miller–Rabin on a cofactor, which is exact for u64s.
+    MillerRabin,
+    /// Brent's variant of Pollard's rho, splitting a composite cofactor in two.
+    PollardRho,
+    /// The continued fraction method, splitting a cofactor that several rho
+    /// walks in a row failed on. Only with the `qs` feature; without it rho
+    /// keeps trying new constants.
+    Cfrac,
+}
+
+/// One run of one algorithm.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FactorStep {
+    /// Which algorithm ran.
+    pub algorithm: FactorAlgorithm,
+    /// The number it was given.
+    pub input: u64,
+    /// How long it took.
+    pub elapsed: Duration,
+    /// What it found: the primes trial division took out (and the
+    /// cofactor, when that is prime), the input if Miller–Rabin proved it
+    /// prime, or the divisor rho split off.
+    pub found: Vec<u64>,
+}
+
+/// What `factorize_with_report` did, step by step.
+///
+/// With the `serde` feature it serializes as
+/// `{"version":1,"steps":[...],"sources":[...]}`, each step with its fields
+/// by name (`elapsed` as `{"secs":..,"nanos":..}`) and each algorithm by its
+/// variant name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FactorReport {
+    /// Every step, in the order it ran.
+    pub steps: Vec<FactorStep>,
+    /// Each distinct prime factor, smallest first, with the algorithm that
+    /// isolated it: trial division for the small ones, rho (or cfrac) for
+    /// the pieces of a split, and Miller–Rabin for a large cofactor that was
+    /// prime all along.
+    pub sources: Vec<(u64, FactorAlgorithm)>,
+}
+
+impl FactorReport {
+    /// Time spent in all the steps together.
+    pub fn total_time(&self) -> Duration {
+        self.steps.iter().map(|step| step.elapsed).sum()
+    }
+
+    /// Time spent in one algorithm's steps.
+    pub fn time_in(&self, algorithm: FactorAlgorithm) -> Duration {
+        self.steps.iter().filter(|step| step.algorithm == algorithm).map(|step| step.elapsed).sum()
+    }
+
+    /// The algorithm that isolated the prime p, or None if p isn't a factor.
+    pub fn source_of(&self, p: u64) -> Option<FactorAlgorithm> {
+        self.sources.iter().find(|&&(q, _)| q == p).map(|&(_, algorithm)| algorithm)
+    }
+}
+
+/// The factorization of x, with a report of which algorithms ran, how long
+/// each took and which one found each prime factor.
+///
+/// The chain is trial division by the primes below 2^16, then
+/// Miller–Rabin on whatever is left and Pollard's rho on anything
+/// composite, repeating on the pieces. With the `qs` feature, a cofactor
+/// that several rho walks fail on goes to the continued fraction method
+/// instead. That makes it quick for any u64,
+/// unlike plain `factorize`, and the report shows where the time went.
+///
+/// Panics for x = 0, which has no factorization.
+///
+/// ```
+/// use prime_tools::{factorize_with_report, FactorAlgorithm};
+///
+/// let (factorization, report) = factorize_with_report(6 * 1_000_003 * 1_000_033);
+/// assert_eq!(factorization.factors(), &[(2, 1), (3, 1), (1_000_003, 1), (1_000_033, 1)]);
+/// assert_eq!(report.source_of(3), Some(FactorAlgorithm::TrialDivision));
+/// assert_eq!(report.source_of(1_000_033), Some(FactorAlgorithm::PollardRho));
+/// assert_eq!(report.steps[0].algorithm, FactorAlgorithm::TrialDivision);
+/// assert_eq!(report.steps[1].algorithm, FactorAlgorithm::MillerRabin);
+/// assert!(report.steps[1].found.is_empty());
+/// assert!(report.time_in(FactorAlgorithm::PollardRho) <= report.total_time());
+/// ```
+pub fn factorize_with_report(x: u64) -> (Factorization, FactorReport) {
+    assert!(x > 0, "0 has no factorization");
+    let _span = debug_span!("factorize_with_report", x);
+    let mut steps = Vec::new();
+    // Steps run back to back, so each one took the time since the last ended.
+    let mut last = Instant::now();
+    let ChainFactors { factors, mut sources } = factor_chain(x, |algorithm, input, found| {
+        let now = Instant::now();
+        steps.push(FactorStep { algorithm, input, elapsed: now - last, found: found.to_vec() });
+        last = now;
+    });
+    sources.sort_unstable_by_key(|&(p, _)| p);
+    sources.dedup_by_key(|&mut (p, _)| p);
+    (Factorization::from_factors(&factors), FactorReport { steps, sources })
+}
+
+/// The chain behind `factorize_with_report`, `factorize_many` and the other
+/// fast paths: trial division by the primes below 2^16, then Miller–Rabin
+/// on what's left and Pollard's rho on anything composite, with cfrac
+/// behind rho, repeating on the pieces.
+///
+/// `on_step(algorithm, input, found)` is called as each step finishes, with
+/// `found` as in `FactorStep`. x must be positive.
+pub(crate) fn factor_chain<F>(x: u64, mut on_step: F) -> ChainFactors
+where
+    F: FnMut(FactorAlgorithm, u64, &[u64]),
+{
+    let mut factors = Vec::new();
+    let mut rest = x;
+    let mut found = Vec::new();
+    for &p in SMALL_PRIMES.iter() {
+        let p = p as u64;
+        if p * p > rest {
+            break;
+        }
+        if rest % p == 0 {
+            let mut count = 0;
+            while rest % p == 0 {
+                rest /= p;
+                count += 1;
+            }
+            factors.push((p, count));
+            found.push(p);
+        }
+    }
+    // With no factor below 2^16 left, anything below 2^32 is prime.
+    let settled = rest > 1 && rest < 1 << 32;
+    if settled {
+        factors.push((rest, 1));
+        found.push(rest);
+    }
+    let mut sources: Vec<(u64, FactorAlgorithm)> = found.iter().map(|&p| (p, FactorAlgorithm::TrialDivision)).collect();
+    on_step(FactorAlgorithm::TrialDivision, x, &found);
+
+    let mut pending = if rest > 1 && !settled { vec![(rest, FactorAlgorithm::MillerRabin)] } else { Vec::new() };
+    while let Some((m, source)) = pending.pop() {
+        if miller_rabin_with_bases(m as u128, &U64_BASES) {
+            on_step(FactorAlgorithm::MillerRabin, m, &[m]);
+            factors.push((m, 1));
+            sources.push((m, source));
+            continue;
+        }
+        on_step(FactorAlgorithm::MillerRabin, m, &[]);
+
+        let (algorithm, d) = split(m, RHO_WALKS);
+        on_step(algorithm, m, &[d]);
+        pending.push((d, algorithm));
+        pending.push((m / d, algorithm));
+    }
+    ChainFactors { factors, sources }
+}
+
+/// Rho walks, each with its own constant, before a cofactor goes to cfrac.
+const RHO_WALKS: u64 = 16;
+
+/// A nontrivial divisor of the composite m, and what found it: rho with
+/// constants 1 to rho_walks, then cfrac if the `qs` feature is on, then rho
+/// with every constant after that.
+fn split(m: u64, rho_walks: u64) -> (FactorAlgorithm, u64) {
+    if let Some(d) = (1..=rho_walks).find_map(|c| brent_rho(m, c, 2)) {
+        return (FactorAlgorithm::PollardRho, d);
+    }
+    #[cfg(feature = "qs")]
+    {
+        if let Some(d) = crate::continued_fraction_factor(m as u128) {
+            return (FactorAlgorithm::Cfrac, d as u64);
+        }
+    }
+    (FactorAlgorithm::PollardRho, (rho_walks + 1..).find_map(|c| brent_rho(m, c, 2)).unwrap())
+}
+
+/// What `factor_chain` found.
+pub(crate) struct ChainFactors {
+    /// Primes with their exponents, in no particular order; a large prime
+    /// can show up more than once.
+    pub(crate) factors: Vec<(u64, u32)>,
+    /// The algorithm that isolated each prime, unsorted.
+    pub(crate) sources: Vec<(u64, FactorAlgorithm)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn agrees_with_factorize() {
+        for x in 1..5_000 {
+            let (factorization, report) = factorize_with_report(x);
+            assert_eq!(factorization.factors(), &crate::factorize(x)[..], "{}", x);
+            assert!(report.sources.iter().all(|&(_, algorithm)| algorithm == FactorAlgorithm::TrialDivision));
+        }
+    }
+
+    #[test]
+    fn large_cofactors_go_through_rho() {
+        let cases = [
+            4_294_967_291 * 4_294_967_279,
+            65_537 * 65_537 * 65_537,
+            1_000_003 * 1_000_003 * 999_983,
+            18_446_744_073_709_551_557,
+            u64::MAX,
+        ];
+        for &x in &cases {
+            let (factorization, report) = factorize_with_report(x);
+            assert_eq!(factorization.to_u64(), Some(x));
+            for &(p, _) in factorization.factors() {
+                assert!(miller_rabin_with_bases(p as u128, &U64_BASES), "{} in {}", p, x);
+                assert!(report.source_of(p).is_some());
+            }
+        }
+        let (_, report) = factorize_with_report(18_446_744_073_709_551_557);
+        assert_eq!(report.sources, vec![(18_446_744_073_709_551_557, FactorAlgorithm::MillerRabin)]);
+    }
+
+    #[test]
+    fn cfrac_takes_over_when_rho_gives_up() {
+        let m = 4_294_967_291 * 4_294_967_279;
+        let (algorithm, d) = split(m, 0);
+        assert!(d == 4_294_967_291 || d == 4_294_967_279, "{}", d);
+        let expected = if cfg!(feature = "qs") { FactorAlgorithm::Cfrac } else { FactorAlgorithm::PollardRho };
+        assert_eq!(algorithm, expected);
+        assert_eq!(split(m, RHO_WALKS).0, FactorAlgorithm::PollardRho);
+    }
+}
@@ -0,0 +1,388 @@
+//! Numbers and fractions kept in factored form, so they can be multiplied,
+//! divided and raised to powers far past what fits in a u128.
+
+use std::fmt;
+use std::ops::{Div, Mul};
+
+use crate::factor::merge_factorizations;
+use crate::{factorize, gcd_of_factorizations, lcm_of_factorizations};
+
+/// A positive integer as a list of (prime, exponent) pairs, smallest prime first.
+///
+/// With the `serde` feature it serializes as
+/// `{"version":1,"factors":[[2,3],[3,2]]}`, and the primes are checked when
+/// it's read back.
+///
+/// ```
+/// use prime_tools::Factorization;
+///
+/// let a = Factorization::new(360);
+/// let b = Factorization::new(84);
+/// assert_eq!((&a * &b).to_u64(), Some(30_240));
+/// assert_eq!(a.gcd(&b), Factorization::new(12));
+/// assert_eq!((&a / &Factorization::new(8)).to_u64(), Some(45));
+///
+/// // 360^20 doesn't fit in a u128, but it can still be divided back down.
+/// let big = a.pow(20);
+/// assert_eq!(big.to_u128(), None);
+/// assert_eq!((&big / &a.pow(19)).to_u64(), Some(360));
+/// assert_eq!(big.to_string(), "2^60 * 3^40 * 5^20");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Factorization {
+    factors: Vec<(u64, u32)>,
+}
+
+impl Factorization {
+    /// The factorization of x. Panics for x = 0, which doesn't have one.
+    pub fn new(x: u64) -> Factorization {
+        assert!(x > 0, "0 has no factorization");
+        Factorization { factors: factorize(x) }
+    }
+
+    /// 1, the empty product.
+    pub fn one() -> Factorization {
+        Factorization { factors: Vec::new() }
+    }
+
+    /// Builds a factorization from (prime, exponent) pairs in any order,
+    /// merging repeated primes and dropping zero exponents. The primes are
+    /// taken on trust. Panics if a merged exponent passes u32::MAX.
+    pub fn from_factors(factors: &[(u64, u32)]) -> Factorization {
+        let mut sorted = factors.to_vec();
+        sorted.sort_unstable();
+        let mut merged: Vec<(u64, u32)> = Vec::with_capacity(sorted.len());
+        for (p, count) in sorted {
+            match merged.last_mut() {
+                Some(last) if last.0 == p => last.1 = last.1.checked_add(count).expect("exponent overflowed u32"),
+                _ => merged.push((p, count)),
+            }
+        }
+        merged.retain(|&(_, count)| count > 0);
+        Factorization { factors: merged }
+    }
+
+    /// The (prime, exponent) pairs, smallest prime first.
+    pub fn factors(&self) -> &[(u64, u32)] {
+        &self.factors
+    }
+
+    /// The number itself, or None if it doesn't fit in a u64.
+    pub fn to_u64(&self) -> Option<u64> {
+        self.factors.iter().try_fold(1u64, |n, &(p, count)| n.checked_mul(p.checked_pow(count)?))
+    }
+
+    /// The number itself, or None if it doesn't fit in a u128.
+    pub fn to_u128(&self) -> Option<u128> {
+        self.factors.iter().try_fold(1u128, |n, &(p, count)| n.checked_mul((p as u128).checked_pow(count)?))
+    }
+
+    /// self^k. Panics if an exponent passes u32::MAX.
+    pub fn pow(&self, k: u32) -> Factorization {
+        if k == 0 {
+            return Factorization::one();
+        }
+        let factors = self
+            .factors
+            .iter()
+            .map(|&(p, count)| (p, count.checked_mul(k).expect("exponent overflowed u32")))
+            .collect();
+        Factorization { factors }
+    }
+
+    /// The greatest common divisor.
+    pub fn gcd(&self, other: &Factorization) -> Factorization {
+        Factorization { factors: gcd_of_factorizations(&self.factors, &other.factors) }
+    }
+
+    /// The least common multiple.
+    pub fn lcm(&self, other: &Factorization) -> Factorization {
+        Factorization { factors: lcm_of_factorizations(&self.factors, &other.factors) }
+    }
+
+    /// True if self divides other.
+    pub fn divides(&self, other: &Factorization) -> bool {
+        self.gcd(other) == *self
+    }
+
+    /// self / other, or None if other doesn't divide self.
+    pub fn checked_div(&self, other: &Factorization) -> Option<Factorization> {
+        if !other.divides(self) {
+            return None;
+        }
+        Some(Factorization { factors: merge_factorizations(&self.factors, &other.factors, |x, y| x - y) })
+    }
+}
+
+impl<'a> Mul<&'a Factorization> for &'a Factorization {
+    type Output = Factorization;
+
+    /// Panics if an exponent passes u32::MAX.
+    fn mul(self, other: &Factorization) -> Factorization {
+        let factors = merge_factorizations(&self.factors, &other.factors, |x, y| {
+            x.checked_add(y).expect("exponent overflowed u32")
+        });
+        Factorization { factors }
+    }
+}
+
+impl Mul for Factorization {
+    type Output = Factorization;
+
+    fn mul(self, other: Factorization) -> Factorization {
+        &self * &other
+    }
+}
+
+impl<'a> Div<&'a Factorization> for &'a Factorization {
+    type Output = Factorization;
+
+    /// Panics unless other divides self; see `checked_div`.
+    fn div(self, other: &Factorization) -> Factorization {
+        self.checked_div(other).expect("Factorization division isn't exact")
+    }
+}
+
+impl Div for Factorization {
+    type Output = Factorization;
+
+    fn div(self, other: Factorization) -> Factorization {
+        &self / &other
+    }
+}
+
+impl fmt::Display for Factorization {
+    /// Like `2^3 * 3 * 5`, or `1` for the empty product.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.factors.is_empty() {
+            return write!(f, "1");
+        }
+        for (i, &(p, count)) in self.factors.iter().enumerate() {
+            if i > 0 {
+                write!(f, " * ")?;
+            }
+            if count == 1 {
+                write!(f, "{}", p)?;
+            } else {
+                write!(f, "{}^{}", p, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// num / den in lowest terms. 0 / x comes out as 0 / 1. Panics if den is 0.
+///
+/// ```
+/// assert_eq!(prime_tools::reduce_fraction(84, 360), (7, 30));
+/// assert_eq!(prime_tools::reduce_fraction(0, 5), (0, 1));
+/// ```
+pub fn reduce_fraction(num: u64, den: u64) -> (u64, u64) {
+    assert!(den != 0, "reduce_fraction needs a nonzero denominator");
+    let g = crate::gcd(num, den);
+    (num / g, den / g)
+}
+
+/// A positive rational number as a numerator and denominator `Factorization`
+/// with no prime in common.
+///
+/// Products and quotients of huge rationals stay exact, and only need to fit
+/// in a u128 at the very end, if ever.
+///
+/// ```
+/// use prime_tools::{FactoredRational, Factorization};
+///
+/// // C(40, 20) = 40! / (20! 20!), worked out one factor at a time.
+/// let mut binomial = FactoredRational::one();
+/// for i in 1..=20 {
+///     binomial = &binomial * &FactoredRational::new(20 + i, i);
+/// }
+/// assert!(binomial.is_integer());
+/// assert_eq!(binomial.to_u128_pair(), Some((137_846_528_820, 1)));
+///
+/// let half = FactoredRational::new(1, 2);
+/// assert_eq!((&binomial / &half).numerator(), &Factorization::new(275_693_057_640));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FactoredRational {
+    numerator: Factorization,
+    denominator: Factorization,
+}
+
+impl FactoredRational {
+    /// num / den, reduced. Panics if either is 0.
+    pub fn new(num: u64, den: u64) -> FactoredRational {
+        assert!(num != 0 && den != 0, "FactoredRational needs a positive numerator and denominator");
+        FactoredRational::from_factorizations(&Factorization::new(num), &Factorization::new(den))
+    }
+
+    /// 1.
+    pub fn one() -> FactoredRational {
+        FactoredRational { numerator: Factorization::one(), denominator: Factorization::one() }
+    }
+
+    /// num / den, reduced.
+    pub fn from_factorizations(num: &Factorization, den: &Factorization) -> FactoredRational {
+        let common = num.gcd(den);
+        FactoredRational { numerator: num / &common, denominator: den / &common }
+    }
+
+    /// The numerator, sharing no prime with the denominator.
+    pub fn numerator(&self) -> &Factorization {
+        &self.numerator
+    }
+
+    /// The denominator, sharing no prime with the numerator.
+    pub fn denominator(&self) -> &Factorization {
+        &self.denominator
+    }
+
+    /// True if the denominator is 1.
+    pub fn is_integer(&self) -> bool {
+        self.denominator.factors().is_empty()
+    }
+
+    /// den / num.
+    pub fn recip(&self) -> FactoredRational {
+        FactoredRational { numerator: self.denominator.clone(), denominator: self.numerator.clone() }
+    }
+
+    /// self^k.
+    pub fn pow(&self, k: u32) -> FactoredRational {
+        FactoredRational { numerator: self.numerator.pow(k), denominator: self.denominator.pow(k) }
+    }
+
+    /// (numerator, denominator), or None if either doesn't fit in a u128.
+    pub fn to_u128_pair(&self) -> Option<(u128, u128)> {
+        Some((self.numerator.to_u128()?, self.denominator.to_u128()?))
+    }
+}
+
+impl<'a> Mul<&'a FactoredRational> for &'a FactoredRational {
+    type Output = FactoredRational;
+
+    fn mul(self, other: &FactoredRational) -> FactoredRational {
+        // Cancel across before multiplying so the exponents stay small.
+        let left = FactoredRational::from_factorizations(&self.numerator, &other.denominator);
+        let right = FactoredRational::from_factorizations(&other.numerator, &self.denominator);
+        FactoredRational {
+            numerator: &left.numerator * &right.numerator,
+            denominator: &left.denominator * &right.denominator,
+        }
+    }
+}
+
+impl Mul for FactoredRational {
+    type Output = FactoredRational;
+
+    fn mul(self, other: FactoredRational) -> FactoredRational {
+        &self * &other
+    }
+}
+
+impl<'a> Div<&'a FactoredRational> for &'a FactoredRational {
+    type Output = FactoredRational;
+
+    fn div(self, other: &FactoredRational) -> FactoredRational {
+        Mul::mul(self, &other.recip())
+    }
+}
+
+impl Div for FactoredRational {
+    type Output = FactoredRational;
+
+    fn div(self, other: FactoredRational) -> FactoredRational {
+        &self / &other
+    }
+}
+
+impl fmt::Display for FactoredRational {
+    /// Like `2^3 * 5 / 3^2` or `5 / (2^2 * 3)`, leaving off `/ 1` for integers.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.numerator)?;
+        match self.denominator.factors().len() {
+            0 => Ok(()),
+            1 => write!(f, " / {}", self.denominator),
+            _ => write!(f, " / ({})", self.denominator),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_matches_plain_integers() {
+        for a in 1..120u64 {
+            for b in 1..120u64 {
+                let (fa, fb) = (Factorization::new(a), Factorization::new(b));
+                assert_eq!(&fa * &fb, Factorization::new(a * b));
+                assert_eq!(fa.gcd(&fb), Factorization::new(crate::gcd(a, b)));
+                assert_eq!(fa.lcm(&fb), Factorization::new(a / crate::gcd(a, b) * b));
+                assert_eq!(fb.divides(&fa), a % b == 0);
+                assert_eq!(fa.checked_div(&fb), if a % b == 0 { Some(Factorization::new(a / b)) } else { None });
+            }
+            assert_eq!(Factorization::new(a).pow(3).to_u64(), Some(a * a * a));
+        }
+    }
+
+    #[test]
+    fn edges() {
+        assert_eq!(Factorization::new(1), Factorization::one());
+        assert_eq!(Factorization::one().to_string(), "1");
+        assert_eq!(Factorization::new(7).pow(0), Factorization::one());
+        assert_eq!(Factorization::from_factors(&[(3, 1), (2, 2), (3, 2), (5, 0)]).factors(), &[(2, 2), (3, 3)]);
+        assert_eq!(Factorization::new(u64::MAX).to_u64(), Some(u64::MAX));
+        assert_eq!(Factorization::new(2).pow(64).to_u64(), None);
+        assert_eq!(Factorization::new(2).pow(127).to_u128(), Some(1 << 127));
+        assert_eq!(Factorization::new(2).pow(128).to_u128(), None);
+        assert_eq!(Factorization::new(6) * Factorization::new(10) / Factorization::new(4), Factorization::new(15));
+    }
+
+    #[test]
+    #[should_panic]
+    fn inexact_division_panics() {
+        let _ = Factorization::new(6) / Factorization::new(4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn exponent_overflow_panics() {
+        Factorization::new(2).pow(u32::MAX).pow(2);
+    }
+
+    #[test]
+    fn fractions_reduce_like_plain_integers() {
+        for num in 0..150u64 {
+            for den in 1..150u64 {
+                let (n, d) = reduce_fraction(num, den);
+                assert_eq!(crate::gcd(n, d), 1);
+                assert_eq!(n * den, d * num);
+                if num > 0 {
+                    let rational = FactoredRational::new(num, den);
+                    assert_eq!(rational.to_u128_pair(), Some((n as u128, d as u128)));
+                    assert_eq!(&rational * &rational.recip(), FactoredRational::one());
+                }
+            }
+        }
+        assert_eq!(reduce_fraction(u64::MAX, u64::MAX), (1, 1));
+    }
+
+    #[test]
+    fn huge_rationals() {
+        // (3/2)^100 * (4/9)^50 = 1, with both sides way past u128.
+        let big = FactoredRational::new(3, 2).pow(100);
+        assert_eq!(big.to_u128_pair(), None);
+        assert_eq!(&big * &FactoredRational::new(4, 9).pow(50), FactoredRational::one());
+        assert_eq!((FactoredRational::new(9, 4) / FactoredRational::new(3, 8)).to_string(), "2 * 3");
+        assert_eq!(FactoredRational::new(5, 12).to_string(), "5 / (2^2 * 3)");
+        assert_eq!(FactoredRational::new(40, 9).to_string(), "2^3 * 5 / 3^2");
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_denominator_panics() {
+        reduce_fraction(1, 0);
+    }
+}
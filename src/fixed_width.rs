@@ -0,0 +1,293 @@
+//! Primality tests on crypto-bigint's fixed-width `Uint`s.
+//!
+//! Only built with the `crypto-bigint` feature. Everything stays on the
+//! stack, and the modular exponentiations and the Lucas ladder run in time
+//! that depends on the width of the type rather than on the candidate, which
+//! is what key generation wants. The early exits once a witness is found are
+//! not hidden; composites are thrown away anyway.
+
+use crypto_bigint::modular::runtime_mod::{DynResidue, DynResidueParams};
+use crypto_bigint::subtle::{Choice, ConditionallySelectable, ConstantTimeEq, ConstantTimeGreater, ConstantTimeLess};
+use crypto_bigint::{Limb, Reciprocal, Uint};
+
+use crate::miller_rabin_with_bases;
+use crate::modular::{jacobi, signed_mod};
+use crate::primality::U64_BASES;
+use crate::small_primes::SMALL_PRIMES;
+
+/// True if x is prime, by the Baillie–PSW test.
+///
+/// Candidates are trial divided by the primes below 2^16, then need to pass
+/// a base-2 Miller–Rabin test and a strong Lucas test with Selfridge's
+/// parameters. No composite is known to pass both. Values of 64 bits or
+/// fewer get deterministic Miller–Rabin instead, which is exact. Meant for
+/// 256- to 2048-bit candidates such as `U256` or `U2048`.
+///
+/// ```
+/// use crypto_bigint::{U256, U1024};
+/// use prime_tools::is_prime_uint;
+///
+/// // 2^255 - 19, the Curve25519 field prime.
+/// let p = U256::from_be_hex("7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffed");
+/// assert!(is_prime_uint(&p));
+/// assert!(!is_prime_uint(&p.wrapping_add(&U256::from_u8(2))));
+/// assert!(is_prime_uint(&U1024::from_u64(1_000_000_007)));
+/// ```
+pub fn is_prime_uint<const LIMBS: usize>(x: &Uint<LIMBS>) -> bool {
+    if x.bits_vartime() <= 64 {
+        let low = x.as_words().iter().rev().fold(0u128, |low, &word| low << Limb::BITS | word as u128);
+        return miller_rabin_with_bases(low, &U64_BASES);
+    }
+    if SMALL_PRIMES.iter().any(|&p| rem_small(x, p as u64) == 0) {
+        return false;
+    }
+    miller_rabin_uint(x, &[2]) && is_strong_lucas_uint(x)
+}
+
+/// True if x is a strong probable prime to every one of `bases`, like
+/// `miller_rabin_with_bases` but for any `Uint` width.
+///
+/// ```
+/// use crypto_bigint::U256;
+/// use prime_tools::miller_rabin_uint;
+///
+/// // 3215031751 = 151 * 751 * 28351 fools the first four prime bases.
+/// let x = U256::from_u64(3_215_031_751);
+/// assert!(miller_rabin_uint(&x, &[2, 3, 5, 7]));
+/// assert!(!miller_rabin_uint(&x, &[2, 3, 5, 7, 11]));
+/// ```
+pub fn miller_rabin_uint<const LIMBS: usize>(x: &Uint<LIMBS>, bases: &[u64]) -> bool {
+    if x.bits_vartime() <= 1 {
+        return false;
+    }
+    if !x.bit_vartime(0) {
+        return *x == Uint::from_u8(2);
+    }
+
+    let params = DynResidueParams::new(x);
+    let one = DynResidue::one(params);
+    let minus_one = -one;
+    // x - 1 = d * 2^s with d odd.
+    let x_minus_one = x.wrapping_sub(&Uint::ONE);
+    let s = x_minus_one.trailing_zeros();
+    let d = x_minus_one.shr_vartime(s);
+    bases.iter().all(|&a| {
+        let a = DynResidue::new(&Uint::from_u64(a), params);
+        if a == DynResidue::zero(params) {
+            return true;
+        }
+        let mut y = a.pow(&d);
+        if y == one || y == minus_one {
+            return true;
+        }
+        for _ in 1..s {
+            y = y.square();
+            if y == minus_one {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// Like `is_prime_uint`, but taking the same steps whatever x is, for
+/// candidates that have to stay secret.
+///
+/// x is trial divided by every prime below 2^16 with no early exit, then
+/// faces Miller–Rabin to the first `rounds` prime bases, each running every
+/// squaring the width allows. A composite passes a round with probability at
+/// most 1/4, and for random candidates of hundreds of bits far less, but the
+/// bases are fixed, so this isn't for inputs an adversary picks. It costs
+/// about twice as much per round as an ordinary Miller–Rabin round. Panics
+/// if `rounds` is more than 6542, the number of primes below 2^16.
+///
+/// ```
+/// use crypto_bigint::U256;
+/// use prime_tools::is_prime_uint_ct;
+///
+/// let p = U256::from_be_hex("7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffed");
+/// assert!(is_prime_uint_ct(&p, 20));
+/// assert!(!is_prime_uint_ct(&p.wrapping_add(&U256::from_u8(2)), 20));
+/// ```
+pub fn is_prime_uint_ct<const LIMBS: usize>(x: &Uint<LIMBS>, rounds: usize) -> bool {
+    let bases = &SMALL_PRIMES[..rounds];
+
+    let mut has_small_factor = Choice::from(0);
+    for &p in SMALL_PRIMES.iter() {
+        let (reciprocal, _) = Reciprocal::ct_new(Limb::from(p));
+        let (_, remainder) = x.ct_div_rem_limb_with_reciprocal(&reciprocal);
+        has_small_factor |= remainder.ct_eq(&Limb::ZERO) & !x.ct_eq(&Uint::from_u16(p));
+    }
+
+    // Even x get an odd stand-in; the choices at the end throw its answer away.
+    let n = x.bitor(&Uint::ONE);
+    let params = DynResidueParams::new(&n);
+    let (zero, one) = (DynResidue::zero(params), DynResidue::one(params));
+    let minus_one = -one;
+    let n_minus_one = n.wrapping_sub(&Uint::ONE);
+    let s = n_minus_one.trailing_zeros();
+    let d = n_minus_one.shr(s);
+
+    let mut all_pass = Choice::from(1);
+    for &base in bases {
+        let a = DynResidue::new(&Uint::from_u16(base), params);
+        let mut y = a.pow(&d);
+        // A base that's a multiple of x says nothing, so it passes.
+        let mut pass = a.ct_eq(&zero) | y.ct_eq(&one) | y.ct_eq(&minus_one);
+        for i in 1..Uint::<LIMBS>::BITS {
+            y = y.square();
+            pass |= y.ct_eq(&minus_one) & (i as u64).ct_lt(&(s as u64));
+        }
+        all_pass &= pass;
+    }
+
+    let odd: Choice = x.bit(0).into();
+    let prime = odd & x.ct_gt(&Uint::ONE) & !has_small_factor & all_pass | x.ct_eq(&Uint::from_u8(2));
+    prime.into()
+}
+
+/// The strong Lucas test of `is_strong_lucas_probable_prime` for an odd x
+/// with no prime factor below 2^16.
+fn is_strong_lucas_uint<const LIMBS: usize>(x: &Uint<LIMBS>) -> bool {
+    let root = x.sqrt_vartime();
+    if root.wrapping_mul(&root) == *x {
+        return false;
+    }
+
+    // For odd x the Jacobi symbol (D / x) only depends on x mod 4|D|.
+    let mut d: i64 = 5;
+    loop {
+        let period = 4 * d.unsigned_abs();
+        let r = rem_small(x, period) as u128;
+        match jacobi(signed_mod(d as i128, r), r) {
+            -1 => break,
+            0 => return false,
+            _ => d = if d > 0 { -d - 2 } else { -d + 2 },
+        }
+    }
+
+    let params = DynResidueParams::new(x);
+    let small = |n: i64| {
+        let residue = DynResidue::new(&Uint::from_u64(n.unsigned_abs()), params);
+        if n < 0 {
+            -residue
+        } else {
+            residue
+        }
+    };
+    let (q, d) = (small((1 - d) / 4), small(d));
+
+    // x + 1 = k * 2^s with k odd; x is odd and below Uint::MAX, so x + 1 fits.
+    let x_plus_one = x.wrapping_add(&Uint::ONE);
+    let s = x_plus_one.trailing_zeros();
+    let k = x_plus_one.shr_vartime(s);
+
+    // (U_k, V_k, Q^k) with P = 1, over every bit of the type so the number of
+    // steps doesn't depend on k.
+    let (mut u, mut v, mut q_power) = (DynResidue::zero(params), small(2), DynResidue::one(params));
+    for i in (0..Uint::<LIMBS>::BITS).rev() {
+        u *= v;
+        v = v.square() - q_power - q_power;
+        q_power = q_power.square();
+        let next_u = (u + v).div_by_2();
+        let next_v = (d * u + v).div_by_2();
+        let next_q_power = q_power * q;
+        let step = k.bit(i).into();
+        u.conditional_assign(&next_u, step);
+        v.conditional_assign(&next_v, step);
+        q_power.conditional_assign(&next_q_power, step);
+    }
+
+    let zero = DynResidue::zero(params);
+    if u == zero || v == zero {
+        return true;
+    }
+    for _ in 1..s {
+        v = v.square() - q_power - q_power;
+        q_power = q_power.square();
+        if v == zero {
+            return true;
+        }
+    }
+    false
+}
+
+/// x mod m, for m below 2^32.
+fn rem_small<const LIMBS: usize>(x: &Uint<LIMBS>, m: u64) -> u64 {
+    x.as_words().iter().rev().fold(0u64, |r, &word| ((((r as u128) << Limb::BITS) | word as u128) % m as u128) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::is_strong_lucas_probable_prime;
+    use crypto_bigint::{U1024, U128, U256, U64};
+    use rand_core::{RngCore, SeedableRng};
+
+    #[test]
+    fn agrees_with_the_u128_tests() {
+        for x in (3..30_000u64).step_by(2) {
+            let big = U64::from_u64(x);
+            assert_eq!(miller_rabin_uint(&big, &[2, 3]), miller_rabin_with_bases(x as u128, &[2, 3]), "{}", x);
+            // Away from the factors of D and Q, which the u128 test settles separately.
+            if x > 50 && SMALL_PRIMES[..15].iter().all(|&p| x % p as u64 != 0) {
+                assert_eq!(is_strong_lucas_uint(&big), is_strong_lucas_probable_prime(x as u128), "{}", x);
+            }
+            assert_eq!(is_prime_uint(&big), crate::is_u64_prime(x), "{}", x);
+        }
+        // The strong Lucas pseudoprimes below 30000.
+        for &x in &[5_459u64, 5_777, 10_877, 16_109, 18_971, 22_499, 24_569, 25_199] {
+            assert!(is_strong_lucas_uint(&U64::from_u64(x)), "{}", x);
+        }
+
+        let mut rng = rand_pcg::Pcg64::seed_from_u64(662);
+        for _ in 0..2_000 {
+            let x = ((rng.next_u64() as u128) << 64 | rng.next_u64() as u128) | 1;
+            let expected = miller_rabin_with_bases(x, &[2]) && is_strong_lucas_probable_prime(x);
+            assert_eq!(is_prime_uint(&U128::from_u128(x)), expected, "{}", x);
+            assert_eq!(is_prime_uint(&U256::from_u128(x)), expected, "{}", x);
+        }
+    }
+
+    #[test]
+    fn big_primes_and_composites() {
+        // 2^64 - 59 is the largest prime below 2^64.
+        assert!(is_prime_uint(&U1024::from_u64(18_446_744_073_709_551_557)));
+        assert!(!is_prime_uint(&U256::from_u64(4_294_967_291 * 4_294_967_279)));
+        let one = U1024::ONE;
+        let mersenne = |e: usize| one.shl_vartime(e).wrapping_sub(&one);
+        for &e in &[127, 521, 607] {
+            assert!(is_prime_uint(&mersenne(e)), "2^{} - 1", e);
+        }
+        for &e in &[128, 523, 1021] {
+            assert!(!is_prime_uint(&mersenne(e)), "2^{} - 1", e);
+        }
+        // A product of two 128-bit primes and a square of one.
+        let p = U256::from_u128(u128::MAX - 158);
+        let q = U256::from_u128(u128::MAX - 172);
+        assert!(is_prime_uint(&p) && is_prime_uint(&q));
+        assert!(!is_prime_uint(&p.wrapping_mul(&q)));
+        assert!(!is_prime_uint(&p.wrapping_mul(&p)));
+        assert!(!is_prime_uint(&U256::ZERO));
+        assert!(!is_prime_uint(&U256::MAX));
+    }
+
+    #[test]
+    fn constant_time_agrees() {
+        for x in 0..1_000u64 {
+            assert_eq!(is_prime_uint_ct(&U64::from_u64(x), 7), crate::is_u64_prime(x), "{}", x);
+        }
+        let mut rng = rand_pcg::Pcg64::seed_from_u64(663);
+        for _ in 0..100 {
+            let x = ((rng.next_u64() as u128) << 64 | rng.next_u64() as u128) | 1;
+            let x = U256::from_u128(x);
+            assert_eq!(is_prime_uint_ct(&x, 12), is_prime_uint(&x), "{}", x);
+        }
+        let p = U256::from_u128(u128::MAX - 158);
+        assert!(is_prime_uint_ct(&p, 12));
+        assert!(!is_prime_uint_ct(&p.wrapping_mul(&p), 12));
+        assert!(!is_prime_uint_ct(&U256::MAX, 12));
+        // 3215031751 fools the first four prime bases, but not trial division.
+        assert!(!is_prime_uint_ct(&U256::from_u64(3_215_031_751), 4));
+    }
+}
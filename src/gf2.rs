@@ -0,0 +1,187 @@
+//! Dense linear algebra over GF(2): bit-packed matrices and Gaussian elimination.
+//!
+//! This is what the quadratic sieve uses to turn relations into squares,
+//! exposed for other factoring or coding-theory experiments. Rows are packed
+//! 64 columns to a word and elimination is plain Gaussian, which handles a
+//! few thousand rows in well under a second; past that, block Lanczos or
+//! Wiedemann would be the tools to reach for.
+//!
+//! ```
+//! use prime_tools::gf2::BitMatrix;
+//!
+//! // x + y, y + z and x + z: the three rows sum to zero.
+//! let mut m = BitMatrix::new(3);
+//! m.push_row([0, 1].iter().copied());
+//! m.push_row([1, 2].iter().copied());
+//! m.push_row([0, 2].iter().copied());
+//! assert_eq!(m.rank(), 2);
+//! assert_eq!(m.dependencies(), vec![vec![0, 1, 2]]);
+//! ```
+
+/// A matrix over GF(2), stored as packed bit rows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitMatrix {
+    cols: usize,
+    rows: Vec<Vec<u64>>,
+}
+
+impl BitMatrix {
+    /// A matrix with no rows yet, whose rows will have `cols` columns.
+    pub fn new(cols: usize) -> BitMatrix {
+        BitMatrix { cols, rows: Vec::new() }
+    }
+
+    /// Number of rows.
+    pub fn rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Number of columns.
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Appends a row with ones in the given columns. A column listed twice
+    /// cancels out, so a relation's prime factors can go in with repeats.
+    ///
+    /// Panics if a column is out of range.
+    pub fn push_row(&mut self, ones: impl IntoIterator<Item = usize>) {
+        let mut row = vec![0u64; words(self.cols)];
+        for c in ones {
+            assert!(c < self.cols, "column {} out of range for {} columns", c, self.cols);
+            row[c / 64] ^= 1 << (c % 64);
+        }
+        self.rows.push(row);
+    }
+
+    /// The entry at (row, col). Panics if either is out of range.
+    pub fn get(&self, row: usize, col: usize) -> bool {
+        assert!(col < self.cols, "column {} out of range for {} columns", col, self.cols);
+        self.rows[row][col / 64] >> (col % 64) & 1 == 1
+    }
+
+    /// Sets the entry at (row, col). Panics if either is out of range.
+    pub fn set(&mut self, row: usize, col: usize, value: bool) {
+        assert!(col < self.cols, "column {} out of range for {} columns", col, self.cols);
+        let bit = 1 << (col % 64);
+        if value {
+            self.rows[row][col / 64] |= bit;
+        } else {
+            self.rows[row][col / 64] &= !bit;
+        }
+    }
+
+    /// The transpose, so that `transpose().dependencies()` gives the
+    /// column combinations that vanish (the kernel, in the usual sense).
+    ///
+    /// ```
+    /// use prime_tools::gf2::BitMatrix;
+    ///
+    /// let mut m = BitMatrix::new(3);
+    /// m.push_row([0, 1, 2].iter().copied());
+    /// let t = m.transpose();
+    /// assert_eq!((t.rows(), t.cols()), (3, 1));
+    /// assert!(t.get(2, 0));
+    /// assert_eq!(t.dependencies(), vec![vec![0, 1], vec![0, 2]]);
+    /// ```
+    pub fn transpose(&self) -> BitMatrix {
+        let mut t = BitMatrix::new(self.rows.len());
+        for c in 0..self.cols {
+            t.push_row((0..self.rows.len()).filter(|&r| self.get(r, c)));
+        }
+        t
+    }
+
+    /// The rank: how many rows are linearly independent.
+    pub fn rank(&self) -> usize {
+        self.rows.len() - self.dependencies().len()
+    }
+
+    /// Sets of row indices whose rows sum to zero, sorted, and forming a
+    /// basis of all such sets: exactly `rows - rank` of them.
+    ///
+    /// Gaussian elimination with every row carrying a record of which
+    /// original rows were added into it; rows that reduce to zero give the
+    /// dependencies.
+    ///
+    /// ```
+    /// use prime_tools::gf2::BitMatrix;
+    ///
+    /// // Exponents mod 2 of 10 = 2·5, 15 = 3·5, 6 = 2·3 and 7 over the primes 2, 3, 5, 7.
+    /// let mut m = BitMatrix::new(4);
+    /// for factors in &[vec![0, 2], vec![1, 2], vec![0, 1], vec![3]] {
+    ///     m.push_row(factors.iter().copied());
+    /// }
+    /// // 10·15·6 = 900 = 30^2.
+    /// assert_eq!(m.dependencies(), vec![vec![0, 1, 2]]);
+    /// ```
+    pub fn dependencies(&self) -> Vec<Vec<usize>> {
+        let n = self.rows.len();
+        let mut rows = self.rows.clone();
+        let mut history: Vec<Vec<u64>> = (0..n)
+            .map(|i| {
+                let mut h = vec![0u64; words(n)];
+                h[i / 64] |= 1 << (i % 64);
+                h
+            })
+            .collect();
+        let mut pivoted = vec![false; n];
+
+        for c in 0..self.cols {
+            let (word, bit) = (c / 64, 1u64 << (c % 64));
+            let pivot = match (0..n).find(|&r| !pivoted[r] && rows[r][word] & bit != 0) {
+                Some(pivot) => pivot,
+                None => continue,
+            };
+            pivoted[pivot] = true;
+            let (pivot_row, pivot_history) = (rows[pivot].clone(), history[pivot].clone());
+            for r in 0..n {
+                if !pivoted[r] && rows[r][word] & bit != 0 {
+                    xor_into(&mut rows[r], &pivot_row);
+                    xor_into(&mut history[r], &pivot_history);
+                }
+            }
+        }
+
+        (0..n)
+            .filter(|&r| !pivoted[r])
+            .map(|r| (0..n).filter(|&i| history[r][i / 64] >> (i % 64) & 1 == 1).collect())
+            .collect()
+    }
+}
+
+fn words(bits: usize) -> usize {
+    bits.div_ceil(64)
+}
+
+fn xor_into(target: &mut [u64], source: &[u64]) {
+    for (t, s) in target.iter_mut().zip(source) {
+        *t ^= s;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dependencies_sum_to_zero() {
+        let mut m = BitMatrix::new(70);
+        let rows: Vec<Vec<usize>> = (0..80usize).map(|i| (0..70).filter(|c| (i * 7 + c * 13) % 5 < 2).collect()).collect();
+        for row in &rows {
+            m.push_row(row.iter().copied());
+        }
+        let deps = m.dependencies();
+        assert!(deps.len() >= 10);
+        for dep in &deps {
+            assert!(!dep.is_empty());
+            let mut sum = vec![0u32; 70];
+            for &r in dep {
+                for &c in &rows[r] {
+                    sum[c] += 1;
+                }
+            }
+            assert!(sum.iter().all(|s| s % 2 == 0));
+        }
+    }
+}
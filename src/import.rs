@@ -0,0 +1,134 @@
+//! Reading prime lists and π(x) tables written by other tools, such as
+//! primesieve and primecount.
+
+use std::io::{self, BufRead};
+
+use crate::PrimeRangeBlock;
+
+/// The primes listed in text such as `primesieve --print` writes, as a
+/// `PrimeRangeBlock` running from the first prime to one past the last.
+///
+/// Numbers may be separated by any mix of whitespace and commas, one per
+/// line or many. Blank lines and anything after a `#` are skipped. The
+/// primes must be strictly increasing; they aren't checked for primality,
+/// so comparing the result with `PrimeRangeBlock::new` over the same range
+/// is a cross-check of the other tool. Only the listed primes are kept, so
+/// a window far from 0, like `primesieve 1e12 1e12+1e6 --print`, costs no
+/// more than one near it.
+///
+/// Text that isn't such a list gives an `InvalidData` error naming the line.
+///
+/// ```
+/// use prime_tools::{import_primes_from_text, PrimeRangeBlock};
+///
+/// let text = "2\n3\n5\n7\n11\n13\n17\n19\n";
+/// let block = import_primes_from_text(text.as_bytes()).unwrap();
+/// assert_eq!(block, PrimeRangeBlock::new(2, 20));
+/// assert!(import_primes_from_text("2 3 5 4".as_bytes()).is_err());
+/// ```
+pub fn import_primes_from_text<R: BufRead>(reader: R) -> io::Result<PrimeRangeBlock> {
+    let mut primes: Vec<u64> = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        for field in fields(&line) {
+            let p = field.parse().map_err(|_| invalid(index, format!("{:?} is not a number", field)))?;
+            if p == u64::MAX {
+                // Not prime, and there'd be no room for the block's max past it.
+                return Err(invalid(index, format!("{} is not prime", p)));
+            }
+            if primes.last().is_some_and(|&last| p <= last) {
+                return Err(invalid(index, format!("{} doesn't come after {}", p, primes.last().unwrap())));
+            }
+            primes.push(p);
+        }
+    }
+    let min = primes.first().map_or(0, |&p| p);
+    let max = primes.last().map_or(0, |&p| p + 1);
+    PrimeRangeBlock::from_primes(min, max, primes).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "primes out of order"))
+}
+
+/// (x, π(x)) pairs from a table of prime counts, in the order given.
+///
+/// Each line holds x and π(x), separated by whitespace or a comma, as in a
+/// shell loop over `primecount`. x can be written the way primecount takes
+/// it, as `10000000000`, `1e10` or `10^10`. Blank lines and anything after
+/// a `#` are skipped. Checking the counts against `prime_pi`, at least for
+/// the smaller x, is a quick way to validate a table.
+///
+/// Text that isn't such a table gives an `InvalidData` error naming the
+/// line.
+///
+/// ```
+/// let text = "# x, pi(x)\n1e3, 168\n10^6, 78498\n2000000000 98222287\n";
+/// let table = prime_tools::import_pi_table(text.as_bytes()).unwrap();
+/// assert_eq!(table, vec![(1_000, 168), (1_000_000, 78_498), (2_000_000_000, 98_222_287)]);
+/// assert!(table.iter().all(|&(x, pi)| x > 1_000_000 || prime_tools::prime_pi_inclusive(x) == pi));
+/// ```
+pub fn import_pi_table<R: BufRead>(reader: R) -> io::Result<Vec<(u64, u64)>> {
+    let mut table = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let row: Vec<&str> = fields(&line).collect();
+        match row[..] {
+            [] => {}
+            [x, pi] => {
+                let x = parse_x(x).ok_or_else(|| invalid(index, format!("{:?} is not a number", x)))?;
+                let pi = pi.parse().map_err(|_| invalid(index, format!("{:?} is not a count", pi)))?;
+                table.push((x, pi));
+            }
+            _ => return Err(invalid(index, format!("expected x and pi(x), found {} fields", row.len()))),
+        }
+    }
+    Ok(table)
+}
+
+/// The fields of a line, without its comment.
+fn fields(line: &str) -> impl Iterator<Item = &str> {
+    let data = line.split('#').next().unwrap();
+    data.split(|c: char| c.is_whitespace() || c == ',').filter(|field| !field.is_empty())
+}
+
+/// An integer, or one written as `AeB` or `A^B`, if it fits in a u64.
+fn parse_x(field: &str) -> Option<u64> {
+    if let Ok(x) = field.parse() {
+        return Some(x);
+    }
+    let (base, exponent, mantissa) = match field.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (10, exponent, mantissa.parse::<u64>().ok()?),
+        None => {
+            let (base, exponent) = field.split_once('^')?;
+            (base.parse().ok()?, exponent, 1)
+        }
+    };
+    mantissa.checked_mul(u64::checked_pow(base, exponent.parse().ok()?)?)
+}
+
+fn invalid(index: usize, message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", index + 1, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_match_the_sieve() {
+        let listing: String = crate::get_primes_between(0, 100_000).iter().map(|p| format!("{}\n", p)).collect();
+        assert_eq!(import_primes_from_text(listing.as_bytes()).unwrap(), PrimeRangeBlock::new(2, 99_992));
+        assert!(import_primes_from_text("".as_bytes()).unwrap().primes().is_empty());
+        let top = import_primes_from_text("18446744073709551557\n".as_bytes()).unwrap();
+        assert_eq!((top.min(), top.max(), top.primes()), (18_446_744_073_709_551_557, 18_446_744_073_709_551_558, &[18_446_744_073_709_551_557][..]));
+        let error = import_primes_from_text("2, 3\n# comment\n5 5\n".as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "line 3: 5 doesn't come after 5");
+        let error = import_primes_from_text("18446744073709551557\n18446744073709551615\n".as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "line 2: 18446744073709551615 is not prime");
+
+        let table = import_pi_table("2^32 203280221\n1E2,25\n\n18446744073709551615 425656284035217743".as_bytes()).unwrap();
+        assert_eq!(table, vec![(1 << 32, 203_280_221), (100, 25), (u64::MAX, 425_656_284_035_217_743)]);
+        for bad in &["100", "100 25 3", "1e20 5", "x 5", "100 -1"] {
+            assert_eq!(import_pi_table(bad.as_bytes()).unwrap_err().kind(), io::ErrorKind::InvalidData, "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Long sieving runs that can be checkpointed to disk and resumed.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::{factorize_range, get_primes_between};
+
+/// What a `SieveJob` produces for each number range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobKind {
+    /// The primes in the range, as `get_primes_between` gives them.
+    Primes,
+    /// The factorization of every number in the range, as `factorize_range` gives them.
+    Factorizations,
+}
+
+/// One step's worth of output from a `SieveJob`, covering `[start, end)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JobChunk {
+    /// The first number covered.
+    pub start: u64,
+    /// One past the last number covered; equal to start for an empty chunk.
+    pub end: u64,
+    /// What the step produced for `[start, end)`.
+    pub output: JobOutput,
+}
+
+/// The payload of a `JobChunk`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JobOutput {
+    /// The primes in the range, in order.
+    Primes(Vec<u64>),
+    /// One factorization per number in the range, in order, each as
+    /// (prime, exponent) pairs like `factorize` gives.
+    Factorizations(Vec<Vec<(u64, u32)>>),
+}
+
+/// A sieve or range factorization over `[min, max)` that runs a step at a
+/// time and can be saved between steps.
+///
+/// The only state is how far the job has got, so a checkpoint is a few lines
+/// of text. Store each chunk's output before checkpointing: after `resume`
+/// the job carries on from the first number not yet handed out.
+///
+/// ```
+/// use prime_tools::{JobOutput, SieveJob};
+///
+/// let path = std::env::temp_dir().join(format!("prime_tools_doc_job_{}.txt", std::process::id()));
+/// let mut job = SieveJob::primes(0, 100);
+/// let first = job.step(50);
+/// job.checkpoint(&path).unwrap();
+///
+/// let mut job = SieveJob::resume(&path).unwrap();
+/// assert_eq!(job.position(), 50);
+/// let second = job.step(1_000);
+/// assert!(job.is_done());
+/// assert_eq!(second.output, JobOutput::Primes(vec![53, 59, 61, 67, 71, 73, 79, 83, 89, 97]));
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SieveJob {
+    kind: JobKind,
+    min: u64,
+    max: u64,
+    next: u64,
+}
+
+const HEADER: &str = "prime_tools sieve job v1";
+
+impl SieveJob {
+    /// A job listing the primes in `[min, max)`.
+    pub fn primes(min: u64, max: u64) -> SieveJob {
+        SieveJob { kind: JobKind::Primes, min, max, next: min }
+    }
+
+    /// A job factoring every number in `[min, max)`.
+    pub fn factorizations(min: u64, max: u64) -> SieveJob {
+        SieveJob { kind: JobKind::Factorizations, min, max, next: min }
+    }
+
+    /// What the job produces.
+    pub fn kind(&self) -> JobKind {
+        self.kind
+    }
+
+    /// The first number the next step will cover.
+    pub fn position(&self) -> u64 {
+        self.next
+    }
+
+    /// True once every number up to max has been handed out.
+    pub fn is_done(&self) -> bool {
+        self.next >= self.max
+    }
+
+    /// Handles the next `width` numbers (fewer at the end) and moves past them.
+    /// Once the job is done this returns empty chunks at max.
+    pub fn step(&mut self, width: u64) -> JobChunk {
+        let start = self.next.min(self.max);
+        let end = start.saturating_add(width).min(self.max);
+        let _span = debug_span!("sieve_job_step", start, end);
+        let output = match self.kind {
+            JobKind::Primes => JobOutput::Primes(get_primes_between(start, end)),
+            JobKind::Factorizations => JobOutput::Factorizations(factorize_range(start, end)),
+        };
+        self.next = end;
+        debug!(start, end, max = self.max, "sieve job step done");
+        JobChunk { start, end, output }
+    }
+
+    /// Writes the job's state to `path`, replacing the file as a whole so a
+    /// crash mid-write leaves the previous checkpoint intact.
+    ///
+    /// The new state goes to a temporary file next to `path`, which is
+    /// flushed to disk before it's renamed over the old one; otherwise a
+    /// crash just after the rename could leave an empty checkpoint.
+    pub fn checkpoint<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let mut file = File::create(&temporary)?;
+        file.write_all(self.to_string().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temporary, path)
+    }
+
+    /// Reads back a job saved with `checkpoint`.
+    ///
+    /// A file that isn't a checkpoint gives an `InvalidData` error.
+    pub fn resume<P: AsRef<Path>>(path: P) -> io::Result<SieveJob> {
+        let text = fs::read_to_string(path)?;
+        parse(&text).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a prime_tools sieve job checkpoint"))
+    }
+}
+
+impl fmt::Display for SieveJob {
+    /// The checkpoint format: a header line, then one `key value` per line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            JobKind::Primes => "primes",
+            JobKind::Factorizations => "factorizations",
+        };
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "kind {}", kind)?;
+        writeln!(f, "min {}", self.min)?;
+        writeln!(f, "max {}", self.max)?;
+        writeln!(f, "next {}", self.next)
+    }
+}
+
+fn parse(text: &str) -> Option<SieveJob> {
+    let mut lines = text.lines();
+    if lines.next()? != HEADER {
+        return None;
+    }
+    let mut field = |key: &str| {
+        let line = lines.next()?;
+        let value = line.strip_prefix(key)?.strip_prefix(' ')?;
+        Some(value.to_string())
+    };
+    let kind = match field("kind")?.as_str() {
+        "primes" => JobKind::Primes,
+        "factorizations" => JobKind::Factorizations,
+        _ => return None,
+    };
+    let min = field("min")?.parse().ok()?;
+    let max = field("max")?.parse().ok()?;
+    let next = field("next")?.parse().ok()?;
+    if next < min {
+        return None;
+    }
+    Some(SieveJob { kind, min, max, next })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("prime_tools_{}_{}.txt", name, std::process::id()))
+    }
+
+    #[test]
+    fn resumed_jobs_finish_the_same() {
+        let path = scratch_file("resume");
+        for &(min, max, width) in &[(0, 10_000, 777), (1_000_000, 1_010_000, 4_096), (5, 6, 1)] {
+            let mut primes = Vec::new();
+            let mut factors = Vec::new();
+            let mut jobs = vec![SieveJob::primes(min, max), SieveJob::factorizations(min, max)];
+            while jobs.iter().any(|job| !job.is_done()) {
+                for job in &mut jobs {
+                    match job.step(width).output {
+                        JobOutput::Primes(chunk) => primes.extend(chunk),
+                        JobOutput::Factorizations(chunk) => factors.extend(chunk),
+                    }
+                    job.checkpoint(&path).unwrap();
+                    *job = SieveJob::resume(&path).unwrap();
+                }
+            }
+            assert_eq!(primes, get_primes_between(min, max));
+            assert_eq!(factors, factorize_range(min, max));
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn finished_jobs_give_empty_chunks() {
+        let mut job = SieveJob::primes(10, 20);
+        job.step(100);
+        assert!(job.is_done());
+        assert_eq!(job.step(100), JobChunk { start: 20, end: 20, output: JobOutput::Primes(vec![]) });
+        let mut job = SieveJob::factorizations((1 << 40) - 1, 1 << 40);
+        assert_eq!(job.step(u64::MAX).end, 1 << 40);
+    }
+
+    #[test]
+    fn bad_checkpoints_are_rejected() {
+        let path = scratch_file("bad");
+        for text in &["", "hello\n", "prime_tools sieve job v1\nkind primes\nmin 5\nmax 10\n", "prime_tools sieve job v1\nkind primes\nmin 5\nmax 10\nnext 4\n"] {
+            fs::write(&path, text).unwrap();
+            assert_eq!(SieveJob::resume(&path).unwrap_err().kind(), io::ErrorKind::InvalidData, "{:?}", text);
+        }
+        fs::remove_file(&path).unwrap();
+        assert_eq!(SieveJob::resume(&path).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,231 @@
+//! Lucas sequences U_k(P, Q) and V_k(P, Q), which include the Fibonacci
+//! (P = 1, Q = -1), Pell (P = 2, Q = -1) and Mersenne (P = 3, Q = 2) numbers.
+
+use std::convert::TryFrom;
+
+use crate::modular::{gcd_u128, mul_mod, signed_mod, sub_mod};
+use crate::{factorize, is_strong_lucas_probable_prime, miller_rabin_with_bases};
+
+/// F_186 is the last Fibonacci number below 2^128.
+const LAST_U128_FIBONACCI: u64 = 186;
+
+/// (U_k, V_k) mod n for the Lucas sequences with parameters P and Q:
+/// U_0 = 0, U_1 = 1, V_0 = 2, V_1 = P and X_(k+2) = P X_(k+1) - Q X_k.
+///
+/// Walks the bits of k keeping the pair (U_k, U_(k+1)), with
+/// U_2k = U_k (2 U_(k+1) - P U_k) and U_(2k+1) = U_(k+1)^2 - Q U_k^2, so it
+/// takes O(log k) steps and never divides: n can be even. Panics for n = 0.
+///
+/// ```
+/// use prime_tools::lucas_uv;
+///
+/// // F_10 = 55 and L_10 = 123, the Fibonacci and Lucas numbers.
+/// assert_eq!(lucas_uv(1, -1, 10, 1_000), (55, 123));
+/// // 2^61 - 1 = U_61(3, 2), so it's 0 mod itself.
+/// assert_eq!(lucas_uv(3, 2, 61, (1 << 61) - 1).0, 0);
+/// ```
+pub fn lucas_uv(p: i64, q: i64, k: u64, n: u64) -> (u64, u64) {
+    assert!(n > 0, "lucas_uv needs a nonzero modulus");
+    let m = n as u128;
+    let (p, q) = (signed_mod(p as i128, m), signed_mod(q as i128, m));
+    let (u, next) = lucas_u_pair(p, q, k as u128, m);
+
+    // V_k = 2 U_(k+1) - P U_k.
+    let v = sub_mod((2 * next) % m, mul_mod(p, u, m), m);
+    (u as u64, v as u64)
+}
+
+/// (U_k, U_(k+1)) mod m, for P, Q < m and 0 < m <= 2^64.
+fn lucas_u_pair(p: u128, q: u128, k: u128, m: u128) -> (u128, u128) {
+    let (mut u, mut next) = (0, 1 % m);
+    for i in (0..128 - k.leading_zeros()).rev() {
+        let twice_next = (2 * next) % m;
+        let doubled = mul_mod(u, sub_mod(twice_next, mul_mod(p, u, m), m), m);
+        let doubled_next = sub_mod(mul_mod(next, next, m), mul_mod(q, mul_mod(u, u, m), m), m);
+        (u, next) = (doubled, doubled_next);
+        if k >> i & 1 == 1 {
+            let after = sub_mod(mul_mod(p, next, m), mul_mod(q, u, m), m);
+            (u, next) = (next, after);
+        }
+    }
+    (u, next)
+}
+
+/// The k with F_k prime, up to and including n: 3, 4, 5, 7, 11, 13, ...
+///
+/// Each F_k is checked with a base-2 Miller–Rabin test plus a strong Lucas
+/// test (BPSW), which has no known counterexample. Panics for n > 186, where
+/// F_n no longer fits in a u128.
+///
+/// ```
+/// assert_eq!(prime_tools::fibonacci_prime_indices_up_to(30), vec![3, 4, 5, 7, 11, 13, 17, 23, 29]);
+/// ```
+pub fn fibonacci_prime_indices_up_to(n: u64) -> Vec<u64> {
+    assert!(n <= LAST_U128_FIBONACCI, "F_n doesn't fit in a u128 past n = 186");
+    let (mut f, mut next) = (0u128, 1u128);
+    let mut indices = Vec::new();
+    for k in 0..=n {
+        if is_probable_prime(f) {
+            indices.push(k);
+        }
+        // F_187 would overflow, but it's never used.
+        (f, next) = (next, f.wrapping_add(next));
+    }
+    indices
+}
+
+/// Whether the Fibonacci number F_k is prime, by the same test as
+/// `fibonacci_prime_indices_up_to`. Panics for k > 186.
+///
+/// ```
+/// assert!(prime_tools::is_fibonacci_prime(83));
+/// assert!(!prime_tools::is_fibonacci_prime(84));
+/// ```
+pub fn is_fibonacci_prime(k: u64) -> bool {
+    assert!(k <= LAST_U128_FIBONACCI, "F_k doesn't fit in a u128 past k = 186");
+    let (mut f, mut next) = (0u128, 1u128);
+    for _ in 0..k {
+        (f, next) = (next, f.wrapping_add(next));
+    }
+    is_probable_prime(f)
+}
+
+fn is_probable_prime(x: u128) -> bool {
+    miller_rabin_with_bases(x, &[2]) && is_strong_lucas_probable_prime(x)
+}
+
+/// The Pisano period π(m): the period of the Fibonacci numbers mod m.
+///
+/// π(m) is the lcm of π(p^k) over the prime powers in m. Each π(p^k) divides
+/// p^(k-1) π(p), and π(p) divides p - 1 for p ≡ ±1 (mod 10) and 2(p + 1) for
+/// p ≡ ±3 (mod 10), so it's found by stripping prime factors off that bound
+/// while F_d ≡ 0 and F_(d+1) ≡ 1 (mod p^k) still hold, with the same
+/// chain as `lucas_uv`.
+///
+/// Panics for m = 0 or if the period doesn't fit in a u64 (it's at most 6m,
+/// so any m below 2^64 / 6 is fine); see `checked_pisano_pi`.
+///
+/// ```
+/// assert_eq!(prime_tools::pisano_pi(10), 60);
+/// assert_eq!(prime_tools::pisano_pi(1_000), 1_500);
+/// assert_eq!(prime_tools::pisano_pi(1), 1);
+/// ```
+pub fn pisano_pi(m: u64) -> u64 {
+    checked_pisano_pi(m).expect("pisano_pi needs m > 0 and a period that fits in a u64")
+}
+
+/// π(m), or None for m = 0 or if the period doesn't fit in a u64.
+///
+/// ```
+/// assert_eq!(prime_tools::checked_pisano_pi(0), None);
+/// assert_eq!(prime_tools::checked_pisano_pi(5), Some(20));
+/// ```
+pub fn checked_pisano_pi(m: u64) -> Option<u64> {
+    if m == 0 {
+        return None;
+    }
+    let period = factorize(m).into_iter().fold(1u128, |period, (p, count)| {
+        // A multiple of π(p^k), p^(k-1) π(p) at most, and the primes dividing it.
+        let (bound, mut primes): (u128, Vec<u64>) = match p {
+            2 => (3, vec![3]),
+            5 => (20, vec![2, 5]),
+            _ if p % 10 == 1 || p % 10 == 9 => (p as u128 - 1, factorize(p - 1).into_iter().map(|(q, _)| q).collect()),
+            _ => (2 * (p as u128 + 1), factorize(p + 1).into_iter().map(|(q, _)| q).collect()),
+        };
+        if count > 1 {
+            primes.push(p);
+        }
+        let bound = bound * (p as u128).pow(count - 1);
+        let own = fibonacci_order(p.pow(count) as u128, bound, &primes);
+        period / gcd_u128(period, own) * own
+    });
+    u64::try_from(period).ok()
+}
+
+/// The smallest period of the Fibonacci numbers mod m, given a multiple of it
+/// and the primes that divide that multiple.
+fn fibonacci_order(m: u128, multiple: u128, primes: &[u64]) -> u128 {
+    let is_period = |d: u128| lucas_u_pair(1, m - 1, d, m) == (0, 1 % m);
+    let mut order = multiple;
+    for &q in primes {
+        let q = q as u128;
+        while order % q == 0 && is_period(order / q) {
+            order /= q;
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_recurrence() {
+        for &(p, q) in &[(1, -1), (2, -1), (3, 2), (-4, 7), (0, 1), (5, 0), (i64::MAX, i64::MIN)] {
+            for &n in &[1, 2, 6, 7, 64, 1_000_003, u64::MAX] {
+                let m = n as i128;
+                let (p_mod, q_mod) = ((p as i128).rem_euclid(m), (q as i128).rem_euclid(m));
+                let (mut u, mut u_next) = (0i128, 1 % m);
+                let (mut v, mut v_next) = (2 % m, p_mod);
+                for k in 0..200 {
+                    assert_eq!(lucas_uv(p, q, k, n), (u as u64, v as u64), "P = {}, Q = {}, k = {}, n = {}", p, q, k, n);
+                    let step = |a: i128, b: i128| {
+                        let product = |x: i128, y: i128| (x as u128 * y as u128 % m as u128) as i128;
+                        (product(p_mod, b) - product(q_mod, a)).rem_euclid(m)
+                    };
+                    (u, u_next) = (u_next, step(u, u_next));
+                    (v, v_next) = (v_next, step(v, v_next));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fibonacci_primes() {
+        let expected = [3, 4, 5, 7, 11, 13, 17, 23, 29, 43, 47, 83, 131, 137];
+        assert_eq!(fibonacci_prime_indices_up_to(186), expected);
+        for k in 0..=186 {
+            assert_eq!(is_fibonacci_prime(k), expected.contains(&k), "{}", k);
+        }
+    }
+
+    #[test]
+    fn pisano_periods_match_iteration() {
+        for m in 1..2_000u64 {
+            let (mut f, mut next, mut period) = (0, 1 % m, 0);
+            loop {
+                (f, next) = (next, (f + next) % m);
+                period += 1;
+                if f == 0 && next == 1 % m {
+                    break;
+                }
+            }
+            assert_eq!(pisano_pi(m), period, "{}", m);
+        }
+        assert_eq!(pisano_pi(1_000_000), 1_500_000);
+        let p = 1_000_000_007;
+        let period = pisano_pi(p);
+        assert_eq!(2 * (p + 1) % period, 0);
+        assert_eq!(lucas_uv(1, -1, period, p).0, 0);
+        assert_eq!(checked_pisano_pi(u64::MAX), Some(3_021_228_124_801_920));
+    }
+
+    #[test]
+    #[ignore] // Trial divides primes near 2^64: run with `cargo test --release -- --ignored`
+    fn pisano_periods_past_the_u64_bound() {
+        // Primes ≡ ±3 (mod 10) above 2^63, so the bound 2(p + 1) is past 2^64
+        // but the period might not be.
+        assert_eq!(checked_pisano_pi(18_446_744_073_709_551_263), Some(12_297_829_382_473_034_176));
+        assert_eq!(checked_pisano_pi(18_446_744_073_709_551_337), None);
+    }
+
+    #[test]
+    fn large_indices() {
+        // F_(p - (5 / p)) ≡ 0 (mod p) for primes p ≠ 5; 1_000_000_007 ≡ 2 (mod 5), so (5 / p) = -1.
+        assert_eq!(lucas_uv(1, -1, 1_000_000_008, 1_000_000_007).0, 0);
+        // V_p ≡ P (mod p).
+        let p = 18_446_744_073_709_551_557;
+        assert_eq!(lucas_uv(12_345, -678, p, p).1, 12_345);
+    }
+}
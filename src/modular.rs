@@ -0,0 +1,303 @@
+//! Modular arithmetic on u128s that can't overflow.
+
+/// (a + b) mod m, for a, b < m.
+pub(crate) fn add_mod(a: u128, b: u128, m: u128) -> u128 {
+    if a >= m - b {
+        a - (m - b)
+    } else {
+        a + b
+    }
+}
+
+/// (a - b) mod m, for a, b < m.
+pub(crate) fn sub_mod(a: u128, b: u128, m: u128) -> u128 {
+    if a >= b {
+        a - b
+    } else {
+        m - (b - a)
+    }
+}
+
+/// x / 2 mod m, for x < m and odd m.
+pub(crate) fn half_mod(x: u128, m: u128) -> u128 {
+    if x % 2 == 0 {
+        x / 2
+    } else {
+        // (x + m) / 2 without the overflow.
+        (x >> 1) + (m >> 1) + 1
+    }
+}
+
+/// The signed value a taken mod m, in 0..m.
+pub(crate) fn signed_mod(a: i128, m: u128) -> u128 {
+    let r = a.unsigned_abs() % m;
+    if a < 0 && r != 0 {
+        m - r
+    } else {
+        r
+    }
+}
+
+/// (a * b) mod m, for m > 0.
+///
+/// Below 2^64 the product fits in a u128 outright; past that it's built up
+/// by doubling and adding, one bit of b at a time.
+pub(crate) fn mul_mod(a: u128, b: u128, m: u128) -> u128 {
+    let (a, b) = (a % m, b % m);
+    if m <= 1 << 64 {
+        return a * b % m;
+    }
+    let mut result = 0;
+    for i in (0..128 - b.leading_zeros()).rev() {
+        result = add_mod(result, result, m);
+        if b >> i & 1 == 1 {
+            result = add_mod(result, a, m);
+        }
+    }
+    result
+}
+
+/// (a * b) mod m without overflow, for any u64s. Panics if m is 0.
+///
+/// The product is taken in a u128, which holds any two u64s multiplied, and
+/// reduced from there; a and b needn't be below m.
+///
+/// ```
+/// use prime_tools::mul_mod_u64;
+///
+/// assert_eq!(mul_mod_u64(u64::MAX, u64::MAX, u64::MAX - 1), 1);
+/// assert_eq!(mul_mod_u64(1 << 63, 4, 1_000_000_007), 164_688_009);
+/// ```
+pub fn mul_mod_u64(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+/// (a * b) mod m without overflow, for any u128s. Panics if m is 0.
+///
+/// Moduli up to 2^64 go through one wide multiplication like `mul_mod_u64`;
+/// bigger ones build the product by doubling and adding mod m, one bit of b
+/// at a time, so nothing ever passes m.
+///
+/// ```
+/// use prime_tools::mul_mod_u128;
+///
+/// let m = (1u128 << 127) - 1;
+/// assert_eq!(mul_mod_u128(1 << 126, 4, m), 2);
+/// assert_eq!(mul_mod_u128(u128::MAX, u128::MAX, u128::MAX - 1), 1);
+/// ```
+pub fn mul_mod_u128(a: u128, b: u128, m: u128) -> u128 {
+    assert!(m > 0, "modulus must be positive");
+    mul_mod(a, b, m)
+}
+
+/// base^exponent mod m, for m > 0.
+pub(crate) fn pow_mod(base: u128, mut exponent: u128, m: u128) -> u128 {
+    let mut base = base % m;
+    let mut result = 1 % m;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// The inverse of a mod m, or None if gcd(a, m) != 1. For m > 0.
+#[cfg_attr(not(feature = "rand_core"), allow(dead_code))]
+pub(crate) fn inverse_mod(a: u128, m: u128) -> Option<u128> {
+    // Extended Euclid, keeping only the coefficient of a, mod m.
+    let (mut old_r, mut r) = (a % m, m);
+    let (mut old_s, mut s) = (1 % m, 0);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_s, s) = (s, sub_mod(old_s, mul_mod(q, s, m), m));
+    }
+    if old_r == 1 {
+        Some(old_s)
+    } else {
+        None
+    }
+}
+
+/// The Jacobi symbol (a / n) for odd n: 0, 1 or -1.
+pub(crate) fn jacobi(a: u128, n: u128) -> i8 {
+    let (mut a, mut n) = (a % n, n);
+    let mut result = 1;
+    while a != 0 {
+        while a % 2 == 0 {
+            a /= 2;
+            if n % 8 == 3 || n % 8 == 5 {
+                result = -result;
+            }
+        }
+        std::mem::swap(&mut a, &mut n);
+        if a % 4 == 3 && n % 4 == 3 {
+            result = -result;
+        }
+        a %= n;
+    }
+    if n == 1 {
+        result
+    } else {
+        0
+    }
+}
+
+/// The smaller square root of a mod an odd prime p, or None if a isn't a
+/// square mod p. Tonelli–Shanks, which needs p prime to terminate.
+pub(crate) fn sqrt_mod_prime(a: u128, p: u128) -> Option<u128> {
+    let a = a % p;
+    if a == 0 {
+        return Some(0);
+    }
+    if jacobi(a, p) != 1 {
+        return None;
+    }
+    // p - 1 = q * 2^s with q odd, and z any non-residue.
+    let s = (p - 1).trailing_zeros();
+    let q = (p - 1) >> s;
+    let z = (2..).find(|&z| jacobi(z, p) == -1).unwrap();
+    let (mut m, mut c) = (s, pow_mod(z, q, p));
+    let (mut t, mut root) = (pow_mod(a, q, p), pow_mod(a, q.div_ceil(2), p));
+    while t != 1 {
+        // The least i with t^(2^i) = 1, which is below m.
+        let mut i = 0;
+        let mut power = t;
+        while power != 1 {
+            power = mul_mod(power, power, p);
+            i += 1;
+        }
+        let b = pow_mod(c, 1 << (m - i - 1), p);
+        m = i;
+        c = mul_mod(b, b, p);
+        t = mul_mod(t, c, p);
+        root = mul_mod(root, b, p);
+    }
+    Some(root.min(p - root))
+}
+
+/// The greatest common divisor of a and b.
+pub(crate) fn gcd_u128(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+/// The largest r with r * r <= x.
+pub(crate) fn isqrt_u128(x: u128) -> u128 {
+    let mut r = (x as f64).sqrt() as u128;
+    while r.checked_mul(r).is_none_or(|square| square > x) {
+        r -= 1;
+    }
+    while (r + 1).checked_mul(r + 1).is_some_and(|square| square <= x) {
+        r += 1;
+    }
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wide_arithmetic_for_small_moduli() {
+        for m in 1..60u128 {
+            for a in 0..60u128 {
+                for b in 0..60u128 {
+                    assert_eq!(mul_mod(a, b, m), a * b % m);
+                }
+                let expected = (0..7).fold(1 % m, |acc, _| acc * a % m);
+                assert_eq!(pow_mod(a, 7, m), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn huge_moduli() {
+        let m = u128::MAX;
+        assert_eq!(mul_mod(m - 1, m - 1, m), 1);
+        assert_eq!(mul_mod(1 << 127, 2, m), 1);
+        // 2^127 - 1 is prime, so Fermat holds.
+        let p = (1u128 << 127) - 1;
+        assert_eq!(pow_mod(3, p - 1, p), 1);
+        assert_eq!(mul_mod((1 << 64) + 5, (1 << 64) + 7, 1 << 64), 35);
+    }
+
+    #[test]
+    fn public_mul_mod_agrees_across_widths() {
+        let mut x = 0x9e37_79b9_7f4a_7c15u64;
+        for _ in 0..10_000 {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            let (a, b, m) = (x, x.rotate_left(21), (x >> (x % 64)).max(1));
+            let expected = mul_mod_u64(a, b, m);
+            assert!(expected < m);
+            assert_eq!(mul_mod_u128(a as u128, b as u128, m as u128), expected as u128, "{} {} {}", a, b, m);
+            // The same product, reduced mod m·2^64 and then mod m.
+            let wide = (m as u128) << 64;
+            assert_eq!(mul_mod_u128(a as u128, b as u128, wide) % m as u128, expected as u128, "{} {} {}", a, b, m);
+        }
+    }
+
+    #[test]
+    fn inverses() {
+        for m in 1..200u128 {
+            for a in 0..2 * m {
+                let expected = (0..m).find(|&x| a * x % m == 1 % m).filter(|_| gcd_u128(a, m) == 1);
+                assert_eq!(inverse_mod(a, m), expected, "{}⁻¹ mod {}", a, m);
+            }
+        }
+        let m = u128::MAX;
+        let inverse = inverse_mod(2, m).unwrap();
+        assert_eq!(mul_mod(inverse, 2, m), 1);
+        assert_eq!(inverse_mod(3, m), None);
+    }
+
+    #[test]
+    fn jacobi_matches_euler_for_primes() {
+        for &p in &[3u128, 5, 7, 11, 13, 101, 65_537] {
+            for a in 0..200u128 {
+                let euler = pow_mod(a, (p - 1) / 2, p);
+                let expected = if euler == 0 { 0 } else if euler == 1 { 1 } else { -1 };
+                assert_eq!(jacobi(a, p), expected, "({} / {})", a, p);
+            }
+        }
+        // (2 / 15) = (2 / 3)(2 / 5) = 1 though 2 isn't a square mod 15.
+        assert_eq!(jacobi(2, 15), 1);
+        assert_eq!(jacobi(5, 15), 0);
+    }
+
+    #[test]
+    fn square_roots_mod_primes() {
+        for &p in &[3u128, 5, 7, 13, 17, 97, 257, 65_537, 1_000_000_007, 18_446_744_073_709_551_557] {
+            for a in (0..300).chain(vec![p - 1, p / 3]) {
+                match sqrt_mod_prime(a, p) {
+                    Some(root) => {
+                        assert_eq!(mul_mod(root, root, p), a % p, "{} mod {}", a, p);
+                        assert!(root <= p - root);
+                    }
+                    None => assert_eq!(jacobi(a, p), -1, "{} mod {}", a, p),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn small_helpers() {
+        assert_eq!(sub_mod(2, 5, 7), 4);
+        assert_eq!(half_mod(3, 7), 5);
+        assert_eq!(half_mod(u128::MAX - 1, u128::MAX), (u128::MAX - 1) / 2);
+        assert_eq!(half_mod(u128::MAX - 2, u128::MAX), u128::MAX - 1);
+        assert_eq!(signed_mod(-7, 5), 3);
+        assert_eq!(signed_mod(-10, 5), 0);
+        assert_eq!(isqrt_u128(u128::MAX), u64::MAX as u128);
+        assert_eq!(isqrt_u128(99), 9);
+    }
+}
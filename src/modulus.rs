@@ -0,0 +1,130 @@
+//! Structural weaknesses of a modulus, from its factorization.
+
+use crate::{divisors, factorize};
+
+/// What the factorization of a modulus n says about its strength.
+///
+/// Meant for teaching and auditing small examples: every number in here
+/// comes from factoring n and p - 1 for each of its prime factors p.
+///
+/// ```
+/// let report = prime_tools::analyze_modulus(1_000_003 * 1_000_033);
+/// assert_eq!(report.factors, vec![(1_000_003, 1), (1_000_033, 1)]);
+/// // The two primes are close, so Fermat's method needs no steps at all.
+/// assert_eq!(report.fermat_steps, Some(0));
+/// // 1_000_032 = 2^5 * 3 * 11 * 947, but 1_000_002 has the factor 166_667.
+/// assert_eq!(report.smoothness(), 166_667);
+/// assert!(report.is_pohlig_hellman_weak(200_000));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModulusReport {
+    /// The modulus.
+    pub n: u64,
+    /// n's prime factorization, smallest prime first.
+    pub factors: Vec<(u64, u32)>,
+    /// The factorization of p - 1 for each prime p in `factors`, in the same order.
+    pub p_minus_one: Vec<Vec<(u64, u32)>>,
+    /// How many steps Fermat's method takes to split n: a - ⌈√n⌉ for the
+    /// nontrivial n = a² - b² with the smallest a. None if there is no such
+    /// split (n prime, n < 4, or n ≡ 2 mod 4).
+    pub fermat_steps: Option<u64>,
+}
+
+/// Factors n and p - 1 for each of its prime factors p, and works out how
+/// far Fermat's method is from splitting n.
+pub fn analyze_modulus(n: u64) -> ModulusReport {
+    let factors = factorize(n);
+    let p_minus_one = factors.iter().map(|&(p, _)| factorize(p - 1)).collect();
+    ModulusReport { n, factors, p_minus_one, fermat_steps: fermat_steps(n) }
+}
+
+impl ModulusReport {
+    /// True if n has exactly one prime factor, once.
+    pub fn is_prime(&self) -> bool {
+        self.factors == [(self.n, 1)]
+    }
+
+    /// The largest prime factor of any p - 1, i.e. the smallest B for which
+    /// every p - 1 is B-smooth (1 if there are none at all).
+    ///
+    /// Pohlig–Hellman takes discrete logarithms mod n in time governed by
+    /// √B, and Pollard's p - 1 method splits n once its bound reaches the
+    /// smallest p - 1's largest factor, so a small value here is a weakness.
+    pub fn smoothness(&self) -> u64 {
+        self.p_minus_one
+            .iter()
+            .filter_map(|factors| factors.last().map(|&(q, _)| q))
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// True if every p - 1 is bound-smooth, so discrete logarithms mod n
+    /// break down into problems no bigger than bound.
+    pub fn is_pohlig_hellman_weak(&self, bound: u64) -> bool {
+        self.smoothness() <= bound
+    }
+
+    /// True if some p - 1 is bound-smooth, so Pollard's p - 1 method with
+    /// that bound finds a factor (for composite n).
+    pub fn is_pollard_p_minus_one_weak(&self, bound: u64) -> bool {
+        !self.is_prime()
+            && self.p_minus_one.iter().any(|factors| factors.last().is_none_or(|&(q, _)| q <= bound))
+    }
+
+    /// True if Fermat's method splits n within max_steps steps.
+    pub fn is_fermat_weak(&self, max_steps: u64) -> bool {
+        self.fermat_steps.is_some_and(|steps| steps <= max_steps)
+    }
+}
+
+fn fermat_steps(n: u64) -> Option<u64> {
+    let root = crate::isqrt(n);
+    let ceil_root = if root * root == n { root } else { root + 1 };
+    // n = de with 1 < d <= e and d ≡ e (mod 2) gives a = (d + e) / 2; the
+    // biggest such d gives the smallest a.
+    divisors(n)
+        .into_iter()
+        .rfind(|&d| d > 1 && d <= n / d && (n / d - d) % 2 == 0)
+        .map(|d| (d as u128 + (n / d) as u128) as u64 / 2 - ceil_root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fermat_steps_match_the_search() {
+        for n in 0..3_000u64 {
+            let root = crate::isqrt(n);
+            let start = if root * root == n { root } else { root + 1 };
+            // Fermat's method: the first a with a² - n a square b² and a - b > 1.
+            let expected = (start..=n / 2 + 1).find(|&a| {
+                let b2 = a * a - n;
+                let b = crate::isqrt(b2);
+                b * b == b2 && a - b > 1
+            });
+            assert_eq!(fermat_steps(n), expected.map(|a| a - start), "{}", n);
+        }
+    }
+
+    #[test]
+    fn reports() {
+        let prime = analyze_modulus((1 << 31) - 1);
+        assert!(prime.is_prime());
+        assert_eq!(prime.fermat_steps, None);
+        assert_eq!(prime.smoothness(), 331);
+        assert!(!prime.is_pollard_p_minus_one_weak(1_000));
+
+        let close = analyze_modulus(65_521 * 65_537);
+        assert_eq!(close.fermat_steps, Some(0));
+        assert!(close.is_fermat_weak(0));
+
+        let far = analyze_modulus(3 * 1_000_003);
+        assert!(far.is_pollard_p_minus_one_weak(2));
+        assert!(!far.is_pohlig_hellman_weak(2));
+        assert!(!far.is_fermat_weak(1_000));
+
+        assert_eq!(analyze_modulus(1).smoothness(), 1);
+        assert_eq!(analyze_modulus(2).factors, vec![(2, 1)]);
+    }
+}
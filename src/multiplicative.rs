@@ -0,0 +1,211 @@
+//! Multiplicative functions and their Dirichlet convolutions.
+//!
+//! A function f on the positive integers is multiplicative when f(1) = 1 and
+//! f(ab) = f(a)f(b) for coprime a and b, so it's pinned down by its values on
+//! prime powers. Values are i128 so that μ and friends can go negative.
+
+use crate::factorize;
+
+/// A multiplicative function, given by its value at each prime power p^k (k >= 1).
+///
+/// Closures `Fn(u64, u32) -> i128` taking (p, k) are multiplicative functions too.
+///
+/// ```
+/// use prime_tools::{MultiplicativeFunction, SigmaK, Totient};
+///
+/// assert_eq!(Totient.evaluate(36), 12);
+/// assert_eq!(SigmaK(1).evaluate(12), 28);
+///
+/// // The number of squarefree divisors, 2^ω(n).
+/// let squarefree_divisors = |_p: u64, _k: u32| 2;
+/// assert_eq!(squarefree_divisors.evaluate(360), 8);
+/// ```
+pub trait MultiplicativeFunction {
+    /// f(p^k) for a prime p and k >= 1.
+    fn at_prime_power(&self, p: u64, k: u32) -> i128;
+
+    /// f(n) from the factorization of n. f(0) is taken to be 0.
+    ///
+    /// Panics if the value doesn't fit in an i128.
+    fn evaluate(&self, n: u64) -> i128 {
+        if n == 0 {
+            return 0;
+        }
+        factorize(n).iter().fold(1, |value, &(p, k)| multiply(value, self.at_prime_power(p, k)))
+    }
+
+    /// f(0), f(1), ..., f(n) in one go, sieving out the smallest prime factor
+    /// of everything up to n rather than factoring each one.
+    ///
+    /// ```
+    /// use prime_tools::{Mobius, MultiplicativeFunction};
+    ///
+    /// assert_eq!(Mobius.values_up_to(6), vec![0, 1, -1, -1, 0, -1, 1]);
+    /// ```
+    fn values_up_to(&self, n: usize) -> Vec<i128> {
+        let mut values = vec![0; n + 1];
+        if n == 0 {
+            return values;
+        }
+        values[1] = 1;
+        let smallest = smallest_prime_factors(n);
+        for i in 2..=n {
+            // i = p^k * rest with p the smallest prime factor of i.
+            let p = smallest[i] as usize;
+            let mut rest = i / p;
+            let mut k = 1;
+            while rest % p == 0 {
+                rest /= p;
+                k += 1;
+            }
+            values[i] = multiply(values[rest], self.at_prime_power(p as u64, k));
+        }
+        values
+    }
+}
+
+impl<F: Fn(u64, u32) -> i128> MultiplicativeFunction for F {
+    fn at_prime_power(&self, p: u64, k: u32) -> i128 {
+        self(p, k)
+    }
+}
+
+/// Euler's totient φ.
+#[derive(Clone, Copy, Debug)]
+pub struct Totient;
+
+impl MultiplicativeFunction for Totient {
+    fn at_prime_power(&self, p: u64, k: u32) -> i128 {
+        let p = p as i128;
+        multiply(p.pow(k - 1), p - 1)
+    }
+}
+
+/// The Möbius function μ.
+#[derive(Clone, Copy, Debug)]
+pub struct Mobius;
+
+impl MultiplicativeFunction for Mobius {
+    fn at_prime_power(&self, _p: u64, k: u32) -> i128 {
+        if k == 1 {
+            -1
+        } else {
+            0
+        }
+    }
+}
+
+/// σ_k, the sum of the k-th powers of the divisors. SigmaK(0) counts divisors.
+#[derive(Clone, Copy, Debug)]
+pub struct SigmaK(pub u32);
+
+impl MultiplicativeFunction for SigmaK {
+    fn at_prime_power(&self, p: u64, k: u32) -> i128 {
+        // 1 + p^s + p^2s + ... + p^ks
+        let step = (p as i128).checked_pow(self.0).expect("SigmaK overflowed an i128");
+        let mut term = 1i128;
+        let mut sum = 1i128;
+        for _ in 0..k {
+            term = multiply(term, step);
+            sum = sum.checked_add(term).expect("SigmaK overflowed an i128");
+        }
+        sum
+    }
+}
+
+/// The Dirichlet convolution (f * g)(m) = Σ f(d) g(m / d) over the divisors d
+/// of m, for every m up to n. Index 0 of the result is 0.
+///
+/// f and g are sieved up to n first, then every pair (d, m / d) is visited
+/// once, which is about n ln n steps. The convolution of two multiplicative
+/// functions is multiplicative again.
+///
+/// Panics if a value doesn't fit in an i128.
+///
+/// ```
+/// use prime_tools::{dirichlet_convolution, Mobius, SigmaK};
+///
+/// // μ * σ_1 is the identity n -> n.
+/// assert_eq!(dirichlet_convolution(&Mobius, &SigmaK(1), 6), vec![0, 1, 2, 3, 4, 5, 6]);
+/// ```
+pub fn dirichlet_convolution<F, G>(f: &F, g: &G, n: usize) -> Vec<i128>
+where
+    F: MultiplicativeFunction + ?Sized,
+    G: MultiplicativeFunction + ?Sized,
+{
+    let f_values = f.values_up_to(n);
+    let g_values = g.values_up_to(n);
+    let mut result = vec![0i128; n + 1];
+    for (d, &fd) in f_values.iter().enumerate().skip(1) {
+        if fd == 0 {
+            continue;
+        }
+        for (e, m) in (d..=n).step_by(d).enumerate() {
+            let term = multiply(fd, g_values[e + 1]);
+            result[m] = result[m].checked_add(term).expect("dirichlet_convolution overflowed an i128");
+        }
+    }
+    result
+}
+
+fn multiply(a: i128, b: i128) -> i128 {
+    a.checked_mul(b).expect("multiplicative function overflowed an i128")
+}
+
+/// The smallest prime factor of every number up to n (0 for 0 and 1).
+fn smallest_prime_factors(n: usize) -> Vec<u32> {
+    let mut smallest = vec![0u32; n + 1];
+    for i in 2..=n {
+        if smallest[i] == 0 {
+            for multiple in (i..=n).step_by(i) {
+                if smallest[multiple] == 0 {
+                    smallest[multiple] = i as u32;
+                }
+            }
+        }
+    }
+    smallest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: usize = 5_000;
+
+    #[test]
+    fn evaluate_matches_the_direct_functions() {
+        let phi = Totient.values_up_to(N);
+        let mu = Mobius.values_up_to(N);
+        let sigma = SigmaK(2).values_up_to(N);
+        for n in 1..=N {
+            let x = n as u64;
+            assert_eq!(phi[n], crate::totient(x) as i128, "{}", n);
+            assert_eq!(mu[n], crate::mobius(x) as i128, "{}", n);
+            assert_eq!(sigma[n], crate::sigma_k(x, 2) as i128, "{}", n);
+            assert_eq!(Totient.evaluate(x), phi[n]);
+            assert_eq!(SigmaK(0).evaluate(x), crate::divisor_count(x) as i128);
+        }
+        assert_eq!(Totient.evaluate(0), 0);
+        assert_eq!(Totient.values_up_to(0), vec![0]);
+    }
+
+    #[test]
+    fn classic_convolutions() {
+        let one = |_: u64, _: u32| 1;
+        let identity = |p: u64, k: u32| (p as i128).pow(k);
+        let unit: Vec<i128> = (0..=N).map(|n| (n == 1) as i128).collect();
+        let naturals: Vec<i128> = (0..=N as i128).collect();
+
+        assert_eq!(dirichlet_convolution(&Mobius, &one, N), unit);
+        assert_eq!(dirichlet_convolution(&Totient, &one, N), naturals);
+        assert_eq!(dirichlet_convolution(&one, &identity, N), SigmaK(1).values_up_to(N));
+        assert_eq!(dirichlet_convolution(&one, &one, N), SigmaK(0).values_up_to(N));
+    }
+
+    #[test]
+    #[should_panic]
+    fn overflow_panics() {
+        SigmaK(40).evaluate(1 << 40);
+    }
+}
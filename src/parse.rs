@@ -0,0 +1,319 @@
+//! Reading numbers written the way people type them.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use crate::Factorization;
+
+/// Why `parse_and_factor` couldn't produce a factorization.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseOrFactorError {
+    /// Nothing but whitespace.
+    Empty,
+    /// A character that isn't a digit of the literal's base, a leading
+    /// underscore, or a `0x` with no digits after it.
+    InvalidDigit,
+    /// More than u64::MAX.
+    Overflow,
+    /// 0, which has no factorization.
+    Zero,
+}
+
+impl fmt::Display for ParseOrFactorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            ParseOrFactorError::Empty => "no number given",
+            ParseOrFactorError::InvalidDigit => "invalid digit in number",
+            ParseOrFactorError::Overflow => "number too large for a u64",
+            ParseOrFactorError::Zero => "0 has no factorization",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl Error for ParseOrFactorError {}
+
+/// Parses s as a u64 and factors it.
+///
+/// Takes decimal (`360`), hex with a `0x` or `0X` prefix (`0x168`), and
+/// underscores between the digits of either (`1_000_000_007`, `0xffff_fffb`)
+/// the way Rust literals allow them. Surrounding whitespace is ignored, so
+/// lines read from stdin can go straight in.
+///
+/// ```
+/// use prime_tools::{parse_and_factor, ParseOrFactorError};
+///
+/// assert_eq!(parse_and_factor("0x168").unwrap().to_string(), "2^3 * 3^2 * 5");
+/// assert_eq!(parse_and_factor(" 1_000_000_007\n").unwrap().factors(), &[(1_000_000_007, 1)]);
+/// assert_eq!(parse_and_factor("12a"), Err(ParseOrFactorError::InvalidDigit));
+/// assert_eq!(parse_and_factor("0"), Err(ParseOrFactorError::Zero));
+/// ```
+pub fn parse_and_factor(s: &str) -> Result<Factorization, ParseOrFactorError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(ParseOrFactorError::Empty);
+    }
+    let x = parse_literal(s)?;
+    if x > u64::MAX as u128 {
+        return Err(ParseOrFactorError::Overflow);
+    }
+    if x == 0 {
+        return Err(ParseOrFactorError::Zero);
+    }
+    Ok(Factorization::new(x as u64))
+}
+
+/// A decimal or `0x` literal with optional underscores, up to u128::MAX.
+fn parse_literal(s: &str) -> Result<u128, ParseOrFactorError> {
+    let (digits, radix) = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => (hex, 16),
+        None if s.starts_with('_') => return Err(ParseOrFactorError::InvalidDigit),
+        None => (s, 10),
+    };
+
+    let digits: String = digits.chars().filter(|&c| c != '_').collect();
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(ParseOrFactorError::InvalidDigit);
+    }
+    // Only the digits are left, so overflow is the one way this can fail.
+    u128::from_str_radix(&digits, radix).map_err(|_| ParseOrFactorError::Overflow)
+}
+
+/// Why `evaluate_expression` couldn't produce a number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpressionError {
+    /// Nothing but whitespace.
+    Empty,
+    /// Something unexpected at this byte offset: a bad literal, an unknown
+    /// function, a missing parenthesis or operand, or trailing input.
+    Syntax(usize),
+    /// A result, intermediate or final, past u128::MAX.
+    Overflow,
+    /// A subtraction that went below 0.
+    Negative,
+    /// Division or remainder by 0.
+    DivisionByZero,
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpressionError::Empty => write!(f, "no expression given"),
+            ExpressionError::Syntax(at) => write!(f, "syntax error at position {}", at),
+            ExpressionError::Overflow => write!(f, "value too large for a u128"),
+            ExpressionError::Negative => write!(f, "value below 0"),
+            ExpressionError::DivisionByZero => write!(f, "division by 0"),
+        }
+    }
+}
+
+impl Error for ExpressionError {}
+
+/// Evaluates an integer expression such as `2^127-1`, `10^18+9` or `fact(20)+1`.
+///
+/// Numbers are literals as `parse_and_factor` reads them. The operators are
+/// `+`, `-`, `*`, `/` (rounding down), `%` and `^` with the usual precedence,
+/// `^` binding right to left, plus parentheses and `fact(n)` for n!. Every
+/// step is checked: anything past u128::MAX or below 0 is an error rather
+/// than a wrapped value.
+///
+/// ```
+/// use prime_tools::{evaluate_expression, ExpressionError};
+///
+/// assert_eq!(evaluate_expression("2^61 - 1"), Ok((1 << 61) - 1));
+/// assert_eq!(evaluate_expression("10^18+9"), Ok(1_000_000_000_000_000_009));
+/// assert_eq!(evaluate_expression("fact(20) + 1"), Ok(2_432_902_008_176_640_001));
+/// assert_eq!(evaluate_expression("2^128"), Err(ExpressionError::Overflow));
+/// assert_eq!(evaluate_expression("(1 + 2"), Err(ExpressionError::Syntax(6)));
+/// ```
+pub fn evaluate_expression(s: &str) -> Result<u128, ExpressionError> {
+    if s.trim().is_empty() {
+        return Err(ExpressionError::Empty);
+    }
+    let mut parser = Parser { s, at: 0 };
+    let value = parser.sum()?;
+    parser.skip_whitespace();
+    if parser.at < s.len() {
+        return Err(ExpressionError::Syntax(parser.at));
+    }
+    Ok(value)
+}
+
+/// Recursive descent over `s`, one method per precedence level.
+struct Parser<'a> {
+    s: &'a str,
+    /// Byte offset of the next unread character.
+    at: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn sum(&mut self) -> Result<u128, ExpressionError> {
+        let mut value = self.product()?;
+        while let Some(op) = self.eat_any(&['+', '-']) {
+            let rhs = self.product()?;
+            value = match op {
+                '+' => value.checked_add(rhs).ok_or(ExpressionError::Overflow)?,
+                _ => value.checked_sub(rhs).ok_or(ExpressionError::Negative)?,
+            };
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<u128, ExpressionError> {
+        let mut value = self.power()?;
+        while let Some(op) = self.eat_any(&['*', '/', '%']) {
+            let rhs = self.power()?;
+            value = match op {
+                '*' => value.checked_mul(rhs).ok_or(ExpressionError::Overflow)?,
+                '/' => value.checked_div(rhs).ok_or(ExpressionError::DivisionByZero)?,
+                _ => value.checked_rem(rhs).ok_or(ExpressionError::DivisionByZero)?,
+            };
+        }
+        Ok(value)
+    }
+
+    fn power(&mut self) -> Result<u128, ExpressionError> {
+        let base = self.atom()?;
+        if self.eat_any(&['^']).is_none() {
+            return Ok(base);
+        }
+        let exponent = self.power()?;
+        match base {
+            0 | 1 => Ok(if exponent == 0 { 1 } else { base }),
+            _ => u32::try_from(exponent)
+                .ok()
+                .and_then(|exponent| base.checked_pow(exponent))
+                .ok_or(ExpressionError::Overflow),
+        }
+    }
+
+    fn atom(&mut self) -> Result<u128, ExpressionError> {
+        self.skip_whitespace();
+        let start = self.at;
+        if self.eat_any(&['(']).is_some() {
+            return self.parenthesized();
+        }
+
+        let token_len = self.s[start..].find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(self.s.len() - start);
+        let token = &self.s[start..start + token_len];
+        self.at += token_len;
+        if token == "fact" && self.eat_any(&['(']).is_some() {
+            let n = self.parenthesized()?;
+            return (2..=n).try_fold(1u128, |product, k| product.checked_mul(k)).ok_or(ExpressionError::Overflow);
+        }
+        if !token.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(ExpressionError::Syntax(start));
+        }
+        parse_literal(token).map_err(|error| match error {
+            ParseOrFactorError::Overflow => ExpressionError::Overflow,
+            _ => ExpressionError::Syntax(start),
+        })
+    }
+
+    /// The rest of a `( ... )` whose opening parenthesis has been read.
+    fn parenthesized(&mut self) -> Result<u128, ExpressionError> {
+        let value = self.sum()?;
+        match self.eat_any(&[')']) {
+            Some(_) => Ok(value),
+            None => Err(ExpressionError::Syntax(self.at)),
+        }
+    }
+
+    /// Skips whitespace, then reads the next character if it's one of `chars`.
+    fn eat_any(&mut self, chars: &[char]) -> Option<char> {
+        self.skip_whitespace();
+        let c = self.s[self.at..].chars().next().filter(|c| chars.contains(c))?;
+        self.at += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.s[self.at..];
+        self.at += rest.len() - rest.trim_start().len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_and_errors() {
+        for &s in &["360", "0x168", "0X168", "3_6_0", "0x_16_8_", "  360\t", "000360"] {
+            assert_eq!(parse_and_factor(s), Ok(Factorization::new(360)), "{:?}", s);
+        }
+        assert_eq!(parse_and_factor("18446744073709551615").unwrap().to_u64(), Some(u64::MAX));
+        assert_eq!(parse_and_factor("0xffff_ffff_ffff_ffff").unwrap().to_u64(), Some(u64::MAX));
+        assert_eq!(parse_and_factor("1").unwrap(), Factorization::one());
+
+        use ParseOrFactorError::*;
+        let errors = [
+            ("", Empty),
+            ("  \n", Empty),
+            ("_360", InvalidDigit),
+            ("0x", InvalidDigit),
+            ("0x__", InvalidDigit),
+            ("+360", InvalidDigit),
+            ("-1", InvalidDigit),
+            ("3 60", InvalidDigit),
+            ("0xfg", InvalidDigit),
+            ("1e6", InvalidDigit),
+            ("١٢", InvalidDigit),
+            ("18446744073709551616", Overflow),
+            ("0x1_0000_0000_0000_0000", Overflow),
+            ("0", Zero),
+            ("0x0_0", Zero),
+        ];
+        for &(s, error) in &errors {
+            assert_eq!(parse_and_factor(s), Err(error), "{:?}", s);
+        }
+        assert_eq!(Overflow.to_string(), "number too large for a u64");
+    }
+
+    #[test]
+    fn expressions() {
+        let values = [
+            ("2^127-1", (1u128 << 127) - 1),
+            ("2 ^ 3 ^ 2", 512),
+            ("(2^3)^2", 64),
+            ("1 + 2 * 3", 7),
+            ("(1 + 2) * 3", 9),
+            ("10 - 3 - 2", 5),
+            ("100 / 7 % 5", 4),
+            ("0x10 * 1_000", 16_000),
+            ("fact(0) + fact(1) + fact(5)", 122),
+            ("fact(34)", 295_232_799_039_604_140_847_618_609_643_520_000_000),
+            ("0^0 + 1^1000000000000 + 0^5", 2),
+            (" ( ( 7 ) ) ", 7),
+            ("340282366920938463463374607431768211455", u128::MAX),
+        ];
+        for &(s, value) in &values {
+            assert_eq!(evaluate_expression(s), Ok(value), "{:?}", s);
+        }
+
+        use ExpressionError::*;
+        let errors = [
+            ("", Empty),
+            ("2^128", Overflow),
+            ("fact(35)", Overflow),
+            ("2^64 * 2^64", Overflow),
+            ("340282366920938463463374607431768211456", Overflow),
+            ("1 - 2", Negative),
+            ("5 / (3 - 3)", DivisionByZero),
+            ("5 % 0", DivisionByZero),
+            ("2 +", Syntax(3)),
+            ("2 3", Syntax(2)),
+            ("(1 + 2", Syntax(6)),
+            ("foo(3)", Syntax(0)),
+            ("fact 3", Syntax(0)),
+            ("12ab", Syntax(0)),
+            ("2 ** 3", Syntax(3)),
+            ("-1", Syntax(0)),
+            ("1 + é", Syntax(4)),
+        ];
+        for &(s, error) in &errors {
+            assert_eq!(evaluate_expression(s), Err(error), "{:?}", s);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Writing numbers as sums of primes: with repeats, fewest terms, or distinct.
+
+use std::convert::TryFrom;
+
+use crate::primality::U64_BASES;
+use crate::{get_primes_between, miller_rabin_with_bases, Primality};
+
+/// The largest n whose prime partition count fits in a u128.
+const LAST_U128_PARTITION: u64 = 5_442;
+
+/// The number of ways to write n as a sum of primes, ignoring order and
+/// allowing repeats: 10 = 7 + 3 = 5 + 5 = 5 + 3 + 2 = 3 + 3 + 2 + 2 =
+/// 2 + 2 + 2 + 2 + 2 gives 5. 0 has one way, the empty sum.
+///
+/// The coin-change count over the primes up to n, in O(n π(n)) steps.
+/// Panics if the count doesn't fit in a u128 (n > 5442); see
+/// `checked_prime_partition_count`.
+///
+/// ```
+/// assert_eq!(prime_tools::prime_partition_count(10), 5);
+/// assert_eq!(prime_tools::prime_partition_count(100), 40_899);
+/// assert_eq!(prime_tools::prime_partition_count(1), 0);
+/// ```
+pub fn prime_partition_count(n: u64) -> u128 {
+    checked_prime_partition_count(n).expect("prime partition count overflowed u128")
+}
+
+/// The number of ways to write n as a sum of primes, or None if it
+/// doesn't fit in a u128.
+///
+/// ```
+/// assert_eq!(
+///     prime_tools::checked_prime_partition_count(5_442),
+///     Some(340_097_580_346_392_646_686_638_881_104_803_584_091)
+/// );
+/// assert_eq!(prime_tools::checked_prime_partition_count(5_443), None);
+/// ```
+pub fn checked_prime_partition_count(n: u64) -> Option<u128> {
+    if n > LAST_U128_PARTITION {
+        return None;
+    }
+    let n = n as usize;
+    let mut ways = vec![0u128; n + 1];
+    ways[0] = 1;
+    for p in get_primes_between(2, n as u64 + 1) {
+        for sum in p as usize..=n {
+            ways[sum] += ways[sum - p as usize];
+        }
+    }
+    Some(ways[n])
+}
+
+/// A shortest list of primes summing to n, smallest first, or None for
+/// n < 2.
+///
+/// A prime is its own sum. Otherwise an even n takes two primes, the
+/// smallest p with n - p prime (Goldbach's conjecture, unproven but never
+/// known to fail, and here found rather than assumed), and an odd n takes
+/// two if n - 2 is prime and otherwise three: 3 and a pair for n - 3.
+///
+/// ```
+/// use prime_tools::min_primes_to_sum;
+///
+/// assert_eq!(min_primes_to_sum(97), Some(vec![97]));
+/// assert_eq!(min_primes_to_sum(100), Some(vec![3, 97]));
+/// assert_eq!(min_primes_to_sum(27), Some(vec![3, 5, 19]));
+/// assert_eq!(min_primes_to_sum(21), Some(vec![2, 19]));
+/// assert_eq!(min_primes_to_sum(1), None);
+/// ```
+pub fn min_primes_to_sum(n: u64) -> Option<Vec<u64>> {
+    if n < 2 {
+        return None;
+    }
+    if is_prime(n) {
+        return Some(vec![n]);
+    }
+    if n % 2 == 0 {
+        return Some(goldbach_pair(n));
+    }
+    if is_prime(n - 2) {
+        return Some(vec![2, n - 2]);
+    }
+    let mut primes = goldbach_pair(n - 3);
+    primes.push(3);
+    primes.sort_unstable();
+    Some(primes)
+}
+
+/// Distinct primes below limit summing to target, smallest first, or None
+/// if there are none. A target of 0 is the empty sum.
+///
+/// A subset-sum over a bitset of reachable sums, shifted and or-ed in once
+/// per prime, which also notes the prime that first reached each sum so the
+/// answer can be walked back. That takes O(π(limit) target / 64) word
+/// operations and four bytes per sum up to target. Every n but 1, 4 and 6
+/// is a sum of distinct primes, so with limit high enough the question is
+/// which.
+///
+/// ```
+/// use prime_tools::prime_subset_sums;
+///
+/// assert_eq!(prime_subset_sums(20, 10), Some(vec![2, 3, 5]));
+/// assert_eq!(prime_subset_sums(5, 10), None);
+/// assert_eq!(prime_subset_sums(20, 6), None);
+/// assert_eq!(prime_subset_sums(7, 15), None);
+/// let primes = prime_subset_sums(100, 1_000).unwrap();
+/// assert_eq!(primes.iter().sum::<u64>(), 1_000);
+/// ```
+pub fn prime_subset_sums(limit: u64, target: u64) -> Option<Vec<u64>> {
+    let primes = get_primes_between(2, limit.min(target.saturating_add(1)));
+    let target = usize::try_from(target).expect("target too large to index");
+    let mut reachable = vec![0u64; words(target + 1)];
+    reachable[0] = 1;
+    // first[s]: 1 + the index of the prime that first made s reachable.
+    let mut first = vec![0u32; target + 1];
+    let last_mask = if (target + 1) % 64 == 0 { !0 } else { (1 << ((target + 1) % 64)) - 1 };
+
+    for (i, &p) in primes.iter().enumerate() {
+        let (skip, shift) = (p as usize / 64, p as u32 % 64);
+        for w in (skip..reachable.len()).rev() {
+            let mut shifted = reachable[w - skip] << shift;
+            if shift > 0 && w > skip {
+                shifted |= reachable[w - skip - 1] >> (64 - shift);
+            }
+            if w == reachable.len() - 1 {
+                shifted &= last_mask;
+            }
+            let mut fresh = shifted & !reachable[w];
+            reachable[w] |= fresh;
+            while fresh != 0 {
+                first[w * 64 + fresh.trailing_zeros() as usize] = i as u32 + 1;
+                fresh &= fresh - 1;
+            }
+        }
+        if reachable[target / 64] >> (target % 64) & 1 == 1 {
+            break;
+        }
+    }
+
+    if reachable[target / 64] >> (target % 64) & 1 == 0 {
+        return None;
+    }
+    // The prime that first reached s came after every prime used for the
+    // rest, so walking back never uses a prime twice.
+    let mut used = Vec::new();
+    let mut sum = target;
+    while sum > 0 {
+        let p = primes[first[sum] as usize - 1];
+        used.push(p);
+        sum -= p as usize;
+    }
+    used.reverse();
+    Some(used)
+}
+
+/// The two primes p <= q with p + q = n, p as small as possible, for even n >= 4.
+fn goldbach_pair(n: u64) -> Vec<u64> {
+    let mut p = 2;
+    loop {
+        if is_prime(n - p) {
+            return vec![p, n - p];
+        }
+        p = p.next_prime().unwrap();
+    }
+}
+
+fn is_prime(x: u64) -> bool {
+    miller_rabin_with_bases(x as u128, &U64_BASES)
+}
+
+fn words(bits: usize) -> usize {
+    bits.div_ceil(64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_primes_match_a_search_over_the_sieve() {
+        let primes = get_primes_between(0, 2_000);
+        let mut fewest = vec![u32::MAX; 2_000];
+        fewest[0] = 0;
+        for sum in 1..2_000 {
+            for &p in primes.iter().take_while(|&&p| p as usize <= sum) {
+                fewest[sum] = fewest[sum].min(fewest[sum - p as usize].saturating_add(1));
+            }
+        }
+        for n in 0..2_000u64 {
+            let found = min_primes_to_sum(n);
+            if n < 2 {
+                assert_eq!(found, None);
+                continue;
+            }
+            let found = found.unwrap();
+            assert_eq!(found.len() as u32, fewest[n as usize], "{}", n);
+            assert_eq!(found.iter().sum::<u64>(), n);
+            assert!(found.iter().all(|&p| is_prime(p)) && found.windows(2).all(|w| w[0] <= w[1]));
+        }
+        let big = min_primes_to_sum(u64::MAX - 1).unwrap();
+        assert_eq!(big.len(), 2);
+        assert_eq!(big.iter().map(|&p| p as u128).sum::<u128>(), u64::MAX as u128 - 1);
+    }
+
+    #[test]
+    fn subset_sums_match_brute_force() {
+        for limit in 0..30u64 {
+            let primes = get_primes_between(2, limit);
+            let mut sums = std::collections::HashSet::new();
+            for mask in 0..1u32 << primes.len() {
+                sums.insert(primes.iter().enumerate().filter(|&(i, _)| mask >> i & 1 == 1).map(|(_, &p)| p).sum::<u64>());
+            }
+            for target in 0..140 {
+                let found = prime_subset_sums(limit, target);
+                assert_eq!(found.is_some(), sums.contains(&target), "{} {}", limit, target);
+                if let Some(found) = found {
+                    assert_eq!(found.iter().sum::<u64>(), target);
+                    assert!(found.windows(2).all(|w| w[0] < w[1]) && found.iter().all(|&p| p < limit && is_prime(p)));
+                }
+            }
+        }
+        let big = prime_subset_sums(u64::MAX, 100_003).unwrap();
+        assert_eq!(big.iter().sum::<u64>(), 100_003);
+    }
+}
@@ -0,0 +1,286 @@
+//! Primes taken by polynomials: runs of prime values and searches for the
+//! polynomials with the most.
+
+use std::ops::Range;
+use std::thread;
+
+use crate::primality::U64_BASES;
+use crate::{is_strong_lucas_probable_prime, miller_rabin_with_bases};
+
+/// How many consecutive n = 0, 1, 2, ... make n² + an + b prime.
+///
+/// Stops at the first n that doesn't, so b itself has to be prime for the
+/// run to start at all. Values below 2 aren't prime. Each value is tested
+/// exactly while it fits in a u64 and with Baillie–PSW past that.
+///
+/// ```
+/// assert_eq!(prime_tools::consecutive_prime_run(1, 41), 40); // Euler's n² + n + 41
+/// assert_eq!(prime_tools::consecutive_prime_run(-79, 1_601), 80);
+/// assert_eq!(prime_tools::consecutive_prime_run(0, 4), 0);
+/// ```
+pub fn consecutive_prime_run(a: i64, b: i64) -> u32 {
+    let (a, b) = (a as i128, b as i128);
+    let mut n: i128 = 0;
+    while is_prime_value(n * n + a * n + b) {
+        n += 1;
+    }
+    n as u32
+}
+
+/// The a in `a_range` and b in `b_range` whose n² + an + b gives the longest
+/// `consecutive_prime_run`, as (a, b, run); ties go to the smallest b, then
+/// the smallest a. None if no pair gives even one prime.
+///
+/// Only pairs with both b and 1 + a + b prime (the values at n = 0 and 1) can
+/// run past 1, so one sieve up to the largest 1 + a + b supplies the
+/// candidates: b runs over its primes and, for each b, a over the primes q
+/// in reach as q - b - 1. Everything else is skipped without a test.
+///
+/// ```
+/// // Project Euler 27: |a| < 1000 and |b| <= 1000.
+/// assert_eq!(prime_tools::longest_quadratic_prime_run(-999..1_000, -1_000..1_001), Some((-61, 971, 71)));
+/// assert_eq!(prime_tools::longest_quadratic_prime_run(0..10, -5..2), None);
+/// ```
+pub fn longest_quadratic_prime_run(a_range: Range<i64>, b_range: Range<i64>) -> Option<(i64, i64, u32)> {
+    if a_range.is_empty() || b_range.is_empty() || b_range.end <= 2 {
+        return None;
+    }
+    // 1 + a + b for the largest a and b.
+    let top = (a_range.end as i128 + b_range.end as i128 - 1).clamp(0, u64::MAX as i128) as u64;
+    let _span = debug_span!("longest_quadratic_prime_run", top);
+    let primes = crate::get_primes_between(0, top.max(b_range.end as u64));
+    let within = |range: &Range<i128>| {
+        let start = primes.partition_point(|&p| (p as i128) < range.start);
+        let end = primes.partition_point(|&p| (p as i128) < range.end);
+        &primes[start..end]
+    };
+
+    let mut best: Option<(i64, i64, u32)> = None;
+    for &b in within(&(b_range.start as i128..b_range.end as i128)) {
+        let b = b as i128;
+        // q = 1 + a + b must be prime too.
+        let q_range = a_range.start as i128 + b + 1..a_range.end as i128 + b + 1;
+        for &q in within(&q_range) {
+            let a = q as i128 - b - 1;
+            let run = consecutive_prime_run(a as i64, b as i64);
+            if best.is_none_or(|(_, _, longest)| run > longest) {
+                best = Some((a as i64, b as i64, run));
+            }
+        }
+        if best.is_none() {
+            // b alone is prime, but no a in range gets past n = 0.
+            best = Some((a_range.start, b as i64, 1));
+        }
+    }
+    best
+}
+
+/// A search over integer polynomials for the ones that take the most prime
+/// values, each evaluated at every n in a range.
+///
+/// ```
+/// use prime_tools::PolynomialPrimeSearch;
+///
+/// let search = PolynomialPrimeSearch { keep: 2, ..PolynomialPrimeSearch::quadratics(-10..10, 0..50) };
+/// let best = search.run();
+/// // n² - 5n + 47 is Euler's n² + n + 41 at n - 3: primes for n = 0 through 42.
+/// assert_eq!(best[0].coefficients, vec![47, -5, 1]);
+/// assert_eq!(best[0].longest_run, (0, 43));
+/// assert_eq!(best[0].primes, 86);
+/// assert_eq!(best[1].coefficients, vec![43, -3, 1]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolynomialPrimeSearch {
+    /// The polynomials to try, each as its coefficients from the constant
+    /// term up: `[b, a, 1]` is n² + an + b.
+    pub polynomials: Vec<Vec<i64>>,
+    /// The n each polynomial is evaluated at.
+    pub n: Range<i64>,
+    /// How many of the best polynomials `run` reports.
+    pub keep: usize,
+    /// Maximum number of worker threads the polynomials are split across.
+    pub threads: usize,
+}
+
+impl Default for PolynomialPrimeSearch {
+    /// No polynomials yet, n from 0 to 99, the best 10 kept, and one thread per available core.
+    fn default() -> PolynomialPrimeSearch {
+        PolynomialPrimeSearch {
+            polynomials: Vec::new(),
+            n: 0..100,
+            keep: 10,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+/// How one polynomial did in a `PolynomialPrimeSearch`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolynomialPrimeStats {
+    /// The polynomial, constant term first.
+    pub coefficients: Vec<i64>,
+    /// How many n in the range gave a prime.
+    pub primes: u64,
+    /// How many n were tried.
+    pub evaluated: u64,
+    /// The longest stretch of consecutive n that all gave primes, as (first n,
+    /// length); the earliest one if there's a tie, and (start of the range, 0)
+    /// if there were no primes.
+    pub longest_run: (i64, u64),
+}
+
+impl PolynomialPrimeStats {
+    /// The fraction of the values that were prime.
+    pub fn density(&self) -> f64 {
+        self.primes as f64 / self.evaluated.max(1) as f64
+    }
+}
+
+impl PolynomialPrimeSearch {
+    /// A search over n² + an + b for every a in `a_range` and b in `b_range`,
+    /// with the other settings at their defaults.
+    pub fn quadratics(a_range: Range<i64>, b_range: Range<i64>) -> PolynomialPrimeSearch {
+        let polynomials = a_range.flat_map(|a| b_range.clone().map(move |b| vec![b, a, 1])).collect();
+        PolynomialPrimeSearch { polynomials, ..PolynomialPrimeSearch::default() }
+    }
+
+    /// Evaluates every polynomial over the range and returns the `keep` best:
+    /// longest run first, then most primes, then the order they were given in.
+    ///
+    /// Values below 2 aren't prime, and neither is a value that overflows an
+    /// i128 along the way. Values are tested exactly up to 2^64 and with
+    /// Baillie–PSW past that.
+    pub fn run(&self) -> Vec<PolynomialPrimeStats> {
+        let _span = debug_span!("polynomial_prime_search", polynomials = self.polynomials.len(), keep = self.keep);
+        let threads = self.threads.clamp(1, self.polynomials.len().max(1));
+        let chunk = self.polynomials.len().div_ceil(threads).max(1);
+        let mut stats: Vec<PolynomialPrimeStats> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .polynomials
+                .chunks(chunk)
+                .map(|polynomials| scope.spawn(move || polynomials.iter().map(|p| self.evaluate(p)).collect::<Vec<_>>()))
+                .collect();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        });
+        // A stable sort keeps the given order among ties.
+        stats.sort_by(|a, b| b.longest_run.1.cmp(&a.longest_run.1).then(b.primes.cmp(&a.primes)));
+        stats.truncate(self.keep);
+        stats
+    }
+
+    fn evaluate(&self, coefficients: &[i64]) -> PolynomialPrimeStats {
+        let mut stats = PolynomialPrimeStats {
+            coefficients: coefficients.to_vec(),
+            primes: 0,
+            evaluated: 0,
+            longest_run: (self.n.start, 0),
+        };
+        let mut run_start = self.n.start;
+        for n in self.n.clone() {
+            stats.evaluated += 1;
+            if evaluate(coefficients, n).is_some_and(is_prime_value) {
+                stats.primes += 1;
+                let length = (n - run_start + 1) as u64;
+                if length > stats.longest_run.1 {
+                    stats.longest_run = (run_start, length);
+                }
+            } else {
+                run_start = n + 1;
+            }
+        }
+        stats
+    }
+}
+
+/// The polynomial with these coefficients (constant term first) at n, by
+/// Horner's rule, or None if that overflows an i128.
+fn evaluate(coefficients: &[i64], n: i64) -> Option<i128> {
+    coefficients
+        .iter()
+        .rev()
+        .try_fold(0i128, |value, &c| value.checked_mul(n as i128)?.checked_add(c as i128))
+}
+
+/// Whether a polynomial value is prime: exact up to 2^64, Baillie–PSW above.
+fn is_prime_value(x: i128) -> bool {
+    if x < 2 {
+        return false;
+    }
+    let x = x as u128;
+    if x <= u64::MAX as u128 {
+        miller_rabin_with_bases(x, &U64_BASES)
+    } else {
+        miller_rabin_with_bases(x, &[2]) && is_strong_lucas_probable_prime(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_match_trial_division() {
+        for a in -60..60 {
+            for b in -60..200 {
+                let expected = (0..).take_while(|&n: &i64| {
+                    let value = n * n + a * n + b;
+                    value >= 2 && crate::is_u64_prime(value as u64)
+                });
+                assert_eq!(consecutive_prime_run(a, b), expected.count() as u32, "{} {}", a, b);
+            }
+        }
+        // Values past 2^64 still get tested.
+        assert_eq!(consecutive_prime_run(0, 18_446_744_073_709_551_557u64 as i64), 0);
+        assert_eq!(consecutive_prime_run(i64::MAX, 2), 1);
+    }
+
+    #[test]
+    fn search_matches_one_polynomial_at_a_time() {
+        let quadratics = PolynomialPrimeSearch { n: 0..60, keep: usize::MAX, ..PolynomialPrimeSearch::quadratics(-20..20, -5..60) };
+        let single = PolynomialPrimeSearch { threads: 1, ..quadratics.clone() };
+        let stats = quadratics.run();
+        assert_eq!(stats, single.run());
+        assert_eq!(stats.len(), 40 * 65);
+        for s in &stats {
+            let (b, a) = (s.coefficients[0], s.coefficients[1]);
+            let primes = (0..60).filter(|&n: &i64| is_prime_value((n * n + a * n + b) as i128)).count() as u64;
+            assert_eq!(s.primes, primes, "{:?}", s.coefficients);
+            // A run from n = 0 is as long as consecutive_prime_run says, and no run is longer.
+            let from_zero = consecutive_prime_run(a, b).min(60) as u64;
+            assert!(s.longest_run.1 >= from_zero, "{:?}", s);
+            if s.longest_run.0 == 0 {
+                assert_eq!(s.longest_run.1, from_zero, "{:?}", s);
+            }
+        }
+        assert!(stats.windows(2).all(|w| (w[0].longest_run.1, w[0].primes) >= (w[1].longest_run.1, w[1].primes)));
+
+        // Higher degrees, negative n, and values that overflow.
+        let search = PolynomialPrimeSearch {
+            polynomials: vec![vec![-1, 0, 0, 0, 1], vec![1, 1], vec![i64::MAX, i64::MAX, i64::MAX, i64::MAX]],
+            n: -1_000..1_000,
+            ..PolynomialPrimeSearch::default()
+        };
+        let stats = search.run();
+        // n + 1 is the primes below 1001 for n >= 1, and n^4 - 1 factors, so it's never prime.
+        assert_eq!(stats[0].coefficients, vec![1, 1]);
+        assert_eq!((stats[0].primes, stats[0].evaluated, stats[0].longest_run), (168, 2_000, (1, 2)));
+        let quartic = stats.iter().find(|s| s.coefficients.len() == 5).unwrap();
+        assert_eq!(quartic.primes, 0);
+    }
+
+    #[test]
+    fn longest_run_is_the_brute_force_maximum() {
+        for (a_range, b_range) in &[(-50..50, -50..101), (-10..0, 0..30), (5..6, 2..4), (-3..3, 2..3)] {
+            let mut expected: Option<(i64, i64, u32)> = None;
+            for b in b_range.clone() {
+                for a in a_range.clone() {
+                    let run = consecutive_prime_run(a, b);
+                    if run > 0 && expected.is_none_or(|(_, _, longest)| run > longest) {
+                        expected = Some((a, b, run));
+                    }
+                }
+            }
+            assert_eq!(longest_quadratic_prime_run(a_range.clone(), b_range.clone()), expected, "{:?} {:?}", a_range, b_range);
+        }
+    }
+}
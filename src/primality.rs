@@ -0,0 +1,488 @@
+//! Probabilistic and deterministic primality tests.
+
+use std::convert::TryFrom;
+use std::thread;
+
+use crate::barrett::Barrett;
+use crate::modular::{add_mod, gcd_u128, half_mod, isqrt_u128, jacobi, mul_mod, pow_mod, signed_mod, sub_mod};
+use crate::sieve::{primes_up_to, sieve_segments};
+use crate::SieveOptions;
+
+/// Miller–Rabin bases that settle primality for every x below 2^64 (Jim Sinclair's set).
+pub(crate) const U64_BASES: [u64; 7] = [2, 325, 9_375, 28_178, 450_775, 9_780_504, 1_795_265_022];
+
+/// True if x is a strong probable prime to every one of `bases`.
+///
+/// That's the Miller–Rabin test with exactly the witnesses you give it, so
+/// it can reproduce results about strong pseudoprimes: a prime always passes,
+/// and a composite that passes is a strong pseudoprime to all of those bases.
+/// Bases that are a multiple of x say nothing and are skipped. x < 2 and even
+/// x > 2 are never prime.
+///
+/// The bases 2, 325, 9375, 28178, 450775, 9780504 and 1795265022 are known to
+/// make this exact for every x below 2^64.
+///
+/// ```
+/// use prime_tools::miller_rabin_with_bases;
+///
+/// // 2047 = 23 * 89 is the smallest strong pseudoprime to base 2.
+/// assert!(miller_rabin_with_bases(2047, &[2]));
+/// assert!(!miller_rabin_with_bases(2047, &[2, 3]));
+/// // 2^127 - 1 is prime.
+/// assert!(miller_rabin_with_bases((1 << 127) - 1, &[2, 3, 5, 7, 11, 13]));
+/// ```
+pub fn miller_rabin_with_bases(x: u128, bases: &[u64]) -> bool {
+    if x < 2 {
+        return false;
+    }
+    if x % 2 == 0 {
+        return x == 2;
+    }
+
+    if x <= u64::MAX as u128 {
+        return miller_rabin_u64(x as u64, bases);
+    }
+
+    // x - 1 = d * 2^s with d odd.
+    let s = (x - 1).trailing_zeros();
+    let d = (x - 1) >> s;
+    bases.iter().all(|&a| {
+        let a = a as u128 % x;
+        if a == 0 {
+            return true;
+        }
+        let mut y = pow_mod(a, d, x);
+        if y == 1 || y == x - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            y = mul_mod(y, y, x);
+            if y == x - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// `miller_rabin_with_bases` for an odd x > 2 that fits in a u64, with every
+/// reduction mod x going through one `Barrett` instead of a u128 division.
+fn miller_rabin_u64(x: u64, bases: &[u64]) -> bool {
+    let field = Barrett::new(x);
+    let s = (x - 1).trailing_zeros();
+    let d = (x - 1) >> s;
+    bases.iter().all(|&a| {
+        let a = field.reduce(a);
+        if a == 0 {
+            return true;
+        }
+        let mut y = field.pow_mod(a, d);
+        if y == 1 || y == x - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            y = field.mul_mod(y, y);
+            if y == x - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// Every strong pseudoprime to `base` below `limit`, in order: the odd
+/// composites n that `miller_rabin_with_bases(n, &[base])` lets through.
+///
+/// The segmented sieve says which odd numbers are composite and only those
+/// get the Miller–Rabin test, split across the cores. n that divide the base
+/// are left out, since the test skips a base that's a multiple of n. Enough
+/// to check a witness set against a table: every spsp(2) below the limit
+/// that also passes the other bases is a counterexample.
+///
+/// ```
+/// use prime_tools::strong_pseudoprimes_to_base;
+///
+/// assert_eq!(strong_pseudoprimes_to_base(2, 10_000), vec![2047, 3277, 4033, 4681, 8321]);
+/// assert_eq!(strong_pseudoprimes_to_base(3, 2_000), vec![121, 703, 1891]);
+/// ```
+pub fn strong_pseudoprimes_to_base(base: u64, limit: u64) -> Vec<u64> {
+    // 9 is the first odd composite.
+    if limit <= 9 {
+        return Vec::new();
+    }
+    let options = SieveOptions::default();
+    let sieving_primes = primes_up_to(crate::isqrt(limit - 1) as u32, &options);
+    let threads = (options.threads as u64).min((limit - 9) >> 16).max(1);
+    let chunk = (limit - 9).div_ceil(threads);
+
+    let sieving_primes = &sieving_primes;
+    let options = &options;
+    let results: Vec<Vec<u64>> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|i| {
+                let (lo, hi) = (9 + i * chunk, (9 + i * chunk).saturating_add(chunk).min(limit));
+                scope.spawn(move || {
+                    let mut found = Vec::new();
+                    sieve_segments(lo, hi, sieving_primes, options, |low, words| {
+                        for (w, &word) in words.iter().enumerate() {
+                            // Segments start at multiples of 64, so the odd numbers are the odd bits.
+                            let mut composites = !word & 0xaaaa_aaaa_aaaa_aaaa;
+                            while composites != 0 {
+                                let n = low + w as u64 * 64 + composites.trailing_zeros() as u64;
+                                composites &= composites - 1;
+                                if n >= lo && n < hi && base % n != 0 && miller_rabin_with_bases(n as u128, &[base]) {
+                                    found.push(n);
+                                }
+                            }
+                        }
+                    });
+                    found
+                })
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+    results.concat()
+}
+
+/// True if x is a strong Lucas probable prime with Selfridge's parameters.
+///
+/// D is the first of 5, -7, 9, -11, ... with Jacobi symbol (D / x) = -1,
+/// P = 1 and Q = (1 - D) / 4. Writing x + 1 = d * 2^s with d odd, x passes if
+/// U_d ≡ 0 or V_(d * 2^r) ≡ 0 (mod x) for some r < s. Primes always pass; the
+/// composites that do are the strong Lucas pseudoprimes, starting 5459, 5777,
+/// 10877. Together with a base-2 Miller–Rabin test this is BPSW.
+///
+/// ```
+/// use prime_tools::is_strong_lucas_probable_prime;
+///
+/// assert!(is_strong_lucas_probable_prime(1_000_000_007));
+/// assert!(!is_strong_lucas_probable_prime(1_000_000_007 * 3));
+/// assert!(is_strong_lucas_probable_prime(5459)); // = 53 * 103
+/// ```
+pub fn is_strong_lucas_probable_prime(x: u128) -> bool {
+    let (q, d) = match selfridge_parameters(x) {
+        Ok(parameters) => parameters,
+        Err(settled) => return settled,
+    };
+
+    let s = (x + 1).trailing_zeros();
+    let (u, mut v, mut q_power) = lucas_uv(x, q, d, (x + 1) >> s);
+    if u == 0 || v == 0 {
+        return true;
+    }
+    for _ in 1..s {
+        v = sub_mod(mul_mod(v, v, x), add_mod(q_power, q_power, x), x);
+        q_power = mul_mod(q_power, q_power, x);
+        if v == 0 {
+            return true;
+        }
+    }
+    false
+}
+
+/// True if x is a Frobenius probable prime with respect to t^2 - t + Q, with
+/// Selfridge's Q = (1 - D) / 4 as in `is_strong_lucas_probable_prime`.
+///
+/// This is the Crandall–Pomerance form of the test: with (D / x) = -1 a prime
+/// x has U_(x+1) ≡ 0 and V_(x+1) ≡ 2Q (mod x). The composites that pass
+/// (5777, 10877, 75077, ...) overlap the strong Lucas pseudoprimes without
+/// being the same set, which is what makes comparing them interesting.
+///
+/// ```
+/// use prime_tools::is_frobenius_probable_prime;
+///
+/// assert!(is_frobenius_probable_prime(1_000_000_007));
+/// assert!(!is_frobenius_probable_prime(5459));
+/// assert!(is_frobenius_probable_prime(5777)); // = 53 * 109
+/// ```
+pub fn is_frobenius_probable_prime(x: u128) -> bool {
+    let (q, d) = match selfridge_parameters(x) {
+        Ok(parameters) => parameters,
+        Err(settled) => return settled,
+    };
+
+    let (u, v, _) = lucas_uv(x, q, d, x + 1);
+    u == 0 && v == add_mod(q, q, x)
+}
+
+/// Selfridge's Q and D for the Lucas tests, both reduced mod x, or Err with
+/// the answer when x is settled without them: x < 2, even, a perfect square
+/// (no D works), or sharing a factor with D or Q.
+fn selfridge_parameters(x: u128) -> Result<(u128, u128), bool> {
+    if x < 2 {
+        return Err(false);
+    }
+    if x % 2 == 0 {
+        return Err(x == 2);
+    }
+    let root = isqrt_u128(x);
+    if root * root == x {
+        return Err(false);
+    }
+
+    let mut d: i128 = 5;
+    loop {
+        match jacobi(signed_mod(d, x), x) {
+            -1 => break,
+            // x shares a factor with D; fine only if x is that small prime.
+            0 => return Err(x <= d.unsigned_abs() && crate::is_u64_prime(x as u64)),
+            _ => d = if d > 0 { -d - 2 } else { -d + 2 },
+        }
+    }
+
+    let q = (1 - d) / 4;
+    let g = gcd_u128(q.unsigned_abs(), x);
+    if g != 1 {
+        return Err(g == x && crate::is_u64_prime(x as u64));
+    }
+    Ok((signed_mod(q, x), signed_mod(d, x)))
+}
+
+/// Exact primality for every primitive integer type up to 64 bits, so
+/// generic code can write `x.is_prime()` whatever x's type is.
+///
+/// Signed types answer for negative numbers too: primes are positive, so
+/// those are never prime. u8, u16 and u32 go to `is_u32_prime`, and u64,
+/// usize and i64 to deterministic Miller–Rabin, which stays quick up to
+/// 2^64 where trial division would not.
+///
+/// ```
+/// use prime_tools::Primality;
+///
+/// fn primes_in<T: Primality>(values: &[T]) -> usize {
+///     values.iter().filter(|x| x.is_prime()).count()
+/// }
+///
+/// assert_eq!(primes_in(&[-7i64, -2, 0, 1, 2, 7, 9]), 2);
+/// assert_eq!(primes_in(&[4_294_967_291u32, 4_294_967_295]), 1);
+/// assert_eq!(primes_in(&[251u8, 255]), 1);
+/// assert_eq!(primes_in(&[18_446_744_073_709_551_557u64, u64::MAX]), 1);
+/// ```
+pub trait Primality: Sized {
+    /// True if self is prime.
+    fn is_prime(&self) -> bool;
+
+    /// The first prime greater than self, or None if it doesn't fit in the type.
+    ///
+    /// ```
+    /// use prime_tools::Primality;
+    ///
+    /// assert_eq!(13u8.next_prime(), Some(17));
+    /// assert_eq!(251u8.next_prime(), None);
+    /// assert_eq!((-40i32).next_prime(), Some(2));
+    /// assert_eq!(65_521u16.next_prime(), None);
+    /// assert_eq!(1_000_000usize.next_prime(), Some(1_000_003));
+    /// ```
+    fn next_prime(&self) -> Option<Self>;
+}
+
+macro_rules! impl_primality {
+    ($($t:ty => $is_prime:expr),*) => {$(
+        impl Primality for $t {
+            fn is_prime(&self) -> bool {
+                $is_prime(*self)
+            }
+
+            fn next_prime(&self) -> Option<$t> {
+                // Negative numbers have 2 next, like 0 and 1.
+                let x = u64::try_from(*self).unwrap_or(0);
+                next_u64_prime(x).and_then(|p| <$t>::try_from(p).ok())
+            }
+        }
+    )*};
+}
+
+impl_primality!(
+    u8 => |x| crate::is_u32_prime(x as u32),
+    u16 => |x| crate::is_u32_prime(x as u32),
+    u32 => crate::is_u32_prime,
+    u64 => |x| miller_rabin_with_bases(x as u128, &U64_BASES),
+    usize => |x| miller_rabin_with_bases(x as u128, &U64_BASES),
+    i32 => crate::is_i32_prime,
+    i64 => |x: i64| x > 0 && miller_rabin_with_bases(x as u128, &U64_BASES)
+);
+
+/// The first prime greater than x, or None past the largest u64 prime.
+///
+/// Trial division would make this crawl near 2^64, so candidates get the
+/// deterministic Miller–Rabin test instead.
+fn next_u64_prime(x: u64) -> Option<u64> {
+    if x < 2 {
+        return Some(2);
+    }
+    let mut y = x.checked_add(1)? | 1;
+    while !miller_rabin_with_bases(y as u128, &U64_BASES) {
+        y = y.checked_add(2)?;
+    }
+    Some(y)
+}
+
+/// (U_k, V_k, Q^k) mod x for the Lucas sequences with P = 1 and the given Q
+/// and D = P^2 - 4Q, by doubling and stepping over the bits of k. x must be odd.
+fn lucas_uv(x: u128, q: u128, d: u128, k: u128) -> (u128, u128, u128) {
+    let (mut u, mut v, mut q_power) = (0, 2 % x, 1 % x);
+    for i in (0..128 - k.leading_zeros()).rev() {
+        // k -> 2k
+        u = mul_mod(u, v, x);
+        v = sub_mod(mul_mod(v, v, x), add_mod(q_power, q_power, x), x);
+        q_power = mul_mod(q_power, q_power, x);
+        if k >> i & 1 == 1 {
+            // k -> k + 1
+            let (next_u, next_v) = (add_mod(u, v, x), add_mod(mul_mod(d, u, x), v, x));
+            u = half_mod(next_u, x);
+            v = half_mod(next_v, x);
+            q_power = mul_mod(q_power, q, x);
+        }
+    }
+    (u, v, q_power)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strong_pseudoprimes_match_brute_force() {
+        for &base in &[2, 3, 5, 10, 1_000_003] {
+            let expected: Vec<u64> = (9..200_000u64)
+                .filter(|&n| n % 2 == 1 && base % n != 0 && !crate::is_u64_prime(n) && miller_rabin_with_bases(n as u128, &[base]))
+                .collect();
+            assert_eq!(strong_pseudoprimes_to_base(base, 200_000), expected, "{}", base);
+        }
+        // 2047 = 23 * 89 is the first spsp(2).
+        assert_eq!(strong_pseudoprimes_to_base(2, 2047), vec![]);
+        assert_eq!(strong_pseudoprimes_to_base(2, 2048), vec![2047]);
+        assert_eq!(strong_pseudoprimes_to_base(3, 0), vec![]);
+        // 9, 27 and 81 divide the base, so the test skips it for them; they're not counted.
+        assert!(strong_pseudoprimes_to_base(81, 1000).iter().all(|&n| 81 % n != 0));
+    }
+
+    #[test]
+    fn signed_types_agree_with_unsigned() {
+        for x in -10_000i32..10_000 {
+            let expected = x > 0 && crate::is_u32_prime(x as u32);
+            assert_eq!(x.is_prime(), expected, "{}", x);
+            assert_eq!((x as i64).is_prime(), expected, "{}", x);
+            if x >= 0 {
+                assert_eq!((x as u64).is_prime(), expected, "{}", x);
+            }
+            assert_eq!(x.unsigned_abs().is_prime(), crate::is_u32_prime(x.unsigned_abs()), "{}", x);
+        }
+        for &x in &[i32::MIN, i32::MIN + 1, i32::MAX] {
+            assert_eq!(x.is_prime(), x == i32::MAX, "{}", x);
+        }
+        for &x in &[i64::MIN, -1, 23_423_412_349, -23_423_412_349, 23_423_414_138] {
+            assert_eq!(x.is_prime(), x == 23_423_412_349, "{}", x);
+        }
+    }
+
+    #[test]
+    fn next_prime_for_every_width() {
+        let primes = crate::get_primes_less_than_x(1 << 17);
+        let next = |x: u64| primes.get(primes.partition_point(|&p| p as u64 <= x)).map(|&p| p as u64);
+        for x in 0..=u8::MAX {
+            assert_eq!(x.is_prime(), crate::is_u32_prime(x as u32), "{}", x);
+            assert_eq!(x.next_prime().map(u64::from), next(x as u64).filter(|&p| p <= 255), "{}", x);
+        }
+        for x in 0..=u16::MAX {
+            assert_eq!(x.next_prime().map(u64::from), next(x as u64).filter(|&p| p <= 65_535), "{}", x);
+            assert_eq!((x as usize).next_prime().map(|p| p as u64), next(x as u64), "{}", x);
+            assert_eq!((x as usize).is_prime(), x.is_prime(), "{}", x);
+        }
+        for x in -1000i32..0 {
+            assert_eq!(x.next_prime(), Some(2));
+            assert_eq!((x as i64).next_prime(), Some(2));
+        }
+        assert_eq!(4_294_967_290u32.next_prime(), Some(4_294_967_291));
+        assert_eq!(4_294_967_291u32.next_prime(), None);
+        assert_eq!(i32::MAX.next_prime(), None);
+        assert_eq!((i32::MAX - 1).next_prime(), Some(i32::MAX));
+        assert_eq!((i64::MAX - 30).next_prime(), Some(i64::MAX - 24));
+        assert_eq!((i64::MAX - 24).next_prime(), None);
+        assert_eq!((u64::MAX - 100).next_prime(), Some(u64::MAX - 94));
+        assert_eq!((u64::MAX - 58).next_prime(), None);
+        assert_eq!(u64::MAX.next_prime(), None);
+    }
+
+    #[test]
+    fn deterministic_bases_match_the_sieve() {
+        let primes = crate::get_primes_less_than_x(200_000);
+        for x in 0..200_000u128 {
+            let expected = primes.binary_search(&(x as u32)).is_ok();
+            assert_eq!(miller_rabin_with_bases(x, &U64_BASES), expected, "{}", x);
+        }
+    }
+
+    #[test]
+    fn known_strong_pseudoprimes() {
+        // The smallest strong pseudoprimes to the first n prime bases (A014233).
+        let first_primes = [2, 3, 5, 7, 11, 13, 17, 19, 23];
+        let pseudoprimes: [u128; 9] = [
+            2_047,
+            1_373_653,
+            25_326_001,
+            3_215_031_751,
+            2_152_302_898_747,
+            3_474_749_660_383,
+            341_550_071_728_321,
+            341_550_071_728_321,
+            3_825_123_056_546_413_051,
+        ];
+        for (n, &x) in pseudoprimes.iter().enumerate() {
+            assert!(miller_rabin_with_bases(x, &first_primes[..=n]), "{}", x);
+            assert!(!miller_rabin_with_bases(x, &U64_BASES), "{}", x);
+        }
+        assert!(!miller_rabin_with_bases(3_825_123_056_546_413_051, &[2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37]));
+    }
+
+    #[test]
+    fn wide_inputs() {
+        // The largest prime below 2^64, and its square, which still fits in a u128.
+        let p = 18_446_744_073_709_551_557u128;
+        assert!(miller_rabin_with_bases(p, &U64_BASES));
+        assert!(!miller_rabin_with_bases(p * p, &[2, 3]));
+        assert!(!miller_rabin_with_bases(p * 5, &[2]));
+        assert!(miller_rabin_with_bases((1 << 89) - 1, &[2, 3, 5]));
+        assert!(!miller_rabin_with_bases(u128::MAX, &[2]));
+        assert!(miller_rabin_with_bases(2, &[]));
+        assert!(!miller_rabin_with_bases(1, &[2]));
+    }
+
+    #[test]
+    fn lucas_pseudoprimes_below_200_000() {
+        let primes = crate::get_primes_less_than_x(200_000);
+        let mut strong = Vec::new();
+        let mut frobenius = Vec::new();
+        for x in 0..200_000u128 {
+            let prime = primes.binary_search(&(x as u32)).is_ok();
+            if is_strong_lucas_probable_prime(x) != prime {
+                strong.push(x);
+            }
+            if is_frobenius_probable_prime(x) != prime {
+                frobenius.push(x);
+            }
+        }
+        // A217255
+        assert_eq!(
+            strong,
+            vec![
+                5_459, 5_777, 10_877, 16_109, 18_971, 22_499, 24_569, 25_199, 40_309, 58_519, 75_077, 97_439, 100_127,
+                113_573, 115_639, 130_139, 155_819, 158_399, 161_027, 162_133, 176_399, 176_471, 189_419, 192_509, 197_801,
+            ]
+        );
+        assert_eq!(frobenius, vec![5_777, 10_877, 75_077, 100_127, 113_573, 161_027, 162_133]);
+    }
+
+    #[test]
+    fn lucas_wide_inputs() {
+        let p = 18_446_744_073_709_551_557u128;
+        assert!(is_strong_lucas_probable_prime(p));
+        assert!(is_frobenius_probable_prime(p));
+        assert!(!is_strong_lucas_probable_prime(p * p));
+        assert!(!is_frobenius_probable_prime(p * 3));
+        assert!(is_strong_lucas_probable_prime((1 << 127) - 1));
+        assert!(is_frobenius_probable_prime((1 << 89) - 1));
+        assert!(!is_strong_lucas_probable_prime(u128::MAX));
+    }
+}
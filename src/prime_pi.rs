@@ -0,0 +1,498 @@
+//! Counting primes without listing them.
+//!
+//! The conventions match the sieve functions: `prime_pi(x)` counts the
+//! primes below x like `get_primes_less_than_x`, `prime_pi_between(a, b)`
+//! the primes in `[a, b)` like `get_primes_between`, and
+//! `prime_pi_inclusive(x)` is π(x) as the textbooks write it, p <= x.
+
+use std::ops::ControlFlow;
+
+use crate::sieve::{for_each_prime_between, prime_count_bound, primes_up_to, sieve_segments_until};
+use crate::{SieveError, SieveOptions};
+
+/// How many primes are below x: p < x.
+///
+/// `prime_pi_inclusive(x - 1)`; see that for how it's computed.
+///
+/// ```
+/// assert_eq!(prime_tools::prime_pi(7), 3); // 2, 3, 5
+/// assert_eq!(prime_tools::prime_pi(10_000_000_000), 455_052_511);
+/// ```
+pub fn prime_pi(x: u64) -> u64 {
+    match x {
+        0 => 0,
+        _ => prime_pi_inclusive(x - 1),
+    }
+}
+
+/// π(x), how many primes are at most x: p <= x.
+///
+/// Uses the Lucy Hedgehog variant of Legendre's method below 2^30, about
+/// x^(3/4) operations and 16·√x bytes without sieving anything past √x, and
+/// `prime_pi_with_options` with its defaults from there on, which overtakes
+/// it around 10^9 and counts to 10^12 in well under a second.
+///
+/// ```
+/// assert_eq!(prime_tools::prime_pi_inclusive(7), 4); // 2, 3, 5, 7
+/// assert_eq!(prime_tools::prime_pi_inclusive(1), 0);
+/// ```
+pub fn prime_pi_inclusive(x: u64) -> u64 {
+    if x < 2 {
+        return 0;
+    }
+    if x >= 1 << 30 {
+        return match prime_pi_with_options(x, &PrimePiOptions::default()) {
+            Ok(report) => report.count,
+            Err(_) => unreachable!("no memory limit was set"),
+        };
+    }
+    lucy_hedgehog(x)
+}
+
+/// π(x) for x >= 2 by the Lucy Hedgehog method.
+fn lucy_hedgehog(x: u64) -> u64 {
+    let _span = debug_span!("prime_pi", x);
+    let r = crate::isqrt(x) as usize;
+    // small[v] counts the numbers in [2, v] that survive the primes sieved so
+    // far, and large[i] the ones in [2, x / i]. Once every prime up to √x
+    // has been sieved, the survivors are exactly the primes.
+    let mut small: Vec<u64> = (0..=r as u64).map(|v| v.saturating_sub(1)).collect();
+    let mut large: Vec<u64> = Some(0).into_iter().chain((1..=r as u64).map(|i| x / i - 1)).collect();
+    for p in 2..=r {
+        if small[p] == small[p - 1] {
+            continue;
+        }
+        // Survivors below p, which are the primes below p.
+        let below = small[p - 1];
+        let square = (p * p) as u64;
+        // Take out the survivors whose smallest prime factor is p: p times a
+        // survivor of [p, v / p].
+        for i in 1..=r.min((x / square) as usize) {
+            let d = i * p;
+            let quotient = if d <= r { large[d] } else { small[(x / d as u64) as usize] };
+            large[i] -= quotient - below;
+        }
+        for v in (square as usize..=r).rev() {
+            small[v] -= small[v / p] - below;
+        }
+    }
+    large[1]
+}
+
+/// How many primes are in `[min, max)`: min <= p < max, like `get_primes_between`.
+///
+/// Counts the window with the segmented sieve when that's cheaper, i.e. when
+/// it's narrow next to max^(3/4), and takes `prime_pi(max) - prime_pi(min)` otherwise.
+///
+/// ```
+/// assert_eq!(prime_tools::prime_pi_between(11, 29), 5); // 11, 13, 17, 19, 23
+/// assert_eq!(prime_tools::prime_pi_between(10_000_000_000, 10_000_000_100), 5);
+/// assert_eq!(prime_tools::prime_pi_between(29, 11), 0);
+/// ```
+pub fn prime_pi_between(min: u64, max: u64) -> u64 {
+    if min >= max {
+        return 0;
+    }
+    let sieving = (max - min) as f64 + (max as f64).sqrt();
+    if sieving < (max as f64).powf(0.75) {
+        let mut count = 0;
+        for_each_prime_between(min, max, &SieveOptions::default(), |_| count += 1);
+        return count;
+    }
+    prime_pi(max) - prime_pi(min)
+}
+
+/// Tuning knobs for `prime_pi_with_options`.
+///
+/// ```
+/// use prime_tools::PrimePiOptions;
+///
+/// let options = PrimePiOptions { memory_limit: Some(64 << 20), ..PrimePiOptions::default() };
+/// let report = prime_tools::prime_pi_with_options(100_000_000_000, &options).unwrap();
+/// assert_eq!(report.count, 4_118_054_813);
+/// assert!(report.memory <= 64 << 20);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrimePiOptions {
+    /// Where to split the work, clamped to `[x^(1/3), √x]`. Everything up to
+    /// y is tabled and [1, x / y] gets sieved, so a bigger y trades memory
+    /// for time. None picks one from x.
+    pub y: Option<u64>,
+    /// Size of one segment of the φ sieve's bitmap.
+    pub segment_bytes: usize,
+    /// Cap in bytes on the tables. y is lowered until they fit; if they don't
+    /// fit even at x^(1/3), that's an error.
+    pub memory_limit: Option<usize>,
+}
+
+impl Default for PrimePiOptions {
+    /// y from x, 64 KiB segments, and no memory cap.
+    fn default() -> PrimePiOptions {
+        PrimePiOptions { y: None, segment_bytes: 64 * 1024, memory_limit: None }
+    }
+}
+
+/// What `prime_pi_with_options` counted, and what it took.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrimePiReport {
+    /// π(x), the number of primes <= x.
+    pub count: u64,
+    /// The y it ran with, or 0 if x was small enough to sieve outright.
+    pub y: u64,
+    /// Bytes its tables and sieve buffers held at their peak.
+    pub memory: usize,
+}
+
+/// π(x), how many primes are at most x, by the Lagarias–Miller–Odlyzko form
+/// of the Meissel–Lehmer method, with explicit knobs.
+///
+/// π(x) = φ(x, a) + a - 1 - P2(x, a) for a = π(y): φ counts what survives
+/// the primes up to y, and P2 takes out the survivors with two prime
+/// factors. The leaves of φ that need more than a table lookup are counted
+/// by sieving [1, x / y] a segment at a time and removing the primes up to y
+/// one by one, so the sieve is never held whole. Time grows like x^(2/3) and
+/// memory like y + √x / ln x, which puts π(10^14) within a laptop's reach.
+///
+/// ```
+/// use prime_tools::PrimePiOptions;
+///
+/// let report = prime_tools::prime_pi_with_options(1_000_000_000_000, &PrimePiOptions::default()).unwrap();
+/// assert_eq!(report.count, 37_607_912_018);
+/// ```
+pub fn prime_pi_with_options(x: u64, options: &PrimePiOptions) -> Result<PrimePiReport, SieveError> {
+    let segment_bits = (options.segment_bytes.clamp(8, 1 << 28) as u64 * 8) / 64 * 64;
+    if x < 1 << 16 {
+        let count = crate::get_primes_less_than_x(x as u32 + 1).len() as u64;
+        return Ok(PrimePiReport { count, y: 0, memory: (x as usize + 1) / 8 + 4 * count as usize });
+    }
+
+    let lowest = icbrt(x);
+    let mut y = options.y.unwrap_or_else(|| default_y(x)).clamp(lowest, crate::isqrt(x));
+    if let Some(limit) = options.memory_limit {
+        while y > lowest && memory_estimate(x, y, segment_bits) > limit {
+            y = (y / 2).max(lowest);
+        }
+        let needed = memory_estimate(x, y, segment_bits);
+        if needed > limit {
+            return Err(SieveError::MemoryLimit { needed, limit });
+        }
+    }
+    let _span = debug_span!("prime_pi_with_options", x, y);
+
+    let options = SieveOptions::default();
+    let primes = primes_up_to(y as u32, &options);
+    let (least_factor, mobius) = least_factors_and_mobius(y as usize);
+    let leaves = Leaves { x, y, primes: &primes, least_factor: &least_factor, mobius: &mobius };
+    let (phi, leaves_memory) = leaves.phi(segment_bits);
+    let tables = 4 * primes.capacity() + 5 * mobius.len();
+    drop((least_factor, mobius));
+
+    let (p2, p2_memory) = p2(x, primes.len());
+    let count = phi + primes.len() as i128 - 1 - p2 as i128;
+    let memory = (tables + leaves_memory).max(4 * primes.capacity() + p2_memory);
+    Ok(PrimePiReport { count: count as u64, y, memory })
+}
+
+/// α · x^(1/3) with α growing slowly with x, a good default balance.
+fn default_y(x: u64) -> u64 {
+    let ln = (x as f64).ln();
+    let alpha = (ln * ln * ln / 6_000.0).max(1.0);
+    (alpha * icbrt(x) as f64) as u64
+}
+
+/// What `prime_pi_with_options` will allocate for x and y, from upper bounds on the prime counts.
+fn memory_estimate(x: u64, y: u64, segment_bits: u64) -> usize {
+    let primes_to_y = 4 * prime_count_bound(2, y + 1);
+    let leaves = 5 * (y + 1) + 8 * prime_count_bound(2, y + 1) + 4 * 30_031 + segment_bits / 64 * 12;
+    let sqrt_x = crate::isqrt(x);
+    let p2 = 8 * prime_count_bound(2, sqrt_x + 1) + 8 * prime_count_bound(2, crate::isqrt(x / y) + 1) + 64 * 1024;
+    (primes_to_y + leaves.max(p2)).min(usize::MAX as u64) as usize
+}
+
+/// The largest r with r³ <= x.
+fn icbrt(x: u64) -> u64 {
+    let mut r = (x as f64).cbrt() as u64;
+    while r.checked_pow(3).is_none_or(|cube| cube > x) {
+        r -= 1;
+    }
+    while (r + 1).checked_pow(3).is_some_and(|cube| cube <= x) {
+        r += 1;
+    }
+    r
+}
+
+/// The least prime factor and the Möbius function of every m <= n, with
+/// u32::MAX standing in for the least prime factor of 1.
+fn least_factors_and_mobius(n: usize) -> (Vec<u32>, Vec<i8>) {
+    let mut least = vec![0u32; n + 1];
+    for i in 2..=n {
+        if least[i] == 0 {
+            for multiple in (i..=n).step_by(i) {
+                if least[multiple] == 0 {
+                    least[multiple] = i as u32;
+                }
+            }
+        }
+    }
+    let mut mobius = vec![0i8; n + 1];
+    mobius[1] = 1;
+    for m in 2..=n {
+        let p = least[m] as usize;
+        let rest = m / p;
+        mobius[m] = if rest % p == 0 { 0 } else { -mobius[rest] };
+    }
+    least[1] = u32::MAX;
+    (least, mobius)
+}
+
+/// The primes that φ(·, c) is worked out for by table rather than sieve.
+const TABLED: usize = 6;
+
+/// Everything the φ(x, a) sum needs.
+struct Leaves<'a> {
+    x: u64,
+    y: u64,
+    /// The primes up to y; a is their count.
+    primes: &'a [u32],
+    least_factor: &'a [u32],
+    mobius: &'a [i8],
+}
+
+impl Leaves<'_> {
+    /// φ(x, a), and the bytes its tables and sieve took.
+    ///
+    /// Expanding φ(x, b) = φ(x, b - 1) - φ(x / p_b, b - 1) from b = a down
+    /// leaves a term ±φ(x / m, c) for every squarefree m <= y free of the first
+    /// c primes, which the period of the first c primes answers directly, plus
+    /// the special leaves -μ(m) φ(x / (p m), b) with p = p_(b+1) the least
+    /// prime factor of p m and m <= y < p m, whose arguments all lie in [1, x / y].
+    fn phi(&self, segment_bits: u64) -> (i128, usize) {
+        let (x, y) = (self.x, self.y);
+        let c = TABLED;
+        let primorial: u64 = self.primes[..c].iter().map(|&p| p as u64).product();
+        // coprime[i] counts 1 <= k <= i with no factor among the first c primes.
+        let mut coprime = vec![0u32; primorial as usize + 1];
+        for i in 1..=primorial as usize {
+            let free = self.primes[..c].iter().all(|&p| i % p as usize != 0);
+            coprime[i] = coprime[i - 1] + free as u32;
+        }
+        let phi_c = |n: u64| (n / primorial) * coprime[primorial as usize] as u64 + coprime[(n % primorial) as usize] as u64;
+
+        let mut phi: i128 = 0;
+        let largest_tabled = self.primes[c - 1];
+        for m in 1..=y as usize {
+            if self.mobius[m] != 0 && self.least_factor[m] > largest_tabled {
+                phi += self.mobius[m] as i128 * phi_c(x / m as u64) as i128;
+            }
+        }
+
+        let (special, sieve_memory) = self.special_leaves(segment_bits);
+        (phi + special, 4 * coprime.len() + sieve_memory)
+    }
+
+    /// The sum of the special leaves, sieving [1, x / y] one segment at a time.
+    ///
+    /// Within a segment the primes are removed in order, and a Fenwick tree
+    /// over the bitmap's words counts the survivors below any point, so
+    /// φ(n, b) is the survivors below the segment plus those in it up to n.
+    fn special_leaves(&self, segment_bits: u64) -> (i128, usize) {
+        let (x, y) = (self.x, self.y);
+        let z = x / y;
+        let word_count = (segment_bits / 64) as usize;
+        let mut words = vec![0u64; word_count];
+        let mut tree = vec![0u32; word_count + 1];
+        // Survivors of the first b primes in [1, low).
+        let mut below = vec![0u64; self.primes.len()];
+        let mut sum: i128 = 0;
+
+        let mut low = 1;
+        while low <= z {
+            let high = low.saturating_add(segment_bits).min(z + 1);
+            let len = (high - low) as usize;
+            for (w, word) in words.iter_mut().enumerate() {
+                *word = match len.saturating_sub(64 * w) {
+                    0 => 0,
+                    n if n >= 64 => !0,
+                    n => (1 << n) - 1,
+                };
+            }
+            for &p in &self.primes[..TABLED] {
+                cross_off(&mut words, low, high, p as u64, |_| {});
+            }
+            build_tree(&words, &mut tree);
+            let mut survivors: u64 = words.iter().map(|w| w.count_ones() as u64).sum();
+
+            for (b, &p) in self.primes.iter().enumerate().skip(TABLED) {
+                let p = p as u64;
+                // Leaves need p < m, so p² <= x / low; past that, no bigger b has any either.
+                if (p * p) as u128 * low as u128 > x as u128 {
+                    break;
+                }
+                let m_from = (y / p).max((x as u128 / (p as u128 * high as u128)) as u64) + 1;
+                let m_to = y.min(x / (p * low));
+                if p * p <= y {
+                    for m in m_from..=m_to {
+                        if self.mobius[m as usize] != 0 && self.least_factor[m as usize] as u64 > p {
+                            let n = x / (p * m);
+                            let count = below[b] + count_up_to(&words, &tree, (n - low) as usize);
+                            sum -= self.mobius[m as usize] as i128 * count as i128;
+                        }
+                    }
+                } else {
+                    // Past √y the only m <= y without a factor up to p are primes, with μ = -1.
+                    let first = self.primes.partition_point(|&q| (q as u64) < m_from.max(p + 1));
+                    let last = self.primes.partition_point(|&q| q as u64 <= m_to);
+                    for &q in &self.primes[first..last.max(first)] {
+                        let n = x / (p * q as u64);
+                        sum += (below[b] + count_up_to(&words, &tree, (n - low) as usize)) as i128;
+                    }
+                }
+                below[b] += survivors;
+                cross_off(&mut words, low, high, p, |w| {
+                    survivors -= 1;
+                    let mut i = w + 1;
+                    while i < tree.len() {
+                        tree[i] -= 1;
+                        i += i & i.wrapping_neg();
+                    }
+                });
+            }
+            low = high;
+        }
+        (sum, 8 * words.len() + 4 * tree.len() + 8 * below.len())
+    }
+}
+
+/// Clears the multiples of an odd p (and p itself) in the segment `[low, high)`,
+/// calling `cleared` with the word index of each bit that was still set.
+/// Only odd multiples are visited when p is odd, since 2 always goes first.
+fn cross_off<F: FnMut(usize)>(words: &mut [u64], low: u64, high: u64, p: u64, mut cleared: F) {
+    let mut n = low.div_ceil(p) * p;
+    let step = if p == 2 { 2 } else { 2 * p };
+    if p != 2 && n % 2 == 0 {
+        n += p;
+    }
+    while n < high {
+        let offset = (n - low) as usize;
+        let bit = 1 << (offset % 64);
+        if words[offset / 64] & bit != 0 {
+            words[offset / 64] &= !bit;
+            cleared(offset / 64);
+        }
+        n += step;
+    }
+}
+
+/// A Fenwick tree (1-based) over the popcounts of `words`.
+fn build_tree(words: &[u64], tree: &mut [u32]) {
+    tree[0] = 0;
+    for (w, word) in words.iter().enumerate() {
+        tree[w + 1] = word.count_ones();
+    }
+    for i in 1..tree.len() {
+        let parent = i + (i & i.wrapping_neg());
+        if parent < tree.len() {
+            tree[parent] += tree[i];
+        }
+    }
+}
+
+/// Set bits at offsets 0 through `offset` of the segment.
+fn count_up_to(words: &[u64], tree: &[u32], offset: usize) -> u64 {
+    let w = offset / 64;
+    let mut count = (words[w] << (63 - offset % 64)).count_ones() as u64;
+    let mut i = w;
+    while i > 0 {
+        count += tree[i] as u64;
+        i -= i & i.wrapping_neg();
+    }
+    count
+}
+
+/// P2(x, a), the numbers up to x with exactly two prime factors, both past
+/// the a-th prime, and the bytes it took: the sum over primes p_b in
+/// (p_a, √x] of π(x / p_b) - b + 1.
+///
+/// The x / p_b come in increasing order as p_b falls, so one segmented
+/// sieve up to x / p_(a+1) counts its way past all of them.
+fn p2(x: u64, a: usize) -> (u64, usize) {
+    let options = SieveOptions::default();
+    let sqrt_primes = primes_up_to(crate::isqrt(x) as u32, &options);
+    if sqrt_primes.len() <= a {
+        return (0, 4 * sqrt_primes.capacity());
+    }
+    let top = x / sqrt_primes[a] as u64;
+    let sieving_primes = primes_up_to(crate::isqrt(top) as u32, &options);
+
+    // Queries from the largest prime down, so x / p goes up.
+    let mut next = sqrt_primes.len();
+    let (mut below, mut sum) = (0u64, 0u64);
+    let mut prefix = Vec::new();
+    sieve_segments_until(2, top + 1, &sieving_primes, &options, |low, words| {
+        let end = low + 64 * words.len() as u64;
+        prefix.clear();
+        let mut running = below;
+        for word in words {
+            prefix.push(running);
+            running += word.count_ones() as u64;
+        }
+        while next > a {
+            let v = x / sqrt_primes[next - 1] as u64;
+            if v >= end {
+                break;
+            }
+            let offset = (v - low) as usize;
+            let pi = prefix[offset / 64] + (words[offset / 64] << (63 - offset % 64)).count_ones() as u64;
+            sum += pi - (next - 1) as u64;
+            next -= 1;
+        }
+        below = running;
+        if next > a {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(())
+        }
+    });
+    let memory = 4 * (sqrt_primes.capacity() + sieving_primes.capacity()) + 8 * prefix.capacity() + options.segment_bytes;
+    (sum, memory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_sieve() {
+        let primes = crate::get_primes_less_than_x(1_000_000);
+        let below = |x: u64| primes.partition_point(|&p| (p as u64) < x) as u64;
+        for x in (0..2_000).chain((1_000..1_000_000).step_by(9_973)).chain(vec![999_999, 1_000_000]) {
+            assert_eq!(prime_pi(x), below(x), "{}", x);
+            assert_eq!(prime_pi_inclusive(x), below(x + 1), "{}", x);
+        }
+        for &(min, max) in &[(0, 1_000_000), (2, 3), (3, 3), (500_000, 500_100), (999_000, 1_000_000)] {
+            assert_eq!(prime_pi_between(min, max), below(max) - below(min), "{} {}", min, max);
+        }
+        // Both ways of counting a window agree.
+        let (min, max) = (1_000_000_000, 1_002_000_000);
+        assert_eq!(prime_pi_between(min, max), prime_pi(max) - prime_pi(min));
+        assert_eq!(prime_pi_inclusive(1_000_000_000), 50_847_534);
+        assert_eq!(prime_pi_inclusive(10_000_000_000), lucy_hedgehog(10_000_000_000));
+    }
+
+    #[test]
+    fn lmo_matches_lucy() {
+        let small = PrimePiOptions { segment_bytes: 64, ..PrimePiOptions::default() };
+        for x in (0..70_000).step_by(997).chain(vec![65_535, 65_536, 1 << 20, 12_345_678, 999_999_999]) {
+            let expected = if x < 2 { 0 } else { lucy_hedgehog(x) };
+            assert_eq!(prime_pi_with_options(x, &PrimePiOptions::default()).unwrap().count, expected, "{}", x);
+            assert_eq!(prime_pi_with_options(x, &small).unwrap().count, expected, "{}", x);
+        }
+        // Every y in range gives the same count.
+        for &y in &[1, 500, 5_000, 31_622, u64::MAX] {
+            let options = PrimePiOptions { y: Some(y), ..PrimePiOptions::default() };
+            assert_eq!(prime_pi_with_options(1_000_000_000, &options).unwrap().count, 50_847_534, "{}", y);
+        }
+        let tight = PrimePiOptions { memory_limit: Some(1_000), ..PrimePiOptions::default() };
+        assert!(matches!(prime_pi_with_options(1_000_000_000, &tight), Err(SieveError::MemoryLimit { limit: 1_000, .. })));
+    }
+}
@@ -0,0 +1,158 @@
+//! A set of primes below some limit, stored as a bitmap.
+
+use bit_vec::BitVec;
+
+use crate::prime_bitmap_between;
+
+/// A set of primes below its limit, one bit per number.
+///
+/// `from_limit` gives every prime below the limit; the set operations can then
+/// carve out any subset. Membership is a single bit test, and anything at or
+/// past the limit is simply not in the set.
+///
+/// ```
+/// use prime_tools::PrimeSet;
+///
+/// let small = PrimeSet::from_limit(20);
+/// let large = PrimeSet::from_limit(50);
+/// assert!(small.contains(19));
+/// assert!(!small.contains(23));
+///
+/// let between = large.difference(&small);
+/// assert_eq!(between.iter().collect::<Vec<_>>(), vec![23, 29, 31, 37, 41, 43, 47]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrimeSet {
+    bits: BitVec,
+}
+
+impl PrimeSet {
+    /// Every prime less than n, sieved in one go.
+    pub fn from_limit(n: u64) -> PrimeSet {
+        PrimeSet { bits: prime_bitmap_between(0, n) }
+    }
+
+    /// One past the largest number the set could hold.
+    pub fn limit(&self) -> u64 {
+        self.bits.len() as u64
+    }
+
+    /// True if x is in the set.
+    pub fn contains(&self, x: u64) -> bool {
+        x < self.limit() && self.bits[x as usize]
+    }
+
+    /// How many primes are in the set.
+    pub fn len(&self) -> usize {
+        self.bits.iter().filter(|&bit| bit).count()
+    }
+
+    /// True if the set holds no primes.
+    pub fn is_empty(&self) -> bool {
+        self.bits.none()
+    }
+
+    /// Bytes of heap and struct the bitmap takes up, counting spare capacity:
+    /// about limit / 8, however many primes the set operations left in it.
+    ///
+    /// ```
+    /// use prime_tools::PrimeSet;
+    ///
+    /// let set = PrimeSet::from_limit(1_000_000);
+    /// assert!(set.memory_bytes() >= 125_000 && set.memory_bytes() < 130_000);
+    /// assert!((set.density() - 0.0785).abs() < 0.0001);
+    /// ```
+    pub fn memory_bytes(&self) -> usize {
+        std::mem::size_of::<PrimeSet>() + self.bits.capacity().div_ceil(8)
+    }
+
+    /// The fraction of numbers below the limit that are in the set; 0 for
+    /// a limit of 0.
+    pub fn density(&self) -> f64 {
+        if self.bits.is_empty() {
+            0.0
+        } else {
+            self.len() as f64 / self.limit() as f64
+        }
+    }
+
+    /// The primes in the set, smallest first.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.bits.iter().enumerate().filter(|&(_, bit)| bit).map(|(x, _)| x as u64)
+    }
+
+    /// The primes in both sets. The limit is the smaller of the two.
+    pub fn intersect(&self, other: &PrimeSet) -> PrimeSet {
+        let len = self.bits.len().min(other.bits.len());
+        let (mut bits, other) = (truncated(&self.bits, len), truncated(&other.bits, len));
+        bits.and(&other);
+        PrimeSet { bits }
+    }
+
+    /// The primes in either set. The limit is the larger of the two.
+    pub fn union(&self, other: &PrimeSet) -> PrimeSet {
+        let len = self.bits.len().max(other.bits.len());
+        let (mut bits, other) = (extended(&self.bits, len), extended(&other.bits, len));
+        bits.or(&other);
+        PrimeSet { bits }
+    }
+
+    /// The primes in this set but not in `other`. The limit stays this set's.
+    pub fn difference(&self, other: &PrimeSet) -> PrimeSet {
+        let len = self.bits.len();
+        let other = extended(&truncated(&other.bits, len.min(other.bits.len())), len);
+        let mut bits = self.bits.clone();
+        bits.difference(&other);
+        PrimeSet { bits }
+    }
+}
+
+fn truncated(bits: &BitVec, len: usize) -> BitVec {
+    let mut bits = bits.clone();
+    bits.truncate(len);
+    bits
+}
+
+fn extended(bits: &BitVec, len: usize) -> BitVec {
+    let mut bits = bits.clone();
+    bits.grow(len - bits.len(), false);
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn set_algebra_matches_btreeset() {
+        let limits = [0, 1, 2, 3, 64, 100, 1_000, 1_025];
+        let sets: Vec<PrimeSet> = limits.iter().map(|&n| PrimeSet::from_limit(n)).collect();
+        let patchy = PrimeSet::from_limit(1_000).difference(&PrimeSet::from_limit(3).union(&PrimeSet::from_limit(500).difference(&PrimeSet::from_limit(100))));
+        for a in sets.iter().chain(Some(&patchy)) {
+            let a_set: BTreeSet<u64> = a.iter().collect();
+            assert_eq!(a.len(), a_set.len());
+            assert_eq!(a.is_empty(), a_set.is_empty());
+            for b in sets.iter().chain(Some(&patchy)) {
+                let b_set: BTreeSet<u64> = b.iter().collect();
+                assert_eq!(a.intersect(b).iter().collect::<BTreeSet<_>>(), &a_set & &b_set);
+                assert_eq!(a.union(b).iter().collect::<BTreeSet<_>>(), &a_set | &b_set);
+                assert_eq!(a.difference(b).iter().collect::<BTreeSet<_>>(), &a_set - &b_set);
+                assert_eq!(a.intersect(b).limit(), a.limit().min(b.limit()));
+                assert_eq!(a.union(b).limit(), a.limit().max(b.limit()));
+                assert_eq!(a.difference(b).limit(), a.limit());
+            }
+        }
+    }
+
+    #[test]
+    fn contains_matches_the_prime_list() {
+        let set = PrimeSet::from_limit(10_000);
+        let primes = crate::get_primes_less_than_x(10_000);
+        assert_eq!(set.iter().collect::<Vec<_>>(), primes.iter().map(|&p| p as u64).collect::<Vec<_>>());
+        assert_eq!(set.len(), primes.len());
+        assert!(!set.contains(10_007));
+        assert!(!set.contains(u64::MAX));
+        assert!(PrimeSet::from_limit(0).is_empty());
+    }
+}
@@ -0,0 +1,537 @@
+//! A self-initializing quadratic sieve for composites past the reach of trial division and rho.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::gf2::BitMatrix;
+use crate::modular::{gcd_u128, inverse_mod, isqrt_u128, jacobi, mul_mod, pow_mod, signed_mod, sqrt_mod_prime, sub_mod};
+use crate::small_primes::SMALL_PRIMES;
+use crate::{is_strong_lucas_probable_prime, least_prime_factor, miller_rabin_with_bases};
+
+/// Relations wanted beyond the number of factor base columns, so there are
+/// that many dependencies to try.
+pub(crate) const EXTRA_RELATIONS: usize = 32;
+
+/// A partial relation's cofactor is kept if it's below this many times the
+/// largest factor base prime.
+const LARGE_PRIME_MULTIPLIER: u128 = 40;
+
+/// Primes below this aren't sieved, only trial divided; the threshold makes
+/// up for the few bits they'd have added.
+const SIEVE_FROM: u32 = 30;
+
+/// A nontrivial factor of n, found with a self-initializing quadratic sieve,
+/// or None if n is prime or below 4.
+///
+/// This is the method for the 80 to 110 bit composites with no small factor,
+/// which trial division can't touch and rho only creeps up on. Rather than
+/// walking one polynomial's values, it sieves (ax + b)^2 - kn over many
+/// polynomials sharing each a (a product of factor base primes, so
+/// switching b is nearly free), with k a small multiplier picked to put
+/// more small primes in the factor base. Smooth values, and pairs of values
+/// sharing one large prime, become relations; elimination over GF(2) finds
+/// subsets whose product is a square on both sides, and each such
+/// x^2 ≡ y^2 (mod n) splits a product of two primes half the time. A 110
+/// bit semiprime takes a few hundredths of a second.
+///
+/// Small factors and perfect powers are found directly, and n below 2^40
+/// goes to trial division. Which factor comes back isn't specified, but it's
+/// the same every run.
+///
+/// Panics if n is 2^124 or more.
+///
+/// ```
+/// use prime_tools::quadratic_sieve;
+///
+/// let (p, q) = (1_000_000_007u128, 998_244_353u128);
+/// let d = quadratic_sieve(p * q).unwrap();
+/// assert!(d == p || d == q);
+/// assert_eq!(quadratic_sieve(1_000_000_007), None);
+/// assert_eq!(quadratic_sieve(91), Some(7));
+/// ```
+pub fn quadratic_sieve(n: u128) -> Option<u128> {
+    assert!(n < 1 << 124, "quadratic_sieve takes n below 2^124");
+    if let Some(answer) = split_directly(n) {
+        return answer;
+    }
+
+    let _span = debug_span!("quadratic_sieve", bits = 128 - n.leading_zeros());
+    Siqs::new(n).and_then(|siqs| siqs.run())
+}
+
+/// The answer for the n that don't need relations at all: primes, n < 4,
+/// small factors, perfect powers, and n below 2^40 (by trial division).
+pub(crate) fn split_directly(n: u128) -> Option<Option<u128>> {
+    if n < 4 || is_probable_prime(n) {
+        return Some(None);
+    }
+    if let Some(&p) = SMALL_PRIMES.iter().find(|&&p| n % p as u128 == 0) {
+        return Some(Some(p as u128));
+    }
+    if let Some(root) = perfect_power_root(n) {
+        return Some(Some(root));
+    }
+    if n < 1 << 40 {
+        return Some(least_prime_factor(n as u64).map(u128::from));
+    }
+    None
+}
+
+fn is_probable_prime(x: u128) -> bool {
+    miller_rabin_with_bases(x, &[2]) && is_strong_lucas_probable_prime(x)
+}
+
+/// r if n = r^k for some k >= 2. The unit group mod a prime power is cyclic,
+/// so x^2 ≡ y^2 (mod p^k) only ever has x ≡ ±y and relations can't split
+/// these. With no factor below 2^16, k is at most 7.
+fn perfect_power_root(n: u128) -> Option<u128> {
+    let root = isqrt_u128(n);
+    if root * root == n {
+        return Some(root);
+    }
+    // Cube roots and up are below 2^42, where the float guess is within one.
+    (3..=7).find_map(|k| {
+        let guess = (n as f64).powf(1.0 / k as f64).round() as u128;
+        (guess.saturating_sub(1)..=guess + 1).find(|&r| r.checked_pow(k) == Some(n))
+    })
+}
+
+/// One factor base prime with a square root of kn mod p.
+pub(crate) struct FactorPrime {
+    pub(crate) p: u32,
+    pub(crate) root: u32,
+    pub(crate) log: u8,
+}
+
+/// The first `size` primes p (2 always among them) for which kn is a
+/// square mod p, or None if one of the primes tried divides n.
+pub(crate) fn factor_base(n: u128, kn: u128, size: usize) -> Option<Vec<FactorPrime>> {
+    let mut base = vec![FactorPrime { p: 2, root: 1, log: 1 }];
+    for &p in &SMALL_PRIMES[1..] {
+        if base.len() == size {
+            break;
+        }
+        let p = p as u32;
+        if n % p as u128 == 0 {
+            return None;
+        }
+        if let Some(root) = sqrt_mod_prime(kn % p as u128, p as u128) {
+            let log = (p as f64).log2().round() as u8;
+            base.push(FactorPrime { p, root: root as u32, log });
+        }
+    }
+    Some(base)
+}
+
+/// The bound below which a cofactor left after the factor base counts as a large prime.
+pub(crate) fn large_prime_bound(base: &[FactorPrime]) -> u128 {
+    let largest = base.last().unwrap().p as u128;
+    (largest * LARGE_PRIME_MULTIPLIER).min(largest * largest)
+}
+
+/// y^2 ≡ (the product of the factor base columns listed) * large^2 (mod n).
+/// Column 0 is -1 and column j + 1 is the jth factor base prime; a column
+/// appears once per power.
+pub(crate) struct Relation {
+    pub(crate) y: u128,
+    pub(crate) columns: Vec<usize>,
+    pub(crate) large: u128,
+}
+
+/// Full relations so far, and the partial ones (large > 1) still waiting
+/// for a partner with the same large prime.
+pub(crate) struct Relations {
+    n: u128,
+    full: Vec<Relation>,
+    partials: HashMap<u128, Relation>,
+}
+
+impl Relations {
+    pub(crate) fn new(n: u128) -> Relations {
+        Relations { n, full: Vec::new(), partials: HashMap::new() }
+    }
+
+    /// Number of full relations, counting combined pairs of partials.
+    pub(crate) fn len(&self) -> usize {
+        self.full.len()
+    }
+
+    pub(crate) fn add(&mut self, relation: Relation) {
+        if relation.large == 1 {
+            self.full.push(relation);
+        } else if let Some(other) = self.partials.remove(&relation.large) {
+            // Two values with the same large prime multiply to one with it squared.
+            let mut columns = other.columns;
+            columns.extend(relation.columns);
+            let y = mul_mod(other.y, relation.y, self.n);
+            self.full.push(Relation { y, columns, large: relation.large });
+        } else {
+            self.partials.insert(relation.large, relation);
+        }
+    }
+
+    /// Finds the dependencies among the full relations and tries the square
+    /// root step on each until one splits n.
+    pub(crate) fn factor(&self, base: &[FactorPrime]) -> Option<u128> {
+        debug!(relations = self.full.len(), partials = self.partials.len(), "relations collected");
+        let mut matrix = BitMatrix::new(base.len() + 1);
+        for relation in &self.full {
+            matrix.push_row(relation.columns.iter().copied());
+        }
+        matrix.dependencies().into_iter().find_map(|dependency| self.split(base, &dependency))
+    }
+
+    fn split(&self, base: &[FactorPrime], dependency: &[usize]) -> Option<u128> {
+        let n = self.n;
+        let mut powers = vec![0u32; base.len() + 1];
+        let (mut x, mut y) = (1u128, 1u128);
+        for &r in dependency {
+            let relation = &self.full[r];
+            for &column in &relation.columns {
+                powers[column] += 1;
+            }
+            x = mul_mod(x, relation.large % n, n);
+            y = mul_mod(y, relation.y, n);
+        }
+        for (i, prime) in base.iter().enumerate() {
+            debug_assert!(powers[i + 1] % 2 == 0);
+            let root = pow_mod(prime.p as u128, (powers[i + 1] / 2) as u128, n);
+            x = mul_mod(x, root, n);
+        }
+        let d = gcd_u128(sub_mod(x, y, n), n);
+        if d > 1 && d < n {
+            Some(d)
+        } else {
+            None
+        }
+    }
+}
+
+/// The current a and the B_l that its 2^(s-1) values of b are the signed sums of.
+struct Family {
+    a: u128,
+    a_primes: Vec<usize>,
+    terms: Vec<u128>,
+}
+
+struct Siqs {
+    n: u128,
+    kn: u128,
+    base: Vec<FactorPrime>,
+    half_width: u32,
+    threshold: u8,
+    large_bound: u128,
+}
+
+impl Siqs {
+    /// The sieve for n, or None if a factor base prime divides n (which
+    /// the small prime check makes impossible).
+    fn new(n: u128) -> Option<Siqs> {
+        let kn = n * multiplier(n) as u128;
+        let bits = 128 - kn.leading_zeros();
+        let (size, half_width) = parameters(bits);
+
+        let base = factor_base(n, kn, size)?;
+        let large_bound = large_prime_bound(&base);
+        // |g(x)| peaks around M √(kn / 2); allow one large prime and the unsieved small ones.
+        let peak = (half_width as f64).log2() + (kn as f64).log2() / 2.0 - 0.5;
+        let threshold = (peak - (large_bound as f64).log2() - 4.0).max(1.0) as u8;
+        debug!(multiplier = kn / n, base = base.len(), largest = base.last().unwrap().p, half_width, threshold, "quadratic sieve parameters");
+        Some(Siqs { n, kn, base, half_width, threshold, large_bound })
+    }
+
+    fn run(&self) -> Option<u128> {
+        let wanted = self.base.len() + 1 + EXTRA_RELATIONS;
+        let mut relations = Relations::new(self.n);
+        let mut families = FamilySource::new(self);
+        let mut sieve = vec![0u8; 2 * self.half_width as usize];
+        while relations.len() < wanted {
+            let family = families.next(self)?;
+            self.sieve_family(&family, &mut sieve, &mut |relation| relations.add(relation));
+        }
+        relations.factor(&self.base)
+    }
+
+    /// Sieves every polynomial with the family's a, handing each relation to `found`.
+    fn sieve_family(&self, family: &Family, sieve: &mut [u8], found: &mut impl FnMut(Relation)) {
+        let m = self.half_width as u64;
+        let s = family.terms.len();
+        let mut skip = vec![false; self.base.len()];
+        for &i in &family.a_primes {
+            skip[i] = true;
+        }
+
+        // Roots of a x + b ≡ ±root (mod p), as offsets from -M, and the
+        // steps 2 B_l / a (mod p) they move by when B_l's sign flips.
+        let mut b: i128 = family.terms.iter().map(|&t| t as i128).sum();
+        let mut roots = vec![(0u32, 0u32); self.base.len()];
+        let mut steps = vec![vec![0u32; self.base.len()]; s];
+        for (i, prime) in self.base.iter().enumerate() {
+            if skip[i] || prime.p == 2 {
+                continue;
+            }
+            let p = prime.p as u64;
+            let a_inverse = inverse_mod(family.a % p as u128, p as u128).unwrap() as u64;
+            let b_mod = signed_mod(b, p as u128) as u64;
+            let root = prime.root as u64;
+            let first = (a_inverse * ((root + p - b_mod) % p) + m) % p;
+            let second = (a_inverse * ((2 * p - root - b_mod) % p) + m) % p;
+            roots[i] = (first as u32, second as u32);
+            for (l, &term) in family.terms.iter().enumerate() {
+                steps[l][i] = (2 * (term % p as u128) as u64 * a_inverse % p) as u32;
+            }
+        }
+
+        let mut signs = vec![1i8; s];
+        for index in 0..1u64 << (s - 1) {
+            if index > 0 {
+                // Gray code order: one B_l changes sign per step.
+                let l = index.trailing_zeros() as usize + 1;
+                let sign = signs[l];
+                b -= 2 * sign as i128 * family.terms[l] as i128;
+                signs[l] = -sign;
+                for (i, prime) in self.base.iter().enumerate() {
+                    if skip[i] || prime.p == 2 {
+                        continue;
+                    }
+                    let (p, step) = (prime.p, steps[l][i]);
+                    let shift = |r: u32| if sign > 0 { (r + step) % p } else { (r + p - step) % p };
+                    roots[i] = (shift(roots[i].0), shift(roots[i].1));
+                }
+            }
+            let c = (b * b - self.kn as i128) / family.a as i128;
+
+            sieve.iter_mut().for_each(|v| *v = 0);
+            for (i, prime) in self.base.iter().enumerate() {
+                if skip[i] || prime.p < SIEVE_FROM {
+                    continue;
+                }
+                let (first, second) = roots[i];
+                let p = prime.p as usize;
+                let mut at = first as usize;
+                while at < sieve.len() {
+                    sieve[at] = sieve[at].wrapping_add(prime.log);
+                    at += p;
+                }
+                if second != first {
+                    let mut at = second as usize;
+                    while at < sieve.len() {
+                        sieve[at] = sieve[at].wrapping_add(prime.log);
+                        at += p;
+                    }
+                }
+            }
+
+            for (offset, &v) in sieve.iter().enumerate() {
+                if v >= self.threshold {
+                    if let Some(relation) = self.relation(family, b, c, offset as u32, &roots, &skip) {
+                        found(relation);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Trial divides g(x) = a x^2 + 2 b x + c, with x = offset - M, by the
+    /// factor base, and gives the relation if what's left is 1 or a large prime.
+    fn relation(&self, family: &Family, b: i128, c: i128, offset: u32, roots: &[(u32, u32)], skip: &[bool]) -> Option<Relation> {
+        let x = offset as i128 - self.half_width as i128;
+        let a = family.a as i128;
+        let g = (a * x + 2 * b) * x + c;
+        if g == 0 {
+            return None;
+        }
+
+        // a g(x) = (a x + b)^2 - kn, so the primes of a come along once each.
+        let mut columns: Vec<usize> = family.a_primes.iter().map(|&i| i + 1).collect();
+        if g < 0 {
+            columns.push(0);
+        }
+        let mut rest = g.unsigned_abs();
+        for (i, prime) in self.base.iter().enumerate() {
+            let p = prime.p;
+            let divides = if skip[i] || p == 2 {
+                rest % p as u128 == 0
+            } else {
+                let r = offset % p;
+                r == roots[i].0 || r == roots[i].1
+            };
+            if divides {
+                while rest % p as u128 == 0 {
+                    rest /= p as u128;
+                    columns.push(i + 1);
+                }
+            }
+        }
+        if rest >= self.large_bound {
+            return None;
+        }
+        let y = signed_mod(a * x + b, self.n);
+        Some(Relation { y, columns, large: rest })
+    }
+}
+
+/// Picks the a values, each a product of s factor base primes near
+/// (√(2 kn) / M)^(1/s) so that g(x) stays small across the interval.
+struct FamilySource {
+    target: f64,
+    s: usize,
+    pool: Vec<usize>,
+    seen: HashSet<u128>,
+    state: u64,
+}
+
+impl FamilySource {
+    fn new(siqs: &Siqs) -> FamilySource {
+        let target = (2.0 * siqs.kn as f64).sqrt() / siqs.half_width as f64;
+        let largest = siqs.base.last().unwrap().p as f64;
+        let ideal = (largest / 2.0).min(2000.0);
+        let s = (target.ln() / ideal.ln()).ceil().max(1.0) as usize;
+        let q = target.powf(1.0 / s as f64);
+
+        // Factor base primes closest to q (in ratio), leaving out 2 and the
+        // ones dividing k, whose roots are 0.
+        let mut eligible: Vec<usize> = (1..siqs.base.len()).filter(|&i| siqs.base[i].root != 0).collect();
+        eligible.sort_by(|&i, &j| {
+            let distance = |i: usize| (siqs.base[i].p as f64 / q).ln().abs();
+            distance(i).partial_cmp(&distance(j)).unwrap()
+        });
+        let near = eligible.iter().take_while(|&&i| (siqs.base[i].p as f64 / q).ln().abs() < 1.0).count();
+        eligible.truncate(near.max(4 * s + 8));
+        FamilySource { target, s, pool: eligible, seen: HashSet::new(), state: 0x9e37_79b9_7f4a_7c15 }
+    }
+
+    fn random(&mut self, below: usize) -> usize {
+        // xorshift64*, seeded the same every time so results are repeatable.
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32) as usize % below
+    }
+
+    /// The next unused a with its B_l, or None once no new a turns up.
+    fn next(&mut self, siqs: &Siqs) -> Option<Family> {
+        if self.pool.len() < self.s + 1 {
+            return None;
+        }
+        for _ in 0..1_000 {
+            let random = if self.s == 1 { 1 } else { self.s - 1 };
+            let mut chosen: Vec<usize> = Vec::with_capacity(self.s);
+            while chosen.len() < random {
+                let pick = self.random(self.pool.len());
+                let i = self.pool[pick];
+                if !chosen.contains(&i) {
+                    chosen.push(i);
+                }
+            }
+            if self.s > 1 {
+                // The last prime brings the product closest to the target.
+                let so_far: f64 = chosen.iter().map(|&i| (siqs.base[i].p as f64).ln()).sum();
+                let want = self.target.ln() - so_far;
+                let last = self.pool.iter().copied().filter(|i| !chosen.contains(i)).min_by(|&i, &j| {
+                    let distance = |i: usize| ((siqs.base[i].p as f64).ln() - want).abs();
+                    distance(i).partial_cmp(&distance(j)).unwrap()
+                })?;
+                chosen.push(last);
+            }
+            let a: u128 = chosen.iter().map(|&i| siqs.base[i].p as u128).product();
+            if !self.seen.insert(a) {
+                continue;
+            }
+
+            // B_l ≡ root (mod q_l) and ≡ 0 mod the other primes of a, so
+            // every signed sum b has b^2 ≡ kn (mod a).
+            let terms = chosen
+                .iter()
+                .map(|&i| {
+                    let q = siqs.base[i].p as u128;
+                    let cofactor = a / q;
+                    let mut gamma = siqs.base[i].root as u128 * inverse_mod(cofactor % q, q).unwrap() % q;
+                    if gamma > q / 2 {
+                        gamma = q - gamma;
+                    }
+                    cofactor * gamma
+                })
+                .collect();
+            return Some(Family { a, a_primes: chosen, terms });
+        }
+        None
+    }
+}
+
+/// Factor base size and sieve half-width M for kn of the given bit length.
+fn parameters(bits: u32) -> (usize, u32) {
+    let size = (100 + (bits as i64 - 64) * 350 / 64).max(50) as usize;
+    let half_width = match bits {
+        0..=69 => 1 << 13,
+        70..=89 => 1 << 14,
+        _ => 1 << 15,
+    };
+    (size, half_width)
+}
+
+/// The odd squarefree multipliers k worth trying.
+pub(crate) const MULTIPLIERS: [u64; 20] = [1, 3, 5, 7, 11, 13, 15, 17, 19, 21, 23, 29, 31, 33, 35, 37, 39, 41, 43, 47];
+
+/// The Knuth–Schroeppel multiplier: the k from `MULTIPLIERS` for which
+/// kn has the most small primes (weighted by how often they divide g) as
+/// quadratic residues, less the cost of kn being bigger.
+pub(crate) fn multiplier(n: u128) -> u64 {
+    let ln2 = std::f64::consts::LN_2;
+    let score = |k: u64| {
+        let kn = n * k as u128;
+        let mut score = -0.5 * (k as f64).ln();
+        score += match kn % 8 {
+            1 => 2.0 * ln2,
+            5 => ln2,
+            _ => 0.5 * ln2,
+        };
+        for &p in &SMALL_PRIMES[1..60] {
+            let (p, ln_p) = (p as u64, (p as f64).ln());
+            if k % p == 0 {
+                score += ln_p / p as f64;
+            } else if jacobi(kn % p as u128, p as u128) == 1 {
+                score += 2.0 * ln_p / (p - 1) as f64;
+            }
+        }
+        score
+    };
+    MULTIPLIERS
+        .iter()
+        .copied()
+        .filter(|&k| n.checked_mul(k as u128).is_some_and(|kn| kn < 1 << 125))
+        .max_by(|&j, &k| score(j).partial_cmp(&score(k)).unwrap())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_semiprimes_of_several_sizes() {
+        let cases = [
+            (1_000_003u128, 1_000_033u128),
+            (4_294_967_291, 4_294_967_279),
+            (1_099_511_627_689, 1_099_511_627_791),
+            (35_184_372_088_891, 35_184_372_088_777),
+        ];
+        for &(p, q) in &cases {
+            let d = quadratic_sieve(p * q).unwrap();
+            assert!(d == p || d == q, "{} * {} gave {}", p, q, d);
+        }
+    }
+
+    #[test]
+    fn small_and_degenerate_inputs() {
+        assert_eq!(quadratic_sieve(0), None);
+        assert_eq!(quadratic_sieve(2), None);
+        assert_eq!(quadratic_sieve(4), Some(2));
+        assert_eq!(quadratic_sieve(65_537 * 65_537), Some(65_537));
+        assert_eq!(quadratic_sieve(4_294_967_291u128.pow(3)), Some(4_294_967_291));
+        let p = 35_184_372_088_891u128;
+        assert_eq!(quadratic_sieve(p * p), Some(p));
+        assert_eq!(quadratic_sieve(618_970_019_642_690_137_449_562_111), None);
+        let n = 65_537u128 * 65_539 * 65_543;
+        assert_eq!(n % quadratic_sieve(n).unwrap(), 0);
+    }
+}
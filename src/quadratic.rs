@@ -0,0 +1,319 @@
+//! Quadratic residue symbols, discriminants of quadratic fields, and
+//! writing numbers as sums of two squares.
+
+use crate::modular::{jacobi, signed_mod, sqrt_mod_prime};
+use crate::primality::U64_BASES;
+use crate::miller_rabin_with_bases;
+
+/// The Kronecker symbol (a / n): 0, 1 or -1, defined for every a and n.
+///
+/// Extends the Jacobi symbol to even and negative n with (a / 2) = 0 for even
+/// a, 1 for a ≡ ±1 (mod 8) and -1 for a ≡ ±3 (mod 8); (a / -1) = -1 for
+/// negative a and 1 otherwise; and (a / 0) = 1 for a = ±1 and 0 otherwise.
+/// For an odd prime n it's the Legendre symbol.
+///
+/// ```
+/// use prime_tools::kronecker_symbol;
+///
+/// assert_eq!(kronecker_symbol(2, 7), 1); // 3² ≡ 2 (mod 7)
+/// assert_eq!(kronecker_symbol(3, 7), -1);
+/// assert_eq!(kronecker_symbol(5, 8), -1);
+/// assert_eq!(kronecker_symbol(-1, -5), -1);
+/// ```
+pub fn kronecker_symbol(a: i64, n: i64) -> i8 {
+    if n == 0 {
+        return if a.unsigned_abs() == 1 { 1 } else { 0 };
+    }
+    let mut result = if n < 0 && a < 0 { -1 } else { 1 };
+
+    let n = n.unsigned_abs();
+    let twos = n.trailing_zeros();
+    if twos > 0 {
+        if a % 2 == 0 {
+            return 0;
+        }
+        if twos % 2 == 1 && matches!(a.rem_euclid(8), 3 | 5) {
+            result = -result;
+        }
+    }
+
+    let odd = (n >> twos) as u128;
+    result * jacobi(signed_mod(a as i128, odd), odd)
+}
+
+/// True if d is the discriminant of a quadratic field (or d = 1): either
+/// d ≡ 1 (mod 4) and squarefree, or d = 4m with m ≡ 2, 3 (mod 4) and m squarefree.
+///
+/// ```
+/// use prime_tools::is_fundamental_discriminant;
+///
+/// assert!(is_fundamental_discriminant(-4));
+/// assert!(is_fundamental_discriminant(12));
+/// assert!(!is_fundamental_discriminant(9));
+/// assert!(!is_fundamental_discriminant(-16));
+/// ```
+pub fn is_fundamental_discriminant(d: i64) -> bool {
+    match d.rem_euclid(4) {
+        1 => is_squarefree(d),
+        0 => matches!((d / 4).rem_euclid(4), 2 | 3) && is_squarefree(d / 4),
+        _ => false,
+    }
+}
+
+/// The discriminant of the quadratic field Q(√d): the squarefree part f of d
+/// (keeping its sign) if f ≡ 1 (mod 4), otherwise 4f. A perfect square d gets 1.
+///
+/// Panics for d = 0, or if 4f doesn't fit in an i64.
+///
+/// ```
+/// use prime_tools::fundamental_discriminant;
+///
+/// assert_eq!(fundamental_discriminant(-1), -4);
+/// assert_eq!(fundamental_discriminant(45), 5);
+/// assert_eq!(fundamental_discriminant(-12), -3);
+/// assert_eq!(fundamental_discriminant(18), 8);
+/// ```
+pub fn fundamental_discriminant(d: i64) -> i64 {
+    assert!(d != 0, "0 has no fundamental discriminant");
+    let (_, squarefree) = crate::square_decomposition(d.unsigned_abs());
+    let f = if d < 0 { -(squarefree as i64) } else { squarefree as i64 };
+    if f.rem_euclid(4) == 1 {
+        f
+    } else {
+        f.checked_mul(4).expect("fundamental_discriminant overflowed i64")
+    }
+}
+
+/// The a <= b with a² + b² = p for a prime p = 2 or p ≡ 1 (mod 4), or None
+/// if p is anything else (Fermat: those are the primes it works for).
+///
+/// `cornacchia(1, p)`, put in order.
+///
+/// ```
+/// assert_eq!(prime_tools::two_square_decomposition(13), Some((2, 3)));
+/// assert_eq!(prime_tools::two_square_decomposition(1_000_000_009), Some((3_747, 31_400)));
+/// assert_eq!(prime_tools::two_square_decomposition(7), None);
+/// assert_eq!(prime_tools::two_square_decomposition(25), None);
+/// ```
+pub fn two_square_decomposition(p: u64) -> Option<(u64, u64)> {
+    let (x, y) = cornacchia(1, p)?;
+    Some((x.min(y), x.max(y)))
+}
+
+/// The x, y >= 0 with x² + d·y² = p for a prime p, or None if there are
+/// none (or p isn't prime). For 1 < d < p the solution is unique.
+///
+/// Cornacchia's algorithm: take a square root r of -d mod p, run Euclid's
+/// algorithm on p and r until the remainder x drops below √p, and check that
+/// (p - x²) / d is a square y². No square root of -d means no solution.
+///
+/// ```
+/// use prime_tools::cornacchia;
+///
+/// assert_eq!(cornacchia(2, 11), Some((3, 1))); // 9 + 2
+/// assert_eq!(cornacchia(3, 31), Some((2, 3))); // 4 + 27
+/// assert_eq!(cornacchia(5, 29), Some((3, 2))); // 9 + 20
+/// assert_eq!(cornacchia(5, 7), None); // -5 is a square mod 7, but x² + 5y² never hits 7: 7 and 7 - 5 aren't squares
+/// assert_eq!(cornacchia(2, 13), None); // -2 isn't a square mod 13
+/// ```
+pub fn cornacchia(d: u64, p: u64) -> Option<(u64, u64)> {
+    if d == 0 || !miller_rabin_with_bases(p as u128, &U64_BASES) {
+        return None;
+    }
+    if d >= p {
+        return if d == p { Some((0, 1)) } else { None };
+    }
+    if p == 2 {
+        // d = 1 here.
+        return Some((1, 1));
+    }
+    let root = sqrt_mod_prime((p - d) as u128, p as u128)? as u64;
+    // Start from the root above p / 2.
+    let limit = crate::isqrt(p);
+    let (mut r0, mut r1) = (p, p - root);
+    while r1 > limit {
+        (r0, r1) = (r1, r0 % r1);
+    }
+    let rest = p - r1 * r1;
+    let y = crate::isqrt(rest / d);
+    if rest % d == 0 && y * y == rest / d {
+        Some((r1, y))
+    } else {
+        None
+    }
+}
+
+/// Every way of writing n as a² + b² with 0 <= a <= b, by increasing a.
+///
+/// Built from n's factorization in the Gaussian integers: each prime
+/// p ≡ 1 (mod 4) splits as (a + bi)(a - bi) from `two_square_decomposition`,
+/// and the choices of how many of each conjugate to take give the
+/// representations. A prime ≡ 3 (mod 4) to an odd power means there are none.
+///
+/// ```
+/// assert_eq!(prime_tools::sum_of_two_squares_representations(25), vec![(0, 5), (3, 4)]);
+/// assert_eq!(prime_tools::sum_of_two_squares_representations(325), vec![(1, 18), (6, 17), (10, 15)]);
+/// assert_eq!(prime_tools::sum_of_two_squares_representations(21), vec![]);
+/// ```
+pub fn sum_of_two_squares_representations(n: u64) -> Vec<(u64, u64)> {
+    if n == 0 {
+        return vec![(0, 0)];
+    }
+    // Gaussian integers x + yi, as (x, y); every partial product stays within √n.
+    let multiply = |(a, b): (i128, i128), (c, d): (i128, i128)| (a * c - b * d, a * d + b * c);
+    let mut products = vec![(1i128, 0i128)];
+    for (p, count) in crate::factorize(n) {
+        let choices: Vec<(i128, i128)> = if p == 2 {
+            vec![(0..count).fold((1, 0), |z, _| multiply(z, (1, 1)))]
+        } else if p % 4 == 3 {
+            if count % 2 == 1 {
+                return Vec::new();
+            }
+            vec![((p as i128).pow(count / 2), 0)]
+        } else {
+            let (a, b) = two_square_decomposition(p).unwrap();
+            let (pi, conjugate) = ((a as i128, b as i128), (a as i128, -(b as i128)));
+            (0..=count)
+                .map(|k| {
+                    let z = (0..k).fold((1, 0), |z, _| multiply(z, pi));
+                    (k..count).fold(z, |z, _| multiply(z, conjugate))
+                })
+                .collect()
+        };
+        products = products.iter().flat_map(|&z| choices.iter().map(move |&w| multiply(z, w))).collect();
+    }
+    let mut representations: Vec<(u64, u64)> = products
+        .into_iter()
+        .map(|(x, y)| {
+            let (x, y) = (x.unsigned_abs() as u64, y.unsigned_abs() as u64);
+            (x.min(y), x.max(y))
+        })
+        .collect();
+    representations.sort_unstable();
+    representations.dedup();
+    representations
+}
+
+fn is_squarefree(x: i64) -> bool {
+    let x = x.unsigned_abs();
+    x != 0 && crate::square_decomposition(x).0 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (a / n) straight from the definition, one prime factor of n at a time.
+    fn kronecker_by_factoring(a: i64, n: i64) -> i8 {
+        if n == 0 {
+            return if a.abs() == 1 { 1 } else { 0 };
+        }
+        let mut result = if n < 0 && a < 0 { -1 } else { 1 };
+        for (p, count) in crate::factorize(n.unsigned_abs()) {
+            let symbol = if p == 2 {
+                match a.rem_euclid(8) {
+                    1 | 7 => 1,
+                    3 | 5 => -1,
+                    _ => 0,
+                }
+            } else {
+                // Euler's criterion.
+                let p = p as i64;
+                let mut power = 1;
+                for _ in 0..(p - 1) / 2 {
+                    power = power * a.rem_euclid(p) % p;
+                }
+                if power == p - 1 { -1 } else { power as i8 }
+            };
+            result *= symbol.pow(count);
+        }
+        result
+    }
+
+    #[test]
+    fn kronecker_matches_the_definition() {
+        for a in -60..60 {
+            for n in -60..60 {
+                assert_eq!(kronecker_symbol(a, n), kronecker_by_factoring(a, n), "({} / {})", a, n);
+            }
+        }
+        assert_eq!(kronecker_symbol(i64::MIN, i64::MIN), 0);
+        assert_eq!(kronecker_symbol(i64::MAX, i64::MIN), kronecker_symbol(i64::MAX, 2));
+        assert_eq!(kronecker_symbol(-i64::MAX, i64::MIN), -kronecker_symbol(-i64::MAX, 2));
+        assert_eq!(kronecker_symbol(-1, i64::MAX), -1);
+    }
+
+    #[test]
+    fn fundamental_discriminants() {
+        let expected = [-24, -23, -20, -19, -15, -11, -8, -7, -4, -3, 1, 5, 8, 12, 13, 17, 21, 24, 28, 29];
+        let found: Vec<i64> = (-25..30).filter(|&d| is_fundamental_discriminant(d)).collect();
+        assert_eq!(found, expected);
+        for d in (-1_000..1_000).filter(|&d| d != 0) {
+            let fundamental = fundamental_discriminant(d);
+            assert!(is_fundamental_discriminant(fundamental), "{}", d);
+            // d is the fundamental discriminant times a square.
+            let ratio = d * 4 / fundamental;
+            assert_eq!(d * 4 % fundamental, 0, "{}", d);
+            assert_eq!(crate::square_decomposition(ratio as u64).1, 1, "{}", d);
+        }
+    }
+
+    #[test]
+    fn cornacchia_matches_brute_force() {
+        for p in crate::get_primes_between(0, 3_000) {
+            for d in 1..60 {
+                let brute = (0..=crate::isqrt(p / d)).find_map(|y| {
+                    let x = crate::isqrt(p - d * y * y);
+                    if x * x + d * y * y == p {
+                        Some((x, y))
+                    } else {
+                        None
+                    }
+                });
+                let found = cornacchia(d, p);
+                assert_eq!(found.is_some(), brute.is_some(), "d = {}, p = {}", d, p);
+                if let Some((x, y)) = found {
+                    assert_eq!(x * x + d * y * y, p, "d = {}, p = {}", d, p);
+                    if d > 1 && d < p {
+                        assert_eq!(found, brute, "d = {}, p = {}", d, p);
+                    }
+                }
+            }
+        }
+        assert_eq!(cornacchia(0, 7), None);
+        assert_eq!(cornacchia(2, 15), None);
+        assert_eq!(cornacchia(999_999_999, 1_000_000_007), None);
+        let p = 18_446_744_073_709_551_557;
+        assert_eq!(cornacchia(13, p), Some((488_357_737, 1_183_484_126)));
+        assert_eq!(cornacchia(7, p), None);
+    }
+
+    #[test]
+    fn sums_of_two_squares_match_brute_force() {
+        for n in 0..5_000u64 {
+            let expected: Vec<(u64, u64)> = (0..=crate::isqrt(n / 2))
+                .filter_map(|a| {
+                    let b = crate::isqrt(n - a * a);
+                    if a * a + b * b == n {
+                        Some((a, b))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            assert_eq!(sum_of_two_squares_representations(n), expected, "{}", n);
+            if crate::is_u64_prime(n) {
+                assert_eq!(two_square_decomposition(n), expected.first().copied(), "{}", n);
+            }
+        }
+        let p = 18_446_744_073_709_551_557; // the largest prime below 2^64, ≡ 1 (mod 4)
+        let (a, b) = two_square_decomposition(p).unwrap();
+        assert_eq!(a as u128 * a as u128 + b as u128 * b as u128, p as u128);
+        assert_eq!(two_square_decomposition(u64::MAX), None);
+        // 5^6 * 13^2 * 17 * 2^3: (6 + 1) * (2 + 1) * 2 = 42 choices of conjugates, paired up by swapping all of them.
+        let n = 5u64.pow(6) * 13 * 13 * 17 * 8;
+        let representations = sum_of_two_squares_representations(n);
+        assert!(representations.iter().all(|&(a, b)| a * a + b * b == n && a <= b));
+        assert_eq!(representations.len(), 21);
+    }
+}
@@ -0,0 +1,303 @@
+//! Random primes of a given bit length, and the randomized algorithms.
+//!
+//! Only built with the `rand_core` feature. Bring any `RngCore`; for keys that
+//! should be a cryptographically secure one such as `rand::rngs::OsRng`. No
+//! function here keeps or creates an RNG of its own, so a seeded one makes
+//! every result and every running time reproducible.
+
+use rand_core::RngCore;
+
+use crate::factor::brent_rho;
+use crate::miller_rabin_with_bases;
+use crate::modular::pow_mod;
+use crate::primality::U64_BASES;
+use crate::small_primes::SMALL_PRIMES;
+
+/// How much work `gen_prime_with_config` puts into each random candidate.
+///
+/// ```
+/// use prime_tools::PrimeGenConfig;
+///
+/// // Fewer rounds for a faster, less certain answer on big candidates.
+/// let config = PrimeGenConfig { miller_rabin_rounds: 8, ..PrimeGenConfig::default() };
+/// assert_eq!(config.trial_division_limit, 2_000);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrimeGenConfig {
+    /// Candidates with a prime factor below this are thrown out before any
+    /// Miller–Rabin round. Cheap, and most composites go here. Capped at 2^16.
+    pub trial_division_limit: u64,
+    /// Miller–Rabin rounds with random bases for candidates past 2^64; a
+    /// composite gets through all of them with probability at most 4^-rounds.
+    /// Below 2^64 a fixed set of bases makes the test exact, so this is unused.
+    pub miller_rabin_rounds: u32,
+}
+
+impl Default for PrimeGenConfig {
+    /// Trial division by the primes below 2000 and 40 rounds (at most 2^-80).
+    fn default() -> PrimeGenConfig {
+        PrimeGenConfig { trial_division_limit: 2_000, miller_rabin_rounds: 40 }
+    }
+}
+
+/// A random prime with exactly `bits` bits, so 2^(bits-1) <= p < 2^bits.
+///
+/// Draws random odd candidates until one passes, with
+/// `PrimeGenConfig::default()`. Panics unless 2 <= bits <= 128.
+///
+/// ```
+/// use rand_core::SeedableRng;
+///
+/// let mut rng = rand_pcg::Pcg64::seed_from_u64(1);
+/// let p = prime_tools::gen_prime(64, &mut rng);
+/// assert!(p >> 63 == 1 && p >> 64 == 0);
+/// assert!(prime_tools::miller_rabin_with_bases(p, &[2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37]));
+/// ```
+pub fn gen_prime<R: RngCore + ?Sized>(bits: u32, rng: &mut R) -> u128 {
+    gen_prime_with_config(bits, rng, &PrimeGenConfig::default())
+}
+
+/// `gen_prime` with explicit trial division depth and Miller–Rabin rounds.
+pub fn gen_prime_with_config<R: RngCore + ?Sized>(bits: u32, rng: &mut R, config: &PrimeGenConfig) -> u128 {
+    assert!((2..=128).contains(&bits), "gen_prime makes primes of 2 to 128 bits");
+    let _span = debug_span!("gen_prime", bits);
+    loop {
+        let candidate = random_bits(bits, rng) | if bits > 2 { 1 } else { 0 };
+        if is_probable_prime(candidate, rng, config) {
+            return candidate;
+        }
+    }
+}
+
+/// A random prime p ≡ 3 (mod 4) with exactly `bits` bits, the kind whose
+/// products are Blum integers (see `is_blum_integer`). Panics unless 2 <= bits <= 128.
+///
+/// ```
+/// use rand_core::SeedableRng;
+///
+/// let mut rng = rand_pcg::Pcg64::seed_from_u64(2);
+/// let p = prime_tools::gen_blum_prime(40, &mut rng);
+/// assert_eq!(p % 4, 3);
+/// assert!(prime_tools::is_u64_prime(p as u64));
+/// ```
+pub fn gen_blum_prime<R: RngCore + ?Sized>(bits: u32, rng: &mut R) -> u128 {
+    assert!((2..=128).contains(&bits), "gen_blum_prime makes primes of 2 to 128 bits");
+    let config = PrimeGenConfig::default();
+    loop {
+        let candidate = random_bits(bits, rng) | 3;
+        if is_probable_prime(candidate, rng, &config) {
+            return candidate;
+        }
+    }
+}
+
+/// A random strong prime with exactly `bits` bits, by Gordon's algorithm: p - 1
+/// has a large prime factor r, p + 1 has a large prime factor s, and r - 1
+/// has a large prime factor t, with r and s about bits / 2 - 8 bits each.
+///
+/// Such primes resist Pollard's p - 1 and Williams' p + 1 factoring, which
+/// used to be a requirement for RSA moduli. Panics unless 32 <= bits <= 128.
+///
+/// ```
+/// use rand_core::SeedableRng;
+///
+/// let mut rng = rand_pcg::Pcg64::seed_from_u64(3);
+/// let p = prime_tools::gen_strong_prime(64, &mut rng);
+/// assert_eq!(p >> 63, 1);
+/// ```
+pub fn gen_strong_prime<R: RngCore + ?Sized>(bits: u32, rng: &mut R) -> u128 {
+    gordon(bits, rng).0
+}
+
+/// Gordon's algorithm, returning (p, r, s, t).
+fn gordon<R: RngCore + ?Sized>(bits: u32, rng: &mut R) -> (u128, u128, u128, u128) {
+    assert!((32..=128).contains(&bits), "gen_strong_prime makes primes of 32 to 128 bits");
+    let _span = debug_span!("gen_strong_prime", bits);
+    let config = PrimeGenConfig::default();
+    let factor_bits = bits / 2 - 8;
+    loop {
+        let s = gen_prime(factor_bits, rng);
+        let t = gen_prime(factor_bits / 2, rng);
+
+        // The first prime r = 2it + 1 with factor_bits bits.
+        let low = 1u128 << (factor_bits - 1);
+        let r = (low / (2 * t) + 1..)
+            .map(|i| 2 * i * t + 1)
+            .take_while(|&r| r >> factor_bits == 0)
+            .find(|&r| is_probable_prime(r, rng, &config));
+        let r = match r {
+            Some(r) if r != s => r,
+            _ => continue,
+        };
+
+        // p0 ≡ 1 (mod r) and p0 ≡ -1 (mod s), so every p0 + 2jrs is too.
+        let p0 = 2 * pow_mod(s, r - 2, r) * s - 1;
+        let step = 2 * r * s;
+        let low = 1u128 << (bits - 1);
+        let first = p0 + (low - p0.min(low)).div_ceil(step) * step;
+        let mut p = Some(first);
+        while let Some(candidate) = p.filter(|&p| bits == 128 || p >> bits == 0) {
+            if is_probable_prime(candidate, rng, &config) {
+                return (candidate, r, s, t);
+            }
+            p = candidate.checked_add(step);
+        }
+    }
+}
+
+/// A uniformly random number with exactly `bits` bits (the top one set).
+pub(crate) fn random_bits<R: RngCore + ?Sized>(bits: u32, rng: &mut R) -> u128 {
+    let raw = (rng.next_u64() as u128) << 64 | rng.next_u64() as u128;
+    let top = 1u128 << (bits - 1);
+    raw & (top - 1) | top
+}
+
+/// Trial division up to the configured limit, then Miller–Rabin: exact
+/// below 2^64, `miller_rabin_rounds` random bases above.
+pub(crate) fn is_probable_prime<R: RngCore + ?Sized>(x: u128, rng: &mut R, config: &PrimeGenConfig) -> bool {
+    for &p in SMALL_PRIMES.iter().take_while(|&&p| (p as u64) < config.trial_division_limit) {
+        let p = p as u128;
+        if x % p == 0 {
+            return x == p;
+        }
+    }
+    if x < 1 << 64 {
+        return miller_rabin_with_bases(x, &U64_BASES);
+    }
+    miller_rabin_random(x, config.miller_rabin_rounds, rng)
+}
+
+/// True if x is a strong probable prime to `rounds` bases drawn from `rng`.
+///
+/// A composite gets through each round with probability at most 1/4. Bases
+/// are uniform in [2, x - 2] for x below 2^64 and uniform 64-bit numbers
+/// past that; for an exact answer below 2^64 use `is_u64_prime` or
+/// `miller_rabin_with_bases` instead.
+///
+/// ```
+/// use rand_core::SeedableRng;
+/// use prime_tools::miller_rabin_random;
+///
+/// let mut rng = rand_pcg::Pcg64::seed_from_u64(4);
+/// assert!(miller_rabin_random((1 << 127) - 1, 20, &mut rng));
+/// assert!(!miller_rabin_random(3_215_031_751, 20, &mut rng));
+/// ```
+pub fn miller_rabin_random<R: RngCore + ?Sized>(x: u128, rounds: u32, rng: &mut R) -> bool {
+    if x < 4 {
+        return x == 2 || x == 3;
+    }
+    (0..rounds).all(|_| {
+        let base = if x <= u64::MAX as u128 { 2 + rng.next_u64() % (x as u64 - 3) } else { rng.next_u64().max(2) };
+        miller_rabin_with_bases(x, &[base])
+    })
+}
+
+/// A nontrivial factor of n by Pollard's rho, or None if n is prime or below 4.
+///
+/// This is Brent's variant, iterating y -> y^2 + c with the start and c drawn
+/// from `rng` and a fresh pair whenever a walk fails. A factor p turns up
+/// after about √p steps, so any u64 takes well under a second, but which
+/// factor comes back depends on the draws.
+///
+/// ```
+/// use rand_core::SeedableRng;
+/// use prime_tools::pollard_rho;
+///
+/// let mut rng = rand_pcg::Pcg64::seed_from_u64(5);
+/// let n = 4_294_967_291 * 4_294_967_279;
+/// let p = pollard_rho(n, &mut rng).unwrap();
+/// assert!(p == 4_294_967_291 || p == 4_294_967_279);
+/// assert_eq!(pollard_rho(1_000_000_007, &mut rng), None);
+/// ```
+pub fn pollard_rho<R: RngCore + ?Sized>(n: u64, rng: &mut R) -> Option<u64> {
+    if n < 4 || miller_rabin_with_bases(n as u128, &U64_BASES) {
+        return None;
+    }
+    if n % 2 == 0 {
+        return Some(2);
+    }
+
+    loop {
+        let c = 1 + rng.next_u64() % (n - 1);
+        if let Some(d) = brent_rho(n, c, rng.next_u64() % n) {
+            return Some(d);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::SeedableRng;
+
+    fn is_prime(x: u128) -> bool {
+        miller_rabin_with_bases(x, &U64_BASES) && crate::is_strong_lucas_probable_prime(x)
+    }
+
+    #[test]
+    fn primes_have_the_right_size() {
+        let mut rng = rand_pcg::Pcg64::seed_from_u64(615);
+        let configs = [
+            PrimeGenConfig::default(),
+            PrimeGenConfig { trial_division_limit: 0, miller_rabin_rounds: 2 },
+            PrimeGenConfig { trial_division_limit: 1 << 20, miller_rabin_rounds: 1 },
+        ];
+        for config in &configs {
+            for bits in 2..=128 {
+                let p = gen_prime_with_config(bits, &mut rng, config);
+                assert_eq!(128 - p.leading_zeros(), bits, "{}", p);
+                assert!(is_prime(p), "{} with {:?}", p, config);
+            }
+        }
+        let small: Vec<u128> = (0..50).map(|_| gen_prime(3, &mut rng)).collect();
+        assert!(small.contains(&5) && small.contains(&7));
+        assert!((0..50).any(|_| gen_prime(2, &mut rng) == 2));
+    }
+
+    #[test]
+    fn blum_and_strong_primes() {
+        let mut rng = rand_pcg::Pcg64::seed_from_u64(658);
+        for bits in 2..=128 {
+            let p = gen_blum_prime(bits, &mut rng);
+            assert_eq!(128 - p.leading_zeros(), bits, "{}", p);
+            assert_eq!(p % 4, 3, "{}", p);
+            assert!(is_prime(p), "{}", p);
+        }
+        for bits in 32..=128 {
+            let (p, r, s, t) = gordon(bits, &mut rng);
+            assert_eq!(128 - p.leading_zeros(), bits, "{}", p);
+            for &q in &[p, r, s, t] {
+                assert!(is_prime(q), "{} for {}", q, p);
+            }
+            assert_eq!((p - 1) % r, 0);
+            assert_eq!((p + 1) % s, 0);
+            assert_eq!((r - 1) % t, 0);
+            assert!(r >> (bits / 2 - 9) == 1 && s >> (bits / 2 - 9) == 1, "{} {} {}", p, r, s);
+        }
+    }
+
+    #[test]
+    fn seeded_rho_and_miller_rabin_are_reproducible() {
+        let semiprimes = [15u64, 4_294_967_291 * 4_294_967_279, 65_521 * 65_521, 1_000_003 * 999_983 * 3];
+        for &n in &semiprimes {
+            let p = pollard_rho(n, &mut rand_pcg::Pcg64::seed_from_u64(664)).unwrap();
+            assert!(p > 1 && p < n && n % p == 0, "{} of {}", p, n);
+            assert_eq!(pollard_rho(n, &mut rand_pcg::Pcg64::seed_from_u64(664)), Some(p));
+        }
+        let mut rng = rand_pcg::Pcg64::seed_from_u64(664);
+        for n in 0..2_000 {
+            let factor = pollard_rho(n, &mut rng);
+            assert_eq!(factor.is_none(), n < 4 || crate::is_u64_prime(n), "{}", n);
+            assert!(factor.is_none_or(|p| p > 1 && p < n && n % p == 0), "{}", n);
+            assert_eq!(miller_rabin_random(n as u128, 10, &mut rng), crate::is_u64_prime(n), "{}", n);
+        }
+        // 3825123056546413051 fools the first nine prime bases; random ones see through it.
+        assert!(!miller_rabin_random(3_825_123_056_546_413_051, 10, &mut rng));
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_bits_panics() {
+        gen_prime(129, &mut rand_pcg::Pcg64::seed_from_u64(0));
+    }
+}
@@ -0,0 +1,217 @@
+//! Slow but obviously correct versions of the crate's algorithms.
+//!
+//! Everything here is plain trial division or a textbook sieve, so it's easy to
+//! trust and easy to compare against. `verify_range` and friends run the fast
+//! paths and these side by side and report where they disagree.
+//!
+//! Only built with the `reference` feature.
+
+use crate::{get_prime_factors_with_counts, get_primes_between, get_primes_between_with_options, get_primes_less_than_x, is_u32_prime, is_u64_prime};
+use crate::{SieveOptions, Wheel};
+
+/// Checks x for primality by dividing by every number up to √x.
+///
+/// ```
+/// assert!(prime_tools::reference::is_prime(97));
+/// assert!(!prime_tools::reference::is_prime(91));
+/// ```
+pub fn is_prime(x: u64) -> bool {
+    if x < 2 {
+        return false;
+    }
+    let mut d = 2u64;
+    while d.saturating_mul(d) <= x {
+        if x % d == 0 {
+            return false;
+        }
+        d += 1;
+    }
+    true
+}
+
+/// Primes between min (inclusive) and max (exclusive), one `is_prime` call at a time.
+pub fn primes_between(min: u64, max: u64) -> Vec<u64> {
+    (min..max).filter(|&x| is_prime(x)).collect()
+}
+
+/// Primes less than x from a textbook sieve of Eratosthenes over a `Vec<bool>`.
+///
+/// ```
+/// assert_eq!(prime_tools::reference::primes_less_than(12), vec![2, 3, 5, 7, 11]);
+/// ```
+pub fn primes_less_than(x: u32) -> Vec<u32> {
+    let n = x as usize;
+    let mut is_prime = vec![true; n];
+    let mut primes = Vec::new();
+    for i in 2..n {
+        if is_prime[i] {
+            primes.push(i as u32);
+            let mut j = i * i;
+            while j < n {
+                is_prime[j] = false;
+                j += i;
+            }
+        }
+    }
+    primes
+}
+
+/// Prime factors of x with their counts, smallest prime first, by trial division.
+///
+/// ```
+/// assert_eq!(prime_tools::reference::prime_factors(360), vec![(2, 3), (3, 2), (5, 1)]);
+/// ```
+pub fn prime_factors(x: u64) -> Vec<(u64, u32)> {
+    let mut factors = Vec::new();
+    let mut rest = x;
+    let mut d = 2u64;
+    while rest > 1 && d.saturating_mul(d) <= rest {
+        let mut count = 0;
+        while rest % d == 0 {
+            rest /= d;
+            count += 1;
+        }
+        if count > 0 {
+            factors.push((d, count));
+        }
+        d += 1;
+    }
+    if rest > 1 {
+        factors.push((rest, 1));
+    }
+    factors
+}
+
+/// A value the fast and reference implementations disagree on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The public function that got it wrong.
+    pub function: &'static str,
+    /// The input (or, for the sieves, the number) it got wrong.
+    pub value: u64,
+}
+
+/// Cross-checks the sieves and `is_u32_prime`/`is_u64_prime` against trial division over `[min, max)`.
+///
+/// The sieves are run with the default options and with every wheel at a small
+/// segment size, so segment and wheel boundaries get exercised too.
+/// Returns every disagreement; an empty Vec means all is well.
+///
+/// Trial division is slow, so keep the range to a few hundred thousand numbers
+/// when max is large.
+///
+/// ```
+/// assert_eq!(prime_tools::reference::verify_range(1_000_000, 1_001_000), vec![]);
+/// ```
+pub fn verify_range(min: u64, max: u64) -> Vec<Mismatch> {
+    let expected = primes_between(min, max);
+    let mut mismatches = Vec::new();
+
+    compare_primes("get_primes_between", &expected, &get_primes_between(min, max), &mut mismatches);
+    for &wheel in &[Wheel::Off, Wheel::Mod2, Wheel::Mod6, Wheel::Mod30, Wheel::Mod210] {
+        let options = SieveOptions { segment_bytes: 64, wheel, threads: 2, ..SieveOptions::default() };
+        let found = get_primes_between_with_options(min, max, &options);
+        compare_primes("get_primes_between_with_options", &expected, &found, &mut mismatches);
+    }
+
+    if max <= u32::MAX as u64 + 1 {
+        let found: Vec<u64> = get_primes_less_than_x(max as u32)
+            .into_iter()
+            .map(|p| p as u64)
+            .filter(|&p| p >= min)
+            .collect();
+        compare_primes("get_primes_less_than_x", &expected, &found, &mut mismatches);
+    }
+
+    let mut primes = expected.iter().peekable();
+    for x in min..max {
+        let prime = primes.next_if_eq(&&x).is_some();
+        if is_u64_prime(x) != prime {
+            mismatches.push(Mismatch { function: "is_u64_prime", value: x });
+        }
+        if x <= u32::MAX as u64 && is_u32_prime(x as u32) != prime {
+            mismatches.push(Mismatch { function: "is_u32_prime", value: x });
+        }
+    }
+
+    mismatches
+}
+
+/// Cross-checks `get_prime_factors_with_counts` against trial division for every x in `[min, max)`.
+///
+/// ```
+/// assert_eq!(prime_tools::reference::verify_factors_range(0, 10_000), vec![]);
+/// ```
+pub fn verify_factors_range(min: u32, max: u32) -> Vec<Mismatch> {
+    let primes = get_primes_less_than_x(crate::isqrt(max as u64) as u32 + 1);
+    let mut mismatches = Vec::new();
+    for x in min..max {
+        let mut found: Vec<(u64, u32)> = get_prime_factors_with_counts(x, &primes)
+            .into_iter()
+            .map(|(p, count)| (p as u64, count))
+            .collect();
+        found.sort_unstable();
+        if found != prime_factors(x as u64) {
+            mismatches.push(Mismatch { function: "get_prime_factors_with_counts", value: x as u64 });
+        }
+    }
+    mismatches
+}
+
+/// Records every number that shows up in exactly one of two sorted prime lists.
+fn compare_primes(function: &'static str, expected: &[u64], found: &[u64], mismatches: &mut Vec<Mismatch>) {
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < found.len() {
+        match (expected.get(i), found.get(j)) {
+            (Some(a), Some(b)) if a == b => {
+                i += 1;
+                j += 1;
+            }
+            (Some(&a), Some(&b)) if a < b => {
+                mismatches.push(Mismatch { function, value: a });
+                i += 1;
+            }
+            (Some(_), Some(&b)) => {
+                mismatches.push(Mismatch { function, value: b });
+                j += 1;
+            }
+            (Some(&a), None) => {
+                mismatches.push(Mismatch { function, value: a });
+                i += 1;
+            }
+            (None, Some(&b)) => {
+                mismatches.push(Mismatch { function, value: b });
+                j += 1;
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_sieve_matches_trial_division() {
+        let from_sieve: Vec<u64> = primes_less_than(2_000).into_iter().map(|p| p as u64).collect();
+        assert_eq!(from_sieve, primes_between(0, 2_000));
+    }
+
+    #[test]
+    fn verify_small_ranges() {
+        assert_eq!(verify_range(0, 5_000), vec![]);
+        assert_eq!(verify_range(4_294_960_000, 4_294_970_000), vec![]);
+        assert_eq!(verify_factors_range(0, 5_000), vec![]);
+    }
+
+    #[test]
+    fn compare_primes_reports_both_sides() {
+        let mut mismatches = Vec::new();
+        compare_primes("f", &[2, 3, 5, 7], &[2, 5, 7, 9], &mut mismatches);
+        assert_eq!(
+            mismatches,
+            vec![Mismatch { function: "f", value: 3 }, Mismatch { function: "f", value: 9 }]
+        );
+    }
+}
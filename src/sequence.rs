@@ -0,0 +1,644 @@
+//! Functions of the sequence of primes itself.
+
+use std::ops::ControlFlow;
+
+use crate::primality::U64_BASES;
+use crate::sieve::{for_each_prime_between, for_each_set_bit, primes_up_to, sieve_segments_until};
+use crate::{miller_rabin_with_bases, SieveOptions};
+
+/// π(2^64): there are this many primes that fit in a u64.
+const PRIMES_BELOW_2_64: u64 = 425_656_284_035_217_743;
+
+/// The n-th prime, counting from nth_prime(1) = 2.
+///
+/// Sieves segment by segment up to a known upper bound for p_n, so this
+/// takes about as long as `get_primes_between(0, p_n)` without holding
+/// on to the primes.
+///
+/// Panics for n = 0 and for n past the last prime below 2^64; see `checked_nth_prime`.
+///
+/// ```
+/// assert_eq!(prime_tools::nth_prime(1), 2);
+/// assert_eq!(prime_tools::nth_prime(10_001), 104_743);
+/// ```
+pub fn nth_prime(n: u64) -> u64 {
+    checked_nth_prime(n).expect("nth_prime needs 1 <= n <= π(2^64)")
+}
+
+/// The n-th prime, or None if n = 0 or the n-th prime doesn't fit in a u64.
+///
+/// ```
+/// assert_eq!(prime_tools::checked_nth_prime(4), Some(7));
+/// assert_eq!(prime_tools::checked_nth_prime(0), None);
+/// assert_eq!(prime_tools::checked_nth_prime(u64::MAX), None);
+/// ```
+pub fn checked_nth_prime(n: u64) -> Option<u64> {
+    if n == 0 || n > PRIMES_BELOW_2_64 {
+        return None;
+    }
+
+    let max = nth_prime_upper_bound(n).saturating_add(1);
+    let _span = debug_span!("nth_prime", n, bound = max);
+    let options = SieveOptions::default();
+    let sieving_primes = primes_up_to(crate::isqrt(max - 1) as u32, &options);
+
+    let mut remaining = n;
+    let mut nth = None;
+    sieve_segments_until(2, max, &sieving_primes, &options, |low, words| {
+        let count: u64 = words.iter().map(|w| w.count_ones() as u64).sum();
+        if count < remaining {
+            remaining -= count;
+            return ControlFlow::Continue(());
+        }
+        for_each_set_bit(words, |offset| {
+            if nth.is_none() {
+                remaining -= 1;
+                if remaining == 0 {
+                    nth = Some(low + offset);
+                }
+            }
+        });
+        ControlFlow::Break(())
+    });
+    nth
+}
+
+/// The primes at each of `indices`, in the order asked, counting from 1 like `nth_prime`.
+///
+/// Sorts the queries and answers them all in one sieve up to the largest
+/// one, so a batch costs about as much as its hardest member alone.
+/// Repeats and any order are fine.
+///
+/// Panics for an index of 0 or past the last prime below 2^64, like `nth_prime`.
+///
+/// ```
+/// assert_eq!(prime_tools::nth_primes(vec![10_001, 1, 4, 4]), vec![104_743, 2, 7, 7]);
+/// assert_eq!(prime_tools::nth_primes(1..=5), vec![2, 3, 5, 7, 11]);
+/// ```
+pub fn nth_primes<I: IntoIterator<Item = u64>>(indices: I) -> Vec<u64> {
+    let mut queries: Vec<(u64, usize)> = indices.into_iter().enumerate().map(|(i, n)| (n, i)).collect();
+    queries.sort_unstable();
+    let mut answers = vec![0; queries.len()];
+    let largest = match queries.last() {
+        Some(&(n, _)) => n,
+        None => return answers,
+    };
+    assert!(queries[0].0 > 0 && largest <= PRIMES_BELOW_2_64, "nth_primes needs 1 <= n <= π(2^64)");
+
+    let max = nth_prime_upper_bound(largest).saturating_add(1);
+    let options = SieveOptions::default();
+    let sieving_primes = primes_up_to(crate::isqrt(max - 1) as u32, &options);
+
+    // Primes passed so far, and the next query to answer.
+    let (mut seen, mut next) = (0, 0);
+    sieve_segments_until(2, max, &sieving_primes, &options, |low, words| {
+        let count: u64 = words.iter().map(|w| w.count_ones() as u64).sum();
+        if queries[next].0 > seen + count {
+            seen += count;
+            return ControlFlow::Continue(());
+        }
+        for_each_set_bit(words, |offset| {
+            seen += 1;
+            while next < queries.len() && queries[next].0 == seen {
+                answers[queries[next].1] = low + offset;
+                next += 1;
+            }
+        });
+        if next == queries.len() {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    answers
+}
+
+/// p_n < n (ln n + ln ln n) for n >= 6 (Rosser's theorem), padded a little
+/// for float error.
+fn nth_prime_upper_bound(n: u64) -> u64 {
+    if n < 6 {
+        return 13;
+    }
+    let n = n as f64;
+    // `as` saturates, which is what we want near 2^64.
+    (n * (n.ln() + n.ln().ln()) * 1.001 + 10.0) as u64
+}
+
+/// The product of all primes less than or equal to n, n#.
+///
+/// Panics if the product doesn't fit in a u64 (n >= 53); see `checked_primorial`.
+///
+/// ```
+/// assert_eq!(prime_tools::primorial(10), 2 * 3 * 5 * 7);
+/// assert_eq!(prime_tools::primorial(1), 1);
+/// ```
+pub fn primorial(n: u64) -> u64 {
+    checked_primorial(n).expect("primorial overflowed u64")
+}
+
+/// n#, or None if it doesn't fit in a u64.
+///
+/// ```
+/// assert_eq!(prime_tools::checked_primorial(52), Some(614_889_782_588_491_410));
+/// assert_eq!(prime_tools::checked_primorial(53), None);
+/// ```
+pub fn checked_primorial(n: u64) -> Option<u64> {
+    // 53# is already past 2^64, so there's no need to look at bigger primes.
+    crate::get_primes_less_than_x(n.min(53) as u32 + 1)
+        .iter()
+        .try_fold(1u64, |product, &p| product.checked_mul(p as u64))
+}
+
+/// n#, or u64::MAX if it doesn't fit in a u64.
+///
+/// ```
+/// assert_eq!(prime_tools::saturating_primorial(1_000), u64::MAX);
+/// ```
+pub fn saturating_primorial(n: u64) -> u64 {
+    checked_primorial(n).unwrap_or(u64::MAX)
+}
+
+/// lcm(1, 2, ..., n): the product of the largest power of each prime that is at most n.
+///
+/// Returns a u128 since this outgrows a u64 at n = 47. Panics if it doesn't
+/// fit in a u128 either (n >= 89); see `checked_lcm_up_to`.
+///
+/// ```
+/// assert_eq!(prime_tools::lcm_up_to(10), 2520);
+/// assert_eq!(prime_tools::lcm_up_to(0), 1);
+/// ```
+pub fn lcm_up_to(n: u64) -> u128 {
+    checked_lcm_up_to(n).expect("lcm_up_to overflowed a u128")
+}
+
+/// lcm(1, 2, ..., n), or None if it doesn't fit in a u128.
+///
+/// ```
+/// assert_eq!(prime_tools::checked_lcm_up_to(88), Some(8_076_030_954_443_701_744_994_070_304_101_969_600));
+/// assert_eq!(prime_tools::checked_lcm_up_to(89), None);
+/// ```
+pub fn checked_lcm_up_to(n: u64) -> Option<u128> {
+    // lcm(1..=89) is already past 2^128, so bigger n only need to overflow.
+    let n = n.min(89);
+    crate::get_primes_less_than_x(n as u32 + 1).iter().try_fold(1u128, |product, &p| {
+        let p = p as u64;
+        let mut power = p;
+        while power <= n / p {
+            power *= p;
+        }
+        product.checked_mul(power as u128)
+    })
+}
+
+/// How many primes below x fall in each residue class: element r counts the
+/// primes p < x with p % modulus == r.
+///
+/// The primes are tallied straight out of the segmented sieve, so this needs
+/// a counter per class rather than memory for every prime. Handy for watching
+/// Chebyshev's bias, the lead primes ≡ 3 (mod 4) hold over primes ≡ 1 (mod 4).
+/// Panics for modulus = 0.
+///
+/// ```
+/// assert_eq!(prime_tools::prime_pi_mod(1_000, 4), vec![0, 80, 1, 87]);
+/// assert_eq!(prime_tools::prime_pi_mod(1_000, 1), vec![168]);
+/// ```
+pub fn prime_pi_mod(x: u64, modulus: u64) -> Vec<u64> {
+    assert!(modulus > 0, "prime_pi_mod needs a nonzero modulus");
+    let _span = debug_span!("prime_pi_mod", x, modulus);
+    let mut counts = vec![0u64; modulus as usize];
+    for_each_prime_between(2, x, &SieveOptions::default(), |p| counts[(p % modulus) as usize] += 1);
+    counts
+}
+
+/// The prime race modulo `modulus`: (r, count) for each residue r coprime
+/// to the modulus, in increasing r, counting the primes p < x with
+/// p % modulus == r.
+///
+/// The other classes hold at most one prime each, so they sit the race out.
+/// Tallied straight out of the segmented sieve like `prime_pi_mod`.
+/// Panics for modulus = 0.
+///
+/// ```
+/// assert_eq!(prime_tools::race_counts(1_000, 4), vec![(1, 80), (3, 87)]);
+/// assert_eq!(prime_tools::race_counts(1_000, 3), vec![(1, 80), (2, 87)]);
+/// ```
+pub fn race_counts(x: u64, modulus: u64) -> Vec<(u64, u64)> {
+    assert!(modulus > 0, "race_counts needs a nonzero modulus");
+    prime_pi_mod(x, modulus)
+        .into_iter()
+        .enumerate()
+        .map(|(r, count)| (r as u64, count))
+        .filter(|&(r, _)| crate::gcd(r, modulus) == 1)
+        .collect()
+}
+
+/// The first prime at which Chebyshev's bias modulo `modulus` gives way: the
+/// quadratic residue classes, having trailed the non-residues, pull ahead.
+///
+/// The two sides are compared per class, so mod 8 the primes ≡ 1 race the
+/// average of the classes 3, 5 and 7. Small primes can put the residues
+/// ahead before the bias sets in, so only a lead taken back from the
+/// non-residues counts. Sieves upward until it happens, and it can take a
+/// while: mod 4 it's 26 861, but mod 3 it's 608 981 813 029.
+///
+/// Panics for modulus < 3, where every class is a residue, and if the lead
+/// never changes below 2^64.
+///
+/// ```
+/// assert_eq!(prime_tools::first_sign_change(4), 26_861);
+/// assert_eq!(prime_tools::first_sign_change(13), 2_083);
+/// ```
+pub fn first_sign_change(modulus: u64) -> u64 {
+    assert!(modulus >= 3, "first_sign_change needs a modulus of at least 3");
+    let _span = debug_span!("first_sign_change", modulus);
+    let m = modulus as usize;
+    let mut residue = vec![false; m];
+    for k in (1..m).filter(|&k| crate::gcd(k as u64, modulus) == 1) {
+        residue[(k as u128 * k as u128 % modulus as u128) as usize] = true;
+    }
+    let residue_classes = residue.iter().filter(|&&r| r).count() as u128;
+    let other_classes = (1..m).filter(|&r| crate::gcd(r as u64, modulus) == 1).count() as u128 - residue_classes;
+
+    let (mut residues, mut others) = (0u128, 0u128);
+    let mut trailed = false;
+    // Tallies p and says whether the residues just took the lead back.
+    let mut tally = |p: u64| {
+        let r = (p % modulus) as usize;
+        if residue[r] {
+            residues += 1;
+        } else if crate::gcd(r as u64, modulus) == 1 {
+            others += 1;
+        }
+        trailed |= residues * other_classes < others * residue_classes;
+        trailed && residues * other_classes > others * residue_classes
+    };
+    if tally(2) {
+        return 2;
+    }
+    let mut found = None;
+    for_each_consecutive_pair(u64::MAX, |_, p| {
+        if tally(p) {
+            found = Some(p);
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    });
+    found.expect("the race never changed leader below 2^64")
+}
+
+/// The first pair of consecutive primes (p, q) with q - p >= g.
+///
+/// Sieves upward from 2 until it finds one, so the cost grows with how far
+/// out the gap first shows up: a gap of 500 means sieving to about 3·10^11.
+/// Panics if there's no such gap below 2^64 (no gap past 1550 is known there).
+///
+/// ```
+/// assert_eq!(prime_tools::first_gap_of_at_least(1), (2, 3));
+/// assert_eq!(prime_tools::first_gap_of_at_least(10), (113, 127));
+/// assert_eq!(prime_tools::first_gap_of_at_least(100), (370_261, 370_373));
+/// ```
+pub fn first_gap_of_at_least(g: u64) -> (u64, u64) {
+    let _span = debug_span!("first_gap_of_at_least", g);
+    let mut found = None;
+    for_each_consecutive_pair(u64::MAX, |p, q| {
+        if q - p >= g {
+            found = Some((p, q));
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    });
+    found.expect("no prime gap that big below 2^64")
+}
+
+/// The record (maximal) prime gaps with both primes below n, as (gap, start)
+/// pairs: each gap is bigger than every gap before it.
+///
+/// ```
+/// assert_eq!(
+///     prime_tools::maximal_gaps_up_to(1_000),
+///     vec![(1, 2), (2, 3), (4, 7), (6, 23), (8, 89), (14, 113), (18, 523), (20, 887)]
+/// );
+/// ```
+pub fn maximal_gaps_up_to(n: u64) -> Vec<(u64, u64)> {
+    let _span = debug_span!("maximal_gaps_up_to", n);
+    let mut records: Vec<(u64, u64)> = Vec::new();
+    for_each_consecutive_pair(n, |p, q| {
+        if records.last().is_none_or(|&(gap, _)| q - p > gap) {
+            records.push((q - p, p));
+        }
+        ControlFlow::Continue(())
+    });
+    records
+}
+
+/// Calls `f` with every pair of consecutive primes below max, in order, until it breaks.
+///
+/// Sieves in windows that double in size, so only the sieving primes for the
+/// current window are ever held in memory.
+fn for_each_consecutive_pair<F>(max: u64, mut f: F)
+where
+    F: FnMut(u64, u64) -> ControlFlow<()>,
+{
+    let options = SieveOptions::default();
+    let mut previous = 2;
+    let mut low = 3;
+    while low < max {
+        let high = low.saturating_add(low.max(1 << 20)).min(max);
+        let sieving_primes = primes_up_to(crate::isqrt(high - 1) as u32, &options);
+        let mut flow = ControlFlow::Continue(());
+        sieve_segments_until(low, high, &sieving_primes, &options, |segment, words| {
+            for_each_set_bit(words, |offset| {
+                if flow.is_continue() {
+                    let p = segment + offset;
+                    flow = f(previous, p);
+                    previous = p;
+                }
+            });
+            flow
+        });
+        if flow.is_break() {
+            return;
+        }
+        low = high;
+    }
+}
+
+/// A pattern of primes p + offsets[i]. Every instance past `small` starts at
+/// one of `residues` mod `modulus`, so only those starts get tested.
+struct Constellation {
+    offsets: &'static [u64],
+    small: &'static [u64],
+    modulus: u64,
+    residues: &'static [u64],
+}
+
+/// Twin primes (p, p + 2): past 5 they start at 11, 17 or 29 mod 30.
+const TWINS: Constellation = Constellation { offsets: &[0, 2], small: &[3, 5], modulus: 30, residues: &[11, 17, 29] };
+
+/// Prime quadruplets (p, p + 2, p + 6, p + 8): past 5 they start at 11, 101 or 191 mod 210.
+const QUADRUPLETS: Constellation = Constellation { offsets: &[0, 2, 6, 8], small: &[5], modulus: 210, residues: &[11, 101, 191] };
+
+impl Constellation {
+    /// True if every p + offset is a prime that fits in a u64.
+    fn fits(&self, p: u64) -> bool {
+        self.offsets.iter().all(|&offset| p.checked_add(offset).is_some_and(is_prime))
+    }
+
+    /// The first start p > x.
+    fn next_after(&self, x: u64) -> Option<u64> {
+        if let Some(&p) = self.small.iter().find(|&&p| p > x) {
+            return Some(p);
+        }
+        let mut base = x - x % self.modulus;
+        loop {
+            for &r in self.residues {
+                let p = base.checked_add(r)?;
+                if p > x && self.fits(p) {
+                    return Some(p);
+                }
+            }
+            base = base.checked_add(self.modulus)?;
+        }
+    }
+
+    /// The last start p < x.
+    fn previous_before(&self, x: u64) -> Option<u64> {
+        let mut base = x - x % self.modulus;
+        loop {
+            // Starts past u64::MAX are skipped; they're above x anyway.
+            for p in self.residues.iter().rev().filter_map(|&r| base.checked_add(r)) {
+                if p < x && p > *self.small.last().unwrap() && self.fits(p) {
+                    return Some(p);
+                }
+            }
+            if base == 0 {
+                break;
+            }
+            base -= self.modulus;
+        }
+        self.small.iter().rev().find(|&&p| p < x).cloned()
+    }
+}
+
+/// Exact for every u64: the prime table below 2^16, Miller–Rabin past it.
+fn is_prime(x: u64) -> bool {
+    if x < 1 << 16 {
+        crate::small_primes::is_small_prime(x as u16)
+    } else {
+        miller_rabin_with_bases(x as u128, &U64_BASES)
+    }
+}
+
+/// The first twin primes (p, p + 2) with p > x, or None if they'd pass u64::MAX.
+///
+/// Only the starts that can hold twins (11, 17 and 29 mod 30) are tested,
+/// with deterministic Miller–Rabin, so the search is quick anywhere in the
+/// u64 range.
+///
+/// ```
+/// assert_eq!(prime_tools::next_twin_prime_after(0), Some((3, 5)));
+/// assert_eq!(prime_tools::next_twin_prime_after(100), Some((101, 103)));
+/// ```
+pub fn next_twin_prime_after(x: u64) -> Option<(u64, u64)> {
+    TWINS.next_after(x).map(|p| (p, p + 2))
+}
+
+/// The last twin primes (p, p + 2) with p < x, or None if there are none.
+///
+/// ```
+/// assert_eq!(prime_tools::previous_twin_prime_before(100), Some((71, 73)));
+/// assert_eq!(prime_tools::previous_twin_prime_before(3), None);
+/// ```
+pub fn previous_twin_prime_before(x: u64) -> Option<(u64, u64)> {
+    TWINS.previous_before(x).map(|p| (p, p + 2))
+}
+
+/// The first prime quadruplet (p, p + 2, p + 6, p + 8) with p > x, or None if
+/// it'd pass u64::MAX.
+///
+/// Only starts at 11, 101 and 191 mod 210 are tested, with deterministic
+/// Miller–Rabin.
+///
+/// ```
+/// assert_eq!(prime_tools::next_prime_quadruplet_after(11), Some([101, 103, 107, 109]));
+/// ```
+pub fn next_prime_quadruplet_after(x: u64) -> Option<[u64; 4]> {
+    QUADRUPLETS.next_after(x).map(|p| [p, p + 2, p + 6, p + 8])
+}
+
+/// The last prime quadruplet (p, p + 2, p + 6, p + 8) with p < x, or None if there are none.
+///
+/// ```
+/// assert_eq!(prime_tools::previous_prime_quadruplet_before(101), Some([11, 13, 17, 19]));
+/// assert_eq!(prime_tools::previous_prime_quadruplet_before(5), None);
+/// ```
+pub fn previous_prime_quadruplet_before(x: u64) -> Option<[u64; 4]> {
+    QUADRUPLETS.previous_before(x).map(|p| [p, p + 2, p + 6, p + 8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nth_prime_matches_the_sieve() {
+        let primes = crate::get_primes_less_than_x(200_000);
+        for (i, &p) in primes.iter().enumerate().step_by(997) {
+            assert_eq!(nth_prime(i as u64 + 1), p as u64);
+        }
+        for n in 1..20 {
+            assert_eq!(nth_prime(n), primes[n as usize - 1] as u64);
+        }
+        assert_eq!(nth_prime(1_000_000), 15_485_863);
+    }
+
+    #[test]
+    fn nth_prime_edges() {
+        assert_eq!(checked_nth_prime(0), None);
+        assert_eq!(checked_nth_prime(PRIMES_BELOW_2_64 + 1), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn nth_prime_zero_panics() {
+        nth_prime(0);
+    }
+
+    #[test]
+    fn nth_primes_in_one_pass() {
+        let primes = crate::get_primes_less_than_x(200_000);
+        let mut indices: Vec<u64> = (1..=primes.len() as u64).rev().step_by(13).collect();
+        indices.extend(&[1, 2, 2, 1_000, 17_984, 1]);
+        let expected: Vec<u64> = indices.iter().map(|&n| primes[n as usize - 1] as u64).collect();
+        assert_eq!(nth_primes(indices.iter().copied()), expected);
+        assert_eq!(nth_primes(vec![]), vec![]);
+        assert_eq!(nth_primes(vec![1_000_000, 1]), vec![15_485_863, 2]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn nth_primes_zero_panics() {
+        nth_primes(vec![5, 0]);
+    }
+
+    #[test]
+    fn prime_pi_mod_adds_up() {
+        let primes = crate::get_primes_between(0, 300_000);
+        for &(x, modulus) in &[(0, 5), (3, 2), (300_000, 1), (300_000, 4), (123_457, 30), (300_000, 1_000)] {
+            let mut expected = vec![0; modulus as usize];
+            for &p in primes.iter().filter(|&&p| p < x) {
+                expected[(p % modulus) as usize] += 1;
+            }
+            assert_eq!(prime_pi_mod(x, modulus), expected, "{} mod {}", x, modulus);
+        }
+    }
+
+    #[test]
+    fn races_match_the_prime_list() {
+        let primes = crate::get_primes_between(0, 1_000_000);
+        assert_eq!(race_counts(1_000_000, 1), vec![(0, primes.len() as u64)]);
+        assert_eq!(race_counts(1_000_000, 10), vec![(1, 19_617), (3, 19_665), (7, 19_621), (9, 19_593)]);
+        // Replays each race over the list, for the moduli whose lead changes within it.
+        let mut changed = 0;
+        for modulus in 3..=40 {
+            let is_unit = |r: u64| crate::gcd(r, modulus) == 1;
+            let residues: Vec<u64> = (1..modulus).filter(|&k| is_unit(k)).map(|k| k * k % modulus).collect();
+            let residue_classes = (0..modulus).filter(|r| residues.contains(r)).count() as u64;
+            let other_classes = (0..modulus).filter(|&r| is_unit(r)).count() as u64 - residue_classes;
+            let (mut ahead, mut behind, mut trailed) = (0, 0, false);
+            let expected = primes.iter().copied().find(|&p| {
+                if residues.contains(&(p % modulus)) {
+                    ahead += 1;
+                } else if is_unit(p % modulus) {
+                    behind += 1;
+                }
+                trailed |= ahead * other_classes < behind * residue_classes;
+                trailed && ahead * other_classes > behind * residue_classes
+            });
+            if let Some(p) = expected {
+                assert_eq!(first_sign_change(modulus), p, "mod {}", modulus);
+                changed += 1;
+            }
+        }
+        assert!(changed >= 15);
+    }
+
+    #[test]
+    fn gaps_match_the_prime_list() {
+        let primes = crate::get_primes_between(0, 3_000_000);
+        let mut records: Vec<(u64, u64)> = Vec::new();
+        for pair in primes.windows(2) {
+            let gap = pair[1] - pair[0];
+            if records.last().is_none_or(|&(record, _)| gap > record) {
+                records.push((gap, pair[0]));
+            }
+        }
+        assert_eq!(maximal_gaps_up_to(3_000_000), records);
+        assert_eq!(maximal_gaps_up_to(3), vec![]);
+        assert_eq!(maximal_gaps_up_to(4), vec![(1, 2)]);
+
+        for &(gap, start) in &records {
+            let end = start + gap;
+            assert_eq!(first_gap_of_at_least(gap), (start, end));
+        }
+        assert_eq!(first_gap_of_at_least(0), (2, 3));
+        assert_eq!(first_gap_of_at_least(135), (2_010_733, 2_010_881));
+    }
+
+    #[test]
+    fn primorial_values() {
+        assert_eq!(primorial(0), 1);
+        assert_eq!(primorial(2), 2);
+        assert_eq!(primorial(3), 6);
+        assert_eq!(primorial(4), 6);
+        assert_eq!(checked_primorial(u64::MAX), None);
+        assert_eq!(saturating_primorial(47), 614_889_782_588_491_410);
+    }
+
+    #[test]
+    fn lcm_up_to_matches_pairwise_lcm() {
+        let mut lcm = 1u128;
+        for n in 1..=88u64 {
+            let (mut a, mut b) = (lcm, n as u128);
+            while b != 0 {
+                let t = a % b;
+                a = b;
+                b = t;
+            }
+            lcm = lcm / a * n as u128;
+            assert_eq!(lcm_up_to(n), lcm, "{}", n);
+        }
+        assert_eq!(checked_lcm_up_to(u64::MAX), None);
+    }
+
+    #[test]
+    fn constellations_match_the_sieve() {
+        let primes = crate::get_primes_less_than_x(100_000);
+        let is_prime = |x: u64| primes.binary_search(&(x as u32)).is_ok();
+        let twins: Vec<u64> = (0..99_990).filter(|&p| is_prime(p) && is_prime(p + 2)).collect();
+        let quads: Vec<u64> = (0..99_990).filter(|&p| [0, 2, 6, 8].iter().all(|&o| is_prime(p + o))).collect();
+
+        for x in 0..99_000u64 {
+            let next = twins.iter().find(|&&p| p > x).map(|&p| (p, p + 2));
+            let previous = twins.iter().rev().find(|&&p| p < x).map(|&p| (p, p + 2));
+            assert_eq!(next_twin_prime_after(x), next, "{}", x);
+            assert_eq!(previous_twin_prime_before(x), previous, "{}", x);
+
+            let next = quads.iter().find(|&&p| p > x).map(|&p| [p, p + 2, p + 6, p + 8]);
+            let previous = quads.iter().rev().find(|&&p| p < x).map(|&p| [p, p + 2, p + 6, p + 8]);
+            assert_eq!(next_prime_quadruplet_after(x), next, "{}", x);
+            assert_eq!(previous_prime_quadruplet_before(x), previous, "{}", x);
+        }
+    }
+
+    #[test]
+    fn constellations_near_u64_max() {
+        assert_eq!(next_twin_prime_after(u64::MAX - 10), None);
+        assert_eq!(next_prime_quadruplet_after(u64::MAX), None);
+        assert_eq!(previous_twin_prime_before(u64::MAX), Some((18_446_744_073_709_550_771, 18_446_744_073_709_550_773)));
+        assert_eq!(previous_prime_quadruplet_before(u64::MAX - 3).map(|q| q[0]), Some(18_446_744_073_707_752_001));
+        assert_eq!(next_twin_prime_after(1_000_000_000_000), Some((1_000_000_000_061, 1_000_000_000_063)));
+        assert_eq!(next_twin_prime_after(1_000_000_000_000_000_000), Some((1_000_000_000_000_000_619, 1_000_000_000_000_000_621)));
+        assert_eq!(next_prime_quadruplet_after(1_000_000_000_000_000_000).map(|q| q[0]), Some(1_000_000_000_000_430_711));
+    }
+}
@@ -0,0 +1,194 @@
+//! Serialize and Deserialize for results worth storing or sending, behind
+//! the `serde` feature.
+//!
+//! Each top-level value is written with a `version` field, currently 1, and
+//! reading refuses any other version, so a stored result is never quietly
+//! misread after the shape changes. In JSON the shapes are
+//!
+//! ```text
+//! Factorization: {"version":1,"factors":[[2,3],[5,1]]}
+//! FactorReport:  {"version":1,
+//!                 "steps":[{"algorithm":"TrialDivision","input":40,
+//!                           "elapsed":{"secs":0,"nanos":830},"found":[2,5]}],
+//!                 "sources":[[2,"TrialDivision"],[5,"TrialDivision"]]}
+//! ```
+//!
+//! with factors as (prime, exponent) pairs, smallest prime first, and
+//! algorithms by their variant names. A factorization is checked as it is
+//! read: the primes must be prime and increasing and the exponents
+//! nonzero.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::primality::U64_BASES;
+use crate::{miller_rabin_with_bases, FactorAlgorithm, FactorReport, FactorStep, Factorization};
+
+/// The version written, and the only one read.
+const VERSION: u32 = 1;
+
+#[derive(Serialize)]
+#[serde(rename = "Factorization")]
+struct FactorizationOut<'a> {
+    version: u32,
+    factors: &'a [(u64, u32)],
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Factorization")]
+struct FactorizationIn {
+    version: u32,
+    factors: Vec<(u64, u32)>,
+}
+
+#[derive(Serialize)]
+#[serde(rename = "FactorReport")]
+struct FactorReportOut<'a> {
+    version: u32,
+    steps: &'a [FactorStep],
+    sources: &'a [(u64, FactorAlgorithm)],
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "FactorReport")]
+struct FactorReportIn {
+    version: u32,
+    steps: Vec<FactorStep>,
+    sources: Vec<(u64, FactorAlgorithm)>,
+}
+
+impl Serialize for Factorization {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        FactorizationOut { version: VERSION, factors: self.factors() }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Factorization {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Factorization, D::Error> {
+        let read = FactorizationIn::deserialize(deserializer)?;
+        check_version(read.version)?;
+        if let Some(&(p, _)) = read.factors.iter().find(|&&(p, _)| !miller_rabin_with_bases(p as u128, &U64_BASES)) {
+            return Err(D::Error::custom(format_args!("{} is not prime", p)));
+        }
+        if read.factors.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(D::Error::custom("primes must be in increasing order"));
+        }
+        if read.factors.iter().any(|&(_, count)| count == 0) {
+            return Err(D::Error::custom("exponents must be nonzero"));
+        }
+        Ok(Factorization::from_factors(&read.factors))
+    }
+}
+
+impl Serialize for FactorReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        FactorReportOut { version: VERSION, steps: &self.steps, sources: &self.sources }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FactorReport {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<FactorReport, D::Error> {
+        let read = FactorReportIn::deserialize(deserializer)?;
+        check_version(read.version)?;
+        Ok(FactorReport { steps: read.steps, sources: read.sources })
+    }
+}
+
+fn check_version<E: Error>(version: u32) -> Result<(), E> {
+    if version == VERSION {
+        Ok(())
+    } else {
+        Err(E::custom(format_args!("unsupported version {}, expected {}", version, VERSION)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_test::{assert_de_tokens_error, assert_tokens, Token};
+
+    use super::*;
+
+    #[test]
+    fn shapes_are_stable_and_round_trip() {
+        assert_tokens(
+            &Factorization::new(40),
+            &[
+                Token::Struct { name: "Factorization", len: 2 },
+                Token::Str("version"),
+                Token::U32(1),
+                Token::Str("factors"),
+                Token::Seq { len: Some(2) },
+                Token::Tuple { len: 2 },
+                Token::U64(2),
+                Token::U32(3),
+                Token::TupleEnd,
+                Token::Tuple { len: 2 },
+                Token::U64(5),
+                Token::U32(1),
+                Token::TupleEnd,
+                Token::SeqEnd,
+                Token::StructEnd,
+            ],
+        );
+
+        let report = FactorReport {
+            steps: vec![FactorStep { algorithm: FactorAlgorithm::TrialDivision, input: 40, elapsed: Duration::from_nanos(830), found: vec![2, 5] }],
+            sources: vec![(2, FactorAlgorithm::PollardRho)],
+        };
+        assert_tokens(
+            &report,
+            &[
+                Token::Struct { name: "FactorReport", len: 3 },
+                Token::Str("version"),
+                Token::U32(1),
+                Token::Str("steps"),
+                Token::Seq { len: Some(1) },
+                Token::Struct { name: "FactorStep", len: 4 },
+                Token::Str("algorithm"),
+                Token::UnitVariant { name: "FactorAlgorithm", variant: "TrialDivision" },
+                Token::Str("input"),
+                Token::U64(40),
+                Token::Str("elapsed"),
+                Token::Struct { name: "Duration", len: 2 },
+                Token::Str("secs"),
+                Token::U64(0),
+                Token::Str("nanos"),
+                Token::U32(830),
+                Token::StructEnd,
+                Token::Str("found"),
+                Token::Seq { len: Some(2) },
+                Token::U64(2),
+                Token::U64(5),
+                Token::SeqEnd,
+                Token::StructEnd,
+                Token::SeqEnd,
+                Token::Str("sources"),
+                Token::Seq { len: Some(1) },
+                Token::Tuple { len: 2 },
+                Token::U64(2),
+                Token::UnitVariant { name: "FactorAlgorithm", variant: "PollardRho" },
+                Token::TupleEnd,
+                Token::SeqEnd,
+                Token::StructEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn rejects_other_versions_and_bad_factors() {
+        let read = |version: u32, factors: &[(u64, u32)]| {
+            let mut tokens = vec![Token::Struct { name: "Factorization", len: 2 }, Token::Str("version"), Token::U32(version), Token::Str("factors"), Token::Seq { len: Some(factors.len()) }];
+            for &(p, count) in factors {
+                tokens.extend_from_slice(&[Token::Tuple { len: 2 }, Token::U64(p), Token::U32(count), Token::TupleEnd]);
+            }
+            tokens.extend_from_slice(&[Token::SeqEnd, Token::StructEnd]);
+            tokens
+        };
+        assert_de_tokens_error::<Factorization>(&read(2, &[(2, 3)]), "unsupported version 2, expected 1");
+        assert_de_tokens_error::<Factorization>(&read(1, &[(4, 1)]), "4 is not prime");
+        assert_de_tokens_error::<Factorization>(&read(1, &[(5, 1), (2, 1)]), "primes must be in increasing order");
+        assert_de_tokens_error::<Factorization>(&read(1, &[(2, 0)]), "exponents must be nonzero");
+    }
+}
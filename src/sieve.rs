@@ -0,0 +1,1390 @@
+//! Segmented sieve of Eratosthenes.
+//!
+//! The window `[min, max)` is sieved one cache-sized segment at a time. Each
+//! segment is a packed bitmap (bit `i` of a segment starting at `low` stands for
+//! `low + i`), pre-filled from a wheel pattern so the smallest primes never have
+//! to cross anything off.
+
+use std::fmt;
+use std::ops::ControlFlow;
+use std::sync::{Arc, OnceLock};
+use std::thread;
+
+use bit_vec::BitVec;
+
+use crate::miller_rabin_with_bases;
+use crate::primality::U64_BASES;
+
+const WORD_BITS: u64 = 64;
+
+/// Which small primes get pre-sieved from a repeating bit pattern.
+///
+/// A bigger wheel removes more composites up front, but the pattern it copies
+/// into every segment is longer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wheel {
+    /// No pre-sieving: every prime crosses off its own multiples.
+    Off,
+    /// Pre-sieves multiples of 2.
+    Mod2,
+    /// Pre-sieves multiples of 2 and 3.
+    Mod6,
+    /// Pre-sieves multiples of 2, 3 and 5.
+    Mod30,
+    /// Pre-sieves multiples of 2, 3, 5 and 7.
+    Mod210,
+}
+
+impl Wheel {
+    /// The primes this wheel takes care of.
+    pub fn primes(self) -> &'static [u64] {
+        match self {
+            Wheel::Off => &[],
+            Wheel::Mod2 => &[2],
+            Wheel::Mod6 => &[2, 3],
+            Wheel::Mod30 => &[2, 3, 5],
+            Wheel::Mod210 => &[2, 3, 5, 7],
+        }
+    }
+
+    /// The wheel circumference (the product of its primes).
+    pub fn modulus(self) -> u64 {
+        self.primes().iter().product()
+    }
+
+    /// The wheel laid out as whole 64-bit words, so it can be copied into any
+    /// 64-aligned segment: word `w` of the result covers `64 * w .. 64 * (w + 1)`
+    /// modulo the pattern length.
+    fn pattern(self) -> Vec<u64> {
+        let modulus = self.modulus();
+        // lcm(modulus, 64) / 64 words
+        let words = (modulus / crate::gcd(modulus, WORD_BITS)) as usize;
+        let primes = self.primes();
+
+        let mut pattern = vec![0u64; words];
+        for (w, word) in pattern.iter_mut().enumerate() {
+            for bit in 0..WORD_BITS {
+                let n = w as u64 * WORD_BITS + bit;
+                if primes.iter().all(|&p| n % p != 0) {
+                    *word |= 1 << bit;
+                }
+            }
+        }
+        pattern
+    }
+}
+
+/// Every number in `[min, max)` that none of the wheel's primes divide, in order.
+///
+/// That's all the primes in the window apart from the wheel primes themselves,
+/// plus the composites with no small factor. Searches for primes of a special
+/// form can walk these instead of every integer: `Wheel::Mod210` leaves 48 of
+/// every 210 numbers. `Wheel::Off` yields the whole window.
+///
+/// ```
+/// use prime_tools::{wheel_candidates, Wheel};
+///
+/// let candidates: Vec<u64> = wheel_candidates(0, 50, Wheel::Mod30).collect();
+/// assert_eq!(candidates, vec![1, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 49]);
+/// ```
+pub fn wheel_candidates(min: u64, max: u64, wheel: Wheel) -> impl Iterator<Item = u64> {
+    let modulus = wheel.modulus();
+    WheelCandidates {
+        residues: crate::residue_classes_coprime_to(modulus),
+        modulus,
+        base: min - min % modulus,
+        index: 0,
+        min,
+        max,
+    }
+}
+
+struct WheelCandidates {
+    residues: Vec<u64>,
+    modulus: u64,
+    base: u64,
+    index: usize,
+    min: u64,
+    max: u64,
+}
+
+impl Iterator for WheelCandidates {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        loop {
+            if self.index == self.residues.len() {
+                self.index = 0;
+                self.base = self.base.checked_add(self.modulus)?;
+            }
+            let n = self.base.checked_add(self.residues[self.index])?;
+            self.index += 1;
+            if n >= self.max {
+                return None;
+            }
+            if n >= self.min {
+                return Some(n);
+            }
+        }
+    }
+}
+
+/// Which sieve finds the primes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SieveAlgorithm {
+    /// The segmented, wheel-accelerated, multithreaded sieve of Eratosthenes.
+    Eratosthenes,
+    /// The sieve of Sundaram over a single bitmap of odd numbers below max.
+    /// Ignores the other options; mostly useful for teaching and benchmarks.
+    Sundaram,
+    /// Pritchard's dynamic wheel sieve, which does fewer operations than
+    /// Eratosthenes asymptotically but needs 8 bytes per number below max and
+    /// sieves from 1 whatever min is. Limited to max <= 2^32, past which the `try_` functions return `SieveError::AlgorithmLimit`; ignores the other options.
+    Pritchard,
+}
+
+/// Tuning knobs for the segmented sieve.
+///
+/// `SieveOptions::default()` picks values that work well on most machines;
+/// override individual fields with struct update syntax:
+///
+/// ```
+/// use prime_tools::{SieveOptions, Wheel};
+///
+/// let options = SieveOptions {
+///     segment_bytes: 256 * 1024,
+///     wheel: Wheel::Mod30,
+///     ..SieveOptions::default()
+/// };
+///
+/// assert_eq!(
+///     prime_tools::get_primes_between_with_options(90, 110, &options),
+///     vec![97, 101, 103, 107, 109]
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SieveOptions {
+    /// Size of one segment's bitmap. Each byte covers 8 consecutive numbers.
+    ///
+    /// The segment is crossed off once per sieving prime, so it should stay
+    /// in cache: the L1 data cache size is the usual pick, and up to about
+    /// half the L2 pays off once most sieving primes are larger than a
+    /// segment (windows past 10^12 or so).
+    pub segment_bytes: usize,
+    /// Small primes that are pre-sieved from a pattern instead of crossed off.
+    pub wheel: Wheel,
+    /// Maximum number of worker threads. Small ranges always run on the calling thread.
+    pub threads: usize,
+    /// The sieve to run. Everything but `Eratosthenes` ignores the knobs above.
+    pub algorithm: SieveAlgorithm,
+    /// Cap in bytes on what `get_primes_between_with_options` and
+    /// `primes_into_with_options` may allocate, output included, or None for
+    /// no cap. Threads are dropped until the estimate fits; a window that
+    /// doesn't fit even on one thread is an error from the `try_` versions.
+    pub memory_limit: Option<usize>,
+}
+
+impl Default for SieveOptions {
+    /// A segment the size of the L1 data cache, the 2·3·5·7 wheel, one
+    /// thread per available core, the sieve of Eratosthenes, and no memory cap.
+    ///
+    /// The cache size is read from sysfs on Linux. Elsewhere, or if it can't
+    /// be read, the segment is 32 KiB, a typical L1 data cache.
+    fn default() -> SieveOptions {
+        SieveOptions {
+            segment_bytes: default_segment_bytes(),
+            wheel: Wheel::Mod210,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            algorithm: SieveAlgorithm::Eratosthenes,
+            memory_limit: None,
+        }
+    }
+}
+
+/// The L1 data cache size, clamped to 16 KiB..=1 MiB, read once per process.
+fn default_segment_bytes() -> usize {
+    static SEGMENT_BYTES: OnceLock<usize> = OnceLock::new();
+    *SEGMENT_BYTES.get_or_init(|| l1_data_cache_bytes().map_or(32 * 1024, |bytes| bytes.clamp(16 * 1024, 1 << 20)))
+}
+
+/// The first level-1 data cache that cpu0 lists in sysfs, e.g. "48K".
+fn l1_data_cache_bytes() -> Option<usize> {
+    let read = |path: std::path::PathBuf| std::fs::read_to_string(path).ok().map(|text| text.trim().to_string());
+    (0..8).find_map(|index| {
+        let dir = std::path::PathBuf::from(format!("/sys/devices/system/cpu/cpu0/cache/index{}", index));
+        if read(dir.join("level"))? != "1" || read(dir.join("type"))? != "Data" {
+            return None;
+        }
+        parse_cache_size(&read(dir.join("size"))?)
+    })
+}
+
+/// "48K" → 49152; sysfs writes cache sizes in bytes, K or M.
+fn parse_cache_size(text: &str) -> Option<usize> {
+    let (digits, unit) = match text.as_bytes().last()? {
+        b'K' => (&text[..text.len() - 1], 1 << 10),
+        b'M' => (&text[..text.len() - 1], 1 << 20),
+        _ => (text, 1),
+    };
+    digits.parse::<usize>().ok()?.checked_mul(unit).filter(|&bytes| bytes > 0)
+}
+
+impl SieveOptions {
+    /// Numbers covered by one segment, rounded up to a whole number of words.
+    fn segment_bits(&self) -> u64 {
+        // Bucketed offsets are stored as u32, so cap a segment at 2^31 numbers.
+        let bits = (self.segment_bytes.clamp(1, 1 << 28) as u64) * 8;
+        bits.div_ceil(WORD_BITS) * WORD_BITS
+    }
+
+    /// A conservative estimate of the bytes `get_primes_between_with_options(min, max, self)`
+    /// has allocated at its peak, the primes it returns included.
+    ///
+    /// Under a `memory_limit` this is for the thread count the sieve would
+    /// drop to, so it's above the limit exactly when the sieve would refuse.
+    ///
+    /// ```
+    /// use prime_tools::SieveOptions;
+    ///
+    /// let options = SieveOptions { threads: 1, ..SieveOptions::default() };
+    /// // About 50 million primes at 8 bytes each, and then some.
+    /// assert!(options.memory_needed(0, 1_000_000_000) > 400_000_000);
+    /// ```
+    pub fn memory_needed(&self, min: u64, max: u64) -> usize {
+        let min = min.max(2);
+        if min >= max {
+            return 0;
+        }
+        let threads = self.threads_within_limit(min, max);
+        self.peak_bytes(min, max, threads).min(usize::MAX as u64) as usize
+    }
+
+    /// How many threads the Eratosthenes sieve would use on `[min, max)` with no memory cap.
+    fn threads_for(&self, min: u64, max: u64) -> u64 {
+        if self.algorithm != SieveAlgorithm::Eratosthenes || self.is_narrow(min, max) {
+            return 1;
+        }
+        // Only bother with threads when every worker gets a few segments of its own.
+        let segments = (max - min) / self.segment_bits() + 1;
+        (self.threads as u64).min(segments / 4).max(1)
+    }
+
+    /// `threads_for`, cut down until the estimate fits under `memory_limit` (or to 1).
+    fn threads_within_limit(&self, min: u64, max: u64) -> u64 {
+        let mut threads = self.threads_for(min, max);
+        if let Some(limit) = self.memory_limit {
+            while threads > 1 && self.peak_bytes(min, max, threads) > limit as u64 {
+                threads -= 1;
+            }
+        }
+        threads
+    }
+
+    /// Whether `[min, max)` gets `primes_in_narrow_window` rather than collected sieving primes.
+    fn is_narrow(&self, min: u64, max: u64) -> bool {
+        crate::isqrt(max - 1) >= 1 << 20 && max - min <= 64 * self.segment_bits()
+    }
+
+    /// Peak allocation on `[min, max)`, 2 <= min < max, sieving on `threads` threads.
+    ///
+    /// Vecs that grow by doubling count twice, and with threads the per-thread
+    /// lists and the combined output are alive at once.
+    fn peak_bytes(&self, min: u64, max: u64, threads: u64) -> u64 {
+        let output = 16 * prime_count_bound(min, max);
+        let highest_factor = crate::isqrt(max - 1);
+        let segment = self.segment_bits() / 8;
+        match self.algorithm {
+            SieveAlgorithm::Sundaram => return output + max / 16,
+            SieveAlgorithm::Pritchard => return output.saturating_add(max.saturating_mul(8)),
+            SieveAlgorithm::Eratosthenes => {}
+        }
+        if self.is_narrow(min, max) {
+            let base = 8 * prime_count_bound(2, crate::isqrt(highest_factor) + 1);
+            return output + (max - min) / 8 + segment + 3 * base;
+        }
+        // Sieving primes are u32s; each thread keeps up to 16 bytes of state per prime.
+        let sieving_primes = prime_count_bound(2, highest_factor + 1);
+        let per_thread = segment + 16 * sieving_primes;
+        let outputs = if threads == 1 { output } else { 2 * output };
+        8 * sieving_primes + threads * per_thread + outputs
+    }
+}
+
+/// An upper bound on the number of primes in `[min, max)`, 2 <= min < max:
+/// π(x) < 1.25506 x / ln x (Rosser and Schoenfeld) and, for a short window,
+/// π(x + y) - π(x) < 2y / ln y (Montgomery and Vaughan).
+pub(crate) fn prime_count_bound(min: u64, max: u64) -> u64 {
+    let x = max as f64;
+    let below_max = if max > 17 { 1.25506 * x / x.ln() } else { 7.0 };
+    let y = (max - min) as f64;
+    let window = if max - min > 1 { 2.0 * y / y.ln() } else { 1.0 };
+    below_max.min(window) as u64 + 1
+}
+
+/// Why a sieve refused to run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SieveError {
+    /// Even on one thread the sieve would need about `needed` bytes, over `SieveOptions::memory_limit`.
+    MemoryLimit { needed: usize, limit: usize },
+    /// The chosen `SieveAlgorithm` only sieves up to `limit`, and `max` is past it.
+    AlgorithmLimit { algorithm: SieveAlgorithm, max: u64, limit: u64 },
+}
+
+impl fmt::Display for SieveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SieveError::MemoryLimit { needed, limit } => {
+                write!(f, "the sieve needs about {} bytes, over the {} byte memory limit", needed, limit)
+            }
+            SieveError::AlgorithmLimit { algorithm, max, limit } => {
+                write!(f, "{:?} sieves up to {}, not {}", algorithm, limit, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SieveError {}
+
+/// Generates primes between min (inclusive) and max (exclusive) with explicit sieve settings.
+///
+/// Same output as `get_primes_between`, which uses `SieveOptions::default()`.
+///
+/// ```
+/// use prime_tools::{SieveOptions, Wheel};
+///
+/// let options = SieveOptions { segment_bytes: 64, wheel: Wheel::Off, threads: 4, ..SieveOptions::default() };
+/// assert_eq!(
+///     prime_tools::get_primes_between_with_options(10, 30, &options),
+///     vec![11, 13, 17, 19, 23, 29]
+/// );
+/// ```
+///
+/// Panics if the window doesn't fit in `options.memory_limit`; see
+/// `try_get_primes_between_with_options`.
+pub fn get_primes_between_with_options(min: u64, max: u64, options: &SieveOptions) -> Vec<u64> {
+    let mut primes = Vec::new();
+    primes_into_with_options(min, max, options, &mut primes);
+    primes
+}
+
+/// `get_primes_between_with_options`, with an error instead of a panic when
+/// the window doesn't fit in `options.memory_limit`.
+///
+/// ```
+/// use prime_tools::{SieveError, SieveOptions};
+///
+/// let options = SieveOptions { memory_limit: Some(1 << 20), ..SieveOptions::default() };
+/// assert_eq!(prime_tools::try_get_primes_between_with_options(0, 1000, &options).unwrap().len(), 168);
+/// assert!(matches!(
+///     prime_tools::try_get_primes_between_with_options(0, 1 << 32, &options),
+///     Err(SieveError::MemoryLimit { limit: 1048576, .. })
+/// ));
+/// ```
+pub fn try_get_primes_between_with_options(min: u64, max: u64, options: &SieveOptions) -> Result<Vec<u64>, SieveError> {
+    let mut primes = Vec::new();
+    try_primes_into_with_options(min, max, options, &mut primes)?;
+    Ok(primes)
+}
+
+/// `get_primes_between` for a window below 2^32, as u32s: half the memory of
+/// the Vec<u64> for the same primes.
+///
+/// Runs the segmented sieve on the calling thread, and reserves room for an
+/// upper bound on the count up front rather than letting the Vec double.
+///
+/// ```
+/// assert_eq!(prime_tools::get_primes_between_u32(4_294_967_270, u32::MAX), vec![4_294_967_279, 4_294_967_291]);
+/// assert_eq!(prime_tools::get_primes_between_u32(0, 12), vec![2, 3, 5, 7, 11]);
+/// ```
+pub fn get_primes_between_u32(min: u32, max: u32) -> Vec<u32> {
+    let min = min.max(2);
+    if min >= max {
+        return Vec::new();
+    }
+    let options = SieveOptions::default();
+    let sieving_primes = primes_up_to(crate::isqrt(max as u64 - 1) as u32, &options);
+    let mut primes = Vec::with_capacity(prime_count_bound(min as u64, max as u64) as usize);
+    sieve_segments(min as u64, max as u64, &sieving_primes, &options, |low, words| {
+        for_each_set_bit(words, |offset| primes.push((low + offset) as u32))
+    });
+    primes
+}
+
+/// `get_primes_between` as a boxed slice, with no spare capacity left over.
+///
+/// ```
+/// let primes: Box<[u64]> = prime_tools::get_primes_between_boxed(10, 30);
+/// assert_eq!(&*primes, &[11, 13, 17, 19, 23, 29]);
+/// ```
+pub fn get_primes_between_boxed(min: u64, max: u64) -> Box<[u64]> {
+    get_primes_between_with_options(min, max, &SieveOptions::default()).into_boxed_slice()
+}
+
+/// `get_primes_between` behind an `Arc`, so threads can share one list.
+///
+/// The list is copied once into the Arc's allocation; after that, handing it
+/// to another thread is a reference count bump however long it is.
+///
+/// ```
+/// use std::thread;
+///
+/// let primes = prime_tools::get_primes_between_shared(0, 1_000_000);
+/// let handles: Vec<_> = (0..4)
+///     .map(|i| {
+///         let primes = primes.clone();
+///         thread::spawn(move || primes.iter().filter(|&&p| p % 4 == i).count())
+///     })
+///     .collect();
+/// let counts: Vec<usize> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+/// assert_eq!(counts, vec![0, 39_175, 1, 39_322]);
+/// ```
+pub fn get_primes_between_shared(min: u64, max: u64) -> Arc<[u64]> {
+    Arc::from(get_primes_between_with_options(min, max, &SieveOptions::default()))
+}
+
+/// Like `get_primes_between`, but fills `out` (clearing it first) so a loop
+/// over many windows can reuse one buffer.
+///
+/// ```
+/// let mut primes = Vec::new();
+/// for start in (0..100).step_by(10) {
+///     prime_tools::primes_into(start, start + 10, &mut primes);
+///     assert!(primes.len() <= 4);
+/// }
+/// assert_eq!(primes, vec![97]);
+/// ```
+pub fn primes_into(min: u64, max: u64, out: &mut Vec<u64>) {
+    primes_into_with_options(min, max, &SieveOptions::default(), out)
+}
+
+/// `primes_into` with explicit sieve settings.
+///
+/// Panics if the window doesn't fit in `options.memory_limit`; see
+/// `try_primes_into_with_options`.
+pub fn primes_into_with_options(min: u64, max: u64, options: &SieveOptions, out: &mut Vec<u64>) {
+    if let Err(error) = try_primes_into_with_options(min, max, options, out) {
+        panic!("{}", error);
+    }
+}
+
+/// `primes_into_with_options`, with an error instead of a panic when the
+/// window doesn't fit in `options.memory_limit` or goes past what
+/// `options.algorithm` can sieve. `out` is left empty then.
+pub fn try_primes_into_with_options(min: u64, max: u64, options: &SieveOptions, out: &mut Vec<u64>) -> Result<(), SieveError> {
+    out.clear();
+    let min = min.max(2);
+    if min >= max {
+        return Ok(());
+    }
+    let threads = options.threads_within_limit(min, max);
+    if let Some(limit) = options.memory_limit {
+        let needed = options.peak_bytes(min, max, threads);
+        if needed > limit as u64 {
+            let needed = needed.min(usize::MAX as u64) as usize;
+            return Err(SieveError::MemoryLimit { needed, limit });
+        }
+    }
+    if options.algorithm == SieveAlgorithm::Pritchard && max > crate::pritchard::MAX {
+        return Err(SieveError::AlgorithmLimit { algorithm: options.algorithm, max, limit: crate::pritchard::MAX });
+    }
+    primes_between(min, max, threads, options, out);
+    Ok(())
+}
+
+/// Fills `out` (already empty) with the primes in `[min, max)`, 2 <= min < max.
+fn primes_between(min: u64, max: u64, threads: u64, options: &SieveOptions, out: &mut Vec<u64>) {
+    let _span = debug_span!("get_primes_between", min, max);
+    match options.algorithm {
+        SieveAlgorithm::Eratosthenes => {}
+        SieveAlgorithm::Sundaram => return out.extend(crate::sundaram::primes_between(min, max)),
+        SieveAlgorithm::Pritchard => return out.extend(crate::pritchard::primes_between(min, max)),
+    }
+
+    // Every composite below max has a prime factor no bigger than this.
+    let highest_factor = crate::isqrt(max - 1) as u32;
+    if options.is_narrow(min, max) {
+        debug!(highest_factor, "streaming sieving primes for a narrow window");
+        out.extend(primes_in_narrow_window(min, max, highest_factor, options));
+        return;
+    }
+
+    let sieving_primes = primes_up_to(highest_factor, options);
+    debug!(sieving_primes = sieving_primes.len(), threads, "sieving primes ready");
+
+    if threads == 1 {
+        sieve_segments(min, max, &sieving_primes, options, |low, words| push_primes(low, words, out));
+        return;
+    }
+
+    let chunk = ((max - min) / threads + WORD_BITS) / WORD_BITS * WORD_BITS;
+    let chunks: Vec<(u64, u64)> = (0..threads)
+        .map(|i| {
+            let lo = min.saturating_add(i * chunk);
+            (lo.min(max), lo.saturating_add(chunk).min(max))
+        })
+        .filter(|(lo, hi)| lo < hi)
+        .collect();
+
+    let sieving_primes = &sieving_primes;
+    let results: Vec<Vec<u64>> = thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .iter()
+            .map(|&(lo, hi)| {
+                scope.spawn(move || {
+                    let mut primes = Vec::new();
+                    sieve_segments(lo, hi, sieving_primes, options, |low, words| {
+                        push_primes(low, words, &mut primes)
+                    });
+                    primes
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    for primes in results {
+        out.extend(primes);
+    }
+}
+
+/// The primality of every number in `[min, max)` as a bitmap: bit i is set iff
+/// min + i is prime.
+///
+/// Handy for scans that keep asking "is this one prime?" about numbers in the
+/// window, e.g. pairing up Goldbach partitions, since a lookup is a single bit
+/// test. Takes one bit per number against eight bytes per prime for the Vec
+/// `get_primes_between` returns.
+///
+/// ```
+/// let bits = prime_tools::prime_bitmap_between(10, 20);
+/// assert_eq!(bits.len(), 10);
+/// assert!(bits[1] && bits[3] && bits[7] && bits[9]);
+/// assert_eq!(bits.iter().filter(|&prime| prime).count(), 4);
+/// ```
+pub fn prime_bitmap_between(min: u64, max: u64) -> BitVec {
+    let mut bits = BitVec::from_elem(max.saturating_sub(min) as usize, false);
+    for_each_prime_between(min, max, &SieveOptions::default(), |p| bits.set((p - min) as usize, true));
+    bits
+}
+
+/// Which numbers in `[min, max)` have no prime factor below prime_limit, as a
+/// bitmap: bit i is set iff min + i survives.
+///
+/// This is the sieve without its last step, for searches whose candidates
+/// get an expensive test of their own (primes of a special form, say) and
+/// only want the ones worth testing. The primes below prime_limit are knocked
+/// out like any other multiple; 1 always survives, and 0 only when there are
+/// no primes below the limit at all.
+///
+/// ```
+/// let survivors = prime_tools::presieve_survivors(100, 130, 10);
+/// let numbers: Vec<u64> = (100..130).filter(|&n| survivors[(n - 100) as usize]).collect();
+/// assert_eq!(numbers, vec![101, 103, 107, 109, 113, 121, 127]);
+/// ```
+pub fn presieve_survivors(min: u64, max: u64, prime_limit: u64) -> BitVec {
+    let len = max.saturating_sub(min) as usize;
+    if prime_limit <= 2 {
+        return BitVec::from_elem(len, true);
+    }
+    let mut bits = BitVec::from_elem(len, false);
+    if len == 0 {
+        return bits;
+    }
+
+    // The wheel may only pre-sieve primes that are below the limit.
+    let wheel = [Wheel::Mod210, Wheel::Mod30, Wheel::Mod6, Wheel::Mod2]
+        .iter()
+        .copied()
+        .find(|wheel| wheel.primes().iter().all(|&p| p < prime_limit))
+        .unwrap_or(Wheel::Off);
+    let options = SieveOptions { wheel, ..SieveOptions::default() };
+    let sieving_limit = (prime_limit - 1).min(crate::isqrt(max - 1));
+    let sieving_primes = primes_up_to(sieving_limit as u32, &options);
+    sieve_segments(min, max, &sieving_primes, &options, |low, words| {
+        for_each_set_bit(words, |offset| bits.set((low + offset - min) as usize, true))
+    });
+
+    // What's left is the numbers with no factor below the limit plus the
+    // primes below the limit, which count as having one; 1 was dropped.
+    for_each_prime_between(min, max.min(prime_limit), &options, |p| bits.set((p - min) as usize, false));
+    if min <= 1 && 1 < max {
+        bits.set((1 - min) as usize, true);
+    }
+    bits
+}
+
+/// Which numbers in `[min, max)` are squarefree, as a bitmap: bit i is set
+/// iff no square above 1 divides min + i.
+///
+/// Sieved a segment at a time by `squarefree_segments`, clearing the
+/// multiples of p² for every prime p up to √max, so the work is about
+/// (max - min) · Σ 1/p² plus the primes up to √max, with no number factored
+/// on its own. 0 isn't squarefree and 1 is.
+///
+/// ```
+/// let bits = prime_tools::squarefree_between(10, 20);
+/// let squarefree: Vec<u64> = (10..20).filter(|&n| bits[(n - 10) as usize]).collect();
+/// assert_eq!(squarefree, vec![10, 11, 13, 14, 15, 17, 19]);
+/// ```
+pub fn squarefree_between(min: u64, max: u64) -> BitVec {
+    let mut bits = BitVec::from_elem(max.saturating_sub(min) as usize, false);
+    squarefree_segments(min, max, &SieveOptions::default(), |low, words| {
+        for_each_set_bit(words, |offset| bits.set((low + offset - min) as usize, true))
+    });
+    bits
+}
+
+/// Sieves `[min, max)` for squarefree numbers one segment at a time, calling
+/// `visit(low, words)` with a segment bitmap: bit `i` is set iff `low + i` is
+/// squarefree and inside the window. Only one segment is held at once.
+pub(crate) fn squarefree_segments<F: FnMut(u64, &[u64])>(min: u64, max: u64, options: &SieveOptions, mut visit: F) {
+    if min >= max {
+        return;
+    }
+    let primes = primes_up_to(crate::isqrt(max - 1) as u32, options);
+    // next[i] is the next multiple of primes[i]² to clear, u64::MAX once it's past 2^64.
+    let mut next: Vec<u64> = primes
+        .iter()
+        .map(|&p| {
+            let square = p as u64 * p as u64;
+            min.div_ceil(square).saturating_mul(square)
+        })
+        .collect();
+    let segment_bits = options.segment_bits();
+    let mut words = vec![0u64; (segment_bits / WORD_BITS) as usize];
+    let mut low = min;
+    while low < max {
+        let high = low.saturating_add(segment_bits).min(max);
+        let len = high - low;
+        let used = len.div_ceil(WORD_BITS) as usize;
+        words[..used].fill(u64::MAX);
+        if len % WORD_BITS != 0 {
+            words[used - 1] = (1 << (len % WORD_BITS)) - 1;
+        }
+        if low == 0 {
+            words[0] &= !1;
+        }
+        for (&p, next) in primes.iter().zip(&mut next) {
+            let square = p as u64 * p as u64;
+            while *next < high {
+                let offset = *next - low;
+                words[(offset / WORD_BITS) as usize] &= !(1 << (offset % WORD_BITS));
+                *next = next.saturating_add(square);
+            }
+        }
+        visit(low, &words[..used]);
+        low = high;
+    }
+}
+
+/// Sorts and dedups `values`, keeps only the primes, and returns how many there are.
+///
+/// Runs of values that sit close together get one sieve over the span they
+/// cover, and isolated values get a deterministic Miller–Rabin test each,
+/// whichever a rough cost estimate says is cheaper. So a dense batch costs about as much as sieving
+/// it and a sparse one about as much as testing it, without having to say which.
+///
+/// ```
+/// let mut values = vec![97, 4, 1_000_003, 2, 97, 1_000_000, 1_000_001];
+/// assert_eq!(prime_tools::count_and_filter_primes(&mut values), 3);
+/// assert_eq!(values, vec![2, 97, 1_000_003]);
+/// ```
+pub fn count_and_filter_primes(values: &mut Vec<u64>) -> usize {
+    values.sort_unstable();
+    values.dedup();
+
+    let mut primes = Vec::with_capacity(values.len());
+    let mut start = 0;
+    while start < values.len() {
+        // Grow the run while the next gap is cheaper to sieve across than to test alone.
+        let mut end = start + 1;
+        while end < values.len() && values[end] - values[end - 1] <= TESTING_COST {
+            end += 1;
+        }
+
+        let run = &values[start..end];
+        let (low, high) = (run[0], run[run.len() - 1]);
+        let sieving_cost = (high - low).saturating_add(sieving_setup_cost(high));
+        if run.len() > 1 && sieving_cost < (run.len() as u64).saturating_mul(TESTING_COST) {
+            // Only u64::MAX can fall outside the window, and it isn't prime.
+            let bits = prime_bitmap_between(low, high.saturating_add(1));
+            primes.extend(run.iter().filter(|&&x| bits.get((x - low) as usize).unwrap_or(false)));
+        } else {
+            primes.extend(run.iter().filter(|&&x| miller_rabin_with_bases(x as u128, &U64_BASES)));
+        }
+        start = end;
+    }
+
+    *values = primes;
+    values.len()
+}
+
+/// Roughly what one Miller–Rabin test costs, counted in numbers sieved:
+/// up to seven bases of about 64 modular squarings each.
+const TESTING_COST: u64 = 500;
+
+/// Roughly what finding the sieving primes for a window ending near x
+/// costs, in the same units: one pass over the √x / ln √x primes up to √x.
+fn sieving_setup_cost(x: u64) -> u64 {
+    let root = crate::isqrt(x);
+    (root as f64 / (root.max(2) as f64).ln()) as u64 + 1
+}
+
+/// Calls `f` with every prime in `[min, max)`, in order, on the current thread.
+pub(crate) fn for_each_prime_between<F: FnMut(u64)>(min: u64, max: u64, options: &SieveOptions, mut f: F) {
+    let min = min.max(2);
+    if min >= max {
+        return;
+    }
+    match options.algorithm {
+        SieveAlgorithm::Eratosthenes => {}
+        SieveAlgorithm::Sundaram => return crate::sundaram::for_each_prime_between(min, max, f),
+        SieveAlgorithm::Pritchard => return crate::pritchard::primes_between(min, max).into_iter().for_each(f),
+    }
+
+    let highest_factor = crate::isqrt(max - 1) as u32;
+    if options.is_narrow(min, max) {
+        primes_in_narrow_window(min, max, highest_factor, options).into_iter().for_each(f);
+        return;
+    }
+
+    let sieving_primes = primes_up_to(highest_factor, options);
+    sieve_segments(min, max, &sieving_primes, options, |low, words| {
+        for_each_set_bit(words, |offset| f(low + offset))
+    });
+}
+
+/// The primes below a limit that can be raised later without starting over.
+///
+/// `extend_to` sieves only the new stretch, reusing the primes already found
+/// as sieving primes whenever they reach far enough.
+///
+/// ```
+/// use prime_tools::Sieve;
+///
+/// let mut sieve = Sieve::new(20);
+/// assert_eq!(sieve.primes(), &[2, 3, 5, 7, 11, 13, 17, 19]);
+///
+/// sieve.extend_to(40);
+/// assert_eq!(sieve.limit(), 40);
+/// assert_eq!(sieve.is_prime(37), Some(true));
+/// assert_eq!(sieve.is_prime(41), None);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sieve {
+    limit: u64,
+    primes: Vec<u64>,
+}
+
+impl Sieve {
+    /// Every prime less than limit.
+    pub fn new(limit: u64) -> Sieve {
+        let mut sieve = Sieve::empty();
+        sieve.extend_to(limit);
+        sieve
+    }
+
+    /// No primes yet, limit 0. Const, so it can start out a static.
+    pub(crate) const fn empty() -> Sieve {
+        Sieve { limit: 0, primes: Vec::new() }
+    }
+
+    /// The primes found so far are exactly those less than this.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Every prime less than the limit, in order.
+    pub fn primes(&self) -> &[u64] {
+        &self.primes
+    }
+
+    /// How many primes the sieve holds: π(limit - 1).
+    pub fn count(&self) -> usize {
+        self.primes.len()
+    }
+
+    /// Bytes of heap and struct the sieve takes up, counting spare capacity.
+    ///
+    /// Every prime costs 8 bytes here, against about one bit per number
+    /// for a `PrimeSet`, so past a limit of a few hundred million the
+    /// bitmap is the smaller of the two.
+    ///
+    /// ```
+    /// use prime_tools::Sieve;
+    ///
+    /// let sieve = Sieve::new(1_000_000);
+    /// assert_eq!(sieve.count(), 78_498);
+    /// assert!(sieve.memory_bytes() >= 78_498 * 8);
+    /// assert!((sieve.density() - 0.0785).abs() < 0.0001);
+    /// ```
+    pub fn memory_bytes(&self) -> usize {
+        std::mem::size_of::<Sieve>() + self.primes.capacity() * std::mem::size_of::<u64>()
+    }
+
+    /// The fraction of numbers below the limit that are prime, about
+    /// 1 / ln(limit); 0 for an empty sieve.
+    pub fn density(&self) -> f64 {
+        if self.limit == 0 {
+            0.0
+        } else {
+            self.primes.len() as f64 / self.limit as f64
+        }
+    }
+
+    /// Whether x is prime, or None if x is at or past the limit.
+    pub fn is_prime(&self, x: u64) -> Option<bool> {
+        if x >= self.limit {
+            return None;
+        }
+        Some(self.primes.binary_search(&x).is_ok())
+    }
+
+    /// Raises the limit to new_limit, sieving just `[limit, new_limit)`.
+    /// A new_limit at or below the current one does nothing.
+    pub fn extend_to(&mut self, new_limit: u64) {
+        if new_limit <= self.limit {
+            return;
+        }
+        let options = SieveOptions::default();
+        let highest_factor = crate::isqrt(new_limit - 1);
+        if highest_factor >= self.limit {
+            // Not enough sieving primes yet: get those first, from the bottom up.
+            if highest_factor >= 1 << 10 {
+                self.extend_to(highest_factor + 1);
+            } else {
+                self.primes = crate::get_primes_less_than_x(highest_factor as u32 + 1).iter().map(|&p| p as u64).collect();
+                self.limit = highest_factor + 1;
+            }
+        }
+
+        let count = self.primes.partition_point(|&p| p <= highest_factor);
+        let sieving_primes: Vec<u32> = self.primes[..count].iter().map(|&p| p as u32).collect();
+        let (min, primes) = (self.limit, &mut self.primes);
+        sieve_segments(min, new_limit, &sieving_primes, &options, |low, words| push_primes(low, words, primes));
+        self.limit = new_limit;
+    }
+}
+
+/// Sieves a window that is narrow compared to √max.
+///
+/// The sieving primes are crossed off as they come out of the segmented sieve
+/// instead of being collected first, so a window just below u64::MAX needs a
+/// bitmap the size of the window rather than the 203 million primes below 2^32.
+fn primes_in_narrow_window(min: u64, max: u64, highest_factor: u32, options: &SieveOptions) -> Vec<u64> {
+    let len = (max - min) as usize;
+    let mut window = vec![!0u64; len.div_ceil(64)];
+    for offset in len..window.len() * 64 {
+        clear(&mut window, offset);
+    }
+
+    for_each_prime_up_to(highest_factor, options, |p| {
+        let first = match first_multiple(p, min) {
+            Some(first) => first,
+            None => return,
+        };
+        let mut offset = first - min;
+        while offset < len as u64 {
+            clear(&mut window, offset as usize);
+            offset += p;
+        }
+    });
+
+    let mut primes = Vec::new();
+    for_each_set_bit(&window, |offset| primes.push(min + offset));
+    primes
+}
+
+/// The first multiple of the prime `p` that is at least `from` and worth
+/// crossing off, i.e. no smaller than p². None if that doesn't fit in a u64.
+fn first_multiple(p: u64, from: u64) -> Option<u64> {
+    let square = p * p;
+    if square >= from {
+        return Some(square);
+    }
+    match from % p {
+        0 => Some(from),
+        r => from.checked_add(p - r),
+    }
+}
+
+/// Every prime up to and including `limit`.
+///
+/// Small limits go through the plain sieve; bigger ones are segmented too, so
+/// the sieving primes for a window near 10^18 don't need a gigabit bitmap.
+pub(crate) fn primes_up_to(limit: u32, options: &SieveOptions) -> Vec<u32> {
+    if limit < 1 << 20 {
+        return crate::get_primes_less_than_x(limit + 1);
+    }
+
+    let base = primes_up_to(crate::isqrt(limit as u64) as u32, options);
+    let mut primes = Vec::new();
+    sieve_segments(2, limit as u64 + 1, &base, options, |low, words| {
+        for_each_set_bit(words, |offset| primes.push((low + offset) as u32))
+    });
+    primes
+}
+
+/// Calls `f` with every prime up to and including `limit`, in order, without
+/// collecting them.
+pub(crate) fn for_each_prime_up_to<F: FnMut(u64)>(limit: u32, options: &SieveOptions, mut f: F) {
+    let base = primes_up_to(crate::isqrt(limit as u64) as u32, options);
+    sieve_segments(2, limit as u64 + 1, &base, options, |low, words| {
+        for_each_set_bit(words, |offset| f(low + offset))
+    });
+}
+
+/// Runs the segmented sieve over `[min, max)`, handing each finished segment to `visit`.
+///
+/// `visit` gets the segment's first number (always a multiple of 64) and its
+/// bitmap: bit `i` is set iff `low + i` is a prime inside `[min, max)`.
+/// `sieving_primes` must be sorted and hold every prime `p` with `p * p < max`.
+///
+/// Primes smaller than a segment keep a running offset from one segment to the
+/// next. Bigger primes hit a segment at most once, so they wait in a ring of
+/// buckets keyed by the segment of their next multiple (Oliveira e Silva's
+/// bucket sieve): each segment only touches the primes that actually land in it.
+pub(crate) fn sieve_segments<F>(min: u64, max: u64, sieving_primes: &[u32], options: &SieveOptions, mut visit: F)
+where
+    F: FnMut(u64, &[u64]),
+{
+    sieve_segments_until(min, max, sieving_primes, options, |low, words| {
+        visit(low, words);
+        ControlFlow::Continue(())
+    });
+}
+
+/// Same as `sieve_segments`, but stops as soon as `visit` breaks.
+pub(crate) fn sieve_segments_until<F>(min: u64, max: u64, sieving_primes: &[u32], options: &SieveOptions, mut visit: F)
+where
+    F: FnMut(u64, &[u64]) -> ControlFlow<()>,
+{
+    if min >= max {
+        return;
+    }
+
+    let wheel = options.wheel;
+    let wheel_primes = wheel.primes();
+    let pattern = wheel.pattern();
+    let segment_bits = options.segment_bits();
+    let start = min - min % WORD_BITS;
+    let segment_count = (max - start - 1) / segment_bits + 1;
+    let _span = debug_span!("sieve_segments", min, max, sieving_primes = sieving_primes.len(), segment_count);
+
+    // (prime, offset of its next multiple from the current segment's start)
+    let mut small: Vec<(u64, u64)> = Vec::new();
+    // bucket[s % buckets.len()] holds (prime, offset) pairs landing in segment s.
+    let largest = sieving_primes.last().map_or(0, |&p| p as u64);
+    let mut buckets: Vec<Vec<(u32, u32)>> = vec![Vec::new(); (largest / segment_bits + 2) as usize];
+    // Large primes whose square lies beyond the ring's reach, in order of that square.
+    let mut pending: Vec<(u32, u64)> = Vec::new();
+
+    for &p in sieving_primes {
+        let p = p as u64;
+        if p * p >= max {
+            break;
+        }
+        if wheel_primes.contains(&p) {
+            continue;
+        }
+        let offset = match first_multiple(p, start) {
+            Some(first) if first < max => first - start,
+            _ => continue,
+        };
+        if p < segment_bits {
+            small.push((p, offset));
+        } else if offset / segment_bits < buckets.len() as u64 {
+            let segment = offset / segment_bits;
+            buckets[segment as usize].push((p as u32, (offset % segment_bits) as u32));
+        } else {
+            pending.push((p as u32, offset));
+        }
+    }
+    let mut pending = pending.into_iter().peekable();
+    let ring = buckets.len() as u64;
+
+    let mut words = Vec::with_capacity((segment_bits / WORD_BITS) as usize);
+    for segment in 0..segment_count {
+        let low = start + segment * segment_bits;
+        let high = low.saturating_add(segment_bits).min(max);
+        let len = (high - low) as usize;
+
+        // Stamp the wheel pattern.
+        words.clear();
+        let first_word = (low / WORD_BITS) as usize;
+        words.extend((0..len.div_ceil(64)).map(|w| pattern[(first_word + w) % pattern.len()]));
+
+        // The pattern knocks out the wheel primes too; put them back.
+        for &p in wheel_primes {
+            if p >= low && p < high {
+                set(&mut words, (p - low) as usize);
+            }
+        }
+
+        for (p, next) in small.iter_mut() {
+            let mut offset = *next;
+            while offset < len as u64 {
+                clear(&mut words, offset as usize);
+                offset += *p;
+            }
+            *next = offset - segment_bits.min(offset);
+        }
+
+        while let Some(&(p, offset)) = pending.peek() {
+            if offset / segment_bits != segment {
+                break;
+            }
+            buckets[(segment % ring) as usize].push((p, (offset % segment_bits) as u32));
+            pending.next();
+        }
+
+        let bucket = (segment % ring) as usize;
+        for (p, offset) in std::mem::take(&mut buckets[bucket]) {
+            if (offset as usize) < len {
+                clear(&mut words, offset as usize);
+            }
+            let next = offset as u64 + p as u64;
+            let next_segment = segment + next / segment_bits;
+            if next_segment < segment_count {
+                let next_bucket = (next_segment % ring) as usize;
+                buckets[next_bucket].push((p, (next % segment_bits) as u32));
+            }
+        }
+
+        // 0 and 1 are not primes, and nothing outside [min, max) belongs to us.
+        for n in low..min.max(low).min(high) {
+            clear(&mut words, (n - low) as usize);
+        }
+        for n in low..2u64.min(high) {
+            clear(&mut words, (n - low) as usize);
+        }
+        for offset in len..words.len() * 64 {
+            clear(&mut words, offset);
+        }
+
+        trace!(low, "segment sieved");
+        if visit(low, &words).is_break() {
+            return;
+        }
+    }
+}
+
+/// Calls `f` with the offset of every set bit in a segment bitmap, in order.
+pub(crate) fn for_each_set_bit<F: FnMut(u64)>(words: &[u64], mut f: F) {
+    for (w, &word) in words.iter().enumerate() {
+        let mut bits = word;
+        while bits != 0 {
+            f(w as u64 * WORD_BITS + bits.trailing_zeros() as u64);
+            bits &= bits - 1;
+        }
+    }
+}
+
+/// Appends the primes marked in a segment bitmap to `primes`.
+pub(crate) fn push_primes(low: u64, words: &[u64], primes: &mut Vec<u64>) {
+    for_each_set_bit(words, |offset| primes.push(low + offset));
+}
+
+fn set(words: &mut [u64], offset: usize) {
+    words[offset / 64] |= 1 << (offset % 64);
+}
+
+fn clear(words: &mut [u64], offset: usize) {
+    words[offset / 64] &= !(1 << (offset % 64));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_primes_less_than_x;
+
+    fn all_options() -> Vec<SieveOptions> {
+        let mut options = Vec::new();
+        for &wheel in &[Wheel::Off, Wheel::Mod2, Wheel::Mod6, Wheel::Mod30, Wheel::Mod210] {
+            for &segment_bytes in &[1, 8, 100, 4096] {
+                for &threads in &[1, 3] {
+                    options.push(SieveOptions { segment_bytes, wheel, threads, ..SieveOptions::default() });
+                }
+            }
+        }
+        options
+    }
+
+    #[test]
+    fn every_option_matches_the_simple_sieve() {
+        let expected: Vec<u64> = get_primes_less_than_x(20_000).iter().map(|&p| p as u64).collect();
+        for options in all_options() {
+            assert_eq!(
+                get_primes_between_with_options(0, 20_000, &options),
+                expected,
+                "{:?}", options
+            );
+        }
+    }
+
+    #[test]
+    fn default_segment_fits_a_cache() {
+        assert_eq!(parse_cache_size("48K"), Some(48 * 1024));
+        assert_eq!(parse_cache_size("2M"), Some(2 << 20));
+        assert_eq!(parse_cache_size("512"), Some(512));
+        assert_eq!(parse_cache_size("0K"), None);
+        assert_eq!(parse_cache_size("lots"), None);
+        assert!((16 * 1024..=1 << 20).contains(&SieveOptions::default().segment_bytes));
+    }
+
+    #[test]
+    fn memory_limit() {
+        let expected = get_primes_between_with_options(1_000_000, 3_000_000, &SieveOptions::default());
+        let unlimited = SieveOptions { segment_bytes: 1024, threads: 8, ..SieveOptions::default() };
+        let one_thread = SieveOptions { threads: 1, ..unlimited };
+        assert!(unlimited.memory_needed(1_000_000, 3_000_000) > one_thread.memory_needed(1_000_000, 3_000_000));
+
+        // Room for one thread only: the sieve drops the rest and still gets it right.
+        let limit = one_thread.memory_needed(1_000_000, 3_000_000);
+        let capped = SieveOptions { memory_limit: Some(limit), ..unlimited };
+        assert_eq!(capped.memory_needed(1_000_000, 3_000_000), limit);
+        assert_eq!(try_get_primes_between_with_options(1_000_000, 3_000_000, &capped), Ok(expected));
+
+        let tight = SieveOptions { memory_limit: Some(limit - 1), ..unlimited };
+        let mut out = vec![1, 2, 3];
+        assert_eq!(
+            try_primes_into_with_options(1_000_000, 3_000_000, &tight, &mut out),
+            Err(SieveError::MemoryLimit { needed: limit, limit: limit - 1 })
+        );
+        assert!(out.is_empty());
+        assert_eq!(try_get_primes_between_with_options(5, 5, &tight), Ok(vec![]));
+        // Pritchard's estimate of 8 bytes per number below max saturates instead of overflowing.
+        let pritchard = SieveOptions { algorithm: SieveAlgorithm::Pritchard, memory_limit: Some(1 << 30), ..unlimited };
+        assert!(matches!(try_get_primes_between_with_options(u64::MAX - 10, u64::MAX, &pritchard), Err(SieveError::MemoryLimit { .. })));
+        // Past 2^32 Pritchard's sieve can't run at all, limit or not.
+        let pritchard = SieveOptions { memory_limit: None, ..pritchard };
+        let mut out = vec![1];
+        assert_eq!(
+            try_primes_into_with_options((1 << 32) - 10, (1 << 32) + 10, &pritchard, &mut out),
+            Err(SieveError::AlgorithmLimit { algorithm: SieveAlgorithm::Pritchard, max: (1 << 32) + 10, limit: 1 << 32 })
+        );
+        assert!(out.is_empty());
+
+        // Every estimate covers what the sieve actually returns.
+        for &(min, max) in &[(2, 3), (0, 100), (1 << 40, (1 << 40) + 10_000)] {
+            let primes = get_primes_between_with_options(min, max, &unlimited);
+            assert!(unlimited.memory_needed(min, max) >= 8 * primes.len(), "{} {}", min, max);
+        }
+        // Near u64::MAX the estimate doesn't overflow; actually sieving there takes a minute.
+        let tiny = SieveOptions { memory_limit: Some(1), ..unlimited };
+        assert!(matches!(try_get_primes_between_with_options(u64::MAX - 1000, u64::MAX, &tiny), Err(SieveError::MemoryLimit { limit: 1, .. })));
+    }
+
+    #[test]
+    fn u32_output_matches_u64() {
+        for &(min, max) in &[(0, 0), (0, 3), (0, 100_000), (99_990, 1 << 20), (u32::MAX - 100_000, u32::MAX)] {
+            let wide: Vec<u64> = get_primes_between_u32(min, max).iter().map(|&p| p as u64).collect();
+            assert_eq!(wide, get_primes_between_with_options(min as u64, max as u64, &SieveOptions::default()), "{} {}", min, max);
+        }
+    }
+
+    #[test]
+    fn unaligned_windows() {
+        let all: Vec<u64> = get_primes_less_than_x(5_000).iter().map(|&p| p as u64).collect();
+        for options in all_options() {
+            for &(min, max) in &[(0, 1), (2, 3), (3, 8), (63, 65), (64, 128), (1_000, 1_010), (1_013, 4_999)] {
+                let expected: Vec<u64> = all.iter().cloned().filter(|&p| p >= min && p < max).collect();
+                assert_eq!(
+                    get_primes_between_with_options(min, max, &options),
+                    expected,
+                    "[{}, {}) with {:?}", min, max, options
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn other_algorithms_go_through_the_same_entry_points() {
+        let all: Vec<u64> = get_primes_less_than_x(5_000).iter().map(|&p| p as u64).collect();
+        for &algorithm in &[SieveAlgorithm::Sundaram, SieveAlgorithm::Pritchard] {
+            let options = SieveOptions { algorithm, ..SieveOptions::default() };
+            for &(min, max) in &[(0, 1), (2, 3), (3, 8), (63, 65), (1_013, 4_999)] {
+                let expected: Vec<u64> = all.iter().cloned().filter(|&p| p >= min && p < max).collect();
+                assert_eq!(get_primes_between_with_options(min, max, &options), expected, "{:?} [{}, {})", algorithm, min, max);
+                let mut found = Vec::new();
+                for_each_prime_between(min, max, &options, |p| found.push(p));
+                assert_eq!(found, expected, "{:?} [{}, {})", algorithm, min, max);
+            }
+        }
+    }
+
+    #[test]
+    fn presieve_survivors_have_no_small_factors() {
+        for &prime_limit in &[0, 2, 3, 4, 7, 8, 11, 100, 1_000, 100_000] {
+            for &(min, max) in &[(0, 0), (0, 2), (0, 1_000), (1, 2), (63, 200), (5_000, 6_000), (1 << 40, (1 << 40) + 500)] {
+                let small_primes = crate::get_primes_between(0, prime_limit);
+                let expected: Vec<bool> = (min..max).map(|n| small_primes.iter().all(|&p| n % p != 0)).collect();
+                let found: Vec<bool> = presieve_survivors(min, max, prime_limit).iter().collect();
+                assert_eq!(found, expected, "[{}, {}) below {}", min, max, prime_limit);
+            }
+        }
+    }
+
+    #[test]
+    fn squarefree_windows_match_mobius() {
+        for &(min, max) in &[(0, 0), (0, 1), (0, 5), (0, 10_000), (4, 9), (1 << 40, (1 << 40) + 3_000), (99_999_999_999_500, 100_000_000_000_500)] {
+            let expected: Vec<bool> = (min..max).map(|n| crate::mobius(n) != 0).collect();
+            let found: Vec<bool> = squarefree_between(min, max).iter().collect();
+            assert_eq!(found, expected, "[{}, {})", min, max);
+        }
+    }
+
+    #[test]
+    fn squarefree_segments_cover_the_window() {
+        let options = SieveOptions { segment_bytes: 8, ..SieveOptions::default() };
+        for &(min, max) in &[(0, 1_000), (5, 70), (1 << 40, (1 << 40) + 1_000), (99_999_999_999_000, 99_999_999_999_500)] {
+            let mut found = Vec::new();
+            let mut expected_low = min;
+            squarefree_segments(min, max, &options, |low, words| {
+                assert_eq!(low, expected_low);
+                assert_eq!(words.len(), 1);
+                expected_low += 64;
+                for_each_set_bit(words, |offset| found.push(low + offset));
+            });
+            let expected: Vec<u64> = (min..max).filter(|&n| crate::mobius(n) != 0).collect();
+            assert_eq!(found, expected, "[{}, {})", min, max);
+        }
+    }
+
+    #[test]
+    fn large_offset_window_matches_trial_division() {
+        let (min, max) = (10_000_000_000, 10_000_002_000);
+        let expected: Vec<u64> = (min..max).filter(|&x| crate::is_u64_prime(x)).collect();
+        for options in all_options() {
+            assert_eq!(
+                get_primes_between_with_options(min, max, &options),
+                expected,
+                "{:?}", options
+            );
+        }
+    }
+
+    #[test]
+    fn offsets_do_not_overflow_near_u64_max() {
+        // Sieving with only the small primes leaves the numbers without a small
+        // factor, which is easy to check directly.
+        let small: Vec<u32> = get_primes_less_than_x(10_000);
+        let (min, max) = (u64::MAX - 5_000, u64::MAX);
+        let expected: Vec<u64> = (min..max)
+            .filter(|&x| small.iter().all(|&p| x % p as u64 != 0))
+            .collect();
+        for options in all_options() {
+            let mut survivors = Vec::new();
+            sieve_segments(min, max, &small, &options, |low, words| {
+                push_primes(low, words, &mut survivors)
+            });
+            assert_eq!(survivors, expected, "{:?}", options);
+        }
+    }
+
+    #[test]
+    fn narrow_windows_match_the_segmented_path() {
+        let options = SieveOptions { segment_bytes: 1024, ..SieveOptions::default() };
+        let (min, max) = (1_000_000_000_000, 1_000_000_010_000);
+        assert_eq!(
+            primes_in_narrow_window(min, max, crate::isqrt(max - 1) as u32, &options),
+            (min..max).filter(|&x| crate::is_u64_prime(x)).collect::<Vec<u64>>()
+        );
+    }
+
+    #[test]
+    #[ignore] // Sieves every prime below 2^32: run with `cargo test --release -- --ignored`
+    fn primes_just_below_u64_max() {
+        let offsets = [279, 257, 189, 179, 95, 83, 59];
+        assert_eq!(
+            get_primes_between_with_options(u64::MAX - 299, u64::MAX, &SieveOptions::default()),
+            offsets.iter().map(|&k| (u64::MAX - k) + 1).collect::<Vec<u64>>()
+        );
+    }
+
+    #[test]
+    fn bitmap_matches_the_prime_list() {
+        for &(min, max) in &[(0, 0), (0, 3), (0, 100_000), (1_000_000_000_000, 1_000_000_300_000)] {
+            let bits = prime_bitmap_between(min, max);
+            assert_eq!(bits.len() as u64, max - min);
+            let primes: Vec<u64> = bits.iter().enumerate().filter(|&(_, prime)| prime).map(|(i, _)| min + i as u64).collect();
+            assert_eq!(primes, crate::get_primes_between(min, max), "{}..{}", min, max);
+        }
+        // min > max is an empty window, not a panic or a wrapped length.
+        assert!(prime_bitmap_between(u64::MAX - 100, u64::MAX - 200).is_empty());
+        assert!(prime_bitmap_between(100, 10).is_empty());
+    }
+
+    #[test]
+    fn primes_into_reuses_the_buffer() {
+        let mut out = vec![1, 2, 3];
+        primes_into(0, 0, &mut out);
+        assert!(out.is_empty());
+        for &(min, max) in &[(0, 1_000), (1_000_000, 2_000_000), (10, 12)] {
+            primes_into(min, max, &mut out);
+            assert_eq!(out, crate::get_primes_between(min, max));
+        }
+    }
+
+    #[test]
+    fn growing_a_sieve_matches_sieving_once() {
+        let mut sieve = Sieve::new(0);
+        assert_eq!(sieve.primes(), &[] as &[u64]);
+        for &limit in &[1, 2, 3, 10, 10, 5, 1_000, 1_024, 100_000, 3_000_000, 3_000_001] {
+            sieve.extend_to(limit);
+            let limit = sieve.limit();
+            assert_eq!(sieve.primes(), &crate::get_primes_between(0, limit)[..], "{}", limit);
+            assert_eq!(sieve, Sieve::new(limit));
+        }
+        assert_eq!(sieve.limit(), 3_000_001);
+        assert_eq!(sieve.is_prime(2_999_999), Some(true));
+        assert_eq!(sieve.is_prime(3_000_000), Some(false));
+        assert_eq!(sieve.is_prime(3_000_001), None);
+    }
+
+    #[test]
+    fn wheel_candidates_are_the_coprime_numbers() {
+        for &wheel in &[Wheel::Off, Wheel::Mod2, Wheel::Mod6, Wheel::Mod30, Wheel::Mod210] {
+            for &(min, max) in &[(0, 0), (0, 1), (0, 1_000), (209, 211), (999, 5_000), (7, 3)] {
+                let expected: Vec<u64> =
+                    (min..max).filter(|&n| wheel.primes().iter().all(|&p| n % p != 0)).collect();
+                assert_eq!(wheel_candidates(min, max, wheel).collect::<Vec<_>>(), expected, "{:?} {}..{}", wheel, min, max);
+            }
+            let top: Vec<u64> = wheel_candidates(u64::MAX - 300, u64::MAX, wheel).collect();
+            let expected: Vec<u64> =
+                (u64::MAX - 300..u64::MAX).filter(|&n| wheel.primes().iter().all(|&p| n % p != 0)).collect();
+            assert_eq!(top, expected, "{:?}", wheel);
+        }
+    }
+
+    #[test]
+    fn count_and_filter_matches_is_prime() {
+        let batches: Vec<Vec<u64>> = vec![
+            vec![],
+            vec![0, 1, 2],
+            (0..5_000).rev().collect(),
+            (0..200).map(|i| i * i * 7_919 + 1).collect(),
+            (1_000_000..1_000_500).chain(10_000_000_000..10_000_000_300).chain(vec![5, 5, 3, 3]).collect(),
+            vec![(1 << 40) - 87, (1 << 40) - 1, 1 << 40, u64::MAX],
+            vec![18_446_744_073_709_551_557, 18_446_744_073_709_551_533, 18_446_744_073_709_551_521, 1 << 63, u64::MAX - 2],
+        ];
+        // The largest primes below 2^64, where trial division is too slow to check against.
+        let top = [18_446_744_073_709_551_557, 18_446_744_073_709_551_533, 18_446_744_073_709_551_521];
+        for batch in batches {
+            let mut expected: Vec<u64> = batch.iter().cloned().filter(|&x| if x < 1 << 60 { crate::is_u64_prime(x) } else { top.contains(&x) }).collect();
+            expected.sort_unstable();
+            expected.dedup();
+            let mut values = batch.clone();
+            assert_eq!(count_and_filter_primes(&mut values), expected.len());
+            assert_eq!(values, expected);
+        }
+    }
+
+    #[test]
+    fn wheel_patterns_tile() {
+        for &wheel in &[Wheel::Off, Wheel::Mod2, Wheel::Mod6, Wheel::Mod30, Wheel::Mod210] {
+            let pattern = wheel.pattern();
+            assert_eq!((pattern.len() as u64 * WORD_BITS) % wheel.modulus(), 0);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Tables of the primes below 2^16, built at compile time.
+
+use crate::tables::{sieve, LIMIT, PRIME_BITS};
+
+/// Number of primes below 2^16.
+const COUNT: usize = 6_542;
+
+/// Every prime below 2^16, in order.
+pub(crate) static SMALL_PRIMES: [u16; COUNT] = list(&sieve());
+
+const fn list(bits: &[u64; LIMIT / 64]) -> [u16; COUNT] {
+    let mut primes = [0u16; COUNT];
+    let mut count = 0;
+    let mut x = 0;
+    while x < LIMIT {
+        if bits[x / 64] >> (x % 64) & 1 == 1 {
+            primes[count] = x as u16;
+            count += 1;
+        }
+        x += 1;
+    }
+    primes
+}
+
+/// Table lookup for x < 2^16.
+pub(crate) fn is_small_prime(x: u16) -> bool {
+    PRIME_BITS[x as usize / 64] >> (x % 64) & 1 == 1
+}
+
+/// Trial division by the tabled primes.
+///
+/// Some(answer) once a factor turns up or the primes pass √x, which always
+/// happens below 65537². None if x survives the whole table.
+pub(crate) fn trial_division(x: u64) -> Option<bool> {
+    for &p in SMALL_PRIMES.iter() {
+        let p = p as u64;
+        if p > x / p {
+            return Some(x >= 2);
+        }
+        if x % p == 0 {
+            return Some(x == p);
+        }
+    }
+    if x < 65_537 * 65_537 {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_match_the_sieve() {
+        let primes = crate::get_primes_less_than_x(1 << 16);
+        assert_eq!(primes.len(), COUNT);
+        assert!(SMALL_PRIMES.iter().zip(&primes).all(|(&a, &b)| a as u32 == b));
+        for x in 0..=u16::MAX {
+            assert_eq!(is_small_prime(x), primes.binary_search(&(x as u32)).is_ok(), "{}", x);
+        }
+    }
+
+    #[test]
+    fn trial_division_edges() {
+        assert_eq!(trial_division(0), Some(false));
+        assert_eq!(trial_division(1), Some(false));
+        assert_eq!(trial_division(65_521 * 65_521), Some(false));
+        assert_eq!(trial_division(u32::MAX as u64), Some(false));
+        assert_eq!(trial_division(4_294_967_291), Some(true));
+        assert_eq!(trial_division(65_537 * 65_537), None);
+    }
+}
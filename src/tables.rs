@@ -0,0 +1,114 @@
+//! Prime tables built at compile time.
+//!
+//! For code that wants small primes or factors on hand without sieving for
+//! them at startup. All three live in the binary's read-only data: about
+//! 2 KB, 8 KB and 128 KB.
+
+pub(crate) const LIMIT: usize = 1 << 16;
+
+/// The first 1000 primes, 2 through 7919.
+///
+/// ```
+/// use prime_tools::tables::FIRST_1000_PRIMES;
+///
+/// assert_eq!(FIRST_1000_PRIMES[..5], [2, 3, 5, 7, 11]);
+/// assert_eq!(FIRST_1000_PRIMES[999], 7_919);
+/// ```
+pub static FIRST_1000_PRIMES: [u16; 1000] = first_primes(&sieve());
+
+/// The primes below 2^16 as a bitset: x is prime iff bit x % 64 of word x / 64 is set.
+///
+/// ```
+/// use prime_tools::tables::PRIME_BITS;
+///
+/// let is_prime = |x: u16| PRIME_BITS[x as usize / 64] >> (x % 64) & 1 == 1;
+/// assert!(is_prime(65_521));
+/// assert!(!is_prime(65_535));
+/// assert!(!is_prime(1));
+/// ```
+pub static PRIME_BITS: [u64; LIMIT / 64] = sieve();
+
+/// The smallest prime factor of every n below 2^16, and 0 for 0 and 1.
+///
+/// Dividing it out repeatedly factors any n < 2^16 without a single trial division.
+///
+/// ```
+/// use prime_tools::tables::SMALLEST_PRIME_FACTOR;
+///
+/// let mut n = 60_060;
+/// let mut factors = Vec::new();
+/// while n > 1 {
+///     let p = SMALLEST_PRIME_FACTOR[n];
+///     factors.push(p);
+///     n /= p as usize;
+/// }
+/// assert_eq!(factors, vec![2, 2, 3, 5, 7, 11, 13]);
+/// ```
+pub static SMALLEST_PRIME_FACTOR: [u16; LIMIT] = smallest_prime_factors();
+
+pub(crate) const fn sieve() -> [u64; LIMIT / 64] {
+    let mut bits = [!0u64; LIMIT / 64];
+    bits[0] &= !0b11;
+    let mut i = 2;
+    while i * i < LIMIT {
+        if bits[i / 64] >> (i % 64) & 1 == 1 {
+            let mut j = i * i;
+            while j < LIMIT {
+                bits[j / 64] &= !(1 << (j % 64));
+                j += i;
+            }
+        }
+        i += 1;
+    }
+    bits
+}
+
+const fn first_primes(bits: &[u64; LIMIT / 64]) -> [u16; 1000] {
+    let mut primes = [0u16; 1000];
+    let mut count = 0;
+    let mut x = 0;
+    while count < 1000 {
+        if bits[x / 64] >> (x % 64) & 1 == 1 {
+            primes[count] = x as u16;
+            count += 1;
+        }
+        x += 1;
+    }
+    primes
+}
+
+const fn smallest_prime_factors() -> [u16; LIMIT] {
+    let mut smallest = [0u16; LIMIT];
+    let mut i = 2;
+    while i < LIMIT {
+        if smallest[i] == 0 {
+            // i is prime; it's the smallest factor of every multiple not claimed yet.
+            let mut j = i;
+            while j < LIMIT {
+                if smallest[j] == 0 {
+                    smallest[j] = i as u16;
+                }
+                j += i;
+            }
+        }
+        i += 1;
+    }
+    smallest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_match_the_runtime_versions() {
+        let primes = crate::get_primes_less_than_x(LIMIT as u32);
+        assert!(FIRST_1000_PRIMES.iter().zip(&primes).all(|(&a, &b)| a as u32 == b));
+        for n in 0..LIMIT {
+            let prime = PRIME_BITS[n / 64] >> (n % 64) & 1 == 1;
+            assert_eq!(prime, primes.binary_search(&(n as u32)).is_ok(), "{}", n);
+            let expected = crate::least_prime_factor(n as u64).unwrap_or(0);
+            assert_eq!(SMALLEST_PRIME_FACTOR[n] as u64, expected, "{}", n);
+        }
+    }
+}
@@ -0,0 +1,447 @@
+//! Euler's totient, the Möbius function and counting coprime numbers.
+
+use std::ops::Range;
+
+use crate::primality::U64_BASES;
+use crate::sieve::primes_up_to;
+use crate::{factorize, miller_rabin_with_bases, SieveOptions};
+
+/// True if a and b share no prime factor, i.e. gcd(a, b) = 1.
+///
+/// 1 is coprime to everything, 0 only to 1.
+///
+/// ```
+/// assert!(prime_tools::are_coprime(8, 15));
+/// assert!(!prime_tools::are_coprime(12, 18));
+/// assert!(!prime_tools::are_coprime(0, 0));
+/// ```
+pub fn are_coprime(a: u64, b: u64) -> bool {
+    crate::gcd(a, b) == 1
+}
+
+/// Euler's totient φ(x): how many of 1..=x are coprime to x.
+///
+/// φ(0) is taken to be 0.
+///
+/// ```
+/// assert_eq!(prime_tools::totient(36), 12);
+/// assert_eq!(prime_tools::totient(1), 1);
+/// ```
+pub fn totient(x: u64) -> u64 {
+    if x == 0 {
+        return 0;
+    }
+    factorize(x).iter().fold(x, |phi, &(p, _)| phi / p * (p - 1))
+}
+
+/// Every n with φ(n) = m, in increasing order.
+///
+/// Each prime p that can divide such an n has p - 1 dividing m, so this walks
+/// those primes from the largest down, taking out p^(k-1)(p - 1) for each
+/// power p^k of p in turn, with each candidate p tested by deterministic
+/// Miller–Rabin. Only n that fit in a u64 are returned. φ never takes odd
+/// values past 1, nor 0.
+///
+/// ```
+/// assert_eq!(prime_tools::inverse_totient(12), vec![13, 21, 26, 28, 36, 42]);
+/// assert_eq!(prime_tools::inverse_totient(1), vec![1, 2]);
+/// assert_eq!(prime_tools::inverse_totient(14), vec![]);
+/// ```
+pub fn inverse_totient(m: u64) -> Vec<u64> {
+    if m == 0 {
+        return Vec::new();
+    }
+    let primes: Vec<u64> = crate::divisors(m)
+        .into_iter()
+        .filter_map(|d| d.checked_add(1))
+        .filter(|&p| miller_rabin_with_bases(p as u128, &U64_BASES))
+        .collect();
+
+    let mut preimages = Vec::new();
+    collect_preimages(m, &primes, 1, &mut preimages);
+    preimages.sort_unstable();
+    preimages
+}
+
+/// Pushes n * k for every k built from `primes` (each used at most once,
+/// as any power) with φ(k) = m.
+fn collect_preimages(m: u64, primes: &[u64], n: u64, preimages: &mut Vec<u64>) {
+    if m == 1 {
+        preimages.push(n);
+    }
+    for (i, &p) in primes.iter().enumerate().rev() {
+        if m % (p - 1) != 0 {
+            continue;
+        }
+        // p^k contributes p^(k-1)(p - 1) to φ.
+        let mut rest = m / (p - 1);
+        let mut power = p;
+        while let Some(next) = n.checked_mul(power) {
+            collect_preimages(rest, &primes[..i], next, preimages);
+            if rest % p != 0 {
+                break;
+            }
+            rest /= p;
+            power = match power.checked_mul(p) {
+                Some(power) => power,
+                None => break,
+            };
+        }
+    }
+}
+
+/// The Möbius function μ(x): 0 if a square divides x, otherwise 1 or -1 for an
+/// even or odd number of prime factors.
+///
+/// μ(0) is taken to be 0.
+///
+/// ```
+/// assert_eq!(prime_tools::mobius(30), -1);
+/// assert_eq!(prime_tools::mobius(12), 0);
+/// assert_eq!(prime_tools::mobius(1), 1);
+/// ```
+pub fn mobius(x: u64) -> i8 {
+    if x == 0 {
+        return 0;
+    }
+    let factors = factorize(x);
+    if factors.iter().any(|&(_, count)| count > 1) {
+        0
+    } else if factors.len() % 2 == 0 {
+        1
+    } else {
+        -1
+    }
+}
+
+/// How many x in [1, n] are k-free, with no prime factor appearing k or more
+/// times; k = 2 counts the squarefree numbers.
+///
+/// Uses Σ μ(d) · ⌊n / d^k⌋ over d up to the integer k-th root of n, with μ
+/// sieved a segment at a time, so it takes about n^(1/k) steps: the
+/// squarefree numbers up to 10^18 take ten seconds or so in a release build.
+/// k = 1 leaves just 1. Panics for k = 0.
+///
+/// ```
+/// assert_eq!(prime_tools::count_k_free_up_to(10, 2), 7); // all but 4, 8 and 9
+/// assert_eq!(prime_tools::count_k_free_up_to(1_000_000, 2), 607_926);
+/// assert_eq!(prime_tools::count_k_free_up_to(1_000_000, 3), 831_910);
+/// ```
+pub fn count_k_free_up_to(n: u64, k: u32) -> u64 {
+    assert!(k > 0, "count_k_free_up_to needs k >= 1");
+    let root = crate::factor::integer_root(n, k);
+    let _span = debug_span!("count_k_free_up_to", n, k, root);
+    let mut count: i128 = 0;
+    for_each_mobius(root, |d, mu| {
+        if mu != 0 {
+            count += mu as i128 * (n / d.pow(k)) as i128;
+        }
+    });
+    count as u64
+}
+
+/// Calls f(d, μ(d)) for d = 1, 2, ..., limit, sieving a segment at a time.
+///
+/// Each prime p up to √limit flips the sign of its multiples, multiplies it
+/// into their running product and zeroes the multiples of p². A squarefree
+/// number whose product falls short has exactly one more prime factor.
+fn for_each_mobius<F: FnMut(u64, i8)>(limit: u64, mut f: F) {
+    const SEGMENT: u64 = 1 << 16;
+    let primes = primes_up_to(crate::isqrt(limit) as u32, &SieveOptions::default());
+    let mut mu = vec![0i8; SEGMENT as usize];
+    let mut product = vec![0u64; SEGMENT as usize];
+    let mut low = 1;
+    while low <= limit {
+        let high = low.saturating_add(SEGMENT).min(limit.saturating_add(1));
+        let len = (high - low) as usize;
+        mu[..len].fill(1);
+        product[..len].fill(1);
+        for &p in primes.iter().take_while(|&&p| (p as u64) * (p as u64) < high) {
+            let p = p as u64;
+            let mut multiple = low.div_ceil(p) * p;
+            while multiple < high {
+                let i = (multiple - low) as usize;
+                mu[i] = -mu[i];
+                product[i] *= p;
+                multiple += p;
+            }
+            let square = p * p;
+            let mut multiple = low.div_ceil(square) * square;
+            while multiple < high {
+                mu[(multiple - low) as usize] = 0;
+                multiple += square;
+            }
+        }
+        for (i, (&mu, &product)) in mu.iter().zip(&product).take(len).enumerate() {
+            let d = low + i as u64;
+            f(d, if mu != 0 && product != d { -mu } else { mu });
+        }
+        low = high;
+    }
+}
+
+/// The residues mod m coprime to m, in increasing order: the spokes of a
+/// wheel of circumference m.
+///
+/// These are what `wheel_candidates` steps through, so a custom sieve built
+/// on them lines up with the crate's own wheels. The multiples of each
+/// prime of m are crossed off a table of all m residues, so m is bounded
+/// by memory. Residues mod 1 are just 0. Panics for m = 0.
+///
+/// ```
+/// assert_eq!(prime_tools::residue_classes_coprime_to(30), vec![1, 7, 11, 13, 17, 19, 23, 29]);
+/// assert_eq!(prime_tools::residue_classes_coprime_to(1), vec![0]);
+/// ```
+pub fn residue_classes_coprime_to(m: u64) -> Vec<u64> {
+    assert!(m > 0, "there are no residues mod 0");
+    if m == 1 {
+        return vec![0];
+    }
+    let mut coprime = vec![true; m as usize];
+    for (p, _) in factorize(m) {
+        for multiple in (0..m as usize).step_by(p as usize) {
+            coprime[multiple] = false;
+        }
+    }
+    (0..m).filter(|&r| coprime[r as usize]).collect()
+}
+
+/// How many residues mod m are coprime to m, which is φ(m), taken from the
+/// factorization without listing them. Panics for m = 0.
+///
+/// ```
+/// assert_eq!(prime_tools::count_coprime_residues(210), 48);
+/// assert_eq!(prime_tools::count_coprime_residues(1), 1);
+/// ```
+pub fn count_coprime_residues(m: u64) -> u64 {
+    assert!(m > 0, "there are no residues mod 0");
+    totient(m)
+}
+
+/// How many k in `range` have gcd(k, n) = 1.
+///
+/// Works by inclusion–exclusion over the distinct primes of n, so it takes
+/// the same time for any range, up to 2^15 terms for the worst n.
+///
+/// ```
+/// // 1, 5, 7 and 11 are the numbers below 12 coprime to it.
+/// assert_eq!(prime_tools::count_coprime_in_range(12, 0..12), 4);
+/// assert_eq!(prime_tools::count_coprime_in_range(1, 0..1_000), 1_000);
+/// ```
+pub fn count_coprime_in_range(n: u64, range: Range<u64>) -> u64 {
+    if range.start >= range.end {
+        return 0;
+    }
+    if n == 0 {
+        // gcd(k, 0) = k, so only k = 1 counts.
+        return range.contains(&1) as u64;
+    }
+
+    let primes: Vec<u64> = factorize(n).into_iter().map(|(p, _)| p).collect();
+    coprime_below(&primes, range.end) - coprime_below(&primes, range.start)
+}
+
+/// How many of 0..end have none of the given primes as a factor.
+fn coprime_below(primes: &[u64], end: u64) -> u64 {
+    if end == 0 {
+        return 0;
+    }
+    // 0 is a multiple of every prime, so it only counts when there are none.
+    let zero = primes.is_empty() as i128;
+    // For 1..end, sum over every squarefree d built from the primes of μ(d)
+    // times the number of multiples of d. Once d reaches end there are none.
+    let mut count = 0i128;
+    let mut stack = vec![(0, 1u64, 1i128)];
+    while let Some((index, d, sign)) = stack.pop() {
+        count += sign * ((end - 1) / d) as i128;
+        for (i, &p) in primes.iter().enumerate().skip(index) {
+            match d.checked_mul(p) {
+                Some(next) if next < end => stack.push((i + 1, next, -sign)),
+                _ => {}
+            }
+        }
+    }
+    (zero + count) as u64
+}
+
+/// The largest n `totient_summatory` takes.
+const TOTIENT_SUMMATORY_MAX: u64 = 1 << 48;
+
+/// φ(1) + φ(2) + ... + φ(n), the number of fractions in the Farey sequence of order n, less one.
+///
+/// Takes about n^(2/3) steps using Φ(v) = v(v + 1)/2 - Σ Φ(v / d) over d >= 2,
+/// with the small values sieved up front. n = 10^11 takes a couple of seconds
+/// in a release build. The sieved table stops at 2^24 values, and the rest
+/// takes 16 bytes per multiple of 2^24 in n, so memory stays under 400 MiB.
+/// Panics if n is above 2^48.
+///
+/// ```
+/// assert_eq!(prime_tools::totient_summatory(10), 32);
+/// assert_eq!(prime_tools::totient_summatory(0), 0);
+/// ```
+pub fn totient_summatory(n: u64) -> u128 {
+    assert!(n <= TOTIENT_SUMMATORY_MAX, "totient_summatory takes n up to 2^48");
+    if n == 0 {
+        return 0;
+    }
+    // Sieve up to about n^(2/3), but keep the table to 128 MiB. Past 2^36 the
+    // large values take over, 2^24 of them at most.
+    let cbrt = (n as f64).cbrt() as u64;
+    let limit = (cbrt.saturating_mul(cbrt).min(1 << 24)).max(crate::isqrt(n)).max(1) as usize;
+    let _span = debug_span!("totient_summatory", n, limit);
+    let small = summed_totients(limit);
+    if n as usize <= limit {
+        return small[n as usize] as u128;
+    }
+
+    // large[k] holds Φ(n / k) for every k with n / k > limit, filled from the
+    // smallest value up so every lookup is already there.
+    let ks = (n / limit as u64) as usize;
+    debug!(large_values = ks, "small totient sums sieved");
+    let mut large = vec![0u128; ks + 1];
+    for k in (1..=ks).rev() {
+        let v = n / k as u64;
+        let mut sum = triangle(v);
+        let mut d = 2;
+        while d <= v {
+            let q = v / d;
+            let last = v / q;
+            let phi = if q as usize <= limit {
+                small[q as usize] as u128
+            } else {
+                large[k * d as usize]
+            };
+            sum -= (last - d + 1) as u128 * phi;
+            d = last + 1;
+        }
+        large[k] = sum;
+    }
+    large[1]
+}
+
+/// v(v + 1)/2 without overflowing.
+fn triangle(v: u64) -> u128 {
+    let v = v as u128;
+    v * (v + 1) / 2
+}
+
+/// Φ(i) for every i up to limit.
+fn summed_totients(limit: usize) -> Vec<u64> {
+    let mut phi: Vec<u64> = (0..=limit as u64).collect();
+    for p in 2..=limit {
+        if phi[p] == p as u64 {
+            for multiple in (p..=limit).step_by(p) {
+                phi[multiple] -= phi[multiple] / p as u64;
+            }
+        }
+    }
+    phi[0] = 0;
+    for i in 1..=limit {
+        phi[i] += phi[i - 1];
+    }
+    phi
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coprime_residues_match_gcd() {
+        for m in 1..500 {
+            let residues = residue_classes_coprime_to(m);
+            let expected: Vec<u64> = (0..m).filter(|&r| are_coprime(r, m)).collect();
+            assert_eq!(residues, expected, "{}", m);
+            assert_eq!(count_coprime_residues(m), residues.len() as u64);
+        }
+    }
+
+    #[test]
+    fn k_free_counts_match_factorizations() {
+        let n = 100_000;
+        for k in 1..=4 {
+            let mut expected = 0;
+            for x in 0..=n {
+                if x > 0 && factorize(x).iter().all(|&(_, count)| count < k) {
+                    expected += 1;
+                }
+                if x % 997 == 0 || x == n {
+                    assert_eq!(count_k_free_up_to(x, k), expected, "{} {}", x, k);
+                }
+            }
+        }
+        assert_eq!(count_k_free_up_to(1_000_000_000_000, 2), 607_927_102_274);
+        assert_eq!(count_k_free_up_to(u64::MAX, 64), u64::MAX);
+    }
+
+    #[test]
+    fn totient_and_mobius_match_brute_force() {
+        for x in 1..2_000u64 {
+            let coprime = (1..=x).filter(|&k| are_coprime(k, x)).count() as u64;
+            assert_eq!(totient(x), coprime, "{}", x);
+
+            let squarefree = (2..x).take_while(|d| d * d <= x).all(|d| x % (d * d) != 0);
+            let expected = if squarefree { if factorize(x).len() % 2 == 0 { 1 } else { -1 } } else { 0 };
+            assert_eq!(mobius(x), expected, "{}", x);
+        }
+        assert_eq!(totient(0), 0);
+        assert_eq!(mobius(0), 0);
+        assert_eq!(totient(u64::MAX), 2 * 4 * 16 * 256 * 640 * 65_536 * 6_700_416);
+    }
+
+    #[test]
+    fn inverse_totient_matches_a_table() {
+        let mut preimages = vec![Vec::new(); 2_001];
+        for n in 1..20_000u64 {
+            let phi = totient(n);
+            if phi <= 2_000 {
+                preimages[phi as usize].push(n);
+            }
+        }
+        // φ(n) >= √(n / 2), so every n with φ(n) <= 2000 is below 20000.
+        for (m, expected) in preimages.iter().enumerate() {
+            assert_eq!(inverse_totient(m as u64), *expected, "{}", m);
+        }
+        for n in inverse_totient(1 << 32) {
+            assert_eq!(totient(n), 1 << 32);
+        }
+        assert_eq!(inverse_totient(1 << 32).len(), 32);
+        // 2^61 - 1 is prime, so it and twice it are preimages of 2^61 - 2.
+        let mersenne = (1 << 61) - 1;
+        let preimages = inverse_totient(mersenne - 1);
+        assert!(preimages.contains(&mersenne) && preimages.contains(&(2 * mersenne)));
+    }
+
+    #[test]
+    fn coprime_counts_match_brute_force() {
+        for n in 0..60u64 {
+            for start in 0..40 {
+                for end in start..80 {
+                    let expected = (start..end).filter(|&k| are_coprime(k, n)).count() as u64;
+                    assert_eq!(count_coprime_in_range(n, start..end), expected, "{} {}..{}", n, start, end);
+                }
+            }
+        }
+        #[allow(clippy::reversed_empty_ranges)]
+        let backwards = 10..5;
+        assert_eq!(count_coprime_in_range(6, backwards), 0);
+        assert_eq!(count_coprime_in_range(2, 0..u64::MAX), u64::MAX / 2);
+        assert_eq!(count_coprime_in_range(u64::MAX, u64::MAX - 1..u64::MAX), 1);
+    }
+
+    #[test]
+    fn totient_summatory_matches_summed_totients() {
+        let sums = summed_totients(100_000);
+        for n in (0..100_000u64).step_by(97).chain(99_990..=100_000) {
+            assert_eq!(totient_summatory(n), sums[n as usize] as u128, "{}", n);
+        }
+        // A064018
+        assert_eq!(totient_summatory(1_000_000_000), 303_963_551_173_008_414);
+    }
+
+    #[test]
+    #[should_panic]
+    fn totient_summatory_past_the_limit() {
+        totient_summatory(TOTIENT_SUMMATORY_MAX + 1);
+    }
+}
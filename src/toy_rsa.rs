@@ -0,0 +1,97 @@
+//! Textbook RSA on u128s, for teaching and tests. Not for real use.
+//!
+//! A modulus of at most 128 bits falls to any decent factoring program in
+//! seconds, and there is no padding: the same message always encrypts to the
+//! same ciphertext, and ciphertexts can be multiplied together. What this does
+//! show is the whole flow: two random primes, n = pq, a public exponent e
+//! coprime to φ(n), and d = e⁻¹ mod φ(n).
+//!
+//! Only built with the `rand_core` feature.
+//!
+//! ```
+//! use prime_tools::toy_rsa;
+//! use rand_core::SeedableRng;
+//!
+//! let mut rng = rand_pcg::Pcg64::seed_from_u64(42);
+//! let key = toy_rsa::generate(64, &mut rng);
+//! assert_eq!(key.n, key.p * key.q);
+//!
+//! let ciphertext = key.encrypt(1_234_567);
+//! assert_ne!(ciphertext, 1_234_567);
+//! assert_eq!(key.decrypt(ciphertext), 1_234_567);
+//! ```
+
+use rand_core::RngCore;
+
+use crate::gen_prime;
+use crate::modular::{inverse_mod, pow_mod};
+
+/// Public exponents to try, most common first.
+const EXPONENTS: [u128; 5] = [65_537, 257, 17, 5, 3];
+
+/// An RSA key pair with everything that went into it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyPair {
+    /// The first secret prime.
+    pub p: u128,
+    /// The second secret prime, different from p.
+    pub q: u128,
+    /// The public modulus pq.
+    pub n: u128,
+    /// The public exponent.
+    pub e: u128,
+    /// The private exponent, e⁻¹ mod (p - 1)(q - 1).
+    pub d: u128,
+}
+
+/// A key pair whose modulus has exactly `bits` bits, from two random primes
+/// of about bits / 2 bits each. Panics unless 8 <= bits <= 128.
+pub fn generate<R: RngCore + ?Sized>(bits: u32, rng: &mut R) -> KeyPair {
+    assert!((8..=128).contains(&bits), "toy_rsa::generate makes moduli of 8 to 128 bits");
+    loop {
+        let p = gen_prime(bits / 2, rng);
+        let q = gen_prime(bits - bits / 2, rng);
+        let n = match p.checked_mul(q) {
+            Some(n) if p != q && 128 - n.leading_zeros() == bits => n,
+            _ => continue,
+        };
+        let phi = (p - 1) * (q - 1);
+        if let Some((e, d)) = EXPONENTS.iter().find_map(|&e| Some((e, inverse_mod(e, phi)?))) {
+            return KeyPair { p, q, n, e, d };
+        }
+    }
+}
+
+impl KeyPair {
+    /// message^e mod n. Panics if message >= n.
+    pub fn encrypt(&self, message: u128) -> u128 {
+        assert!(message < self.n, "the message has to be smaller than n");
+        pow_mod(message, self.e, self.n)
+    }
+
+    /// ciphertext^d mod n. Panics if ciphertext >= n.
+    pub fn decrypt(&self, ciphertext: u128) -> u128 {
+        assert!(ciphertext < self.n, "the ciphertext has to be smaller than n");
+        pow_mod(ciphertext, self.d, self.n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::SeedableRng;
+
+    #[test]
+    fn round_trips() {
+        let mut rng = rand_pcg::Pcg64::seed_from_u64(659);
+        for bits in 8..=128 {
+            let key = generate(bits, &mut rng);
+            assert_eq!(128 - key.n.leading_zeros(), bits);
+            let phi = (key.p - 1) * (key.q - 1);
+            assert_eq!(crate::modular::mul_mod(key.e, key.d, phi), 1);
+            for message in [0, 1, 2, key.n / 3, key.n - 1].iter().cloned() {
+                assert_eq!(key.decrypt(key.encrypt(message)), message, "{:?}", key);
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Internal logging macros that compile to nothing without the `tracing` feature.
+//!
+//! `debug_span!` gives back an entered span guard (or a stand-in), so the
+//! span covers the rest of the caller's scope: `let _span = debug_span!(...);`.
+
+#[cfg(feature = "tracing")]
+macro_rules! debug_span {
+    ($($arg:tt)*) => {
+        tracing::debug_span!($($arg)*).entered()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! debug_span {
+    ($($arg:tt)*) => {
+        crate::trace::NoSpan
+    };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        tracing::debug!($($arg)*)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {};
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        tracing::trace!($($arg)*)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace {
+    ($($arg:tt)*) => {};
+}
+
+/// What `debug_span!` hands back when tracing is off.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoSpan;
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    #[derive(Default)]
+    struct Counts {
+        spans: AtomicUsize,
+        events: AtomicUsize,
+    }
+
+    /// Counts spans and events, and nothing else.
+    struct Counter(Arc<Counts>);
+
+    impl Subscriber for Counter {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes) -> Id {
+            Id::from_u64(self.0.spans.fetch_add(1, Ordering::SeqCst) as u64 + 1)
+        }
+        fn record(&self, _: &Id, _: &Record) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event) {
+            self.0.events.fetch_add(1, Ordering::SeqCst);
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn sieving_reports_every_segment() {
+        let counts = Arc::new(Counts::default());
+        let options = crate::SieveOptions { segment_bytes: 64, wheel: crate::Wheel::Mod6, threads: 1, ..Default::default() };
+        tracing::subscriber::with_default(Counter(counts.clone()), || {
+            crate::get_primes_between_with_options(0, 10 * 512, &options);
+        });
+        // get_primes_between and sieve_segments, then the ready event and one per segment.
+        assert_eq!(counts.spans.load(Ordering::SeqCst), 2);
+        assert_eq!(counts.events.load(Ordering::SeqCst), 1 + 10);
+    }
+}